@@ -0,0 +1,141 @@
+//! Measures building requests, serializing bodies, parsing responses, and
+//! executing endpoints against an in-process mock server.
+//!
+//! Run with `cargo bench --bench endpoint`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use http::Response;
+use httpmock::prelude::*;
+use rustify::{
+    clients::reqwest::Client,
+    endpoint::{Endpoint, EndpointResult},
+    enums::{RequestType, ResponseType},
+    http::{build_body, build_request, parse},
+};
+use rustify_derive::Endpoint;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct User {
+    id: u64,
+    name: String,
+    email: String,
+    roles: Vec<String>,
+}
+
+#[derive(Endpoint, Serialize)]
+#[endpoint(
+    path = "orgs/{self.org}/users",
+    method = "POST",
+    response = "Vec<User>"
+)]
+struct CreateUsers {
+    #[endpoint(skip)]
+    org: String,
+    #[endpoint(query)]
+    dry_run: Option<bool>,
+    users: Vec<User>,
+}
+
+fn users(n: u64) -> Vec<User> {
+    (0..n)
+        .map(|id| User {
+            id,
+            name: format!("user-{}", id),
+            email: format!("user-{}@example.com", id),
+            roles: vec!["read".to_string(), "write".to_string()],
+        })
+        .collect()
+}
+
+fn endpoint(n: u64) -> CreateUsers {
+    CreateUsers {
+        org: "rustify".to_string(),
+        dry_run: Some(true),
+        users: users(n),
+    }
+}
+
+fn bench_build(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build");
+    let small = endpoint(1);
+    let large = endpoint(1_000);
+    group.bench_function("request_small", |b| {
+        b.iter(|| black_box(&small).request("http://localhost").unwrap())
+    });
+    group.bench_function("request_large", |b| {
+        b.iter(|| black_box(&large).request("http://localhost").unwrap())
+    });
+    group.bench_function("build_request", |b| {
+        b.iter(|| {
+            build_request(
+                black_box("http://localhost"),
+                black_box("orgs/rustify/users"),
+                rustify::enums::RequestMethod::POST,
+                Some("dry_run=true".to_string()),
+                None,
+            )
+            .unwrap()
+        })
+    });
+    let body = users(1_000);
+    group.throughput(Throughput::Bytes(
+        build_body(&body, RequestType::JSON).unwrap().len() as u64,
+    ));
+    group.bench_function("build_body", |b| {
+        b.iter(|| build_body(black_box(&body), RequestType::JSON).unwrap())
+    });
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for n in [1, 10, 1_000] {
+        let body = serde_json::to_vec(&users(n)).unwrap();
+        group.throughput(Throughput::Bytes(body.len() as u64));
+        group.bench_function(format!("from_slice_{}", n), |b| {
+            b.iter(|| serde_json::from_slice::<Vec<User>>(black_box(&body)).unwrap())
+        });
+        group.bench_function(format!("parse_{}", n), |b| {
+            b.iter(|| parse::<Vec<User>>(black_box(&body), ResponseType::JSON).unwrap())
+        });
+        group.bench_function(format!("endpoint_result_{}", n), |b| {
+            b.iter_batched(
+                || Response::new(body.clone()),
+                |resp| {
+                    EndpointResult::<Vec<User>>::new(resp, ResponseType::JSON)
+                        .parse()
+                        .unwrap()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_exec(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    for n in [1, 100] {
+        let server = MockServer::start();
+        let response = serde_json::to_vec(&users(n)).unwrap();
+        server.mock(|when, then| {
+            when.method(POST).path("/orgs/rustify/users");
+            then.status(200).body(response);
+        });
+        let client = Client::default(&server.base_url());
+        let endpoint = endpoint(n);
+
+        c.bench_function(&format!("exec/exec_parse_{}", n), |b| {
+            b.iter(|| {
+                runtime.block_on(async { endpoint.exec(&client).await.unwrap().parse().unwrap() })
+            })
+        });
+    }
+}
+
+criterion_group!(benches, bench_build, bench_parse, bench_exec);
+criterion_main!(benches);
@@ -1,9 +1,9 @@
 use std::collections::HashMap;
 
 use derive_builder::Builder;
-use rustify::{Client, Endpoint, Wrapper};
-use rustify_derive::Endpoint;
-use serde::{de::DeserializeOwned, Deserialize};
+use rustify::{Client, Endpoint, Unwrap};
+use rustify_derive::{Endpoint, Wrapper};
+use serde::Deserialize;
 
 // While using a builder archetype for requests is not required, it's often the
 // cleanest way for building requests. For this endpoint it doesn't bring too
@@ -33,26 +33,23 @@ struct ListUsersRequest {
 //
 // Below we define the details of the wrapper that appears around paginated
 // responses. The form of the resulting data field is specified with a generic
-// and will be supplied when we call the endpoint. Endpoints have a special
-// `exec_wrap()` method which will automatically wrap the response from the
+// and will be supplied when we call the endpoint. Results have a special
+// `wrap()` method which will automatically wrap the response from the
 // endpoint in the given wrapper.
-#[derive(Debug, Deserialize)]
+//
+// Deriving `Wrapper` implements the trait for us. The field tagged with
+// `#[wrapper(value)]` is the one which encloses the actual response data.
+#[derive(Debug, Deserialize, Wrapper)]
 pub struct PaginationWrapper<T> {
     pub page: usize,
     pub per_page: usize,
     pub total: usize,
     pub total_pages: usize,
+    #[wrapper(value)]
     pub data: T,
     pub support: HashMap<String, String>,
 }
 
-// This is almost always the form that the implementation will take.
-// Unfortunately, Rust does not support associated types having a default
-// type set to a generic, so we must define it when we use it.
-impl<T: DeserializeOwned + Send + Sync> Wrapper for PaginationWrapper<T> {
-    type Value = T;
-}
-
 // Our endpoint returns a JSON array of objects which each contain information
 // about a user. We represent this by creating a `User` struct and then using
 // `Vec<User>` in the `response` parameter of the endpoint to inform rustify on
@@ -100,7 +97,7 @@ async fn main() {
         // wrapped in a pagination wrapper).
         Ok(r) => match r.wrap::<PaginationWrapper<_>>() {
             Ok(d) => {
-                d.data.iter().for_each(print_user);
+                d.into_value().iter().for_each(print_user);
             }
             Err(e) => println!("Error: {:#?}", e),
         },
@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+/// The error type returned by this crate
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Invalid operation {method} {path}: {message}")]
+    OperationError {
+        method: String,
+        path: String,
+        message: String,
+    },
+    #[error("Error parsing OpenAPI document")]
+    ParseError {
+        #[from]
+        source: serde_json::Error,
+    },
+    #[error("Error resolving reference {reference}")]
+    ReferenceError { reference: String },
+    #[error("Unsupported OpenAPI version {version}, only 3.x documents are supported")]
+    VersionError { version: String },
+}
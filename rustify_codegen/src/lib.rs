@@ -0,0 +1,433 @@
+//! Generates `#[derive(Endpoint)]` structs for the [rustify][1] crate from an
+//! OpenAPI 3 document in JSON.
+//!
+//! Every operation of the document becomes an endpoint named after its
+//! `operationId`, with its path and query parameters as `#[endpoint(skip)]`
+//! and `#[endpoint(query)]` fields and the properties of its JSON request
+//! body as `#[endpoint(body)]` fields. Request bodies which aren't JSON
+//! objects are sent as a single `#[endpoint(raw)]` field instead. The JSON
+//! body of the first successful response becomes the `response` of the
+//! endpoint, and every schema in the components of the document becomes a
+//! struct or type alias which endpoints refer to by name:
+//!
+//! ```
+//! let document = br##"{
+//!     "openapi": "3.0.3",
+//!     "info": { "title": "Pets", "version": "1.0.0" },
+//!     "paths": {
+//!         "/pets/{petId}": {
+//!             "get": {
+//!                 "operationId": "getPet",
+//!                 "parameters": [
+//!                     { "name": "petId", "in": "path", "required": true, "schema": { "type": "integer" } }
+//!                 ],
+//!                 "responses": {
+//!                     "200": {
+//!                         "description": "The pet",
+//!                         "content": {
+//!                             "application/json": { "schema": { "$ref": "#/components/schemas/Pet" } }
+//!                         }
+//!                     }
+//!                 }
+//!             }
+//!         }
+//!     },
+//!     "components": {
+//!         "schemas": {
+//!             "Pet": { "type": "object", "properties": { "name": { "type": "string" } } }
+//!         }
+//!     }
+//! }"##;
+//!
+//! let source = rustify_codegen::generate(document).unwrap();
+//! assert!(source.contains(r#"path = "pets/{self.pet_id}", method = "GET", response = "Pet""#));
+//! assert!(source.contains("pub struct GetPet {"));
+//! assert!(source.contains("pub struct Pet {"));
+//! ```
+//!
+//! The generated module uses `rustify_derive`, `serde`, and `serde_json` and
+//! is meant to be written by a build script and included with [include!], or
+//! to be checked in as the starting point of a hand-maintained SDK. Header
+//! and cookie parameters are skipped, as are request and response bodies
+//! composed with `allOf`, `anyOf`, or `oneOf`, which are typed as
+//! `serde_json::Value`.
+//!
+//! [1]: https://docs.rs/rustify/
+
+mod error;
+mod model;
+mod naming;
+
+use serde_json::{Map, Value};
+
+pub use error::Error;
+pub use model::{Endpoint, Field, Location, Model};
+
+/// The methods of a path item, in the order their operations are generated.
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// The maximum number of references followed when resolving a reference.
+const MAX_REFERENCES: usize = 16;
+
+/// Generates Rust source code from an OpenAPI 3 document.
+#[derive(Clone, Debug)]
+pub struct Generator {
+    document: Value,
+}
+
+impl Generator {
+    /// Returns a new [Generator] for the given document, failing if it isn't
+    /// an OpenAPI 3 document.
+    pub fn new(document: Value) -> Result<Self, Error> {
+        let version = document
+            .get("openapi")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if !version.starts_with("3.") {
+            return Err(Error::VersionError {
+                version: version.to_string(),
+            });
+        }
+        Ok(Generator { document })
+    }
+
+    /// Returns a new [Generator] for the given JSON document, see
+    /// [Generator::new].
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        Generator::new(serde_json::from_slice(data)?)
+    }
+
+    /// Returns the endpoints generated from the operations of the document.
+    pub fn endpoints(&self) -> Result<Vec<Endpoint>, Error> {
+        let mut endpoints = Vec::new();
+        for (path, item) in object(self.document.get("paths")) {
+            let item = self.resolve(item)?;
+            for method in METHODS {
+                if let Some(op) = item.get(method) {
+                    endpoints.push(self.endpoint(path, method, item, op)?);
+                }
+            }
+        }
+        Ok(endpoints)
+    }
+
+    /// Returns the models generated from the schemas in the components of
+    /// the document.
+    pub fn models(&self) -> Result<Vec<Model>, Error> {
+        let schemas = self
+            .document
+            .pointer("/components/schemas")
+            .and_then(Value::as_object);
+        let mut models = Vec::new();
+        for (name, schema) in schemas.into_iter().flatten() {
+            let name = naming::type_name(name);
+            let doc = doc(schema, &["description"]);
+            let model = match schema.get("properties") {
+                Some(properties) => Model::Struct {
+                    name,
+                    doc,
+                    fields: self.properties(schema, properties, None)?,
+                },
+                None => Model::Alias {
+                    name,
+                    doc,
+                    ty: self.rust_type(schema)?,
+                },
+            };
+            models.push(model);
+        }
+        Ok(models)
+    }
+
+    /// Returns the source code of a module containing all generated models
+    /// and endpoints.
+    pub fn generate(&self) -> Result<String, Error> {
+        let models = self.models()?;
+        let endpoints = self.endpoints()?;
+        let structs = models.iter().any(|m| matches!(m, Model::Struct { .. }));
+
+        let info = self.document.get("info");
+        let title = info.and_then(|i| i.get("title")).and_then(Value::as_str);
+        let version = info.and_then(|i| i.get("version")).and_then(Value::as_str);
+        let mut source = match (title, version) {
+            (Some(title), Some(version)) => format!(
+                "// Generated by rustify_codegen from {} {}, do not edit.\n",
+                title, version
+            ),
+            _ => "// Generated by rustify_codegen, do not edit.\n".to_string(),
+        };
+        let mut imports = Vec::new();
+        if !endpoints.is_empty() {
+            imports.push("use rustify_derive::Endpoint;");
+        }
+        match (structs, endpoints.iter().any(Endpoint::needs_serialize)) {
+            (true, _) => imports.push("use serde::{Deserialize, Serialize};"),
+            (false, true) => imports.push("use serde::Serialize;"),
+            (false, false) => {}
+        }
+        if !imports.is_empty() {
+            source.push('\n');
+            imports.iter().for_each(|i| {
+                source.push_str(i);
+                source.push('\n');
+            });
+        }
+        for model in &models {
+            source.push('\n');
+            source.push_str(&model.to_string());
+        }
+        for endpoint in &endpoints {
+            source.push('\n');
+            source.push_str(&endpoint.to_string());
+        }
+        Ok(source)
+    }
+
+    /// Follows the given value if it's a reference into the document.
+    fn resolve<'a>(&'a self, mut value: &'a Value) -> Result<&'a Value, Error> {
+        for _ in 0..MAX_REFERENCES {
+            let reference = match value.get("$ref").and_then(Value::as_str) {
+                Some(r) => r,
+                None => return Ok(value),
+            };
+            value = reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.document.pointer(pointer))
+                .ok_or_else(|| Error::ReferenceError {
+                    reference: reference.to_string(),
+                })?;
+        }
+        Err(Error::ReferenceError {
+            reference: value["$ref"].to_string(),
+        })
+    }
+
+    /// Returns the Rust type of the given schema.
+    fn rust_type(&self, schema: &Value) -> Result<String, Error> {
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(name) = reference.strip_prefix("#/components/schemas/") {
+                if !name.contains('/') {
+                    return Ok(naming::type_name(name));
+                }
+            }
+            return self.rust_type(self.resolve(schema)?);
+        }
+        let format = schema.get("format").and_then(Value::as_str);
+        let ty = match schema.get("type").and_then(Value::as_str) {
+            Some("string") => "String".to_string(),
+            Some("integer") if format == Some("int32") => "i32".to_string(),
+            Some("integer") => "i64".to_string(),
+            Some("number") if format == Some("float") => "f32".to_string(),
+            Some("number") => "f64".to_string(),
+            Some("boolean") => "bool".to_string(),
+            Some("array") => match schema.get("items") {
+                Some(items) => format!("Vec<{}>", self.rust_type(items)?),
+                None => "Vec<serde_json::Value>".to_string(),
+            },
+            Some("object") if schema.get("properties").is_none() => {
+                match schema.get("additionalProperties") {
+                    Some(values @ Value::Object(map)) if !map.is_empty() => format!(
+                        "std::collections::HashMap<String, {}>",
+                        self.rust_type(values)?
+                    ),
+                    _ => "serde_json::Value".to_string(),
+                }
+            }
+            _ => "serde_json::Value".to_string(),
+        };
+        match schema.get("nullable").and_then(Value::as_bool) {
+            Some(true) => Ok(format!("Option<{}>", ty)),
+            _ => Ok(ty),
+        }
+    }
+
+    /// Returns the fields generated from the properties of an object schema.
+    fn properties(
+        &self,
+        schema: &Value,
+        properties: &Value,
+        location: Option<Location>,
+    ) -> Result<Vec<Field>, Error> {
+        let required = schema.get("required").and_then(Value::as_array);
+        let mut fields = Vec::new();
+        for (name, property) in object(Some(properties)) {
+            let required = required.is_some_and(|r| r.iter().any(|r| r == name));
+            fields.push(self.field(name, property, required, location)?);
+        }
+        Ok(fields)
+    }
+
+    fn field(
+        &self,
+        name: &str,
+        schema: &Value,
+        required: bool,
+        location: Option<Location>,
+    ) -> Result<Field, Error> {
+        let ident = naming::field_name(name);
+        let ty = self.rust_type(schema)?;
+        Ok(Field {
+            rename: (ident != name).then(|| name.to_string()),
+            name: ident,
+            ty: match required || ty.starts_with("Option<") {
+                true => ty,
+                false => format!("Option<{}>", ty),
+            },
+            location,
+            doc: doc(self.resolve(schema)?, &["description"]),
+        })
+    }
+
+    fn endpoint(
+        &self,
+        path: &str,
+        method: &str,
+        item: &Value,
+        op: &Value,
+    ) -> Result<Endpoint, Error> {
+        let operation_id = op.get("operationId").and_then(Value::as_str);
+        let name = match operation_id {
+            Some(id) => naming::type_name(id),
+            None => naming::type_name(&format!("{} {}", method, path)),
+        };
+        let fail = |message: &str| Error::OperationError {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+
+        // Parameters of the operation override those of the path item
+        let mut parameters: Vec<&Value> = Vec::new();
+        for parameter in array(item.get("parameters")).chain(array(op.get("parameters"))) {
+            let parameter = self.resolve(parameter)?;
+            let key = (parameter.get("name"), parameter.get("in"));
+            parameters.retain(|p| (p.get("name"), p.get("in")) != key);
+            parameters.push(parameter);
+        }
+
+        let mut fields = Vec::new();
+        let mut template = path.trim_start_matches('/').to_string();
+        for parameter in parameters {
+            let name = parameter
+                .get("name")
+                .and_then(Value::as_str)
+                .ok_or_else(|| fail("parameter is missing its name"))?;
+            let required = parameter.get("required").and_then(Value::as_bool) == Some(true);
+            let schema = parameter.get("schema").unwrap_or(&Value::Null);
+            let field = match parameter.get("in").and_then(Value::as_str) {
+                Some("path") => self.field(name, schema, true, Some(Location::Path))?,
+                Some("query") => self.field(name, schema, required, Some(Location::Query))?,
+                _ => continue,
+            };
+            let mut field = Field {
+                doc: doc(parameter, &["description"]).or(field.doc),
+                ..field
+            };
+            // Path parameters are interpolated rather than serialized
+            if field.location == Some(Location::Path) {
+                template = template.replace(
+                    &format!("{{{}}}", name),
+                    &format!("{{self.{}}}", field.name),
+                );
+                field.rename = None;
+            }
+            fields.push(field);
+        }
+        if let Some(body) = op.get("requestBody") {
+            fields.extend(self.body(self.resolve(body)?)?);
+        }
+
+        let security = op.get("security").or_else(|| self.document.get("security"));
+        let scopes = array(security)
+            .next()
+            .map(|requirement| {
+                object(Some(requirement))
+                    .flat_map(|(_, scopes)| array(Some(scopes)))
+                    .filter_map(|s| s.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Endpoint {
+            name,
+            doc: doc(op, &["summary", "description"]),
+            path: template,
+            method: method.to_uppercase(),
+            operation_id: operation_id.map(String::from),
+            scopes,
+            deprecated: op.get("deprecated").and_then(Value::as_bool) == Some(true),
+            response: self.response(op)?,
+            fields,
+        })
+    }
+
+    /// Returns the fields generated from a request body.
+    fn body(&self, body: &Value) -> Result<Vec<Field>, Error> {
+        let schema = match json_schema(body) {
+            Some(schema) => self.resolve(schema)?,
+            None => return Ok(vec![raw_field()]),
+        };
+        match schema.get("properties") {
+            Some(properties) => self.properties(schema, properties, Some(Location::Body)),
+            None => Ok(vec![raw_field()]),
+        }
+    }
+
+    /// Returns the type of the JSON body of the first successful response.
+    fn response(&self, op: &Value) -> Result<Option<String>, Error> {
+        let success = object(op.get("responses"))
+            .filter(|(status, _)| status.starts_with('2'))
+            .min_by_key(|(status, _)| status.to_string());
+        match success {
+            Some((_, response)) => match json_schema(self.resolve(response)?) {
+                Some(schema) => Ok(Some(self.rust_type(schema)?)),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+/// Generates the source code of a module containing the models and endpoints
+/// of the given OpenAPI 3 document in JSON, see [Generator::generate].
+pub fn generate(document: &[u8]) -> Result<String, Error> {
+    Generator::from_slice(document)?.generate()
+}
+
+fn object(value: Option<&Value>) -> impl Iterator<Item = (&String, &Value)> {
+    value
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(Map::iter)
+}
+
+fn array(value: Option<&Value>) -> impl Iterator<Item = &Value> {
+    value.and_then(Value::as_array).into_iter().flatten()
+}
+
+/// Returns the first of the given keys which holds a non-empty string.
+fn doc(value: &Value, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .filter_map(|k| value.get(*k).and_then(Value::as_str))
+        .find(|d| !d.trim().is_empty())
+        .map(String::from)
+}
+
+/// Returns the schema of the JSON content of a request body or response.
+fn json_schema(body: &Value) -> Option<&Value> {
+    object(body.get("content"))
+        .find(|(ty, _)| *ty == "application/json" || ty.ends_with("+json"))
+        .and_then(|(_, content)| content.get("schema"))
+}
+
+fn raw_field() -> Field {
+    Field {
+        name: "body".to_string(),
+        rename: None,
+        ty: "Vec<u8>".to_string(),
+        location: Some(Location::Raw),
+        doc: None,
+    }
+}
@@ -0,0 +1,27 @@
+//! Writes the module generated from the OpenAPI document at the given path to
+//! standard output.
+
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: rustify-codegen <openapi.json>");
+            return ExitCode::FAILURE;
+        }
+    };
+    let result = std::fs::read(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| rustify_codegen::generate(&data).map_err(|e| e.to_string()));
+    match result {
+        Ok(source) => {
+            print!("{}", source);
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("Error generating code from {}: {}", path, e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::fmt;
+
+/// Where the value of a [Field] is sent when executing an [Endpoint].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    /// Interpolated into the path of the request
+    Path,
+    /// Serialized into the query string
+    Query,
+    /// Serialized as a property of the request body
+    Body,
+    /// Sent as the request body without serializing it
+    Raw,
+}
+
+/// A field of a generated [Endpoint] or [Model].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field in Rust
+    pub name: String,
+    /// The name of the field in the OpenAPI document if it differs from
+    /// [Field::name]
+    pub rename: Option<String>,
+    /// The Rust type of the field
+    pub ty: String,
+    /// Where the field is sent, only set for fields of an [Endpoint]
+    pub location: Option<Location>,
+    pub doc: Option<String>,
+}
+
+/// An `#[derive(Endpoint)]` struct generated from an operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub name: String,
+    pub doc: Option<String>,
+    /// The path in the format expected by the `path` parameter of the derive
+    /// macro
+    pub path: String,
+    pub method: String,
+    pub operation_id: Option<String>,
+    pub scopes: Vec<String>,
+    pub deprecated: bool,
+    /// The Rust type of a successful response, if it has a JSON body
+    pub response: Option<String>,
+    pub fields: Vec<Field>,
+}
+
+/// A type generated from a schema in the components of a document.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Model {
+    /// A struct generated from an object schema with properties
+    Struct {
+        name: String,
+        doc: Option<String>,
+        fields: Vec<Field>,
+    },
+    /// An alias generated from any other schema
+    Alias {
+        name: String,
+        doc: Option<String>,
+        ty: String,
+    },
+}
+
+impl Endpoint {
+    /// Returns whether the generated struct must derive `Serialize` for the
+    /// `serde` attributes of its fields to be accepted.
+    pub fn needs_serialize(&self) -> bool {
+        self.fields.iter().any(|f| f.rename.is_some())
+    }
+}
+
+fn write_doc(f: &mut fmt::Formatter<'_>, doc: &Option<String>, indent: &str) -> fmt::Result {
+    if let Some(doc) = doc {
+        for line in doc.trim().lines() {
+            match line.trim_end() {
+                "" => writeln!(f, "{}///", indent)?,
+                line => writeln!(f, "{}/// {}", indent, line)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_fields(f: &mut fmt::Formatter<'_>, fields: &[Field]) -> fmt::Result {
+    if fields.is_empty() {
+        return writeln!(f, " {{}}");
+    }
+    writeln!(f, " {{")?;
+    for field in fields {
+        write!(f, "{}", field)?;
+    }
+    writeln!(f, "}}")
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_doc(f, &self.doc, "    ")?;
+        match self.location {
+            Some(Location::Path) => writeln!(f, "    #[endpoint(skip)]")?,
+            Some(Location::Query) => writeln!(f, "    #[endpoint(query)]")?,
+            Some(Location::Body) => writeln!(f, "    #[endpoint(body)]")?,
+            Some(Location::Raw) => writeln!(f, "    #[endpoint(raw)]")?,
+            None => {}
+        }
+        if let Some(rename) = &self.rename {
+            writeln!(f, "    #[serde(rename = {:?})]", rename)?;
+        }
+        if self.location.is_none() && self.ty.starts_with("Option<") {
+            writeln!(f, "    #[serde(skip_serializing_if = \"Option::is_none\")]")?;
+        }
+        writeln!(f, "    pub {}: {},", self.name, self.ty)
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_doc(f, &self.doc, "")?;
+        match self.needs_serialize() {
+            true => writeln!(f, "#[derive(Clone, Debug, Endpoint, Serialize)]")?,
+            false => writeln!(f, "#[derive(Clone, Debug, Endpoint)]")?,
+        }
+        let mut params = vec![
+            format!("path = {:?}", self.path),
+            format!("method = {:?}", self.method),
+        ];
+        if let Some(response) = &self.response {
+            params.push(format!("response = {:?}", response));
+        }
+        if let Some(id) = &self.operation_id {
+            params.push(format!("operation_id = {:?}", id));
+        }
+        if !self.scopes.is_empty() {
+            let scopes = self.scopes.iter().map(|s| format!("{:?}", s));
+            params.push(format!(
+                "scopes = [{}]",
+                scopes.collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if self.deprecated {
+            params.push("deprecated = \"Deprecated by the API\"".to_string());
+        }
+        writeln!(f, "#[endpoint({})]", params.join(", "))?;
+        write!(f, "pub struct {}", self.name)?;
+        write_fields(f, &self.fields)
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Model::Struct { name, doc, fields } => {
+                write_doc(f, doc, "")?;
+                writeln!(f, "#[derive(Clone, Debug, Deserialize, Serialize)]")?;
+                write!(f, "pub struct {}", name)?;
+                write_fields(f, fields)
+            }
+            Model::Alias { name, doc, ty } => {
+                write_doc(f, doc, "")?;
+                writeln!(f, "pub type {} = {};", name, ty)
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+/// Words which can't be used as identifiers and are suffixed with an
+/// underscore instead.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
+];
+
+/// Splits a name into its words at separators and case boundaries, i.e.
+/// `petId`, `pet_id`, and `PetID` all become `["pet", "id"]`.
+fn words(name: &str) -> Vec<String> {
+    let chars: Vec<char> = name.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && match prev {
+                Some(p) if p.is_lowercase() || p.is_numeric() => true,
+                Some(p) if p.is_uppercase() => next.is_some_and(|n| n.is_lowercase()),
+                _ => false,
+            };
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+fn identifier(name: String) -> String {
+    match name.chars().next() {
+        None => "_".to_string(),
+        Some(c) if c.is_numeric() => format!("_{}", name),
+        Some(_) if KEYWORDS.contains(&name.as_str()) => format!("{}_", name),
+        Some(_) => name,
+    }
+}
+
+/// Returns the given name as an identifier for a type.
+pub fn type_name(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(c) => c.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect();
+    identifier(name)
+}
+
+/// Returns the given name as an identifier for a field.
+pub fn field_name(name: &str) -> String {
+    identifier(words(name).join("_"))
+}
@@ -1,6 +1,6 @@
-//! Provides a derive macro for easily implementing an `Endpoint` from the
-//! [rustify][1] crate. See the documentation for `rustify` for details on how
-//! to use this macro.
+//! Provides derive macros for easily implementing an `Endpoint` and a
+//! `Wrapper` from the [rustify][1] crate. See the documentation for `rustify`
+//! for details on how to use these macros.
 //!
 //! [1]: https://docs.rs/rustify/
 
@@ -11,11 +11,13 @@ extern crate proc_macro;
 mod error;
 mod params;
 mod parse;
+mod wrapper;
 
 use std::{collections::HashMap, convert::TryFrom};
 
 use error::Error;
 use params::Parameters;
+use parse::{ResponseVariant, StructAttribute};
 use proc_macro2::Span;
 use quote::quote;
 use regex::Regex;
@@ -27,6 +29,7 @@ const ATTR_NAME: &str = "endpoint";
 #[derive(Debug, PartialEq, Eq, Hash)]
 pub(crate) enum EndpointAttribute {
     Body,
+    Dual,
     Query,
     Raw,
     Skip,
@@ -39,6 +42,7 @@ impl TryFrom<&Meta> for EndpointAttribute {
         match m.path().get_ident() {
             Some(i) => match i.to_string().to_lowercase().as_str() {
                 "body" => Ok(EndpointAttribute::Body),
+                "dual" => Ok(EndpointAttribute::Dual),
                 "query" => Ok(EndpointAttribute::Query),
                 "raw" => Ok(EndpointAttribute::Raw),
                 "skip" => Ok(EndpointAttribute::Skip),
@@ -52,15 +56,93 @@ impl TryFrom<&Meta> for EndpointAttribute {
     }
 }
 
+/// Generates the `cache_policy` method from the `cache` parameter.
+///
+/// The value is either `no-store` or a time to live in the form `ttl=60s`,
+/// where the unit is one of `ms`, `s`, `m`, or `h` and defaults to seconds.
+fn gen_cache(value: &syn::LitStr) -> Result<proc_macro2::TokenStream, Error> {
+    let policy = value.value();
+    let invalid = || {
+        Error::new(
+            value.span(),
+            "Cache policy must be `no-store` or `ttl=<duration>`, i.e. `ttl=60s`",
+        )
+    };
+    let policy = match policy.trim() {
+        "no-store" => quote! { rustify::cache::CachePolicy::NoStore },
+        p => {
+            let ttl = p.strip_prefix("ttl=").ok_or_else(invalid)?;
+            let millis = parse_millis(ttl).ok_or_else(invalid)?;
+            quote! {
+                rustify::cache::CachePolicy::Ttl(std::time::Duration::from_millis(#millis))
+            }
+        }
+    };
+    Ok(quote! {
+        fn cache_policy(&self) -> rustify::cache::CachePolicy {
+            #policy
+        }
+    })
+}
+
+/// Parses a duration in the form `60s` into milliseconds, where the unit is
+/// one of `ms`, `s`, `m`, or `h` and defaults to seconds.
+fn parse_millis(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let amount = amount.parse::<u64>().ok()?;
+    match unit {
+        "ms" => Some(amount),
+        "" | "s" => amount.checked_mul(1_000),
+        "m" => amount.checked_mul(60_000),
+        "h" => amount.checked_mul(3_600_000),
+        _ => None,
+    }
+}
+
+/// Generates the `timeouts` method from the `connect_timeout`,
+/// `read_timeout`, and `total_timeout` parameters, each of which is a
+/// duration in the form `30s`.
+fn gen_timeouts(timeouts: [&Option<syn::LitStr>; 3]) -> Result<proc_macro2::TokenStream, Error> {
+    if timeouts.iter().all(|t| t.is_none()) {
+        return Ok(quote! {});
+    }
+    let mut values = Vec::new();
+    for timeout in timeouts {
+        values.push(match timeout {
+            Some(t) => {
+                let millis = parse_millis(&t.value()).ok_or_else(|| {
+                    Error::new(t.span(), "Timeouts must be durations, i.e. `30s`")
+                })?;
+                quote! { Some(std::time::Duration::from_millis(#millis)) }
+            }
+            None => quote! { None },
+        });
+    }
+    let [connect, read, total] = [&values[0], &values[1], &values[2]];
+    Ok(quote! {
+        fn timeouts(&self) -> rustify::deadline::Timeouts {
+            rustify::deadline::Timeouts {
+                connect: #connect,
+                read: #read,
+                total: #total,
+            }
+        }
+    })
+}
+
 /// Generates the path string for the endpoint.
 ///
 /// The string supplied by the end-user supports basic interpolation using curly
 /// braces. For example,
-/// ```
+/// ```ignore
 /// endpoint(path = "user/{self.name}")
 /// ```
 /// Should produce:
-/// ```
+/// ```ignore
 /// format!("user/{}", self.name);
 /// ```
 /// This is currently accomplished using a basic regular expression which
@@ -111,11 +193,12 @@ fn gen_path(path: &syn::LitStr) -> Result<proc_macro2::TokenStream, Error> {
 fn gen_query(
     fields: &HashMap<EndpointAttribute, Vec<Field>>,
     serde_attrs: &[Meta],
+    none_as_null: bool,
 ) -> proc_macro2::TokenStream {
     let query_fields = fields.get(&EndpointAttribute::Query);
     if let Some(v) = query_fields {
         // Construct query function
-        let temp = parse::fields_to_struct(v, serde_attrs);
+        let temp = parse::fields_to_struct(v, serde_attrs, none_as_null);
         quote! {
             fn query(&self) -> Result<Option<String>, ClientError> {
                 #temp
@@ -128,6 +211,18 @@ fn gen_query(
     }
 }
 
+/// Returns the field making up the request body if it's the only body field
+/// and is a patch document.
+fn patch_field(fields: &HashMap<EndpointAttribute, Vec<Field>>) -> Option<&Field> {
+    let body = fields
+        .get(&EndpointAttribute::Body)
+        .or_else(|| fields.get(&EndpointAttribute::Untagged))?;
+    match body.as_slice() {
+        [f] if parse::patch_request_type(&f.ty).is_some() => Some(f),
+        _ => None,
+    }
+}
+
 /// Generates the body method for generating the request body.
 ///
 /// The final result is determined by which attributes are present and/or
@@ -144,9 +239,24 @@ fn gen_query(
 ///   struct and then serialized into the request body depending on the request
 ///   type of the Endpoint.
 /// * If none of the above is true, the body method is not generated.
+///
+/// When the body consists of a single [MergePatch or JsonPatch][1] field, the
+/// field is serialized as the entire body.
+///
+/// [Option] fields which are [Option::None] are omitted from the body unless
+/// `none_as_null` is true or the field is tagged with
+/// `#[endpoint(serialize_none_as_null)]`, in which case they're serialized as
+/// `null`. Fields tagged with `#[endpoint(skip_none)]` are always omitted.
+/// Fields tagged with `#[endpoint(encrypt)]` are encrypted using the field
+/// cipher configured on the client executing the endpoint, see the
+/// [encryption module][2].
+///
+/// [1]: https://docs.rs/rustify/latest/rustify/patch/index.html
+/// [2]: https://docs.rs/rustify/latest/rustify/encryption/index.html
 fn gen_body(
     fields: &HashMap<EndpointAttribute, Vec<Field>>,
     serde_attrs: &[Meta],
+    none_as_null: bool,
 ) -> Result<proc_macro2::TokenStream, Error> {
     // Check for a raw field first
     if let Some(v) = fields.get(&EndpointAttribute::Raw) {
@@ -160,9 +270,17 @@ fn gen_body(
                 Ok(Some(self.#id.clone()))
             }
         })
+    // Then for a single patch document
+    } else if let Some(f) = patch_field(fields) {
+        let id = f.ident.clone().unwrap();
+        Ok(quote! {
+            fn body(&self) -> Result<Option<Vec<u8>>, ClientError> {
+                Ok(Some(build_body(&self.#id, Self::REQUEST_BODY_TYPE)?))
+            }
+        })
     // Then for any body fields
     } else if let Some(v) = fields.get(&EndpointAttribute::Body) {
-        let temp = parse::fields_to_struct(v, serde_attrs);
+        let temp = parse::fields_to_struct(v, serde_attrs, none_as_null);
         Ok(quote! {
             fn body(&self) -> Result<Option<Vec<u8>>, ClientError> {
                 #temp
@@ -172,7 +290,7 @@ fn gen_body(
         })
     // Then for any untagged fields
     } else if let Some(v) = fields.get(&EndpointAttribute::Untagged) {
-        let temp = parse::fields_to_struct(v, serde_attrs);
+        let temp = parse::fields_to_struct(v, serde_attrs, none_as_null);
         Ok(quote! {
             fn body(&self) -> Result<Option<Vec<u8>>, ClientError> {
                 #temp
@@ -186,6 +304,286 @@ fn gen_body(
     }
 }
 
+/// Generates the parse_response method for unwrapping the response.
+///
+/// When a wrapper is given, the response body is first deserialized into the
+/// wrapper and then unwrapped into the final response type. Wrappers without
+/// any generic arguments are assumed to be generic over the response type (for
+/// example, `wrapper = "ApiEnvelope"` becomes `ApiEnvelope<Response>`). Nested
+/// wrappers are unwrapped through every layer, so `wrapper = "Outer<Inner>"`
+/// becomes `Outer<Inner<Response>>` and is unwrapped twice. If no wrapper is
+/// given the parse_response method is not generated.
+fn gen_parse(wrapper: &Option<syn::Type>, response: &syn::Type) -> proc_macro2::TokenStream {
+    let wrapper = match wrapper {
+        Some(w) => w,
+        None => return quote! {},
+    };
+
+    let (wrapper, layers) = nest_wrapper(wrapper, response);
+    let unwrap = (0..layers).map(|_| {
+        quote! {
+            let value = rustify::endpoint::Unwrap::into_result(value)?;
+        }
+    });
+
+    quote! {
+        fn parse_response(
+            resp: &rustify::__private::http::Response<Vec<u8>>,
+            ty: ResponseType,
+        ) -> Result<Self::Response, ClientError> {
+            let value: #wrapper = rustify::http::parse(resp.body(), ty)?;
+            #(#unwrap)*
+            Ok(value)
+        }
+    }
+}
+
+/// Generates the parse_response method for deserializing only the subtree at
+/// the given dotted path of the response.
+fn gen_extract(path: &syn::LitStr) -> proc_macro2::TokenStream {
+    quote! {
+        fn parse_response(
+            resp: &rustify::__private::http::Response<Vec<u8>>,
+            ty: ResponseType,
+        ) -> Result<Self::Response, ClientError> {
+            rustify::http::parse_extract(resp.body(), ty, #path)
+        }
+    }
+}
+
+/// Returns the full type of the given wrapper and the number of layers
+/// enclosing the response type.
+///
+/// The single generic argument of each wrapper is followed until either the
+/// response type or a wrapper without generic arguments is reached, the
+/// latter of which is made generic over the response type.
+fn nest_wrapper(wrapper: &syn::Type, response: &syn::Type) -> (syn::Type, usize) {
+    if quote! { #wrapper }.to_string() == quote! { #response }.to_string() {
+        return (wrapper.clone(), 0);
+    }
+
+    let mut tp = match wrapper {
+        syn::Type::Path(tp) => tp.clone(),
+        _ => return (wrapper.clone(), 1),
+    };
+    let last = match tp.path.segments.last_mut() {
+        Some(s) => s,
+        None => return (wrapper.clone(), 1),
+    };
+    let layers = match &mut last.arguments {
+        syn::PathArguments::None => {
+            last.arguments = syn::PathArguments::AngleBracketed(syn::parse_quote! { <#response> });
+            1
+        }
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &mut args.args[0]
+        {
+            syn::GenericArgument::Type(inner) => {
+                let (nested, layers) = nest_wrapper(inner, response);
+                *inner = nested;
+                layers + 1
+            }
+            _ => 1,
+        },
+        _ => 1,
+    };
+    (syn::Type::Path(tp), layers)
+}
+
+/// Returns the name of the enum variant used for the given response variant type.
+fn variant_name(ty: &syn::Type) -> Option<Ident> {
+    match ty {
+        syn::Type::Path(tp) => tp.path.segments.last().map(|s| s.ident.clone()),
+        _ => None,
+    }
+}
+
+/// Generates an enum for endpoints which declare response variants.
+///
+/// The enum is named after the endpoint with a `Response` suffix and contains a
+/// variant for each response variant. Variants are named after the last
+/// segment of their type, for example, `200 = "models::User"` produces a
+/// `User(User)` variant. The enum implements `Deserialize` only to satisfy the
+/// bound on `Endpoint::Response`; the status code or discriminator determines
+/// which variant is parsed.
+///
+/// Returns the enum definition along with its type or [None] if the endpoint
+/// doesn't declare any response variants.
+fn gen_responses(
+    id: &Ident,
+    vis: &syn::Visibility,
+    responses: &[ResponseVariant],
+) -> Result<Option<(proc_macro2::TokenStream, syn::Type)>, Error> {
+    if responses.is_empty() {
+        return Ok(None);
+    }
+
+    let enum_id = Ident::new(format!("{}Response", id).as_str(), id.span());
+    let mut variants = Vec::<Ident>::new();
+    for response in responses {
+        match variant_name(&response.ty) {
+            Some(v) if !variants.contains(&v) => variants.push(v),
+            _ => {
+                return Err(Error::new(
+                    response.ty.span(),
+                    "Unable to derive a unique variant name from this type, consider using a type alias",
+                ))
+            }
+        }
+    }
+
+    let tys = responses.iter().map(|r| &r.ty);
+    let doc = format!("The possible responses returned by [{}].", id);
+    let def = quote! {
+        #[doc = #doc]
+        #[derive(rustify::__private::serde::Deserialize)]
+        #[serde(crate = "rustify::__private::serde", untagged)]
+        #vis enum #enum_id {
+            #(#variants(#tys),)*
+        }
+    };
+
+    Ok(Some((def, syn::parse_quote! { #enum_id })))
+}
+
+/// Generates the parse_response method for endpoints with response variants.
+///
+/// Without a discriminator, the status code of the response determines which
+/// variant of the enum created by [gen_responses] the body is deserialized
+/// into. With a discriminator, the body is first parsed into a generic value
+/// and the variant is determined by the string value of the discriminator
+/// field. Any other status code or discriminator value results in an error.
+fn gen_variant_parse(
+    response: &syn::Type,
+    responses: &[ResponseVariant],
+    discriminator: &Option<syn::LitStr>,
+) -> proc_macro2::TokenStream {
+    let keys = responses.iter().map(|r| &r.key);
+    let variants = responses.iter().map(|r| variant_name(&r.ty));
+
+    let body = match discriminator {
+        Some(field) => quote! {
+            let (value, variant) = rustify::http::parse_discriminated(resp.body(), ty, #field)?;
+            match variant.as_str() {
+                #(#keys => Ok(#response::#variants(rustify::http::from_value(value)?)),)*
+                _ => Err(ClientError::ResponseDiscriminatorError {
+                    field: #field.to_string(),
+                    value: Some(variant),
+                    content: String::from_utf8(resp.body().to_vec()).ok(),
+                }),
+            }
+        },
+        None => quote! {
+            match resp.status().as_u16() {
+                #(#keys => Ok(#response::#variants(rustify::http::parse(resp.body(), ty)?)),)*
+                code => Err(ClientError::ResponseStatusError {
+                    code,
+                    content: String::from_utf8(resp.body().to_vec()).ok(),
+                }),
+            }
+        },
+    };
+
+    quote! {
+        fn parse_response(
+            resp: &rustify::__private::http::Response<Vec<u8>>,
+            ty: ResponseType,
+        ) -> Result<Self::Response, ClientError> {
+            #body
+        }
+    }
+}
+
+/// Returns the names of the fields interpolated into the given path, i.e.
+/// `name` for `user/{self.name}`.
+fn path_fields(path: &syn::LitStr) -> Vec<String> {
+    let re = Regex::new(r"self\.([A-Za-z_][A-Za-z0-9_]*)").unwrap();
+    let args = Regex::new(r"\{(.*?)\}").unwrap();
+    args.captures_iter(path.value().as_str())
+        .flat_map(|arg| {
+            re.captures_iter(&arg[1])
+                .map(|cap| cap[1].to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Generates `PartialEq`, `Eq`, and `Hash` implementations which only
+/// consider the fields making up the request.
+///
+/// These are the fields interpolated into the path, the query fields, and the
+/// fields making up the body as determined by [gen_body]. Fields which are
+/// skipped and not part of the path are ignored, so two endpoints which would
+/// send the same request compare equal and have the same hash. Every
+/// considered field must implement `Eq` and `Hash`.
+fn gen_eq(
+    id: &Ident,
+    data: &syn::Data,
+    generics: &Generics,
+    path: &syn::LitStr,
+    fields: &HashMap<EndpointAttribute, Vec<Field>>,
+) -> proc_macro2::TokenStream {
+    let body = [
+        EndpointAttribute::Raw,
+        EndpointAttribute::Body,
+        EndpointAttribute::Untagged,
+    ]
+    .iter()
+    .find_map(|a| fields.get(a));
+    let mut names = path_fields(path);
+    for f in fields
+        .get(&EndpointAttribute::Query)
+        .into_iter()
+        .chain(body)
+        .flatten()
+    {
+        names.extend(f.ident.as_ref().map(|i| i.to_string()));
+    }
+
+    // Keep the order of the struct fields
+    let considered = match data {
+        syn::Data::Struct(data) => data
+            .fields
+            .iter()
+            .filter(|f| {
+                f.ident
+                    .as_ref()
+                    .is_some_and(|i| names.contains(&i.to_string()))
+            })
+            .collect::<Vec<_>>(),
+        _ => Vec::new(),
+    };
+    let ids = considered
+        .iter()
+        .map(|f| f.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+
+    let mut generics = generics.clone();
+    let where_clause = generics.make_where_clause();
+    for f in considered.iter() {
+        let ty = &f.ty;
+        where_clause
+            .predicates
+            .push(syn::parse_quote! { #ty: ::core::cmp::Eq + ::core::hash::Hash });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::core::cmp::PartialEq for #id #ty_generics #where_clause {
+            fn eq(&self, other: &Self) -> bool {
+                true #(&& self.#ids == other.#ids)*
+            }
+        }
+
+        impl #impl_generics ::core::cmp::Eq for #id #ty_generics #where_clause {}
+
+        impl #impl_generics ::core::hash::Hash for #id #ty_generics #where_clause {
+            fn hash<__H: ::core::hash::Hasher>(&self, state: &mut __H) {
+                #(::core::hash::Hash::hash(&self.#ids, state);)*
+            }
+        }
+    }
+}
+
 /// Generates `builder()` and `exec_*` helper methods for use with
 /// `derive_builder`.
 ///
@@ -209,21 +607,21 @@ fn gen_builder(id: &Ident, generics: &Generics) -> proc_macro2::TokenStream {
 
 /// Parses parameters passed into the `endpoint` attribute attached to the
 /// struct.
-fn parse_params(attr: &Meta) -> Result<Parameters, Error> {
+fn parse_params(attr: StructAttribute) -> Result<Parameters, Error> {
     // Parse the attribute as a key/value pair list
-    let kv = parse::attr_kv(attr)?;
+    let kv = parse::attr_kv(&attr.meta)?;
 
     // Create map from key/value pair list
     let map = parse::to_map(&kv)?;
 
     // Convert map to Parameters
-    params::Parameters::new(map)
+    params::Parameters::new(map, attr.responses, attr.scopes)
 }
 
 /// Implements `Endpoint` on the provided struct.
 fn endpoint_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
     // Parse `endpoint` attributes attached to input struct
-    let attrs = match parse::attributes(&s.ast().attrs, ATTR_NAME) {
+    let mut attrs = match parse::struct_attributes(&s.ast().attrs, ATTR_NAME) {
         Ok(v) => v,
         Err(e) => return e.into_tokens(),
     };
@@ -257,25 +655,39 @@ fn endpoint_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
     }
 
     // Parse endpoint attribute parameters
-    let params = match parse_params(&attrs[0]) {
+    let params = match parse_params(attrs.remove(0)) {
         Ok(v) => v,
         Err(e) => return e.into_tokens(),
     };
 
     let path = params.path;
     let method = params.method;
-    let response = params.response;
-    let request_type = params.request_type;
-    let response_type = params.response_type;
     let id = &s.ast().ident;
 
+    // Generate equality and hashing over the request fields
+    let eq = match params.eq {
+        true => gen_eq(id, &s.ast().data, &s.ast().generics, &path, &field_attrs),
+        false => quote! {},
+    };
+
+    // Generate an enum for response variants and use it as the response
+    let (responses, response) = match gen_responses(id, &s.ast().vis, &params.responses) {
+        Ok(Some((def, ty))) => (def, ty),
+        Ok(None) => (quote! {}, params.response),
+        Err(e) => return e.into_tokens(),
+    };
+    let request_type = params.request_type.unwrap_or_else(|| {
+        let ty = patch_field(&field_attrs)
+            .filter(|_| !field_attrs.contains_key(&EndpointAttribute::Raw))
+            .and_then(|f| parse::patch_request_type(&f.ty))
+            .unwrap_or("JSON");
+        syn::parse_str(ty).unwrap()
+    });
+    let response_type = params.response_type;
+
     // Find serde attributes
     let serde_attrs = parse::attributes(&s.ast().attrs, "serde");
-    let serde_attrs = if let Ok(v) = serde_attrs {
-        v
-    } else {
-        Vec::<Meta>::new()
-    };
+    let serde_attrs = serde_attrs.unwrap_or_default();
 
     // Generate path string
     let path = match gen_path(&path) {
@@ -284,14 +696,97 @@ fn endpoint_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
     };
 
     // Generate query function
-    let query = gen_query(&field_attrs, &serde_attrs);
+    let query = gen_query(&field_attrs, &serde_attrs, params.serialize_none_as_null);
 
     // Generate body function
-    let body = match gen_body(&field_attrs, &serde_attrs) {
+    let body = match gen_body(&field_attrs, &serde_attrs, params.serialize_none_as_null) {
         Ok(d) => d,
         Err(e) => return e.into_tokens(),
     };
 
+    // Generate response parsing function
+    let parse = match (params.responses.is_empty(), &params.extract) {
+        (true, Some(path)) => gen_extract(path),
+        (true, None) => gen_parse(&params.wrapper, &response),
+        (false, _) => gen_variant_parse(&response, &params.responses, &params.discriminator),
+    };
+
+    // Generate priority function
+    let priority = match params.priority {
+        Some(p) => quote! {
+            fn priority(&self) -> RequestPriority {
+                RequestPriority::#p
+            }
+        },
+        None => quote! {},
+    };
+
+    // Generate cache policy function
+    let cache = match params.cache.as_ref().map(gen_cache) {
+        Some(Ok(c)) => c,
+        Some(Err(e)) => return e.into_tokens(),
+        None => quote! {},
+    };
+
+    // Generate timeouts function
+    let timeouts = match gen_timeouts([
+        &params.connect_timeout,
+        &params.read_timeout,
+        &params.total_timeout,
+    ]) {
+        Ok(t) => t,
+        Err(e) => return e.into_tokens(),
+    };
+
+    // Generate deprecation note function
+    let deprecated = match &params.deprecated {
+        Some(d) => quote! {
+            fn deprecated(&self) -> Option<&'static str> {
+                Some(#d)
+            }
+        },
+        None => quote! {},
+    };
+
+    // Generate metadata function
+    #[cfg(feature = "docs")]
+    let doc = parse::doc_comment(&s.ast().attrs);
+    #[cfg(not(feature = "docs"))]
+    let doc: Option<String> = None;
+    let metadata = match (&params.operation_id, &params.scopes, &doc) {
+        (None, None, None) => quote! {},
+        (operation_id, scopes, doc) => {
+            let operation_id = match operation_id {
+                Some(o) => quote! { Some(#o) },
+                None => quote! { None },
+            };
+            let scopes = scopes.iter().flatten();
+            let doc = match doc {
+                Some(d) => quote! { Some(#d) },
+                None => quote! { None },
+            };
+            quote! {
+                fn metadata(&self) -> rustify::metadata::EndpointMetadata {
+                    rustify::metadata::EndpointMetadata {
+                        operation_id: #operation_id,
+                        scopes: &[#(#scopes),*],
+                        doc: #doc,
+                    }
+                }
+            }
+        }
+    };
+
+    // Generate body on GET opt-in
+    let allow_body_on_get = match params.allow_body_on_get {
+        true => quote! {
+            fn allow_body_on_get(&self) -> bool {
+                true
+            }
+        },
+        false => quote! {},
+    };
+
     // Generate helper functions when deriving Builder
     let builder = match params.builder {
         true => gen_builder(&s.ast().ident, &s.ast().generics),
@@ -302,17 +797,24 @@ fn endpoint_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
     let (impl_generics, ty_generics, where_clause) = s.ast().generics.split_for_impl();
 
     // Generate Endpoint implementation
-    let const_name = format!("_DERIVE_Endpoint_FOR_{}", id);
-    let const_ident = Ident::new(const_name.as_str(), Span::call_site());
     quote! {
-        const #const_ident: () = {
+        #responses
+
+        const _: () = {
             use rustify::__private::serde::Serialize;
             use rustify::http::{build_body, build_query};
             use rustify::client::Client;
             use rustify::endpoint::Endpoint;
-            use rustify::enums::{RequestMethod, RequestType, ResponseType};
+            use rustify::enums::{RequestMethod, RequestPriority, RequestType, ResponseType};
             use rustify::errors::ClientError;
 
+            // Explains why an invalid response type doesn't satisfy the
+            // trait bounds of `Endpoint::Response`
+            #[allow(dead_code)]
+            fn __assert_response #impl_generics () #where_clause {
+                rustify::__private::assert_response::<#response>();
+            }
+
             impl #impl_generics Endpoint for #id #ty_generics #where_clause {
                 type Response = #response;
                 const REQUEST_BODY_TYPE: RequestType = RequestType::#request_type;
@@ -330,11 +832,28 @@ fn endpoint_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
 
 
                 #body
+
+                #priority
+
+                #cache
+
+                #timeouts
+
+                #deprecated
+
+                #metadata
+
+                #allow_body_on_get
+
+                #parse
             }
 
             #builder
+
+            #eq
         };
     }
 }
 
 synstructure::decl_derive!([Endpoint, attributes(endpoint)] => endpoint_derive);
+synstructure::decl_derive!([Wrapper, attributes(wrapper)] => wrapper::wrapper_derive);
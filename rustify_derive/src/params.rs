@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 
-use crate::Error;
+use crate::{parse::ResponseVariant, Error};
 use proc_macro2::Span;
-use syn::{Expr, Ident, LitStr, Type};
+use syn::{Expr, Ident, Lit, LitStr, Type};
 
 /// Used for building the parameter list for the derive function
 #[derive(Default, Debug)]
@@ -13,6 +13,20 @@ pub struct ParametersBuilder {
     pub request_type: Option<Expr>,
     pub response_type: Option<Expr>,
     pub builder: Option<bool>,
+    pub eq: Option<bool>,
+    pub wrapper: Option<Type>,
+    pub extract: Option<LitStr>,
+    pub discriminator: Option<LitStr>,
+    pub priority: Option<Expr>,
+    pub cache: Option<LitStr>,
+    pub connect_timeout: Option<LitStr>,
+    pub read_timeout: Option<LitStr>,
+    pub total_timeout: Option<LitStr>,
+    pub deprecated: Option<LitStr>,
+    pub operation_id: Option<LitStr>,
+    pub scopes: Option<Vec<LitStr>>,
+    pub allow_body_on_get: Option<bool>,
+    pub serialize_none_as_null: Option<bool>,
 }
 
 /// Represents all valid parameters that can be passed to the derive function
@@ -21,9 +35,24 @@ pub struct Parameters {
     pub path: LitStr,
     pub method: Expr,
     pub response: Type,
-    pub request_type: Expr,
+    pub request_type: Option<Expr>,
     pub response_type: Expr,
     pub builder: bool,
+    pub eq: bool,
+    pub wrapper: Option<Type>,
+    pub extract: Option<LitStr>,
+    pub discriminator: Option<LitStr>,
+    pub priority: Option<Expr>,
+    pub cache: Option<LitStr>,
+    pub connect_timeout: Option<LitStr>,
+    pub read_timeout: Option<LitStr>,
+    pub total_timeout: Option<LitStr>,
+    pub deprecated: Option<LitStr>,
+    pub operation_id: Option<LitStr>,
+    pub scopes: Option<Vec<LitStr>>,
+    pub allow_body_on_get: bool,
+    pub serialize_none_as_null: bool,
+    pub responses: Vec<ResponseVariant>,
 }
 
 impl Parameters {
@@ -32,9 +61,19 @@ impl Parameters {
     ///
     /// The only required parameter is `path` and not providing it will cause
     /// the function to fail. All other parameters are optional and will have
-    /// sane defaults provided if they are not found in the map.
-    pub fn new(map: HashMap<Ident, LitStr>) -> Result<Parameters, Error> {
-        let mut builder = ParametersBuilder::default();
+    /// sane defaults provided if they are not found in the map. A `response`
+    /// cannot be given when the endpoint also declares response variants,
+    /// which must be keyed by strings when a `discriminator` is given and by
+    /// status codes otherwise.
+    pub fn new(
+        map: HashMap<Ident, LitStr>,
+        responses: Vec<ResponseVariant>,
+        scopes: Option<Vec<LitStr>>,
+    ) -> Result<Parameters, Error> {
+        let mut builder = ParametersBuilder {
+            scopes,
+            ..Default::default()
+        };
         for key in map.keys() {
             match key.to_string().as_str() {
                 "path" => builder.path = Some(map[key].clone()),
@@ -53,12 +92,116 @@ impl Parameters {
                 "builder" => {
                     builder.builder = Some(true);
                 }
+                "eq" => {
+                    builder.eq = Some(true);
+                }
+                "wrapper" => {
+                    builder.wrapper = Some(parse(&map[key])?);
+                }
+                "extract" => {
+                    builder.extract = Some(map[key].clone());
+                }
+                "discriminator" => {
+                    builder.discriminator = Some(map[key].clone());
+                }
+                "priority" => {
+                    builder.priority = Some(parse(&map[key])?);
+                }
+                "cache" => {
+                    builder.cache = Some(map[key].clone());
+                }
+                "connect_timeout" => {
+                    builder.connect_timeout = Some(map[key].clone());
+                }
+                "read_timeout" => {
+                    builder.read_timeout = Some(map[key].clone());
+                }
+                "total_timeout" => {
+                    builder.total_timeout = Some(map[key].clone());
+                }
+                "deprecated" => {
+                    builder.deprecated = Some(map[key].clone());
+                }
+                "operation_id" => {
+                    builder.operation_id = Some(map[key].clone());
+                }
+                "scopes" => {
+                    return Err(Error::new(
+                        map[key].span(),
+                        "Scopes must be a list of string literals, i.e. `scopes = [\"repo:read\"]`",
+                    ));
+                }
+                "allow_body_on_get" => {
+                    builder.allow_body_on_get = Some(true);
+                }
+                "serialize_none_as_null" => {
+                    builder.serialize_none_as_null = Some(true);
+                }
                 _ => {
                     return Err(Error::new(key.span(), "Unknown parameter"));
                 }
             }
         }
 
+        if let Some(r) = responses.first() {
+            if builder.response.is_some() {
+                return Err(Error::new(
+                    r.key.span(),
+                    "Cannot define both a response and response variants",
+                ));
+            }
+            if builder.wrapper.is_some() {
+                return Err(Error::new(
+                    r.key.span(),
+                    "Cannot define both a wrapper and response variants",
+                ));
+            }
+            if builder.extract.is_some() {
+                return Err(Error::new(
+                    r.key.span(),
+                    "Cannot define both an extract path and response variants",
+                ));
+            }
+        }
+        if let (Some(e), Some(_)) = (&builder.extract, &builder.wrapper) {
+            return Err(Error::new(
+                e.span(),
+                "Cannot define both an extract path and a wrapper",
+            ));
+        }
+        if let Some(e) = &builder.extract {
+            if e.value().split('.').any(str::is_empty) {
+                return Err(Error::new(
+                    e.span(),
+                    "Extract paths cannot have empty segments",
+                ));
+            }
+        }
+        if let Some(r) = &builder.response {
+            crate::parse::owned_response(r)?;
+        }
+        for r in responses.iter() {
+            crate::parse::owned_response(&r.ty)?;
+            match (&r.key, &builder.discriminator) {
+                (Lit::Int(_), None) | (Lit::Str(_), Some(_)) => {}
+                (_, None) => {
+                    return Err(Error::new(r.key.span(), "Status codes must be integers"));
+                }
+                (_, Some(_)) => {
+                    return Err(Error::new(
+                        r.key.span(),
+                        "Discriminator values must be string literals",
+                    ));
+                }
+            }
+        }
+        if let (Some(d), true) = (&builder.discriminator, responses.is_empty()) {
+            return Err(Error::new(
+                d.span(),
+                "A discriminator requires defining response variants",
+            ));
+        }
+
         let params = Parameters {
             path: match builder.path {
                 Some(p) => p,
@@ -75,13 +218,26 @@ impl Parameters {
             response: builder
                 .response
                 .unwrap_or_else(|| syn::parse_str("()").unwrap()),
-            request_type: builder
-                .request_type
-                .unwrap_or_else(|| syn::parse_str("JSON").unwrap()),
+            request_type: builder.request_type,
             response_type: builder
                 .response_type
                 .unwrap_or_else(|| syn::parse_str("JSON").unwrap()),
             builder: builder.builder.unwrap_or(false),
+            eq: builder.eq.unwrap_or(false),
+            wrapper: builder.wrapper,
+            extract: builder.extract,
+            discriminator: builder.discriminator,
+            priority: builder.priority,
+            cache: builder.cache,
+            connect_timeout: builder.connect_timeout,
+            read_timeout: builder.read_timeout,
+            total_timeout: builder.total_timeout,
+            deprecated: builder.deprecated,
+            operation_id: builder.operation_id,
+            scopes: builder.scopes,
+            allow_body_on_get: builder.allow_body_on_get.unwrap_or(false),
+            serialize_none_as_null: builder.serialize_none_as_null.unwrap_or(false),
+            responses,
         };
 
         Ok(params)
@@ -5,9 +5,27 @@ use std::{
 
 use crate::{EndpointAttribute, Error};
 use syn::{
-    spanned::Spanned, Attribute, Field, Ident, LitStr, Meta, MetaNameValue, NestedMeta, Type,
+    parse::ParseStream, punctuated::Punctuated, spanned::Spanned, Attribute, Field, Ident, Lit,
+    LitStr, Meta, MetaList, MetaNameValue, NestedMeta, Token, Type,
 };
 
+/// A status code or discriminator value mapped to the type the response body
+/// is deserialized into.
+#[derive(Debug)]
+pub(crate) struct ResponseVariant {
+    pub key: Lit,
+    pub ty: Type,
+}
+
+/// An `endpoint` attribute attached to a struct along with the parameters
+/// which cannot be parsed as a [Meta], see [struct_attributes].
+#[derive(Debug)]
+pub(crate) struct StructAttribute {
+    pub meta: Meta,
+    pub responses: Vec<ResponseVariant>,
+    pub scopes: Option<Vec<LitStr>>,
+}
+
 /// Returns all [Meta] values contained in a [Meta::List].
 ///
 /// For example:
@@ -46,15 +64,22 @@ pub(crate) fn attr_list(attr: &Meta) -> Result<Vec<Meta>, Error> {
 /// ```
 /// #[endpoint(path = "my/path", method = "POST")]
 /// ```
-/// Would return individual [MetaNameValue] values for `path` and `method`. This
-/// function fails if the [Meta::List] is empty, contains literals, or cannot
-/// be parsed as name/value pairs.
+/// Would return individual [MetaNameValue] values for `path` and `method`.
+/// Flags without a value (i.e. `builder`) are treated as having the value
+/// `"true"`. This function fails if the [Meta::List] is empty, contains
+/// literals, or cannot be parsed as name/value pairs.
 pub(crate) fn attr_kv(attr: &Meta) -> Result<Vec<MetaNameValue>, Error> {
     let meta_list = attr_list(attr)?;
     let mut result = Vec::<MetaNameValue>::new();
     for meta in meta_list.iter() {
         if let syn::Meta::NameValue(nv_meta) = meta {
             result.push(nv_meta.clone());
+        } else if let syn::Meta::Path(path) = meta {
+            result.push(MetaNameValue {
+                path: path.clone(),
+                eq_token: Default::default(),
+                lit: Lit::Str(LitStr::new("true", path.span())),
+            });
         } else {
             return Err(Error::new(
                 attr.span(),
@@ -96,12 +121,102 @@ pub(crate) fn attributes(attrs: &[Attribute], name: &str) -> Result<Vec<Meta>, E
     let mut result = Vec::<Meta>::new();
     for attr in attrs.iter() {
         let meta = attr.parse_meta().map_err(Error::from)?;
-        match meta.path().is_ident(name) {
-            true => {
-                result.push(meta);
-            }
-            false => {}
+        if meta.path().is_ident(name) {
+            result.push(meta);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Searches a list of [Attribute]'s and returns any matching [crate::ATTR_NAME]
+/// along with any response variants and scopes contained within them.
+///
+/// This behaves like [attributes] except that `response(...)` and
+/// `scopes = [...]` lists are extracted from the attribute before it's
+/// converted into a [Meta]. For example:
+/// ```ignore
+/// #[endpoint(path = "my/path", response(200 = "User", 202 = "Job"), scopes = ["user:read"])]
+/// ```
+/// Would return a [Meta] containing only `path` along with a
+/// [ResponseVariant] for both `200` and `202` and the scope `user:read`. This
+/// is necessary because literal keys and arrays cannot be parsed as a [Meta].
+pub(crate) fn struct_attributes(
+    attrs: &[Attribute],
+    name: &str,
+) -> Result<Vec<StructAttribute>, Error> {
+    let mut result = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident(name)) {
+        // Defer to the usual parsing when the attribute isn't a list
+        if attr.tokens.is_empty() {
+            result.push(StructAttribute {
+                meta: attr.parse_meta().map_err(Error::from)?,
+                responses: Vec::new(),
+                scopes: None,
+            });
+            continue;
         }
+
+        let mut responses = Vec::<ResponseVariant>::new();
+        let mut scopes = None;
+        let nested = attr
+            .parse_args_with(|input: ParseStream| {
+                let mut nested = Punctuated::<NestedMeta, Token![,]>::new();
+                while !input.is_empty() {
+                    if input.peek(syn::Ident)
+                        && input.peek2(Token![=])
+                        && input.peek3(syn::token::Bracket)
+                        && input.fork().parse::<Ident>()? == "scopes"
+                    {
+                        input.parse::<Ident>()?;
+                        input.parse::<Token![=]>()?;
+                        let content;
+                        syn::bracketed!(content in input);
+                        let list = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+                        scopes = Some(list.into_iter().collect());
+                    } else if input.peek(syn::Ident) && input.peek2(syn::token::Paren) {
+                        let fork = input.fork();
+                        if fork.parse::<Ident>()? == "response" {
+                            input.parse::<Ident>()?;
+                            let content;
+                            syn::parenthesized!(content in input);
+                            while !content.is_empty() {
+                                let key: Lit = content.parse()?;
+                                content.parse::<Token![=]>()?;
+                                let ty: LitStr = content.parse()?;
+                                responses.push(ResponseVariant {
+                                    key,
+                                    ty: ty.parse()?,
+                                });
+                                if !content.is_empty() {
+                                    content.parse::<Token![,]>()?;
+                                }
+                            }
+                        } else {
+                            nested.push(input.parse()?);
+                        }
+                    } else {
+                        nested.push(input.parse()?);
+                    }
+
+                    if !input.is_empty() {
+                        input.parse::<Token![,]>()?;
+                    }
+                }
+                Ok(nested)
+            })
+            .map_err(Error::from)?;
+
+        let meta = Meta::List(MetaList {
+            path: attr.path.clone(),
+            paren_token: Default::default(),
+            nested,
+        });
+        result.push(StructAttribute {
+            meta,
+            responses,
+            scopes,
+        });
     }
 
     Ok(result)
@@ -111,28 +226,22 @@ pub(crate) fn attributes(attrs: &[Attribute], name: &str) -> Result<Vec<Meta>, E
 ///
 /// Parses all [Attribute]'s on the given [syn::Field]'s, searching for any
 /// attributes which match [crate::ATTR_NAME] and creating a map of attributes
-/// to a list of their associated fields.
+/// to a list of their associated fields. Options which don't determine where
+/// a field is placed (i.e. `datetime = "..."`) are validated but otherwise
+/// ignored and fields with only options are considered untagged.
+///
+/// Fields tagged with [EndpointAttribute::Dual] are added to the query fields
+/// and to whichever fields make up the request body: the body fields if any
+/// are present, otherwise the untagged fields.
 pub(crate) fn field_attributes(
     data: &syn::Data,
 ) -> Result<HashMap<EndpointAttribute, Vec<Field>>, Error> {
-    let mut result = HashMap::<EndpointAttribute, Vec<Field>>::new();
+    let mut tagged = Vec::<(&Field, HashSet<EndpointAttribute>)>::new();
     if let syn::Data::Struct(data) = data {
         for field in data.fields.iter() {
             // Collect all `endpoint` attributes attached to this field
             let attrs = attributes(&field.attrs, crate::ATTR_NAME)?;
 
-            // Add field as untagged is no attributes were found
-            if attrs.is_empty() {
-                match result.get_mut(&EndpointAttribute::Untagged) {
-                    Some(r) => {
-                        r.push(field.clone());
-                    }
-                    None => {
-                        result.insert(EndpointAttribute::Untagged, vec![field.clone()]);
-                    }
-                }
-            }
-
             // Combine all meta parameters from each attribute
             let attrs = attrs
                 .iter()
@@ -142,24 +251,153 @@ pub(crate) fn field_attributes(
             // Flatten and eliminate duplicates
             let attrs = attrs.into_iter().flatten().collect::<HashSet<Meta>>();
 
-            // Add this field to the list of fields for each attribute
+            // Separate field options from attributes
+            let mut attr_tys = HashSet::<EndpointAttribute>::new();
             for attr in attrs.iter() {
-                let attr_ty = EndpointAttribute::try_from(attr)?;
-                match result.get_mut(&attr_ty) {
-                    Some(r) => {
-                        r.push(field.clone());
+                match attr {
+                    Meta::Path(p)
+                        if p.is_ident("skip_none") || p.is_ident("serialize_none_as_null") => {}
+                    Meta::Path(p) if p.is_ident("encrypt") => {
+                        if datetime_format(field).is_some() {
+                            return Err(Error::new(
+                                p.span(),
+                                "Cannot encrypt a field with a datetime format",
+                            ));
+                        }
+                    }
+                    Meta::NameValue(nv) if nv.path.is_ident("datetime") => {
+                        if !matches!(nv.lit, syn::Lit::Str(_)) {
+                            return Err(Error::new(
+                                nv.lit.span(),
+                                "Values must be in string literal form",
+                            ));
+                        }
                     }
-                    None => {
-                        result.insert(attr_ty, vec![field.clone()]);
+                    _ => {
+                        attr_tys.insert(EndpointAttribute::try_from(attr)?);
                     }
                 }
             }
+
+            // Add field as untagged is no attributes were found
+            if attr_tys.is_empty() {
+                attr_tys.insert(EndpointAttribute::Untagged);
+            }
+
+            tagged.push((field, attr_tys));
+        }
+    }
+
+    // Dual fields follow the body fields if there are any
+    let has_body = tagged
+        .iter()
+        .any(|(_, a)| a.contains(&EndpointAttribute::Body));
+    let mut result = HashMap::<EndpointAttribute, Vec<Field>>::new();
+    for (field, mut attr_tys) in tagged {
+        if attr_tys.remove(&EndpointAttribute::Dual) {
+            attr_tys.insert(EndpointAttribute::Query);
+            attr_tys.insert(match has_body {
+                true => EndpointAttribute::Body,
+                false => EndpointAttribute::Untagged,
+            });
+        }
+
+        // Add this field to the list of fields for each attribute
+        for attr_ty in attr_tys {
+            result.entry(attr_ty).or_default().push(field.clone());
         }
     }
 
     Ok(result)
 }
 
+/// Returns the `datetime` format attached to a [Field], if any.
+///
+/// For example:
+/// ```ignore
+/// #[endpoint(query, datetime = "epoch")]
+/// ```
+/// Would return the string literal `"epoch"`. Invalid attributes are ignored
+/// since they're rejected by [field_attributes].
+pub(crate) fn datetime_format(field: &Field) -> Option<LitStr> {
+    let attrs = attributes(&field.attrs, crate::ATTR_NAME).ok()?;
+    attrs
+        .iter()
+        .filter_map(|a| attr_list(a).ok())
+        .flatten()
+        .find_map(|m| match m {
+            Meta::NameValue(MetaNameValue {
+                path,
+                lit: syn::Lit::Str(lit),
+                ..
+            }) if path.is_ident("datetime") => Some(lit),
+            _ => None,
+        })
+}
+
+/// Returns the doc comments of an item joined into a single string, with the
+/// leading space rustdoc inserts stripped from each line.
+#[cfg(feature = "docs")]
+pub(crate) fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|a| a.path.is_ident("doc"))
+        .filter_map(|a| match a.parse_meta() {
+            Ok(Meta::NameValue(MetaNameValue {
+                lit: syn::Lit::Str(lit),
+                ..
+            })) => Some(lit.value()),
+            _ => None,
+        })
+        .map(|l| l.strip_prefix(' ').map(String::from).unwrap_or(l))
+        .collect();
+    let doc = lines.join("\n").trim().to_string();
+    match doc.is_empty() {
+        true => None,
+        false => Some(doc),
+    }
+}
+
+/// Returns whether a [Field] is tagged with the `encrypt` option.
+pub(crate) fn is_encrypted(field: &Field) -> bool {
+    attributes(&field.attrs, crate::ATTR_NAME)
+        .map(|attrs| {
+            attrs
+                .iter()
+                .filter_map(|a| attr_list(a).ok())
+                .flatten()
+                .any(|m| matches!(m, Meta::Path(p) if p.is_ident("encrypt")))
+        })
+        .unwrap_or(false)
+}
+
+/// Returns whether an [Option] field should be serialized as `null` when it's
+/// [Option::None].
+///
+/// The `skip_none` and `serialize_none_as_null` field options take precedence
+/// over the given endpoint-wide default.
+pub(crate) fn none_as_null(field: &Field, default: bool) -> bool {
+    let attrs = match attributes(&field.attrs, crate::ATTR_NAME) {
+        Ok(a) => a,
+        Err(_) => return default,
+    };
+    let options = attrs
+        .iter()
+        .filter_map(|a| attr_list(a).ok())
+        .flatten()
+        .collect::<Vec<Meta>>();
+    match (
+        options.iter().any(|m| m.path().is_ident("skip_none")),
+        options
+            .iter()
+            .any(|m| m.path().is_ident("serialize_none_as_null")),
+    ) {
+        (true, _) => false,
+        (_, true) => true,
+        _ => default,
+    }
+}
+
 /// Creates and instantiates a struct from a list of [Field]s.
 ///
 /// This function effectively creates a new struct from a list [Field]s and then
@@ -168,18 +406,25 @@ pub(crate) fn field_attributes(
 ///
 /// The new struct will automatically derive `Serialize` and any [Option] fields
 /// will automatically be excluded from serialization if their value is
-/// [Option::None].
+/// [Option::None], unless [none_as_null] returns true for the field. Fields
+/// with a `datetime` format are wrapped so they're serialized using that
+/// format and fields tagged with `encrypt` are wrapped so they're encrypted.
 ///
 /// The result is a [proc_macro2::TokenStream] that contains the new struct and
 /// and it's instantiation. The instantiated variable can be accessed by it's
 /// static name of `__temp`.
-pub(crate) fn fields_to_struct(fields: &[Field], attrs: &[Meta]) -> proc_macro2::TokenStream {
+pub(crate) fn fields_to_struct(
+    fields: &[Field],
+    attrs: &[Meta],
+    null_default: bool,
+) -> proc_macro2::TokenStream {
     // Construct struct field definitions
     let def = fields
         .iter()
         .map(|f| {
             let id = f.ident.clone().unwrap();
             let ty = &f.ty;
+            let skip_none = is_std_option(ty) && !none_as_null(f, null_default);
 
             // Pass serde attributes onto our temporary struct
             let mut attrs = Vec::<&Attribute>::new();
@@ -191,8 +436,34 @@ pub(crate) fn fields_to_struct(fields: &[Field], attrs: &[Meta]) -> proc_macro2:
                 }
             }
 
+            // Encrypt fields using the configured field cipher
+            if is_encrypted(f) {
+                let skip = match skip_none {
+                    true => quote! {
+                        #[serde(skip_serializing_if = "rustify::encryption::Encrypted::is_none")]
+                    },
+                    false => quote! {},
+                };
+                quote! {
+                    #(#attrs)*
+                    #skip
+                    #id: rustify::encryption::Encrypted<'a, #ty>,
+                }
+            // Serialize date and time values using the requested format
+            } else if datetime_format(f).is_some() {
+                let skip = match skip_none {
+                    true => quote! {
+                        #[serde(skip_serializing_if = "rustify::datetime::Formatted::is_none")]
+                    },
+                    false => quote! {},
+                };
+                quote! {
+                    #(#attrs)*
+                    #skip
+                    #id: rustify::datetime::Formatted<'a, #ty>,
+                }
             // If this field is an Option, don't serialize when it's None
-            if is_std_option(ty) {
+            } else if skip_none {
                 quote! {
                     #(#attrs)*
                     #[serde(skip_serializing_if = "Option::is_none")]
@@ -216,7 +487,20 @@ pub(crate) fn fields_to_struct(fields: &[Field], attrs: &[Meta]) -> proc_macro2:
         .iter()
         .map(|f| {
             let id = f.ident.clone().unwrap();
-            quote! { #id: &self.#id, }
+            if is_encrypted(f) {
+                return quote! {
+                    #id: rustify::encryption::Encrypted::new(&self.#id),
+                };
+            }
+            match datetime_format(f) {
+                Some(fmt) => quote! {
+                    #id: rustify::datetime::Formatted::new(
+                        &self.#id,
+                        rustify::datetime::Format::parse(#fmt),
+                    ),
+                },
+                None => quote! { #id: &self.#id, },
+            }
         })
         .collect::<Vec<proc_macro2::TokenStream>>();
 
@@ -248,3 +532,56 @@ pub(crate) fn is_std_option(ty: &Type) -> bool {
         false
     }
 }
+
+/// Returns the [RequestType] variant a body consisting only of the given type
+/// is sent as, if the type is one of the `rustify::patch` documents.
+///
+/// This only checks the final path segment, i.e. both `JsonPatch` and
+/// `rustify::patch::JsonPatch` match.
+pub(crate) fn patch_request_type(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Path(tp) => match tp.path.segments.last() {
+            Some(s) if s.ident == "MergePatch" => Some("JSON_MERGE_PATCH"),
+            Some(s) if s.ident == "JsonPatch" => Some("JSON_PATCH"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns an error if the given response type borrows data or isn't a
+/// concrete type, neither of which can be deserialized from a response body.
+///
+/// Types nested in generic arguments, tuples, arrays, and slices are checked
+/// as well, i.e. both `&str` and `Vec<&str>` are rejected.
+pub(crate) fn owned_response(ty: &Type) -> Result<(), Error> {
+    match ty {
+        Type::Reference(r) => Err(Error::new(
+            r.span(),
+            "Responses cannot borrow from the response body, use an owned type such as `String` instead of a reference",
+        )),
+        Type::TraitObject(_) | Type::ImplTrait(_) => Err(Error::new(
+            ty.span(),
+            "Responses must be concrete types which implement `Deserialize`",
+        )),
+        Type::Path(tp) => tp
+            .path
+            .segments
+            .iter()
+            .filter_map(|s| match &s.arguments {
+                syn::PathArguments::AngleBracketed(args) => Some(args.args.iter()),
+                _ => None,
+            })
+            .flatten()
+            .try_for_each(|a| match a {
+                syn::GenericArgument::Type(t) => owned_response(t),
+                _ => Ok(()),
+            }),
+        Type::Tuple(t) => t.elems.iter().try_for_each(owned_response),
+        Type::Array(a) => owned_response(&a.elem),
+        Type::Slice(s) => owned_response(&s.elem),
+        Type::Paren(p) => owned_response(&p.elem),
+        Type::Group(g) => owned_response(&g.elem),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,153 @@
+use proc_macro2::Span;
+use quote::quote;
+use syn::{spanned::Spanned, Field, Member, Meta};
+
+use crate::{error::Error, parse};
+
+const MACRO_NAME: &str = "Wrapper";
+const ATTR_NAME: &str = "wrapper";
+
+/// The fields of a struct tagged with `wrapper` attributes.
+struct WrapperFields {
+    /// The field tagged with `#[wrapper(value)]`
+    value: (Member, Field),
+    /// The field tagged with `#[wrapper(error)]`, if any
+    error: Option<Member>,
+}
+
+/// Returns the fields tagged with `#[wrapper(value)]` and `#[wrapper(error)]`.
+///
+/// Exactly one field must be tagged as the value and at most one field may be
+/// tagged as the error.
+fn tagged_fields(s: &synstructure::Structure) -> Result<WrapperFields, Error> {
+    let fields = match &s.ast().data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(Error::new(
+                Span::call_site(),
+                format!("Deriving `{}` is only supported on structs", MACRO_NAME).as_str(),
+            ))
+        }
+    };
+
+    let mut value: Option<(Member, Field)> = None;
+    let mut error: Option<Member> = None;
+    for (i, field) in fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(id) => Member::Named(id.clone()),
+            None => Member::Unnamed(syn::Index::from(i)),
+        };
+
+        let attrs = parse::attributes(&field.attrs, ATTR_NAME)?;
+        for attr in attrs.iter() {
+            for meta in parse::attr_list(attr)? {
+                match &meta {
+                    Meta::Path(p) if p.is_ident("value") => {
+                        if value.is_some() {
+                            return Err(Error::new(
+                                field.span(),
+                                "May only mark one field as the wrapper value",
+                            ));
+                        }
+                        value = Some((member.clone(), field.clone()));
+                    }
+                    Meta::Path(p) if p.is_ident("error") => {
+                        if error.is_some() {
+                            return Err(Error::new(
+                                field.span(),
+                                "May only mark one field as the wrapper error",
+                            ));
+                        }
+                        if !parse::is_std_option(&field.ty) {
+                            return Err(Error::new(
+                                field.ty.span(),
+                                "The wrapper error field must be an Option",
+                            ));
+                        }
+                        error = Some(member.clone());
+                    }
+                    _ => return Err(Error::new(meta.span(), "Unknown attribute")),
+                }
+            }
+        }
+    }
+
+    match value {
+        Some(value) => Ok(WrapperFields { value, error }),
+        None => Err(Error::new(
+            Span::call_site(),
+            format!(
+                "Deriving `{}` requires tagging a field with `#[{}(value)]`",
+                MACRO_NAME, ATTR_NAME
+            )
+            .as_str(),
+        )),
+    }
+}
+
+/// Implements `Wrapper` and `Unwrap` on the provided struct.
+///
+/// The type of the field tagged with `#[wrapper(value)]` becomes the
+/// `Wrapper::Value` and any generic type parameters are bound by the same
+/// constraints placed on the `Wrapper` trait itself. If a field is tagged with
+/// `#[wrapper(error)]` then `into_result` returns an error whenever that field
+/// contains a value.
+pub(crate) fn wrapper_derive(s: synstructure::Structure) -> proc_macro2::TokenStream {
+    let fields = match tagged_fields(&s) {
+        Ok(v) => v,
+        Err(e) => return e.into_tokens(),
+    };
+    let (member, field) = fields.value;
+
+    let id = &s.ast().ident;
+    let ty = &field.ty;
+
+    // Bound all type parameters so the wrapper is deserializable
+    let mut generics = s.ast().generics.clone();
+    let params = generics
+        .type_params()
+        .map(|p| p.ident.clone())
+        .collect::<Vec<_>>();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(syn::parse_quote! {
+            #param: DeserializeOwned + Send + Sync
+        });
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    // Fail with the contents of the error field when it's present
+    let into_result = match fields.error {
+        Some(error) => quote! {
+            fn into_result(self) -> Result<Self::Value, ClientError> {
+                match &self.#error {
+                    Some(e) => Err(ClientError::ApiError {
+                        message: e.to_string(),
+                    }),
+                    None => Ok(self.#member),
+                }
+            }
+        },
+        None => quote! {},
+    };
+
+    quote! {
+        const _: () = {
+            use rustify::__private::serde::de::DeserializeOwned;
+            use rustify::endpoint::{Unwrap, Wrapper};
+            use rustify::errors::ClientError;
+
+            impl #impl_generics Wrapper for #id #ty_generics #where_clause {
+                type Value = #ty;
+            }
+
+            impl #impl_generics Unwrap for #id #ty_generics #where_clause {
+                fn into_value(self) -> Self::Value {
+                    self.#member
+                }
+
+                #into_result
+            }
+        };
+    }
+}
@@ -0,0 +1,275 @@
+//! Contains the [AdaptiveLimit] type for adjusting the number of requests a
+//! [Client][crate::client::Client] has in flight based on how the server
+//! responds.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant},
+};
+
+use crate::errors::ClientError;
+use http::{Response, StatusCode};
+
+/// Limits the number of concurrent in-flight requests using an additive
+/// increase, multiplicative decrease (AIMD) algorithm.
+///
+/// Every request which completes within the latency threshold raises the
+/// limit by a small amount (roughly one per window of `limit` requests).
+/// Requests which take longer than the threshold, fail to connect, or are
+/// answered with `429 Too Many Requests` or a `5xx` status reduce the limit by
+/// the backoff ratio. The limit always stays between the configured minimum
+/// and maximum.
+///
+/// An [AdaptiveLimit] is shared by all clones of the
+/// [ClientConfig][crate::config::ClientConfig] it belongs to.
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use rustify::{adaptive::AdaptiveLimit, clients::reqwest::Client, config::ClientConfig};
+///
+/// let limit = AdaptiveLimit::new(10)
+///     .bounds(1, 100)
+///     .latency_threshold(Duration::from_millis(500));
+/// let client = Client::default("http://myapi.com")
+///     .with_config(ClientConfig::new().adaptive_concurrency(limit));
+/// ```
+#[derive(Clone)]
+pub struct AdaptiveLimit {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+struct State {
+    limit: f64,
+    min: usize,
+    max: usize,
+    backoff: f64,
+    latency_threshold: Option<Duration>,
+    in_flight: usize,
+    blocked: usize,
+    next: u64,
+    wakers: HashMap<u64, Waker>,
+}
+
+/// A permit held by a request while it's in flight.
+///
+/// The outcome of the request should be recorded with [Permit::record]. The
+/// permit is released without adjusting the limit if it's dropped without
+/// recording an outcome.
+pub struct Permit {
+    inner: Arc<Inner>,
+    start: Instant,
+}
+
+/// A future which resolves once a request is permitted to be sent.
+struct Acquire {
+    inner: Arc<Inner>,
+    id: u64,
+}
+
+impl AdaptiveLimit {
+    /// Returns a new [AdaptiveLimit] starting at the given limit, with a
+    /// minimum of 1, a maximum of 1000, and a backoff ratio of 0.9.
+    pub fn new(initial: usize) -> Self {
+        AdaptiveLimit {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    limit: initial.clamp(1, 1000) as f64,
+                    min: 1,
+                    max: 1000,
+                    backoff: 0.9,
+                    latency_threshold: None,
+                    in_flight: 0,
+                    blocked: 0,
+                    next: 0,
+                    wakers: HashMap::new(),
+                }),
+                ready: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Sets the minimum and maximum limit.
+    pub fn bounds(self, min: usize, max: usize) -> Self {
+        {
+            let mut state = self.inner.lock();
+            state.min = min.max(1);
+            state.max = max.max(state.min);
+            state.limit = state.limit.clamp(state.min as f64, state.max as f64);
+        }
+        self
+    }
+
+    /// Sets the ratio the limit is multiplied by when the server appears to
+    /// be overloaded. The ratio is clamped between 0.5 and 1.
+    pub fn backoff(self, ratio: f64) -> Self {
+        self.inner.lock().backoff = ratio.clamp(0.5, 1.0);
+        self
+    }
+
+    /// Treats requests which take longer than the given threshold as a sign
+    /// the server is overloaded.
+    pub fn latency_threshold(self, threshold: Duration) -> Self {
+        self.inner.lock().latency_threshold = Some(threshold);
+        self
+    }
+
+    /// Returns the current limit.
+    pub fn limit(&self) -> usize {
+        self.inner.lock().limit as usize
+    }
+
+    /// Returns the number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner.lock().in_flight
+    }
+
+    /// Returns the number of requests waiting to be sent.
+    pub fn queued(&self) -> usize {
+        let state = self.inner.lock();
+        state.wakers.len() + state.blocked
+    }
+
+    /// Waits until a request is permitted to be sent.
+    pub async fn acquire(&self) -> Permit {
+        let id = {
+            let mut state = self.inner.lock();
+            state.next += 1;
+            state.next
+        };
+        Acquire {
+            inner: self.inner.clone(),
+            id,
+        }
+        .await
+    }
+
+    /// Blocks until a request is permitted to be sent.
+    pub fn acquire_blocking(&self) -> Permit {
+        let mut state = self.inner.lock();
+        state.blocked += 1;
+        while !state.try_take() {
+            state = self
+                .inner
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        state.blocked -= 1;
+        drop(state);
+        self.inner.permit()
+    }
+}
+
+impl State {
+    /// Takes a slot if the number of requests in flight is below the limit.
+    fn try_take(&mut self) -> bool {
+        match self.in_flight < self.limit as usize {
+            true => {
+                self.in_flight += 1;
+                true
+            }
+            false => false,
+        }
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn permit(self: &Arc<Self>) -> Permit {
+        Permit {
+            inner: self.clone(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Frees a slot, adjusts the limit, and wakes any waiting requests.
+    fn release(&self, overloaded: Option<bool>) {
+        let mut state = self.lock();
+        state.in_flight = state.in_flight.saturating_sub(1);
+        let (min, max) = (state.min as f64, state.max as f64);
+        state.limit = match overloaded {
+            Some(true) => (state.limit * state.backoff).floor().max(min),
+            Some(false) => (state.limit + 1.0 / state.limit).min(max),
+            None => state.limit,
+        };
+        for (_, waker) in state.wakers.drain() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+impl Permit {
+    /// Releases the permit and adjusts the limit using the outcome of the
+    /// request.
+    pub fn record<B>(self, result: &Result<Response<B>, ClientError>) {
+        let slow = self
+            .inner
+            .lock()
+            .latency_threshold
+            .map(|t| self.start.elapsed() > t)
+            .unwrap_or(false);
+        let overloaded = match result {
+            Ok(r) => Some(
+                slow || r.status() == StatusCode::TOO_MANY_REQUESTS || r.status().is_server_error(),
+            ),
+            Err(ClientError::RequestError { .. }) => Some(true),
+            Err(_) => None,
+        };
+        self.inner.release(overloaded);
+        std::mem::forget(self);
+    }
+}
+
+impl Future for Acquire {
+    type Output = Permit;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.inner.lock();
+        if state.try_take() {
+            state.wakers.remove(&self.id);
+            drop(state);
+            return Poll::Ready(self.inner.permit());
+        }
+        state.wakers.insert(self.id, cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        self.inner.lock().wakers.remove(&self.id);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.inner.release(None);
+    }
+}
+
+impl fmt::Debug for AdaptiveLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.inner.lock();
+        f.debug_struct("AdaptiveLimit")
+            .field("limit", &(state.limit as usize))
+            .field("in_flight", &state.in_flight)
+            .field("min", &state.min)
+            .field("max", &state.max)
+            .finish()
+    }
+}
@@ -0,0 +1,132 @@
+//! Contains the [RefreshLock] type for refreshing credentials without a
+//! thundering herd.
+//!
+//! When many concurrent requests find their token expired at once, only the
+//! first caller of [RefreshLock::get_or_refresh] runs the refresh while the
+//! others wait for it and then reuse the refreshed token. Credential providers
+//! which refresh tokens should keep them in a [RefreshLock] to get the same
+//! guarantee.
+//!
+//! # Example
+//! ```
+//! use rustify::{auth::RefreshLock, errors::ClientError};
+//!
+//! # tokio_test::block_on(async {
+//! let lock = RefreshLock::<String>::new();
+//! let token = lock
+//!     .get_or_refresh(|_| true, || async { Ok::<_, ClientError>("token".to_string()) })
+//!     .await
+//!     .unwrap();
+//!
+//! // After the server rejects the token, only the first caller refreshes it
+//! lock.invalidate(&token).await;
+//! # });
+//! ```
+
+use std::future::Future;
+
+use crate::errors::ClientError;
+use async_lock::Mutex;
+
+/// Holds a credential which is refreshed by at most one caller at a time.
+#[derive(Debug, Default)]
+pub struct RefreshLock<T> {
+    value: Mutex<Option<T>>,
+}
+
+impl<T: Clone + PartialEq> RefreshLock<T> {
+    /// Returns a new, empty [RefreshLock].
+    pub fn new() -> Self {
+        RefreshLock {
+            value: Mutex::new(None),
+        }
+    }
+
+    /// Returns a new [RefreshLock] holding the given value.
+    pub fn with_value(value: T) -> Self {
+        RefreshLock {
+            value: Mutex::new(Some(value)),
+        }
+    }
+
+    /// Returns the current value, if any, without refreshing it.
+    pub async fn get(&self) -> Option<T> {
+        self.value.lock().await.clone()
+    }
+
+    /// Returns the current value if `is_fresh` returns true for it, otherwise
+    /// stores and returns the value returned by `refresh`.
+    ///
+    /// Concurrent callers wait for an in-progress refresh and then receive its
+    /// result instead of refreshing again. A failed refresh leaves the
+    /// previous value in place and is returned to the caller which ran it.
+    pub async fn get_or_refresh<F, Fut>(
+        &self,
+        is_fresh: impl Fn(&T) -> bool,
+        refresh: F,
+    ) -> Result<T, ClientError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, ClientError>>,
+    {
+        let mut value = self.value.lock().await;
+        match value.as_ref() {
+            Some(v) if is_fresh(v) => Ok(v.clone()),
+            _ => {
+                debug!("Refreshing credentials");
+                let refreshed = refresh().await?;
+                *value = Some(refreshed.clone());
+                Ok(refreshed)
+            }
+        }
+    }
+
+    /// Clears the current value if it's equal to the given value, i.e. the
+    /// token a request was rejected with, so the next caller refreshes it.
+    ///
+    /// Values which were already replaced by a refresh are left in place, so
+    /// many requests rejected with the same token only cause one refresh.
+    pub async fn invalidate(&self, stale: &T) {
+        let mut value = self.value.lock().await;
+        if value.as_ref() == Some(stale) {
+            *value = None;
+        }
+    }
+
+    /// Returns the current value, if any, without refreshing it, blocking the
+    /// current thread while a refresh is in progress.
+    pub fn get_blocking(&self) -> Option<T> {
+        self.value.lock_blocking().clone()
+    }
+
+    /// Like [RefreshLock::get_or_refresh] but blocks the current thread and
+    /// runs a blocking `refresh`.
+    pub fn get_or_refresh_blocking<F>(
+        &self,
+        is_fresh: impl Fn(&T) -> bool,
+        refresh: F,
+    ) -> Result<T, ClientError>
+    where
+        F: FnOnce() -> Result<T, ClientError>,
+    {
+        let mut value = self.value.lock_blocking();
+        match value.as_ref() {
+            Some(v) if is_fresh(v) => Ok(v.clone()),
+            _ => {
+                debug!("Refreshing credentials");
+                let refreshed = refresh()?;
+                *value = Some(refreshed.clone());
+                Ok(refreshed)
+            }
+        }
+    }
+
+    /// Like [RefreshLock::invalidate] but blocks the current thread while a
+    /// refresh is in progress.
+    pub fn invalidate_blocking(&self, stale: &T) {
+        let mut value = self.value.lock_blocking();
+        if value.as_ref() == Some(stale) {
+            *value = None;
+        }
+    }
+}
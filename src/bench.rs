@@ -0,0 +1,194 @@
+//! Contains the [Bench] type for measuring the latency and error rate of
+//! [Endpoints][Endpoint] under load.
+//!
+//! A bench replays its endpoints in turn at a fixed rate against a client and
+//! returns a [BenchReport] with latency percentiles and error rates, so SDK
+//! authors can regression-test the performance of their endpoint definitions
+//! in ordinary tests. Requests are started on schedule regardless of how long
+//! earlier requests take, so a slow server shows up as higher latencies
+//! rather than a lower request rate. Each measured latency covers building
+//! the request, executing it, and parsing the response.
+//!
+//! Schedules use [tokio::time], so benches against a
+//! [MockClient][crate::mock::MockClient] with paused time are deterministic.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::{bench::Bench, clients::reqwest::Client};
+//! use rustify_derive::Endpoint;
+//! use std::time::Duration;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "users")]
+//! struct ListUsers {}
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("http://api.example.com");
+//! let report = Bench::new(50.0)
+//!     .endpoint(ListUsers {})
+//!     .requests(500)
+//!     .run(&client)
+//!     .await;
+//! assert!(report.error_rate() < 0.01);
+//! assert!(report.percentile(99.0).unwrap() < Duration::from_millis(250));
+//! # });
+//! ```
+
+use std::{collections::HashMap, time::Duration};
+
+use futures_util::{future::BoxFuture, stream::FuturesUnordered, StreamExt};
+use tokio::time::Instant;
+
+use crate::{
+    client::Client,
+    endpoint::Endpoint,
+    errors::{ClientError, ErrorKind},
+};
+
+type Execution<C> =
+    Box<dyn for<'a> Fn(&'a C) -> BoxFuture<'a, Result<(), ClientError>> + Send + Sync>;
+
+/// Replays [Endpoints][Endpoint] at a fixed rate, see the
+/// [module documentation][self].
+pub struct Bench<C> {
+    endpoints: Vec<Execution<C>>,
+    rate: f64,
+    requests: usize,
+}
+
+impl<C> std::fmt::Debug for Bench<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Bench")
+            .field("endpoints", &self.endpoints.len())
+            .field("rate", &self.rate)
+            .field("requests", &self.requests)
+            .finish()
+    }
+}
+
+impl<C: Client> Bench<C> {
+    /// Returns a new [Bench] which starts the given number of requests per
+    /// second and sends one request per endpoint by default.
+    pub fn new(rate: f64) -> Self {
+        Bench {
+            endpoints: Vec::new(),
+            rate,
+            requests: 0,
+        }
+    }
+
+    /// Adds an endpoint to the endpoints replayed in turn.
+    pub fn endpoint<E>(mut self, endpoint: E) -> Self
+    where
+        E: Endpoint + Send + Sync + 'static,
+    {
+        let endpoint = std::sync::Arc::new(endpoint);
+        self.endpoints.push(Box::new(move |client| {
+            let endpoint = endpoint.clone();
+            Box::pin(async move { endpoint.exec(client).await?.parse().map(|_| ()) })
+        }));
+        self
+    }
+
+    /// Sets the total number of requests sent.
+    pub fn requests(mut self, requests: usize) -> Self {
+        self.requests = requests;
+        self
+    }
+
+    /// Sends requests using the given client until the configured number of
+    /// requests completed and returns the measurements.
+    pub async fn run(&self, client: &C) -> BenchReport {
+        let total = match self.requests {
+            0 => self.endpoints.len(),
+            n => n,
+        };
+        let interval = match self.rate > 0.0 {
+            true => Duration::from_secs_f64(1.0 / self.rate),
+            false => Duration::ZERO,
+        };
+        let start = Instant::now();
+        let mut pending = (0..total)
+            .filter_map(|i| {
+                let execution = self.endpoints.get(i % self.endpoints.len().max(1))?;
+                let scheduled = start + interval * i as u32;
+                Some(async move {
+                    tokio::time::sleep_until(scheduled).await;
+                    let sent = Instant::now();
+                    let result = execution(client).await;
+                    (sent.elapsed(), result)
+                })
+            })
+            .collect::<FuturesUnordered<_>>();
+
+        let mut report = BenchReport::default();
+        while let Some((latency, result)) = pending.next().await {
+            report.latencies.push(latency);
+            if let Err(e) = result {
+                *report.errors.entry(e.kind()).or_default() += 1;
+            }
+        }
+        report.elapsed = start.elapsed();
+        report.latencies.sort();
+        report
+    }
+}
+
+/// The measurements taken by a [Bench].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BenchReport {
+    /// The latency of every request, sorted in ascending order
+    pub latencies: Vec<Duration>,
+    /// The number of failed requests by the kind of their error
+    pub errors: HashMap<ErrorKind, usize>,
+    /// The time from the start of the bench until the last request completed
+    pub elapsed: Duration,
+}
+
+impl BenchReport {
+    /// Returns the number of requests sent.
+    pub fn requests(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Returns the number of failed requests.
+    pub fn failures(&self) -> usize {
+        self.errors.values().sum()
+    }
+
+    /// Returns the fraction of requests which failed, between 0 and 1.
+    pub fn error_rate(&self) -> f64 {
+        match self.requests() {
+            0 => 0.0,
+            n => self.failures() as f64 / n as f64,
+        }
+    }
+
+    /// Returns the number of requests completed per second.
+    pub fn throughput(&self) -> f64 {
+        match self.elapsed.is_zero() {
+            true => 0.0,
+            false => self.requests() as f64 / self.elapsed.as_secs_f64(),
+        }
+    }
+
+    /// Returns the latency below which the given percentage of requests
+    /// completed using the nearest-rank method, i.e. `percentile(99.0)` for
+    /// the p99 latency.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.latencies.is_empty() {
+            return None;
+        }
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * self.latencies.len() as f64).ceil();
+        let index = (rank as usize).clamp(1, self.latencies.len()) - 1;
+        Some(self.latencies[index])
+    }
+
+    /// Returns the mean latency of all requests.
+    pub fn mean(&self) -> Option<Duration> {
+        match self.requests() {
+            0 => None,
+            n => Some(self.latencies.iter().sum::<Duration>() / n as u32),
+        }
+    }
+}
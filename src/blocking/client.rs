@@ -1,7 +1,41 @@
 //! Contains the blocking [Client] trait for executing
 //! [Endpoints][crate::endpoint::Endpoint].
-use crate::{client::HTTP_SUCCESS_CODES, errors::ClientError};
+use crate::{
+    client::{priority, HTTP_SUCCESS_CODES},
+    config::{ClientConfig, DEFAULT_CONFIG},
+    errors::ClientError,
+    events::{Attempt, BodySizes, Limiter, Operation},
+    probe::{HealthCheckEndpoint, ProbeReport},
+    resilience::ResilienceState,
+};
 use http::{Request, Response};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Sends the given [Request] using the given [Client], resending it once if
+/// the connection was stale, see [ClientConfig::retry_stale_connections].
+pub(crate) fn send<C: Client + ?Sized>(
+    client: &C,
+    mut req: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let config = client.config();
+    config.prepare_attempt(&mut req)?;
+    let retry = config.stale_retry(&req);
+    match (client.send(req), retry) {
+        (Err(e), Some(mut req)) if e.is_stale_connection() => {
+            warn!(
+                "Retrying {} {} after a stale connection",
+                req.method(),
+                req.uri()
+            );
+            config.prepare_attempt(&mut req)?;
+            client.send(req)
+        }
+        (result, _) => result,
+    }
+}
 
 /// Represents an HTTP client which is capable of executing
 /// [Endpoints][crate::endpoint::Endpoint] by sending the [Request] generated
@@ -16,17 +50,117 @@ pub trait Client {
     /// [Endpoints][crate::endpoint::Endpoint].
     fn base(&self) -> &str;
 
+    /// Returns the [ClientConfig] the client is configured with. Clients which
+    /// don't support configuration can rely on the default implementation.
+    fn config(&self) -> &ClientConfig {
+        &DEFAULT_CONFIG
+    }
+
+    /// Returns a snapshot of the limits applied to requests, see
+    /// [ClientConfig::resilience_state].
+    fn resilience_state(&self) -> ResilienceState {
+        self.config().resilience_state()
+    }
+
+    /// Stops accepting new executions and waits up to the given grace period
+    /// for the requests in flight to complete, see
+    /// [Shutdown::shutdown_blocking][crate::shutdown::Shutdown::shutdown_blocking]. Returns the number of requests which
+    /// were still in flight.
+    ///
+    /// This does nothing unless the client is configured with
+    /// [ClientConfig::graceful_shutdown].
+    fn shutdown(&self, grace: Duration) -> usize {
+        match &self.config().shutdown {
+            Some(s) => s.shutdown_blocking(grace),
+            None => 0,
+        }
+    }
+
     /// This method provides a common interface to
     /// [Endpoints][crate::endpoint::Endpoint] for execution.
-    #[instrument(skip(self, req), err)]
-    fn execute(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+    #[instrument(
+        skip(self, req),
+        fields(
+            attempt = Attempt::of(req.extensions()),
+            request_body = tracing::field::Empty,
+            request_bytes = req.body().len(),
+            response_body = tracing::field::Empty,
+            response_bytes = tracing::field::Empty,
+            response_encoding = tracing::field::Empty,
+        ),
+        err
+    )]
+    fn execute(&self, mut req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
         debug!(
             "Client sending {} request to {} with {} bytes of data",
             req.method().to_string(),
             req.uri(),
             req.body().len(),
         );
-        let response = self.send(req)?;
+        let config = self.config();
+        let hooks = &config.hooks;
+        config.annotate_attempt(&mut req);
+        config.start_deadline(&mut req);
+        config.apply_state(&mut req);
+        hooks.request(&req);
+        config.emit_started(&req);
+        let captured = config.capture_request(&req);
+        let operation = Operation::of(req.extensions());
+        let _active = config
+            .check_gate(&mut req)
+            .and_then(|_| config.check_url(&req))
+            .and_then(|_| config.enter())
+            .inspect_err(|e| {
+                hooks.error(e);
+                config.emit_failed(operation, e);
+            })?;
+
+        let _permit = config.concurrency.as_ref().map(|limit| {
+            config.admit_blocking(&req, Limiter::Concurrency, limit.queued(), || {
+                limit.acquire_blocking(req.uri())
+            })
+        });
+        let _scheduled = config.scheduler.as_ref().map(|s| {
+            config.admit_blocking(&req, Limiter::Scheduler, s.queued(), || {
+                s.acquire_blocking(priority(&req))
+            })
+        });
+        let adaptive = config.adaptive.as_ref().map(|a| {
+            config.admit_blocking(&req, Limiter::Adaptive, a.queued(), || a.acquire_blocking())
+        });
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let extensions = req.extensions().clone();
+        let request_bytes = req.body().len();
+        let start = Instant::now();
+        let result = match config.redirects {
+            Some(max) => crate::redirect::follow_blocking(self, req, max),
+            None => send(self, req),
+        };
+        config.check_latency(&method, &uri, start.elapsed());
+        config.record_metrics(operation, &result, start.elapsed());
+        if let Some(permit) = adaptive {
+            permit.record(&result);
+        }
+        let mut response = result.inspect_err(|e| {
+            hooks.error(e);
+            config.emit_failed(operation, e);
+        })?;
+
+        // Carry request extensions over so they're available to middleware
+        response.extensions_mut().extend(extensions);
+        let mut sizes = BodySizes::new(request_bytes, &response);
+        sizes.decoded = crate::encoding::decode(&mut response).inspect_err(|e| {
+            hooks.error(e);
+            config.emit_failed(operation, e);
+        })?;
+        config.record_sizes(&method, &uri, &sizes);
+        config.record_rate_limit(&uri, &response);
+        config.intercept_response(&uri, &response);
+        response.extensions_mut().insert(sizes);
+        if let Some(events) = &config.events {
+            response.extensions_mut().insert(events.clone());
+        }
 
         debug!(
             "Client received {} response with {} bytes of body data",
@@ -34,15 +168,56 @@ pub trait Client {
             response.body().len()
         );
 
+        hooks.response(&response);
+        if captured {
+            config.capture_response(&response);
+        }
+
         // Check response
         if !HTTP_SUCCESS_CODES.contains(&response.status().as_u16()) {
-            return Err(ClientError::ServerResponseError {
+            let err = ClientError::ServerResponseError {
                 code: response.status().as_u16(),
                 content: String::from_utf8(response.body().to_vec()).ok(),
-            });
+            };
+            hooks.error(&err);
+            config.emit_failed(operation, &err);
+            return Err(err);
         }
 
         // Parse response content
         Ok(response)
     }
+
+    /// Sends a `GET` request to the given path to verify the base URL, TLS
+    /// configuration, and authentication of the client. Failures are returned
+    /// as a [ClientError::ProbeError] describing the likely cause.
+    ///
+    /// See [HealthCheckEndpoint] for probing with a different method.
+    fn probe(&self, path: &str) -> Result<ProbeReport, ClientError> {
+        self.probe_with(&HealthCheckEndpoint::new(path))
+    }
+
+    /// Probes the client using the given [HealthCheckEndpoint].
+    fn probe_with(&self, endpoint: &HealthCheckEndpoint) -> Result<ProbeReport, ClientError> {
+        let req = crate::probe::prepare(self.base(), endpoint)?;
+        let url = req.uri().to_string();
+        let start = Instant::now();
+        crate::probe::report(url, start, self.execute(req))
+    }
+}
+
+/// Clients behind an [Arc] can be used directly, i.e. one client shared by
+/// multiple threads.
+impl<C: Client + ?Sized> Client for Arc<C> {
+    fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        (**self).send(req)
+    }
+
+    fn base(&self) -> &str {
+        (**self).base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        (**self).config()
+    }
 }
@@ -2,9 +2,21 @@
 //! [Client][crate::blocking::client::Client] being backed by the
 //! [reqwest](https://docs.rs/reqwest/) crate.
 
-use crate::{blocking::client::Client as RustifyClient, errors::ClientError};
+#[cfg(feature = "mmap")]
+use crate::client::HTTP_SUCCESS_CODES;
+use crate::{
+    blocking::client::Client as RustifyClient,
+    config::ClientConfig,
+    deadline::{AttemptTimeout, Timeouts},
+    errors::ClientError,
+    proxy::{self, ProxyPool, ProxyRoute},
+};
+#[cfg(feature = "mmap")]
+use bytes::Bytes;
 use http::{Request, Response};
-use std::convert::TryFrom;
+#[cfg(feature = "mmap")]
+use std::io::{Read, Write};
+use std::{convert::TryFrom, sync::Arc};
 
 /// A client based on the
 /// [reqwest::blocking::Client][1] which can be used for executing
@@ -14,6 +26,22 @@ use std::convert::TryFrom;
 /// to qualify the full path of any [Endpoints][crate::endpoint::Endpoint] which
 /// are executed by this client.
 ///
+/// Requests with a [ProxyRoute][crate::proxy::ProxyRoute] in their extensions
+/// are sent using a separate client for that proxy, and requests with a
+/// connect timeout, see [Timeouts], using a separate client built with that
+/// timeout. Read timeouts aren't supported by the blocking reqwest client and
+/// are ignored. These clients are derived from the builder passed to
+/// [Client::from_builder], or the default reqwest builder for
+/// [Client::default], and cached in `proxies`. Clients created with
+/// [Client::new] can't route requests through a proxy and ignore connect
+/// timeouts, only applying the total timeout to each request.
+///
+/// Cloning the client is cheap: clones share the connection pool of the
+/// backing [reqwest::blocking::Client][1], the cached proxy clients, and the state of
+/// the [ClientConfig], such as concurrency and rate limits. The client is
+/// `Send + Sync`, so it can also be shared behind an [Arc] returned by
+/// `shared()`.
+///
 /// # Example
 /// ```
 /// use rustify::blocking::clients::reqwest::Client;
@@ -31,17 +59,26 @@ use std::convert::TryFrom;
 /// ```
 ///
 /// [1]: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html
+#[derive(Clone)]
 pub struct Client {
     pub http: reqwest::blocking::Client,
     pub base: String,
+    pub config: ClientConfig,
+    pub proxies: ProxyPool<reqwest::blocking::Client>,
+    builder: Option<Builder>,
 }
 
+type Builder = Arc<dyn Fn() -> reqwest::blocking::ClientBuilder + Send + Sync>;
+
 impl Client {
     /// Creates a new instance of [Client] using the provided parameters.
     pub fn new(base: &str, http: reqwest::blocking::Client) -> Self {
         Client {
             base: base.to_string(),
             http,
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: None,
         }
     }
 
@@ -53,6 +90,74 @@ impl Client {
         Client {
             base: base.to_string(),
             http: reqwest::blocking::Client::default(),
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: Some(Arc::new(reqwest::blocking::Client::builder)),
+        }
+    }
+
+    /// Creates a new instance of [Client] backed by a
+    /// [reqwest::blocking::Client][1] built from the builder returned by the
+    /// given function.
+    ///
+    /// The function is called again to derive the clients for proxies and
+    /// connect timeouts, so they share all other settings of the backing
+    /// client, such as its DNS resolver and redirect policy.
+    ///
+    /// [1]: https://docs.rs/reqwest/latest/reqwest/blocking/struct.Client.html
+    pub fn from_builder<F>(base: &str, builder: F) -> Result<Self, ClientError>
+    where
+        F: Fn() -> reqwest::blocking::ClientBuilder + Send + Sync + 'static,
+    {
+        let http = builder()
+            .build()
+            .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+        Ok(Client {
+            base: base.to_string(),
+            http,
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: Some(Arc::new(builder)),
+        })
+    }
+
+    /// Sets the [ClientConfig] used when executing requests.
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the client behind an [Arc] for sharing it across tasks or
+    /// request handlers.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// Returns the backing client for the given [Request], deriving a client
+    /// for its proxy and connect timeout if needed.
+    fn client_for(&self, req: &Request<Vec<u8>>) -> Result<reqwest::blocking::Client, ClientError> {
+        let route = req.extensions().get::<ProxyRoute>().map(|r| r.0.as_str());
+        let timeouts = Timeouts::of(req.extensions());
+        match (route, timeouts.connect, &self.builder) {
+            (None, None, _) => Ok(self.http.clone()),
+            (Some(route), _, None) => Err(proxy::missing_builder(route)),
+            (None, Some(_), None) => {
+                warn!("Ignoring connect timeout, which requires a client created with Client::from_builder");
+                Ok(self.http.clone())
+            }
+            (Some(route), None, Some(builder)) => self
+                .proxies
+                .get_or_build(route, |p| builder().proxy(p).build()),
+            (route, Some(connect), Some(builder)) => {
+                let timeouts = Timeouts::new().connect(connect);
+                self.proxies.get_or_build_with(route, &timeouts, |p| {
+                    let mut builder = builder();
+                    if let Some(p) = p {
+                        builder = builder.proxy(p);
+                    }
+                    builder.connect_timeout(connect).build()
+                })
+            }
         }
     }
 }
@@ -62,15 +167,23 @@ impl RustifyClient for Client {
         self.base.as_str()
     }
 
+    fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
     #[instrument(skip(self, req), err)]
     fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
-        let request = reqwest::blocking::Request::try_from(req)
+        let client = self.client_for(&req)?;
+        let timeout = AttemptTimeout::of(req.extensions());
+        let mut request = reqwest::blocking::Request::try_from(req)
             .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+        if timeout.is_some() {
+            *request.timeout_mut() = timeout;
+        }
 
         let url_err = request.url().to_string();
         let method_err = request.method().to_string();
-        let response = self
-            .http
+        let response = client
             .execute(request)
             .map_err(|e| ClientError::RequestError {
                 source: e.into(),
@@ -79,18 +192,118 @@ impl RustifyClient for Client {
             })?;
 
         let status_code = response.status().as_u16();
-        let mut headers = http::header::HeaderMap::new();
-        let http_resp = http::Response::builder().status(status_code);
+        let mut http_resp = http::Response::builder().status(status_code);
         for v in response.headers().into_iter() {
-            headers.append::<http::header::HeaderName>(v.0.into(), v.1.into());
+            http_resp = http_resp.header(v.0, v.1);
+        }
+        if let Some(info) = crate::tls::from_reqwest(response.extensions()) {
+            http_resp = http_resp.extension(info);
         }
         http_resp
             .body(
                 response
                     .bytes()
                     .map_err(|e| ClientError::ResponseError { source: e.into() })?
-                    .to_vec(),
+                    .into(),
             )
             .map_err(|e| ClientError::ResponseError { source: e.into() })
     }
 }
+
+#[cfg(feature = "mmap")]
+impl Client {
+    /// Executes the given [Request] and returns a [Response] whose body is
+    /// held as [Bytes].
+    ///
+    /// Response bodies up to `threshold` bytes are buffered in memory. Larger
+    /// bodies are spilled to a temporary file which is then memory-mapped,
+    /// allowing endpoints which occasionally return massive payloads to be
+    /// consumed without holding the entire body in memory. The returned body
+    /// can be passed directly to [crate::http::parse].
+    ///
+    /// # Example
+    /// ```
+    /// use rustify::blocking::clients::reqwest::Client;
+    /// use rustify::Endpoint;
+    /// use rustify_derive::Endpoint;
+    ///
+    /// #[derive(Endpoint)]
+    /// #[endpoint(path = "my/export")]
+    /// struct Export {}
+    ///
+    /// let client = Client::default("http://myapi.com");
+    /// let req = Export {}.request(&client.base).unwrap();
+    /// let result = client.execute_spilled(req, 64 * 1024 * 1024);
+    /// ```
+    #[instrument(skip(self, req), err)]
+    pub fn execute_spilled(
+        &self,
+        req: Request<Vec<u8>>,
+        threshold: usize,
+    ) -> Result<Response<Bytes>, ClientError> {
+        let client = self.client_for(&req)?;
+        let request = reqwest::blocking::Request::try_from(req)
+            .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+
+        let url_err = request.url().to_string();
+        let method_err = request.method().to_string();
+        let mut response = client
+            .execute(request)
+            .map_err(|e| ClientError::RequestError {
+                source: e.into(),
+                url: url_err,
+                method: method_err,
+            })?;
+
+        let status_code = response.status().as_u16();
+        let mut http_resp = http::Response::builder().status(status_code);
+        for v in response.headers().into_iter() {
+            http_resp = http_resp.header(v.0, v.1);
+        }
+        if let Some(info) = crate::tls::from_reqwest(response.extensions()) {
+            http_resp = http_resp.extension(info);
+        }
+
+        let body = read_spilled(&mut response, threshold)
+            .map_err(|e| ClientError::ResponseError { source: e.into() })?;
+        if !HTTP_SUCCESS_CODES.contains(&status_code) {
+            return Err(ClientError::ServerResponseError {
+                code: status_code,
+                content: String::from_utf8(body.to_vec()).ok(),
+            });
+        }
+
+        http_resp
+            .body(body)
+            .map_err(|e| ClientError::ResponseError { source: e.into() })
+    }
+}
+
+/// Reads the contents of `reader` into [Bytes], spilling it to a memory-mapped
+/// temporary file if it exceeds `threshold` bytes.
+#[cfg(feature = "mmap")]
+fn read_spilled(reader: &mut impl Read, threshold: usize) -> std::io::Result<Bytes> {
+    let mut buf = Vec::new();
+    reader
+        .by_ref()
+        .take(threshold as u64 + 1)
+        .read_to_end(&mut buf)?;
+    if buf.len() <= threshold {
+        return Ok(Bytes::from(buf));
+    }
+
+    debug!(
+        "Spilling response body larger than {} bytes to disk",
+        threshold
+    );
+    let mut file = tempfile::tempfile()?;
+    file.write_all(&buf)?;
+    drop(buf);
+    std::io::copy(reader, &mut file)?;
+    file.flush()?;
+
+    // SAFETY: The temporary file is unlinked on creation and is only reachable
+    // through this handle, so it cannot be modified while it's mapped.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(Bytes::from_owner(mmap))
+}
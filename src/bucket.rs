@@ -0,0 +1,221 @@
+//! Contains the [RateLimiter] type for limiting the rate at which
+//! [Endpoints][crate::endpoint::Endpoint] are executed.
+//!
+//! A rate limiter configured with
+//! [ClientConfig::rate_limit][crate::config::ClientConfig::rate_limit] holds
+//! one or more [TokenBucket]s, each of which applies either to every endpoint
+//! or to a group of endpoints selected by a [Matcher]. Executing an endpoint
+//! takes a token from every bucket it matches, waiting when the budget of a
+//! bucket is exhausted until enough tokens have been refilled. Asynchronous
+//! executions wait without blocking the runtime, while blocking executions
+//! put the current thread to sleep.
+//!
+//! Tokens are taken before middleware is applied, so signatures and
+//! timestamps added by middleware are as fresh as possible when the request
+//! is sent. Every attempt of a retried execution takes its own token.
+//! Requests sent with [Client::execute][crate::client::Client::execute]
+//! directly aren't limited.
+//!
+//! # Example
+//! ```
+//! use rustify::{
+//!     bucket::{RateLimiter, TokenBucket},
+//!     clients::reqwest::Client,
+//!     config::ClientConfig,
+//!     matcher::Matcher,
+//! };
+//!
+//! // At most 10 requests per second, of which at most 2 may be searches
+//! let limiter = RateLimiter::new()
+//!     .limit(TokenBucket::new(10.0))
+//!     .group(Matcher::new().path("search/**"), TokenBucket::new(2.0));
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().rate_limit(limiter));
+//! ```
+
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{
+    endpoint::Endpoint,
+    matcher::{Matcher, Target},
+};
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    updated: Instant,
+    waiting: usize,
+}
+
+#[derive(Debug)]
+struct Inner {
+    burst: f64,
+    rate: f64,
+    state: Mutex<State>,
+}
+
+/// A budget of requests which is refilled at a constant rate.
+///
+/// The bucket starts full and holds at most `burst` tokens, so up to `burst`
+/// requests can be sent at once after a period of inactivity. Requests which
+/// find the bucket empty wait in the order they arrived. All clones share the
+/// same budget.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    inner: Arc<Inner>,
+}
+
+/// Counts a request as waiting for a token until it's dropped, returning the
+/// token it reserved if the request is cancelled while waiting.
+struct Reservation<'a> {
+    inner: &'a Inner,
+    done: bool,
+}
+
+impl TokenBucket {
+    /// Returns a new [TokenBucket] which allows the given number of requests
+    /// per second, with a burst of the same size rounded up.
+    pub fn new(rate: f64) -> Self {
+        TokenBucket::with_burst(rate, rate.ceil() as usize)
+    }
+
+    /// Returns a new [TokenBucket] which allows the given number of requests
+    /// per second and holds at most `burst` tokens.
+    ///
+    /// A bucket with a rate of zero or below is never refilled, so requests
+    /// beyond its burst wait indefinitely.
+    pub fn with_burst(rate: f64, burst: usize) -> Self {
+        let burst = burst.max(1) as f64;
+        TokenBucket {
+            inner: Arc::new(Inner {
+                burst,
+                rate: rate.max(f64::MIN_POSITIVE),
+                state: Mutex::new(State {
+                    tokens: burst,
+                    updated: Instant::now(),
+                    waiting: 0,
+                }),
+            }),
+        }
+    }
+
+    /// Returns the number of requests allowed per second.
+    pub fn rate(&self) -> f64 {
+        self.inner.rate
+    }
+
+    /// Returns the maximum number of tokens held by the bucket.
+    pub fn burst(&self) -> usize {
+        self.inner.burst as usize
+    }
+
+    /// Returns the number of requests waiting for a token.
+    pub fn queued(&self) -> usize {
+        self.inner.lock().waiting
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        let (delay, mut reservation) = self.reserve();
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        reservation.done = true;
+    }
+
+    /// Blocks until a token is available and takes it.
+    pub fn acquire_blocking(&self) {
+        let (delay, mut reservation) = self.reserve();
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        reservation.done = true;
+    }
+
+    /// Takes a token, allowing the bucket to go into debt, and returns how
+    /// long to wait until the debt is repaid.
+    fn reserve(&self) -> (Duration, Reservation<'_>) {
+        let mut state = self.inner.lock();
+        let now = Instant::now();
+        let refilled = now.duration_since(state.updated).as_secs_f64() * self.inner.rate;
+        state.tokens = (state.tokens + refilled).min(self.inner.burst) - 1.0;
+        state.updated = now;
+        state.waiting += 1;
+        let delay = match state.tokens < 0.0 {
+            true => Duration::try_from_secs_f64(-state.tokens / self.inner.rate)
+                .unwrap_or(Duration::MAX),
+            false => Duration::ZERO,
+        };
+        let reservation = Reservation {
+            inner: &self.inner,
+            done: false,
+        };
+        (delay, reservation)
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut state = self.inner.lock();
+        state.waiting -= 1;
+        if !self.done {
+            state.tokens += 1.0;
+        }
+    }
+}
+
+/// Limits the rate at which endpoints are executed using one or more
+/// [TokenBucket]s, see the [module documentation][self].
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    groups: Vec<(Matcher, TokenBucket)>,
+}
+
+impl RateLimiter {
+    /// Returns a new [RateLimiter] without any limits.
+    pub fn new() -> Self {
+        RateLimiter::default()
+    }
+
+    /// Limits every endpoint using the given bucket.
+    pub fn limit(self, bucket: TokenBucket) -> Self {
+        self.group(Matcher::new(), bucket)
+    }
+
+    /// Limits the endpoints matched by the given [Matcher] using the given
+    /// bucket. Endpoints matching multiple groups take a token from each.
+    pub fn group(mut self, matcher: Matcher, bucket: TokenBucket) -> Self {
+        self.groups.push((matcher, bucket));
+        self
+    }
+
+    /// Returns the buckets which apply to the given [Endpoint].
+    pub fn buckets<E: Endpoint>(&self, endpoint: &E) -> Vec<&TokenBucket> {
+        if self.groups.is_empty() {
+            return Vec::new();
+        }
+        let path = endpoint.path();
+        let target = Target::new(endpoint, &path);
+        self.groups
+            .iter()
+            .filter(|(m, _)| m.matches(&target))
+            .map(|(_, b)| b)
+            .collect()
+    }
+
+    /// Returns the total number of requests waiting for a token.
+    pub fn queued(&self) -> usize {
+        self.groups.iter().map(|(_, b)| b.queued()).sum()
+    }
+}
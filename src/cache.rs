@@ -0,0 +1,377 @@
+//! Contains the [CachePolicy] type which lets individual
+//! [Endpoints][crate::endpoint::Endpoint] tune how their responses are
+//! cached, and the [HttpCache] client which caches responses.
+//!
+//! The policy of an endpoint is stored as an extension of its request so
+//! caching middleware can honor it without reconfiguring the whole client. It
+//! can be set with the derive macro using the `cache` parameter:
+//!
+//! ```
+//! use rustify::{cache::CachePolicy, Endpoint};
+//! use rustify_derive::Endpoint;
+//! use std::time::Duration;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "secrets", cache = "no-store")]
+//! struct ReadSecrets {}
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "regions", cache = "ttl=10m")]
+//! struct ListRegions {}
+//!
+//! assert_eq!(ReadSecrets {}.cache_policy(), CachePolicy::NoStore);
+//! assert_eq!(
+//!     ListRegions {}.cache_policy(),
+//!     CachePolicy::Ttl(Duration::from_secs(600))
+//! );
+//! ```
+//!
+//! ## Revalidation
+//!
+//! An [HttpCache] wraps a client and keeps the most recent successful
+//! response to every `GET` and `HEAD` request, keyed by its method and URL.
+//! Later requests for the same URL are sent with an `If-None-Match` header
+//! containing the stored `ETag` and an `If-Modified-Since` header containing
+//! the stored `Last-Modified` date. When the server responds with
+//! `304 Not Modified`, the stored response is returned in its place, so
+//! endpoints parse it as if the server had sent the full body. Every response
+//! returned by the cache carries a [CacheStatus] extension telling how it was
+//! obtained.
+//!
+//! Only responses with an `ETag` or a `Last-Modified` header are stored,
+//! unless the endpoint has a [CachePolicy::Ttl], in which case any successful
+//! response is stored and returned without contacting the server until it's
+//! older than the TTL. Responses of endpoints with [CachePolicy::NoStore] and
+//! responses with a `Cache-Control: no-store` header are never stored.
+//!
+//! ```
+//! use rustify::{cache::HttpCache, clients::reqwest::Client};
+//!
+//! let client = HttpCache::new(Client::default("https://api.example.com"));
+//! ```
+//!
+//! ## Stale while revalidate
+//!
+//! Latency sensitive reads can be served from the cache while it's refreshed
+//! in the background by configuring [HttpCache::stale_while_revalidate].
+//! Stored responses which would otherwise be revalidated before being
+//! returned, i.e. responses older than their TTL or responses without a TTL,
+//! are returned immediately with [CacheStatus::Stale] for the given duration
+//! after they were stored. The request is sent in a background task, which
+//! updates the stored response once the server replies. Only one background
+//! refresh is sent for each URL at a time.
+//!
+//! ```
+//! use rustify::{cache::HttpCache, clients::reqwest::Client};
+//! use std::time::Duration;
+//!
+//! let client = HttpCache::new(Client::default("https://api.example.com"))
+//!     .stale_while_revalidate(Duration::from_secs(60));
+//! ```
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use http::{
+    header::{
+        CACHE_CONTROL, CONTENT_LENGTH, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    },
+    Extensions, HeaderMap, Method, Request, Response, StatusCode,
+};
+use tokio::time::Instant;
+
+use crate::{client::Client, config::ClientConfig, errors::ClientError};
+
+/// Determines how the response of an [Endpoint][crate::endpoint::Endpoint]
+/// is cached.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CachePolicy {
+    /// Caches the response as configured for the client
+    #[default]
+    Default,
+    /// Never caches the response
+    NoStore,
+    /// Caches the response for the given duration, regardless of the
+    /// freshness reported by the server
+    Ttl(Duration),
+}
+
+impl CachePolicy {
+    /// Returns the [CachePolicy] stored in the given extensions, defaulting to
+    /// [CachePolicy::Default].
+    pub fn of(extensions: &Extensions) -> Self {
+        extensions.get::<CachePolicy>().copied().unwrap_or_default()
+    }
+}
+
+/// How a response returned by an [HttpCache] was obtained. This is stored in
+/// the extensions of the [Response].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CacheStatus {
+    /// The response was received from the server
+    Miss,
+    /// The server reported the stored response was unchanged
+    Revalidated,
+    /// The stored response was returned without contacting the server
+    Hit,
+    /// The stored response was returned while it's being refreshed in the
+    /// background, see [HttpCache::stale_while_revalidate]
+    Stale,
+}
+
+impl CacheStatus {
+    /// Returns the [CacheStatus] stored in the given extensions, if any.
+    pub fn of(extensions: &Extensions) -> Option<Self> {
+        extensions.get::<CacheStatus>().copied()
+    }
+}
+
+/// A stored response.
+#[derive(Clone, Debug)]
+struct Entry {
+    body: Vec<u8>,
+    headers: HeaderMap,
+    stored: Instant,
+}
+
+impl Entry {
+    /// Returns a response containing the stored headers and body.
+    fn response(&self, status: CacheStatus) -> Result<Response<Vec<u8>>, ClientError> {
+        let mut resp = Response::builder()
+            .status(StatusCode::OK)
+            .extension(status)
+            .body(self.body.clone())
+            .map_err(|e| ClientError::ResponseError { source: e.into() })?;
+        *resp.headers_mut() = self.headers.clone();
+        Ok(resp)
+    }
+}
+
+type Refresh = Arc<dyn Fn(String, CachePolicy, Request<Vec<u8>>, Entry) + Send + Sync>;
+
+/// The state of an [HttpCache] shared with its background refreshes.
+#[derive(Debug)]
+struct Shared<C> {
+    client: C,
+    entries: Mutex<HashMap<String, Entry>>,
+    refreshing: Mutex<HashSet<String>>,
+}
+
+impl<C: Client> Shared<C> {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stores the given response under the given key if it can be
+    /// revalidated or the policy allows it.
+    fn store(&self, key: String, policy: CachePolicy, resp: &Response<Vec<u8>>) {
+        let headers = resp.headers();
+        let no_store = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .any(|v| v.to_ascii_lowercase().contains("no-store"));
+        let validated = headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED);
+        if resp.status() == StatusCode::OK
+            && !no_store
+            && (validated || matches!(policy, CachePolicy::Ttl(_)))
+        {
+            let entry = Entry {
+                body: resp.body().clone(),
+                headers: headers.clone(),
+                stored: Instant::now(),
+            };
+            self.lock().insert(key, entry);
+        }
+    }
+
+    /// Sends the given conditional request and updates the stored response
+    /// with the reply.
+    async fn fetch(
+        &self,
+        key: String,
+        policy: CachePolicy,
+        req: Request<Vec<u8>>,
+        entry: Option<Entry>,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let mut resp = self.client.send(req).await?;
+        match entry {
+            Some(mut entry) if resp.status() == StatusCode::NOT_MODIFIED => {
+                debug!("Stored response for {} was revalidated", key);
+                for (name, value) in resp.headers() {
+                    if name != CONTENT_LENGTH {
+                        entry.headers.insert(name.clone(), value.clone());
+                    }
+                }
+                entry.stored = Instant::now();
+                let resp = entry.response(CacheStatus::Revalidated)?;
+                self.lock().insert(key, entry);
+                Ok(resp)
+            }
+            _ => {
+                self.store(key, policy, &resp);
+                resp.extensions_mut().insert(CacheStatus::Miss);
+                Ok(resp)
+            }
+        }
+    }
+}
+
+/// A [Client] which wraps another [Client] and caches the responses it
+/// receives, revalidating them with conditional requests, see the
+/// [module documentation][self#revalidation].
+pub struct HttpCache<C> {
+    shared: Arc<Shared<C>>,
+    stale: Option<(Duration, Refresh)>,
+}
+
+impl<C: Client> HttpCache<C> {
+    /// Returns a new, empty [HttpCache] which sends requests using the given
+    /// client.
+    pub fn new(client: C) -> Self {
+        HttpCache {
+            shared: Arc::new(Shared {
+                client,
+                entries: Mutex::new(HashMap::new()),
+                refreshing: Mutex::new(HashSet::new()),
+            }),
+            stale: None,
+        }
+    }
+
+    /// Returns stored responses which need revalidating for the given
+    /// duration after they were stored, refreshing them in the background,
+    /// see the [module documentation][self#stale-while-revalidate].
+    pub fn stale_while_revalidate(mut self, window: Duration) -> Self
+    where
+        C: 'static,
+    {
+        let shared = self.shared.clone();
+        let refresh = move |key: String, policy, req, entry| {
+            let shared = shared.clone();
+            tokio::spawn(async move {
+                if let Err(e) = shared.fetch(key.clone(), policy, req, Some(entry)).await {
+                    warn!("Failed refreshing stored response for {}: {}", key, e);
+                }
+                shared
+                    .refreshing
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&key);
+            });
+        };
+        self.stale = Some((window, Arc::new(refresh)));
+        self
+    }
+
+    /// Returns the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.shared.client
+    }
+
+    /// Returns the number of stored responses.
+    pub fn len(&self) -> usize {
+        self.shared.lock().len()
+    }
+
+    /// Returns whether no responses are stored.
+    pub fn is_empty(&self) -> bool {
+        self.shared.lock().is_empty()
+    }
+
+    /// Removes all stored responses.
+    pub fn clear(&self) {
+        self.shared.lock().clear()
+    }
+
+    /// Starts refreshing the given stale entry in the background and returns
+    /// it, unless it's too old to be served or already being refreshed.
+    fn serve_stale(
+        &self,
+        key: &str,
+        policy: CachePolicy,
+        req: &Request<Vec<u8>>,
+        entry: &Entry,
+        fresh_for: Duration,
+    ) -> Option<Result<Response<Vec<u8>>, ClientError>> {
+        let (window, refresh) = self.stale.as_ref()?;
+        if entry.stored.elapsed() >= fresh_for.saturating_add(*window) {
+            return None;
+        }
+        let started = self
+            .shared
+            .refreshing
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string());
+        if started {
+            let mut copy = Request::new(req.body().clone());
+            *copy.method_mut() = req.method().clone();
+            *copy.uri_mut() = req.uri().clone();
+            *copy.headers_mut() = req.headers().clone();
+            *copy.extensions_mut() = req.extensions().clone();
+            refresh(key.to_string(), policy, copy, entry.clone());
+        }
+        debug!("Returning stale response for {}", key);
+        Some(entry.response(CacheStatus::Stale))
+    }
+}
+
+impl<C: fmt::Debug> fmt::Debug for HttpCache<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HttpCache")
+            .field("shared", &self.shared)
+            .field("stale", &self.stale.as_ref().map(|(w, _)| w))
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for HttpCache<C> {
+    async fn send(&self, mut req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let policy = CachePolicy::of(req.extensions());
+        if policy == CachePolicy::NoStore || !matches!(*req.method(), Method::GET | Method::HEAD) {
+            return self.shared.client.send(req).await;
+        }
+
+        let key = format!("{} {}", req.method(), req.uri());
+        let entry = self.shared.lock().get(&key).cloned();
+        if let Some(entry) = &entry {
+            let fresh_for = match policy {
+                CachePolicy::Ttl(ttl) => ttl,
+                _ => Duration::ZERO,
+            };
+            if entry.stored.elapsed() < fresh_for {
+                debug!("Returning stored response for {}", key);
+                return entry.response(CacheStatus::Hit);
+            }
+            for (validator, condition) in
+                [(ETAG, IF_NONE_MATCH), (LAST_MODIFIED, IF_MODIFIED_SINCE)]
+            {
+                if let Some(value) = entry.headers.get(validator) {
+                    if !req.headers().contains_key(&condition) {
+                        req.headers_mut().insert(condition, value.clone());
+                    }
+                }
+            }
+            if let Some(resp) = self.serve_stale(&key, policy, &req, entry, fresh_for) {
+                return resp;
+            }
+        }
+
+        self.shared.fetch(key, policy, req, entry).await
+    }
+
+    fn base(&self) -> &str {
+        self.shared.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.shared.client.config()
+    }
+}
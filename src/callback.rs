@@ -0,0 +1,199 @@
+//! Contains the [CallbackServer] type for turning APIs which deliver results
+//! to a callback URL into awaitable calls.
+//!
+//! This module requires the `callback` feature and a tokio runtime.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    net::SocketAddr,
+    time::Duration,
+};
+
+use crate::{
+    client::Client,
+    endpoint::{Endpoint, EndpointResult},
+    enums::ResponseType,
+    errors::ClientError,
+};
+use bytes::Bytes;
+use http::{Request, Response, StatusCode};
+use http_body_util::{BodyExt, Full};
+use hyper::{body::Incoming, server::conn::http1, service::service_fn};
+use hyper_util::rt::TokioIo;
+use serde::de::DeserializeOwned;
+use tokio::{
+    net::{TcpListener, ToSocketAddrs},
+    sync::mpsc,
+    task::JoinHandle,
+};
+
+/// A request received by a [CallbackServer].
+#[derive(Debug)]
+pub struct Callback {
+    pub request: Request<Vec<u8>>,
+}
+
+impl Callback {
+    /// Parses the body of the callback request as JSON.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, ClientError> {
+        crate::http::parse(self.request.body(), ResponseType::JSON)
+    }
+}
+
+/// A temporary HTTP listener which receives callbacks.
+///
+/// The server listens on a random, unguessable path and only requests sent to
+/// that path are delivered; all others receive a `404`. Every delivered
+/// callback is answered with a `200`. The listener stops when the server is
+/// dropped.
+///
+/// # Example
+/// ```no_run
+/// use std::time::Duration;
+/// use rustify::{callback::CallbackServer, clients::reqwest::Client};
+/// use rustify_derive::Endpoint;
+///
+/// #[derive(Endpoint)]
+/// #[endpoint(path = "jobs", method = "POST")]
+/// struct StartJob {
+///     callback_url: String,
+/// }
+///
+/// # tokio_test::block_on(async {
+/// let client = Client::default("http://myapi.com");
+/// let (_, callback) = CallbackServer::bind("0.0.0.0:0")
+///     .await
+///     .unwrap()
+///     .public_url("https://tunnel.example.com")
+///     .exec(&client, Duration::from_secs(60), |url| StartJob {
+///         callback_url: url.to_string(),
+///     })
+///     .await
+///     .unwrap();
+/// let status: String = callback.parse().unwrap();
+/// # })
+/// ```
+pub struct CallbackServer {
+    addr: SocketAddr,
+    path: String,
+    url: String,
+    callbacks: mpsc::UnboundedReceiver<Callback>,
+    task: JoinHandle<()>,
+}
+
+impl CallbackServer {
+    /// Starts listening for callbacks on the given address.
+    ///
+    /// The callback URL defaults to `http://<addr>/<path>`, use
+    /// [CallbackServer::public_url] when the listener is only reachable
+    /// through another address, i.e. a tunnel or load balancer.
+    pub async fn bind(addr: impl ToSocketAddrs) -> Result<Self, ClientError> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        let path = format!(
+            "/callback/{:016x}",
+            RandomState::new().build_hasher().finish()
+        );
+        let (tx, callbacks) = mpsc::unbounded_channel();
+        let task = tokio::spawn(serve(listener, path.clone(), tx));
+
+        Ok(CallbackServer {
+            addr,
+            url: format!("http://{}{}", addr, path),
+            path,
+            callbacks,
+            task,
+        })
+    }
+
+    /// Sets the base URL the listener is publicly reachable at.
+    pub fn public_url(mut self, base: &str) -> Self {
+        self.url = format!("{}{}", base.trim_end_matches('/'), self.path);
+        self
+    }
+
+    /// Returns the local address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the URL callbacks should be sent to.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Waits up to the given timeout for the next callback.
+    pub async fn wait(&mut self, timeout: Duration) -> Result<Callback, ClientError> {
+        match tokio::time::timeout(timeout, self.callbacks.recv()).await {
+            Ok(Some(c)) => Ok(c),
+            Ok(None) => Err(ClientError::GenericError {
+                source: anyhow::anyhow!("Callback server stopped"),
+            }),
+            Err(_) => Err(ClientError::GenericError {
+                source: anyhow::anyhow!("Timed out after {:?} waiting for callback", timeout),
+            }),
+        }
+    }
+
+    /// Executes the [Endpoint] built by `f` from the callback URL and then
+    /// waits up to the given timeout for the callback.
+    pub async fn exec<E, F>(
+        mut self,
+        client: &impl Client,
+        timeout: Duration,
+        f: F,
+    ) -> Result<(EndpointResult<E::Response>, Callback), ClientError>
+    where
+        E: Endpoint,
+        F: FnOnce(&str) -> E,
+    {
+        let result = f(&self.url).exec(client).await?;
+        let callback = self.wait(timeout).await?;
+        Ok((result, callback))
+    }
+}
+
+impl Drop for CallbackServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Accepts connections and delivers requests sent to `path`.
+async fn serve(listener: TcpListener, path: String, tx: mpsc::UnboundedSender<Callback>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let path = path.clone();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req: Request<Incoming>| {
+                let path = path.clone();
+                let tx = tx.clone();
+                async move {
+                    if req.uri().path() != path {
+                        return respond(StatusCode::NOT_FOUND);
+                    }
+                    let (parts, body) = req.into_parts();
+                    let body = body.collect().await?.to_bytes().to_vec();
+                    let _ = tx.send(Callback {
+                        request: Request::from_parts(parts, body),
+                    });
+                    respond(StatusCode::OK)
+                }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await;
+        });
+    }
+}
+
+fn respond(status: StatusCode) -> Result<Response<Full<Bytes>>, hyper::Error> {
+    let mut resp = Response::new(Full::new(Bytes::new()));
+    *resp.status_mut() = status;
+    Ok(resp)
+}
@@ -0,0 +1,162 @@
+//! Contains the [BodyCapture] type for recording request and response bodies
+//! on tracing spans.
+//!
+//! Logging every body is too noisy and too risky for production, so a
+//! [BodyCapture] only records the bodies of a fraction of requests, truncates
+//! them to a maximum size, and replaces the values of sensitive JSON fields
+//! before recording them. Captured bodies are recorded as the `request_body`
+//! and `response_body` fields of the `execute` span when configured with
+//! [ClientConfig::capture_bodies][crate::config::ClientConfig::capture_bodies].
+//!
+//! # Example
+//! ```
+//! use rustify::capture::BodyCapture;
+//!
+//! let capture = BodyCapture::new().sample_rate(0.01).max_bytes(512).redact("ssn");
+//! assert_eq!(
+//!     capture.render(br#"{"user":{"ssn":"123-45-6789"}}"#),
+//!     r#"{"user":{"ssn":"<redacted>"}}"#
+//! );
+//! ```
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use serde_json::Value;
+
+/// The JSON fields which are always redacted.
+const SENSITIVE_FIELDS: [&str; 8] = [
+    "access_token",
+    "api_key",
+    "authorization",
+    "client_secret",
+    "password",
+    "refresh_token",
+    "secret",
+    "token",
+];
+
+const REDACTED: &str = "<redacted>";
+
+/// Records a sample of request and response bodies, see the
+/// [module documentation][self].
+///
+/// Sampling is deterministic: with a rate of `0.1` exactly one in every ten
+/// requests is captured. The sampling state is shared by all clones of a
+/// [BodyCapture].
+#[derive(Clone)]
+pub struct BodyCapture {
+    rate: f64,
+    max_bytes: usize,
+    redact: Vec<String>,
+    seen: Arc<AtomicU64>,
+}
+
+impl Default for BodyCapture {
+    fn default() -> Self {
+        BodyCapture {
+            rate: 1.0,
+            max_bytes: 1024,
+            redact: SENSITIVE_FIELDS.iter().map(|f| f.to_string()).collect(),
+            seen: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+impl BodyCapture {
+    /// Returns a new [BodyCapture] which captures up to 1 KiB of the bodies
+    /// of every request, redacting common credential fields.
+    pub fn new() -> Self {
+        BodyCapture::default()
+    }
+
+    /// Sets the fraction of requests whose bodies are captured, between `0.0`
+    /// and `1.0`.
+    pub fn sample_rate(mut self, rate: f64) -> Self {
+        self.rate = rate.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the maximum number of bytes captured of each body.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.max_bytes = max;
+        self
+    }
+
+    /// Redacts the values of JSON fields with the given name, compared case
+    /// insensitively, at any depth.
+    pub fn redact(mut self, field: &str) -> Self {
+        self.redact.push(field.to_string());
+        self
+    }
+
+    /// Returns whether the bodies of the next request should be captured.
+    pub fn sample(&self) -> bool {
+        let n = self.seen.fetch_add(1, Ordering::Relaxed) as f64;
+        ((n + 1.0) * self.rate).floor() > (n * self.rate).floor()
+    }
+
+    /// Returns the given body as it's captured: redacted, truncated, and
+    /// with binary data replaced by its size.
+    pub fn render(&self, body: &[u8]) -> String {
+        if body.is_empty() {
+            return String::new();
+        }
+        let text = match serde_json::from_slice::<Value>(body) {
+            Ok(mut value) => {
+                self.redact_value(&mut value);
+                value.to_string()
+            }
+            Err(_) => match std::str::from_utf8(body) {
+                Ok(s) => s.to_string(),
+                Err(_) => return format!("<{} bytes of binary data>", body.len()),
+            },
+        };
+        truncate(text, self.max_bytes)
+    }
+
+    fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (k, v) in map.iter_mut() {
+                    match self.redact.iter().any(|r| r.eq_ignore_ascii_case(k)) {
+                        true => *v = Value::String(REDACTED.to_string()),
+                        false => self.redact_value(v),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| self.redact_value(v)),
+            _ => {}
+        }
+    }
+}
+
+/// Truncates the given text to at most `max` bytes on a character boundary,
+/// noting how much was left out.
+fn truncate(mut text: String, max: usize) -> String {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let omitted = text.len() - end;
+    text.truncate(end);
+    format!("{}... ({} more bytes)", text, omitted)
+}
+
+impl fmt::Debug for BodyCapture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BodyCapture")
+            .field("rate", &self.rate)
+            .field("max_bytes", &self.max_bytes)
+            .field("redact", &self.redact)
+            .finish()
+    }
+}
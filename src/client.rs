@@ -1,13 +1,173 @@
 //! Contains the [Client] trait for executing
 //! [Endpoints][crate::endpoint::Endpoint].
-use crate::errors::ClientError;
+use crate::{
+    adaptive,
+    config::{ClientConfig, DEFAULT_CONFIG},
+    enums::RequestPriority,
+    errors::ClientError,
+    events::{Attempt, BodySizes, Limiter, Operation},
+    limit,
+    probe::{HealthCheckEndpoint, ProbeReport},
+    resilience::ResilienceState,
+    scheduler,
+    shutdown::Active,
+    stream::ByteStream,
+};
 use async_trait::async_trait;
-use http::{Request, Response};
-use std::ops::RangeInclusive;
+use http::{Method, Request, Response, Uri};
+use std::{
+    ops::RangeInclusive,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 /// An array of HTTP response codes which indicate a successful response
 pub const HTTP_SUCCESS_CODES: RangeInclusive<u16> = 200..=208;
 
+/// Returns the [RequestPriority] stored in the extensions of a [Request].
+pub(crate) fn priority<T>(req: &Request<T>) -> RequestPriority {
+    req.extensions()
+        .get::<RequestPriority>()
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Sends the given [Request] using the given [Client], resending it once if
+/// the connection was stale, see [ClientConfig::retry_stale_connections].
+pub(crate) async fn send<C: Client + ?Sized>(
+    client: &C,
+    mut req: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let config = client.config();
+    config.prepare_attempt(&mut req)?;
+    let retry = config.stale_retry(&req);
+    match (client.send(req).await, retry) {
+        (Err(e), Some(mut req)) if e.is_stale_connection() => {
+            warn!(
+                "Retrying {} {} after a stale connection",
+                req.method(),
+                req.uri()
+            );
+            config.prepare_attempt(&mut req)?;
+            client.send(req).await
+        }
+        (result, _) => result,
+    }
+}
+
+/// The permits which count an execution against the limits of a client until
+/// they're dropped.
+pub(crate) struct Guards {
+    _active: Option<Active>,
+    _permit: Option<limit::Permit>,
+    _scheduled: Option<scheduler::Permit>,
+}
+
+/// An execution which was admitted by the limits of a client, see [admit].
+struct Admission {
+    operation: Option<&'static str>,
+    captured: bool,
+    adaptive: Option<adaptive::Permit>,
+    guards: Guards,
+}
+
+/// Prepares the given [Request] for execution and waits until it's admitted
+/// by the shutdown tracking and the limiters of the given [ClientConfig].
+async fn admit(
+    config: &ClientConfig,
+    req: &mut Request<Vec<u8>>,
+) -> Result<Admission, ClientError> {
+    config.annotate_attempt(req);
+    config.start_deadline(req);
+    config.apply_state(req);
+    config.hooks.request(req);
+    config.emit_started(req);
+    let captured = config.capture_request(req);
+    let operation = Operation::of(req.extensions());
+    let active = config
+        .check_gate(req)
+        .and_then(|_| config.check_url(req))
+        .and_then(|_| config.enter())
+        .inspect_err(|e| failed(config, operation, e))?;
+
+    let permit = match &config.concurrency {
+        Some(limit) => {
+            let acquire = limit.acquire(req.uri());
+            Some(
+                config
+                    .admit(req, Limiter::Concurrency, limit.queued(), acquire)
+                    .await,
+            )
+        }
+        None => None,
+    };
+    let scheduled = match &config.scheduler {
+        Some(s) => {
+            let acquire = s.acquire(priority(req));
+            Some(
+                config
+                    .admit(req, Limiter::Scheduler, s.queued(), acquire)
+                    .await,
+            )
+        }
+        None => None,
+    };
+    let adaptive = match &config.adaptive {
+        Some(a) => Some(
+            config
+                .admit(req, Limiter::Adaptive, a.queued(), a.acquire())
+                .await,
+        ),
+        None => None,
+    };
+    Ok(Admission {
+        operation,
+        captured,
+        adaptive,
+        guards: Guards {
+            _active: active,
+            _permit: permit,
+            _scheduled: scheduled,
+        },
+    })
+}
+
+/// Records the latency and outcome of the request of the given [Admission]
+/// once its response, or an error, is received.
+fn settle<B>(
+    config: &ClientConfig,
+    admission: &mut Admission,
+    method: &Method,
+    uri: &Uri,
+    elapsed: Duration,
+    result: Result<Response<B>, ClientError>,
+) -> Result<Response<B>, ClientError> {
+    config.check_latency(method, uri, elapsed);
+    config.record_metrics(admission.operation, &result, elapsed);
+    if let Some(permit) = admission.adaptive.take() {
+        permit.record(&result);
+    }
+    result.inspect_err(|e| failed(config, admission.operation, e))
+}
+
+/// Reports the given error of an execution to the hooks and event sink of the
+/// given [ClientConfig].
+fn failed(config: &ClientConfig, operation: Option<&'static str>, err: &ClientError) {
+    config.hooks.error(err);
+    config.emit_failed(operation, err);
+}
+
+/// Returns a copy of the status, headers, and extensions of the given
+/// [Response] with an empty body.
+fn head<B>(response: &Response<B>) -> Response<Vec<u8>> {
+    let mut head = Response::new(Vec::new());
+    *head.status_mut() = response.status();
+    *head.version_mut() = response.version();
+    *head.headers_mut() = response.headers().clone();
+    *head.extensions_mut() = response.extensions().clone();
+    head
+}
+
 /// Represents an HTTP client which is capable of executing
 /// [Endpoints][crate::endpoint::Endpoint] by sending the [Request] generated
 /// by the Endpoint and returning a [Response].
@@ -22,20 +182,113 @@ pub trait Client: Sync + Send {
     /// [Endpoints][crate::endpoint::Endpoint].
     fn base(&self) -> &str;
 
+    /// Returns the [ClientConfig] the client is configured with. Clients which
+    /// don't support configuration can rely on the default implementation.
+    fn config(&self) -> &ClientConfig {
+        &DEFAULT_CONFIG
+    }
+
+    /// Returns whether [Client::send_stream] returns before the response body
+    /// is received, see [crate::stream]. Defaults to false.
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Sends the given [Request] and returns a [Response] whose body is read
+    /// as it's received. The default implementation receives the complete
+    /// body using [Client::send] and yields it as a single chunk.
+    async fn send_stream(
+        &self,
+        req: Request<Vec<u8>>,
+    ) -> Result<Response<ByteStream>, ClientError> {
+        self.send(req)
+            .await
+            .map(|resp| resp.map(crate::stream::single))
+    }
+
+    /// Returns a snapshot of the limits applied to requests, see
+    /// [ClientConfig::resilience_state].
+    fn resilience_state(&self) -> ResilienceState {
+        self.config().resilience_state()
+    }
+
+    /// Stops accepting new executions and waits up to the given grace period
+    /// for the requests in flight to complete before cancelling them, see
+    /// [Shutdown::shutdown][crate::shutdown::Shutdown::shutdown]. Returns the number of requests which were
+    /// cancelled.
+    ///
+    /// This does nothing unless the client is configured with
+    /// [ClientConfig::graceful_shutdown].
+    async fn shutdown(&self, grace: Duration) -> usize {
+        match &self.config().shutdown {
+            Some(s) => s.shutdown(grace).await,
+            None => 0,
+        }
+    }
+
     /// This method provides a common interface to
     /// [Endpoints][crate::endpoint::Endpoint] for execution.
     // TODO: remove the allow when the upstream clippy issue is fixed:
     // <https://github.com/rust-lang/rust-clippy/issues/12281>
     #[allow(clippy::blocks_in_conditions)]
-    #[instrument(skip(self, req), err)]
-    async fn execute(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+    #[instrument(
+        skip(self, req),
+        fields(
+            attempt = Attempt::of(req.extensions()),
+            request_body = tracing::field::Empty,
+            request_bytes = req.body().len(),
+            response_body = tracing::field::Empty,
+            response_bytes = tracing::field::Empty,
+            response_encoding = tracing::field::Empty,
+        ),
+        err
+    )]
+    async fn execute(&self, mut req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
         debug!(
             "Client sending {} request to {} with {} bytes of data",
             req.method().to_string(),
             req.uri(),
             req.body().len(),
         );
-        let response = self.send(req).await?;
+        let config = self.config();
+        let mut admission = admit(config, &mut req).await?;
+        let operation = admission.operation;
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let extensions = req.extensions().clone();
+        let request_bytes = req.body().len();
+        let start = Instant::now();
+        let sent = async {
+            match config.redirects {
+                Some(max) => crate::redirect::follow(self, req, max).await,
+                None => send(self, req).await,
+            }
+        };
+        let result = match &config.shutdown {
+            Some(s) => s.cancellable(sent).await,
+            None => sent.await,
+        };
+        let mut response = settle(
+            config,
+            &mut admission,
+            &method,
+            &uri,
+            start.elapsed(),
+            result,
+        )?;
+
+        // Carry request extensions over so they're available to middleware
+        response.extensions_mut().extend(extensions);
+        let mut sizes = BodySizes::new(request_bytes, &response);
+        sizes.decoded =
+            crate::encoding::decode(&mut response).inspect_err(|e| failed(config, operation, e))?;
+        config.record_sizes(&method, &uri, &sizes);
+        config.record_rate_limit(&uri, &response);
+        config.intercept_response(&uri, &response);
+        response.extensions_mut().insert(sizes);
+        if let Some(events) = &config.events {
+            response.extensions_mut().insert(events.clone());
+        }
 
         debug!(
             "Client received {} response with {} bytes of body data",
@@ -43,15 +296,138 @@ pub trait Client: Sync + Send {
             response.body().len()
         );
 
+        config.hooks.response(&response);
+        if admission.captured {
+            config.capture_response(&response);
+        }
+
         // Check response
         if !HTTP_SUCCESS_CODES.contains(&response.status().as_u16()) {
-            return Err(ClientError::ServerResponseError {
+            let err = ClientError::ServerResponseError {
                 code: response.status().as_u16(),
                 content: String::from_utf8(response.body().to_vec()).ok(),
-            });
+            };
+            failed(config, operation, &err);
+            return Err(err);
         }
 
         // Parse response content
         Ok(response)
     }
+
+    /// Executes the given [Request] like [Client::execute], but returns as
+    /// soon as the response headers are received, see [crate::stream].
+    ///
+    /// The request goes through the same hooks, limits, and accounting as
+    /// [Client::execute], except for redirects, which aren't followed, and
+    /// decoding. The permits of the request are held until the response body
+    /// is dropped. Response hooks and interceptors receive the response with
+    /// an empty body since it's yet to be received.
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(
+        skip(self, req),
+        fields(
+            attempt = Attempt::of(req.extensions()),
+            request_body = tracing::field::Empty,
+            request_bytes = req.body().len(),
+        ),
+        err
+    )]
+    async fn execute_stream(
+        &self,
+        mut req: Request<Vec<u8>>,
+    ) -> Result<Response<ByteStream>, ClientError> {
+        let config = self.config();
+        let mut admission = admit(config, &mut req).await?;
+        let operation = admission.operation;
+        let method = req.method().clone();
+        let uri = req.uri().clone();
+        let extensions = req.extensions().clone();
+        let start = Instant::now();
+        let sent = async {
+            config.prepare_attempt(&mut req)?;
+            self.send_stream(req).await
+        };
+        let result = match &config.shutdown {
+            Some(s) => s.cancellable(sent).await,
+            None => sent.await,
+        };
+        let mut response = settle(
+            config,
+            &mut admission,
+            &method,
+            &uri,
+            start.elapsed(),
+            result,
+        )?;
+
+        response.extensions_mut().extend(extensions);
+        config.record_rate_limit(&uri, &response);
+        if let Some(events) = &config.events {
+            response.extensions_mut().insert(events.clone());
+        }
+        let head = head(&response);
+        config.intercept_response(&uri, &head);
+        config.hooks.response(&head);
+
+        let status = response.status().as_u16();
+        if !HTTP_SUCCESS_CODES.contains(&status) {
+            let body = crate::stream::collect(response.into_body()).await;
+            let err = ClientError::ServerResponseError {
+                code: status,
+                content: body.ok().and_then(|b| String::from_utf8(b).ok()),
+            };
+            failed(config, operation, &err);
+            return Err(err);
+        }
+        let shutdown = config.shutdown.clone();
+        Ok(response.map(|body| crate::stream::guarded(body, admission.guards, shutdown)))
+    }
+
+    /// Sends a `GET` request to the given path to verify the base URL, TLS
+    /// configuration, and authentication of the client. Failures are returned
+    /// as a [ClientError::ProbeError] describing the likely cause.
+    ///
+    /// See [HealthCheckEndpoint] for probing with a different method.
+    async fn probe(&self, path: &str) -> Result<ProbeReport, ClientError> {
+        self.probe_with(&HealthCheckEndpoint::new(path)).await
+    }
+
+    /// Probes the client using the given [HealthCheckEndpoint].
+    async fn probe_with(&self, endpoint: &HealthCheckEndpoint) -> Result<ProbeReport, ClientError> {
+        let req = crate::probe::prepare(self.base(), endpoint)?;
+        let url = req.uri().to_string();
+        let start = Instant::now();
+        crate::probe::report(url, start, self.execute(req).await)
+    }
+}
+
+/// Clients behind an [Arc] can be used directly, i.e. one client shared by
+/// the request handlers of a web server.
+#[async_trait]
+impl<C: Client + ?Sized> Client for Arc<C> {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        (**self).send(req).await
+    }
+
+    fn base(&self) -> &str {
+        (**self).base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        (**self).config()
+    }
+
+    fn supports_streaming(&self) -> bool {
+        (**self).supports_streaming()
+    }
+
+    async fn send_stream(
+        &self,
+        req: Request<Vec<u8>>,
+    ) -> Result<Response<ByteStream>, ClientError> {
+        (**self).send_stream(req).await
+    }
 }
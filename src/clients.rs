@@ -2,4 +2,4 @@
 //! varying HTTP clients.
 #[cfg(feature = "reqwest")]
 pub mod reqwest;
-pub mod reqwest_middleware;
\ No newline at end of file
+pub mod reqwest_middleware;
@@ -1,10 +1,18 @@
 //! Contains an implementation of [Client][crate::client::Client] being backed
 //! by the [reqwest](https://docs.rs/reqwest/) crate.
 
-use crate::{client::Client as RustifyClient, errors::ClientError};
+use crate::{
+    client::Client as RustifyClient,
+    config::ClientConfig,
+    deadline::{AttemptTimeout, Timeouts},
+    errors::ClientError,
+    proxy::{self, ProxyPool, ProxyRoute},
+    stream::ByteStream,
+};
 use async_trait::async_trait;
+use futures_util::StreamExt;
 use http::{Request, Response};
-use std::convert::TryFrom;
+use std::{convert::TryFrom, sync::Arc};
 
 /// A client based on the
 /// [reqwest::Client][1] which can be used for executing
@@ -14,6 +22,21 @@ use std::convert::TryFrom;
 /// qualify the full path of any [Endpoints][crate::endpoint::Endpoint] which
 /// are executed by this client.
 ///
+/// Requests with a [ProxyRoute][crate::proxy::ProxyRoute] in their extensions
+/// are sent using a separate client for that proxy, and requests with a
+/// connect or read timeout, see [Timeouts], using a separate client built
+/// with those timeouts. These clients are derived from the builder passed to
+/// [Client::from_builder], or the default reqwest builder for
+/// [Client::default], and cached in `proxies`. Clients created with
+/// [Client::new] can't route requests through a proxy and ignore connect and
+/// read timeouts, only applying the total timeout to each request.
+///
+/// Cloning the client is cheap: clones share the connection pool of the
+/// backing [reqwest::Client][1], the cached proxy clients, and the state of
+/// the [ClientConfig], such as concurrency and rate limits. The client is
+/// `Send + Sync`, so it can also be shared behind an [Arc] returned by
+/// `shared()`.
+///
 /// # Example
 /// ```
 /// use rustify::clients::reqwest::Client;
@@ -33,17 +56,26 @@ use std::convert::TryFrom;
 /// ```
 ///
 /// [1]: https://docs.rs/reqwest/latest/reqwest/struct.Client.html
+#[derive(Clone)]
 pub struct Client {
     pub http: reqwest::Client,
     pub base: String,
+    pub config: ClientConfig,
+    pub proxies: ProxyPool<reqwest::Client>,
+    builder: Option<Builder>,
 }
 
+type Builder = Arc<dyn Fn() -> reqwest::ClientBuilder + Send + Sync>;
+
 impl Client {
     /// Creates a new instance of [Client] using the provided parameters.
     pub fn new(base: &str, http: reqwest::Client) -> Self {
         Client {
             base: base.to_string(),
             http,
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: None,
         }
     }
 
@@ -55,8 +87,56 @@ impl Client {
         Client {
             base: base.to_string(),
             http: reqwest::Client::default(),
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: Some(Arc::new(reqwest::Client::builder)),
         }
     }
+
+    /// Creates a new instance of [Client] backed by a [reqwest::Client][1]
+    /// built from the builder returned by the given function.
+    ///
+    /// The function is called again to derive the clients for proxies and
+    /// connection timeouts, so they share all other settings of the backing
+    /// client, such as its DNS resolver and redirect policy.
+    ///
+    /// ```
+    /// use rustify::clients::reqwest::Client;
+    ///
+    /// let client = Client::from_builder("https://api.example.com", || {
+    ///     reqwest::Client::builder().tls_info(true)
+    /// })
+    /// .unwrap();
+    /// ```
+    ///
+    /// [1]: https://docs.rs/reqwest/latest/reqwest/struct.Client.html
+    pub fn from_builder<F>(base: &str, builder: F) -> Result<Self, ClientError>
+    where
+        F: Fn() -> reqwest::ClientBuilder + Send + Sync + 'static,
+    {
+        let http = builder()
+            .build()
+            .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+        Ok(Client {
+            base: base.to_string(),
+            http,
+            config: ClientConfig::default(),
+            proxies: ProxyPool::new(),
+            builder: Some(Arc::new(builder)),
+        })
+    }
+
+    /// Sets the [ClientConfig] used when executing requests.
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the client behind an [Arc] for sharing it across tasks or
+    /// request handlers.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
 }
 
 #[async_trait]
@@ -65,40 +145,116 @@ impl RustifyClient for Client {
         self.base.as_str()
     }
 
+    fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
     // TODO: remove the allow when the upstream clippy issue is fixed:
     // <https://github.com/rust-lang/rust-clippy/issues/12281>
     #[allow(clippy::blocks_in_conditions)]
     #[instrument(skip(self, req), err)]
     async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
-        let request = reqwest::Request::try_from(req)
+        let response = self.dispatch(req).await?;
+        head(&response)
+            .body(
+                response
+                    .bytes()
+                    .await
+                    .map_err(|e| ClientError::ResponseError { source: e.into() })?
+                    .into(),
+            )
+            .map_err(|e| ClientError::ResponseError { source: e.into() })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip(self, req), err)]
+    async fn send_stream(
+        &self,
+        req: Request<Vec<u8>>,
+    ) -> Result<Response<ByteStream>, ClientError> {
+        let response = self.dispatch(req).await?;
+        let builder = head(&response);
+        let body = response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(|e| ClientError::ResponseError { source: e.into() }))
+            .boxed();
+        builder
+            .body(body)
+            .map_err(|e| ClientError::ResponseError { source: e.into() })
+    }
+}
+
+impl Client {
+    /// Returns the backing client for the given [Request], deriving a client
+    /// for its proxy and connection timeouts if needed.
+    fn client_for(&self, req: &Request<Vec<u8>>) -> Result<reqwest::Client, ClientError> {
+        let route = req.extensions().get::<ProxyRoute>().map(|r| r.0.as_str());
+        let timeouts = Timeouts::of(req.extensions());
+        match (route, timeouts.is_connection_level(), &self.builder) {
+            (None, false, _) => Ok(self.http.clone()),
+            (Some(route), _, None) => Err(proxy::missing_builder(route)),
+            (None, true, None) => {
+                warn!("Ignoring connect and read timeouts, which require a client created with Client::from_builder");
+                Ok(self.http.clone())
+            }
+            (Some(route), false, Some(builder)) => self
+                .proxies
+                .get_or_build(route, |p| builder().proxy(p).build()),
+            (route, true, Some(builder)) => self.proxies.get_or_build_with(route, &timeouts, |p| {
+                let mut builder = builder();
+                if let Some(p) = p {
+                    builder = builder.proxy(p);
+                }
+                if let Some(t) = timeouts.connect {
+                    builder = builder.connect_timeout(t);
+                }
+                if let Some(t) = timeouts.read {
+                    builder = builder.read_timeout(t);
+                }
+                builder.build()
+            }),
+        }
+    }
+
+    /// Sends the given [Request] using the backing client of its proxy, if
+    /// any, and returns the response once its headers are received.
+    async fn dispatch(&self, req: Request<Vec<u8>>) -> Result<reqwest::Response, ClientError> {
+        let client = self.client_for(&req)?;
+        let timeout = AttemptTimeout::of(req.extensions());
+        let mut request = reqwest::Request::try_from(req)
             .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+        if timeout.is_some() {
+            *request.timeout_mut() = timeout;
+        }
 
         let url_err = request.url().to_string();
         let method_err = request.method().to_string();
-        let response = self
-            .http
+        client
             .execute(request)
             .await
             .map_err(|e| ClientError::RequestError {
                 source: e.into(),
                 url: url_err,
                 method: method_err,
-            })?;
-
-        let status_code = response.status().as_u16();
-        let mut http_resp = http::Response::builder().status(status_code);
-        for v in response.headers().into_iter() {
-            http_resp = http_resp.header(v.0, v.1);
-        }
+            })
+    }
+}
 
-        http_resp
-            .body(
-                response
-                    .bytes()
-                    .await
-                    .map_err(|e| ClientError::ResponseError { source: e.into() })?
-                    .to_vec(),
-            )
-            .map_err(|e| ClientError::ResponseError { source: e.into() })
+/// Returns a [Response][http::Response] builder with the status, headers, and
+/// TLS session of the given response.
+fn head(response: &reqwest::Response) -> http::response::Builder {
+    let mut http_resp = http::Response::builder().status(response.status().as_u16());
+    for v in response.headers().into_iter() {
+        http_resp = http_resp.header(v.0, v.1);
+    }
+    if let Some(info) = crate::tls::from_reqwest(response.extensions()) {
+        http_resp = http_resp.extension(info);
     }
+    http_resp
 }
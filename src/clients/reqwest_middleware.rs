@@ -1,10 +1,13 @@
 //! Contains an implementation of [Client][crate::client::Client] being backed
 //! by the [reqwest](https://docs.rs/reqwest/) crate.
 
-use crate::{client::Client as RustifyClient, errors::ClientError};
+use crate::{
+    client::Client as RustifyClient, config::ClientConfig, deadline::AttemptTimeout,
+    errors::ClientError,
+};
 use async_trait::async_trait;
 use http::{Request, Response};
-use std::convert::TryFrom;
+use std::{convert::TryFrom, sync::Arc};
 
 /// A client based on the
 /// [reqwest_middleware::ClientWithMiddleware][1] which can be used for executing
@@ -14,6 +17,12 @@ use std::convert::TryFrom;
 /// qualify the full path of any [Endpoints][crate::endpoint::Endpoint] which
 /// are executed by this client.
 ///
+/// Cloning the client is cheap: clones share the connection pool of the
+/// backing [reqwest_middleware::ClientWithMiddleware][1] and the state of the
+/// [ClientConfig], such as concurrency and rate limits. The client is
+/// `Send + Sync`, so it can also be shared behind an [Arc] returned by
+/// `shared()`.
+///
 /// # Example
 /// ```
 /// use rustify::clients::reqwest_middleware::ClientWithMiddleware;
@@ -33,9 +42,11 @@ use std::convert::TryFrom;
 /// ```
 ///
 /// [1]: https://docs.rs/reqwest-middleware/latest/reqwest_middleware/struct.ClientWithMiddleware.html
+#[derive(Clone)]
 pub struct ClientWithMiddleware {
     pub http: reqwest_middleware::ClientWithMiddleware,
     pub base: String,
+    pub config: ClientConfig,
 }
 
 impl ClientWithMiddleware {
@@ -44,6 +55,7 @@ impl ClientWithMiddleware {
         Self {
             base: base.to_string(),
             http,
+            config: ClientConfig::default(),
         }
     }
 
@@ -55,8 +67,21 @@ impl ClientWithMiddleware {
         Self {
             base: base.to_string(),
             http: reqwest_middleware::ClientBuilder::new(reqwest::Client::default()).build(),
+            config: ClientConfig::default(),
         }
     }
+
+    /// Sets the [ClientConfig] used when executing requests.
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Returns the client behind an [Arc] for sharing it across tasks or
+    /// request handlers.
+    pub fn shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
 }
 
 #[async_trait]
@@ -65,22 +90,31 @@ impl RustifyClient for ClientWithMiddleware {
         self.base.as_str()
     }
 
+    fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
     #[instrument(skip(self, req), err)]
     async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
-        let request = reqwest::Request::try_from(req)
+        let timeout = AttemptTimeout::of(req.extensions());
+        let mut request = reqwest::Request::try_from(req)
             .map_err(|e| ClientError::ReqwestBuildError { source: e })?;
+        if timeout.is_some() {
+            *request.timeout_mut() = timeout;
+        }
 
         let url_err = request.url().to_string();
         let method_err = request.method().to_string();
-        let response = self
-            .http
-            .execute(request)
-            .await
-            .map_err(|e| ClientError::RequestError {
+        let response = self.http.execute(request).await.map_err(|e| match e {
+            reqwest_middleware::Error::Middleware(source) => {
+                ClientError::MiddlewareError { source }
+            }
+            e => ClientError::RequestError {
                 source: e.into(),
                 url: url_err,
                 method: method_err,
-            })?;
+            },
+        })?;
 
         let status_code = response.status().as_u16();
         let mut http_resp = http::Response::builder().status(status_code);
@@ -94,8 +128,8 @@ impl RustifyClient for ClientWithMiddleware {
                     .bytes()
                     .await
                     .map_err(|e| ClientError::ResponseError { source: e.into() })?
-                    .to_vec(),
+                    .into(),
             )
             .map_err(|e| ClientError::ResponseError { source: e.into() })
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,186 @@
+//! Contains the [ConditionalFetcher] type for fetching resources using
+//! conditional `GET` requests backed by a local copy.
+
+use std::{collections::HashMap, sync::Mutex};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{client::Client, config::ClientConfig, endpoint::Endpoint, errors::ClientError};
+use http::{
+    header::{ETAG, IF_NONE_MATCH},
+    HeaderValue, Request, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+
+/// A response body stored along with the ETag it was returned with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoredObject {
+    pub etag: String,
+    pub body: Vec<u8>,
+}
+
+/// Represents a store which keeps the most recent [StoredObject] for each
+/// key.
+pub trait ObjectStore: Send + Sync {
+    /// Returns the object stored under the given key, if any.
+    fn get(&self, key: &str) -> Result<Option<StoredObject>, ClientError>;
+
+    /// Stores an object under the given key, replacing any existing object.
+    fn put(&self, key: &str, object: StoredObject) -> Result<(), ClientError>;
+}
+
+/// An [ObjectStore] which keeps objects in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    objects: Mutex<HashMap<String, StoredObject>>,
+}
+
+impl MemoryStore {
+    /// Returns a new, empty [MemoryStore].
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, StoredObject>> {
+        self.objects.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<StoredObject>, ClientError> {
+        Ok(self.lock().get(key).cloned())
+    }
+
+    fn put(&self, key: &str, object: StoredObject) -> Result<(), ClientError> {
+        self.lock().insert(key.to_string(), object);
+        Ok(())
+    }
+}
+
+/// The store key, stored copy, and request of a conditional fetch.
+type Prepared = (String, Option<StoredObject>, Request<Vec<u8>>);
+
+/// The result of a [ConditionalFetcher] fetch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fetched<T> {
+    /// The server returned a new version of the resource
+    Fresh(T),
+    /// The server reported the resource was unchanged and the stored copy
+    /// was used
+    Stored(T),
+}
+
+impl<T> Fetched<T> {
+    /// Returns the fetched value.
+    pub fn into_inner(self) -> T {
+        match self {
+            Fetched::Fresh(v) | Fetched::Stored(v) => v,
+        }
+    }
+
+    /// Returns whether the server returned a new version of the resource.
+    pub fn is_fresh(&self) -> bool {
+        matches!(self, Fetched::Fresh(_))
+    }
+}
+
+/// Fetches the response of an [Endpoint] using conditional requests.
+///
+/// The response body and ETag of every successful fetch are kept in an
+/// [ObjectStore], keyed by the request URL. Subsequent fetches send the
+/// stored ETag in an `If-None-Match` header and parse the stored body when
+/// the server responds with `304 Not Modified`.
+///
+/// # Example
+/// ```
+/// use rustify::clients::reqwest::Client;
+/// use rustify::conditional::{ConditionalFetcher, MemoryStore};
+/// use rustify_derive::Endpoint;
+///
+/// #[derive(Endpoint)]
+/// #[endpoint(path = "config", response = "String")]
+/// struct GetConfig {}
+///
+/// # tokio_test::block_on(async {
+/// let client = Client::default("http://myapi.com");
+/// let fetcher = ConditionalFetcher::new(GetConfig {}, MemoryStore::new());
+/// let config = fetcher.fetch(&client).await.map(|f| f.into_inner());
+/// # })
+/// ```
+pub struct ConditionalFetcher<E: Endpoint, S: ObjectStore> {
+    pub endpoint: E,
+    pub store: S,
+}
+
+impl<E: Endpoint, S: ObjectStore> ConditionalFetcher<E, S> {
+    /// Returns a new [ConditionalFetcher] for the given [Endpoint] which keeps
+    /// responses in the given [ObjectStore].
+    pub fn new(endpoint: E, store: S) -> Self {
+        ConditionalFetcher { endpoint, store }
+    }
+
+    /// Fetches the resource, returning the stored copy if it hasn't changed.
+    pub async fn fetch(&self, client: &impl Client) -> Result<Fetched<E::Response>, ClientError> {
+        let (key, stored, req) = self.prepare(client.config(), client.base())?;
+        let result = client.execute(req).await;
+        self.complete(&key, stored, result)
+    }
+
+    /// Fetches the resource, returning the stored copy if it hasn't changed.
+    #[cfg(feature = "blocking")]
+    pub fn fetch_block(
+        &self,
+        client: &impl BlockingClient,
+    ) -> Result<Fetched<E::Response>, ClientError> {
+        let (key, stored, req) = self.prepare(client.config(), client.base())?;
+        let result = client.execute(req);
+        self.complete(&key, stored, result)
+    }
+
+    /// Builds the request, adding the ETag of the stored copy if there is one.
+    fn prepare(&self, config: &ClientConfig, base: &str) -> Result<Prepared, ClientError> {
+        let mut req = config.build_request(&self.endpoint, base)?;
+        let key = req.uri().to_string();
+        let stored = self.store.get(&key)?;
+        if let Some(etag) = stored
+            .as_ref()
+            .and_then(|s| HeaderValue::from_str(&s.etag).ok())
+        {
+            req.headers_mut().insert(IF_NONE_MATCH, etag);
+        }
+        Ok((key, stored, req))
+    }
+
+    /// Parses the response, falling back to the stored copy if the resource
+    /// wasn't modified.
+    fn complete(
+        &self,
+        key: &str,
+        stored: Option<StoredObject>,
+        result: Result<Response<Vec<u8>>, ClientError>,
+    ) -> Result<Fetched<E::Response>, ClientError> {
+        let not_modified = StatusCode::NOT_MODIFIED.as_u16();
+        match (result, stored) {
+            (Err(ClientError::ServerResponseError { code, .. }), Some(stored))
+                if code == not_modified =>
+            {
+                let resp = Response::new(stored.body);
+                E::parse_response(&resp, E::RESPONSE_BODY_TYPE).map(Fetched::Stored)
+            }
+            (Err(e), _) => Err(e),
+            (Ok(resp), _) => {
+                let value = E::parse_response(&resp, E::RESPONSE_BODY_TYPE)?;
+                if let Some(etag) = resp.headers().get(ETAG).and_then(|v| v.to_str().ok()) {
+                    self.store.put(
+                        key,
+                        StoredObject {
+                            etag: etag.to_string(),
+                            body: resp.into_body(),
+                        },
+                    )?;
+                }
+                Ok(Fetched::Fresh(value))
+            }
+        }
+    }
+}
@@ -0,0 +1,749 @@
+//! Contains the [ClientConfig] type for configuring behavior shared by all
+//! [Client][crate::client::Client] implementations.
+
+use std::{cell::RefCell, future::Future, time::Duration};
+
+use crate::{
+    adaptive::AdaptiveLimit,
+    bucket::RateLimiter,
+    capture::BodyCapture,
+    deadline::{AttemptTimeout, Deadline, Timeouts},
+    encryption::{Cipher, FieldCipher},
+    endpoint::{Endpoint, MiddleWare},
+    enums::TrailingSlash,
+    errors::ClientError,
+    events::{Attempt, BodySizes, Event, EventSink, Events, Limiter, Operation},
+    gate::{Gate, Gatekeeper},
+    guard::UrlGuard,
+    hooks::Hooks,
+    intercept::Interceptors,
+    limit::ConcurrencyLimit,
+    middleware::{catch_panic, SharedMiddleware},
+    mode::ApiMode,
+    ratelimit::RateLimitTracker,
+    resilience::{AdaptiveState, ConcurrencyState, ResilienceState, SchedulerState},
+    retry::RetryPolicy,
+    scheduler::Scheduler,
+    shutdown::{Active, Shutdown},
+};
+use http::{HeaderName, HeaderValue, Method, Request, Response, Uri};
+
+/// The configuration used when no other configuration has been provided.
+pub(crate) static DEFAULT_CONFIG: ClientConfig = ClientConfig::new();
+
+thread_local! {
+    /// The settings of the [ClientConfig] building a request on this thread,
+    /// see [ClientConfig::building].
+    static BUILDING: RefCell<Option<Building>> = const { RefCell::new(None) };
+}
+
+/// The settings of a [ClientConfig] which apply while an [Endpoint] builds
+/// its request, before any middleware runs.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Building {
+    pub(crate) field_cipher: Option<FieldCipher>,
+    pub(crate) trailing_slash: TrailingSlash,
+}
+
+impl Building {
+    /// Returns the settings of the request being built on this thread, if
+    /// it's being built by [ClientConfig::building].
+    pub(crate) fn current() -> Option<Building> {
+        BUILDING.with(|b| b.borrow().clone())
+    }
+
+    /// Applies the [TrailingSlash] policy to the path of the given URL.
+    pub(crate) fn normalize_path(&self, uri: Uri) -> Result<Uri, ClientError> {
+        let path = uri.path();
+        let normalized = match self.trailing_slash {
+            TrailingSlash::PRESERVE => return Ok(uri),
+            _ if path == "/" || path.is_empty() => return Ok(uri),
+            TrailingSlash::ALWAYS if !path.ends_with('/') => format!("{}/", path),
+            TrailingSlash::NEVER if path.ends_with('/') => {
+                format!("/{}", path.trim_matches('/'))
+            }
+            _ => return Ok(uri),
+        };
+        let path_and_query = match uri.query() {
+            Some(q) => format!("{}?{}", normalized, q),
+            None => normalized,
+        };
+        let mut parts = uri.into_parts();
+        parts.path_and_query = Some(
+            path_and_query
+                .parse()
+                .map_err(|e: http::uri::InvalidUri| ClientError::UrlBuildError { source: e })?,
+        );
+        Uri::from_parts(parts).map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+/// Configures how a [Client][crate::client::Client] executes requests.
+///
+/// The configuration is independent of the backing HTTP client and is applied
+/// by the default `execute` implementation of both the asynchronous and
+/// blocking [Client][crate::client::Client] traits.
+#[derive(Clone, Debug, Default)]
+pub struct ClientConfig {
+    pub adaptive: Option<AdaptiveLimit>,
+    pub attempt_header: Option<HeaderName>,
+    pub attempt_timeout: Option<Duration>,
+    pub body_capture: Option<BodyCapture>,
+    pub body_sizes: bool,
+    pub catch_panics: bool,
+    pub concurrency: Option<ConcurrencyLimit>,
+    pub connect_timeout: Option<Duration>,
+    pub deadline: Option<Duration>,
+    pub events: Option<Events>,
+    pub field_cipher: Option<FieldCipher>,
+    pub gatekeeper: Option<Gate>,
+    pub hooks: Hooks,
+    pub interceptors: Option<Interceptors>,
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<crate::metrics::Metrics>,
+    pub middleware: Option<SharedMiddleware>,
+    pub mode: ApiMode,
+    pub rate_limiter: Option<RateLimiter>,
+    pub rate_limits: Option<RateLimitTracker>,
+    pub read_timeout: Option<Duration>,
+    pub redirects: Option<usize>,
+    pub retry: Option<RetryPolicy>,
+    pub scheduler: Option<Scheduler>,
+    pub shutdown: Option<Shutdown>,
+    pub slow_request_threshold: Option<Duration>,
+    pub stale_retry: bool,
+    pub trailing_slash: TrailingSlash,
+    pub url_guard: Option<UrlGuard>,
+}
+
+impl ClientConfig {
+    /// Returns a new [ClientConfig] with default settings.
+    pub const fn new() -> Self {
+        ClientConfig {
+            adaptive: None,
+            attempt_header: None,
+            attempt_timeout: None,
+            body_capture: None,
+            body_sizes: false,
+            catch_panics: false,
+            concurrency: None,
+            connect_timeout: None,
+            deadline: None,
+            events: None,
+            field_cipher: None,
+            gatekeeper: None,
+            hooks: Hooks::new(),
+            interceptors: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            middleware: None,
+            mode: ApiMode::Production,
+            rate_limiter: None,
+            rate_limits: None,
+            read_timeout: None,
+            redirects: None,
+            retry: None,
+            scheduler: None,
+            shutdown: None,
+            slow_request_threshold: None,
+            stale_retry: false,
+            trailing_slash: TrailingSlash::PRESERVE,
+            url_guard: None,
+        }
+    }
+
+    /// Adjusts the number of requests which may be in flight at once based on
+    /// observed latency and errors using the given [AdaptiveLimit].
+    pub fn adaptive_concurrency(mut self, limit: AdaptiveLimit) -> Self {
+        self.adaptive = Some(limit);
+        self
+    }
+
+    /// Tracks the requests in flight so the client can be shut down
+    /// gracefully with [Client::shutdown][crate::client::Client::shutdown],
+    /// see [Shutdown].
+    pub fn graceful_shutdown(mut self) -> Self {
+        self.shutdown = Some(Shutdown::new());
+        self
+    }
+
+    /// Sends the attempt number of every request in the given header, i.e.
+    /// `X-Attempt: 2`, so retried requests can be told apart in server logs.
+    pub fn attempt_header(mut self, name: HeaderName) -> Self {
+        self.attempt_header = Some(name);
+        self
+    }
+
+    /// Limits how long each attempt of a request may take, including retries
+    /// and redirects, see [AttemptTimeout] and [Timeouts].
+    pub fn attempt_timeout(mut self, timeout: Duration) -> Self {
+        self.attempt_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how long establishing the connection of each attempt may take,
+    /// see [Timeouts].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how long each attempt may wait for a read from the connection,
+    /// such as the response headers or the next chunk of the body, see
+    /// [Timeouts].
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Returns the [Timeouts] of each attempt, unless overridden by the
+    /// endpoint.
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            connect: self.connect_timeout,
+            read: self.read_timeout,
+            total: self.attempt_timeout,
+        }
+    }
+
+    /// Limits the total time spent executing a request to the given budget,
+    /// shortening the timeout of each attempt to the time left, see
+    /// [Deadline]. Requests which already carry a [Deadline] keep it.
+    pub fn deadline(mut self, budget: Duration) -> Self {
+        self.deadline = Some(budget);
+        self
+    }
+
+    /// Records a sample of request and response bodies on the `execute` span
+    /// as `request_body` and `response_body`, see [BodyCapture].
+    pub fn capture_bodies(mut self, capture: BodyCapture) -> Self {
+        self.body_capture = Some(capture);
+        self
+    }
+
+    /// Logs the request and response body sizes of every request, see
+    /// [BodySizes]. The sizes are always recorded on the `execute` span as
+    /// `request_bytes`, `response_bytes`, and `response_encoding`.
+    pub fn log_body_sizes(mut self) -> Self {
+        self.body_sizes = true;
+        self
+    }
+
+    /// Limits the number of requests which may be in flight at once using the
+    /// given [ConcurrencyLimit].
+    pub fn concurrency_limit(mut self, limit: ConcurrencyLimit) -> Self {
+        self.concurrency = Some(limit);
+        self
+    }
+
+    /// Sets the [EventSink] which receives typed [Event]s while executing
+    /// requests and parsing their responses.
+    pub fn event_sink(mut self, sink: impl EventSink + 'static) -> Self {
+        self.events = Some(Events::new(sink));
+        self
+    }
+
+    /// Encrypts fields tagged with the `encrypt` option using the given
+    /// [Cipher], see [crate::encryption].
+    pub fn field_cipher(mut self, cipher: impl Cipher + 'static) -> Self {
+        self.field_cipher = Some(FieldCipher::new(cipher));
+        self
+    }
+
+    /// Consults the given [Gatekeeper] before sending each request, see
+    /// [crate::gate].
+    pub fn gatekeeper(mut self, gatekeeper: impl Gatekeeper + 'static) -> Self {
+        self.gatekeeper = Some(Gate::new(gatekeeper));
+        self
+    }
+
+    /// Applies the given [MiddleWare] to every endpoint executed by the
+    /// client, see [crate::middleware].
+    pub fn middleware(mut self, middleware: impl MiddleWare + 'static) -> Self {
+        self.middleware = Some(SharedMiddleware::new(middleware));
+        self
+    }
+
+    /// Records every request to the installed metrics recorder, see
+    /// [crate::metrics].
+    #[cfg(feature = "metrics")]
+    pub fn metrics(mut self, metrics: crate::metrics::Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sets whether requests are executed against a sandbox or production,
+    /// see [crate::mode].
+    pub fn mode(mut self, mode: ApiMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Waits before executing endpoints whose requests-per-second budget is
+    /// exhausted using the given [RateLimiter], see [crate::bucket].
+    pub fn rate_limit(mut self, limiter: RateLimiter) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Converts panics of the middleware configured with
+    /// [ClientConfig::middleware] into [ClientError::MiddlewarePanic] errors
+    /// instead of unwinding into the caller, see
+    /// [CatchPanic][crate::middleware::CatchPanic].
+    pub fn catch_middleware_panics(mut self) -> Self {
+        self.catch_panics = true;
+        self
+    }
+
+    /// Sets the [Hooks] invoked while executing requests.
+    pub fn hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Adjusts the state of the client based on the responses it receives
+    /// using the given [Interceptors].
+    pub fn intercept(mut self, interceptors: Interceptors) -> Self {
+        self.interceptors = Some(interceptors);
+        self
+    }
+
+    /// Records the rate limit state reported by each host in the given
+    /// [RateLimitTracker].
+    pub fn track_rate_limits(mut self, tracker: RateLimitTracker) -> Self {
+        self.rate_limits = Some(tracker);
+        self
+    }
+
+    /// Follows up to the given number of redirects, recording each one in the
+    /// [Redirects][crate::redirect::Redirects] extension of the response. The
+    /// backing HTTP client must be configured not to follow redirects itself,
+    /// see [crate::redirect].
+    pub fn follow_redirects(mut self, max: usize) -> Self {
+        self.redirects = Some(max);
+        self
+    }
+
+    /// Retries failed executions of endpoints according to the given
+    /// [RetryPolicy], see [crate::retry].
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Schedules requests by their priority using the given [Scheduler].
+    pub fn scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Emits a warning for any request which takes longer than the given
+    /// threshold to complete.
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> Self {
+        self.slow_request_threshold = Some(threshold);
+        self
+    }
+
+    /// Retries idempotent requests once when they fail because the connection
+    /// was stale, see [ClientError::is_stale_connection]. The failed
+    /// connection is discarded by the backing client, so the retry is sent on
+    /// a fresh connection.
+    pub fn retry_stale_connections(mut self) -> Self {
+        self.stale_retry = true;
+        self
+    }
+
+    /// Adds or removes the trailing slash of every request path according to
+    /// the given [TrailingSlash] policy.
+    ///
+    /// The policy is applied while an endpoint builds its request, before any
+    /// middleware runs, so signatures cover the path which is sent. The root
+    /// path, requests to [Presigned][crate::http::Presigned] URLs, and
+    /// requests passed directly to
+    /// [Client::execute][crate::client::Client::execute] are never changed.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Checks every request against the given [UrlGuard] before it's sent,
+    /// failing requests to URLs it blocks with a
+    /// [ClientError::BlockedUrlError].
+    pub fn url_guard(mut self, guard: UrlGuard) -> Self {
+        self.url_guard = Some(guard);
+        self
+    }
+
+    /// Returns a snapshot of the state of the configured limits.
+    pub fn resilience_state(&self) -> ResilienceState {
+        ResilienceState {
+            adaptive: self.adaptive.as_ref().map(|a| AdaptiveState {
+                limit: a.limit(),
+                in_flight: a.in_flight(),
+                queued: a.queued(),
+            }),
+            concurrency: self.concurrency.as_ref().map(|c| ConcurrencyState {
+                max_total: c.max_total(),
+                max_per_host: c.max_per_host(),
+                in_flight: c.in_flight(),
+                queued: c.queued(),
+            }),
+            scheduler: self.scheduler.as_ref().map(|s| SchedulerState {
+                max: s.max(),
+                available: s.available(),
+                queued: s.queued(),
+            }),
+            rate_limits: self
+                .rate_limits
+                .as_ref()
+                .map(|r| r.snapshot())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Passes the given request of the given [Endpoint] to the configured
+    /// middleware, if any.
+    pub(crate) fn middleware_request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        req.extensions_mut().insert(self.mode);
+        match &self.middleware {
+            Some(m) if self.catch_panics => catch_panic(|| m.request(endpoint, req)),
+            Some(m) => m.request(endpoint, req),
+            None => Ok(()),
+        }
+    }
+
+    /// Passes the given response of the given [Endpoint] to the configured
+    /// middleware, if any.
+    pub(crate) fn middleware_response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        match &self.middleware {
+            Some(m) if self.catch_panics => catch_panic(|| m.response(endpoint, resp)),
+            Some(m) => m.response(endpoint, resp),
+            None => Ok(()),
+        }
+    }
+
+    /// Logs a warning if the elapsed time exceeds the configured threshold.
+    pub(crate) fn check_latency(&self, method: &Method, uri: &Uri, elapsed: Duration) {
+        if let Some(threshold) = self.slow_request_threshold {
+            if elapsed > threshold {
+                warn!(
+                    method = %method,
+                    url = %uri,
+                    elapsed_ms = elapsed.as_millis() as u64,
+                    "Slow request took {:?} (threshold {:?})",
+                    elapsed,
+                    threshold
+                );
+            }
+        }
+    }
+
+    /// Records the outcome of a request with the configured
+    /// [Metrics][crate::metrics::Metrics], if any.
+    #[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+    pub(crate) fn record_metrics<B>(
+        &self,
+        operation: Option<&'static str>,
+        result: &Result<Response<B>, ClientError>,
+        elapsed: Duration,
+    ) {
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.record(operation, result, elapsed);
+        }
+    }
+
+    /// Records the given [BodySizes] on the current span and logs them, if
+    /// configured.
+    pub(crate) fn record_sizes(&self, method: &Method, uri: &Uri, sizes: &BodySizes) {
+        let span = tracing::Span::current();
+        span.record("response_bytes", sizes.response as u64);
+        if let Some(encoding) = &sizes.encoding {
+            span.record("response_encoding", encoding.as_str());
+        }
+        if self.body_sizes {
+            info!(
+                method = %method,
+                url = %uri,
+                request_bytes = sizes.request as u64,
+                response_bytes = sizes.response as u64,
+                response_encoding = sizes.encoding.as_deref().unwrap_or("identity"),
+                "Request sent {} bytes and received {} bytes",
+                sizes.request,
+                sizes.response
+            );
+        }
+    }
+
+    /// Records the body of the given request on the current span if it's
+    /// sampled by the [BodyCapture], returning whether it was.
+    pub(crate) fn capture_request(&self, req: &Request<Vec<u8>>) -> bool {
+        match &self.body_capture {
+            Some(c) if c.sample() => {
+                tracing::Span::current().record("request_body", c.render(req.body()).as_str());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records the body of the given response on the current span.
+    pub(crate) fn capture_response(&self, response: &Response<Vec<u8>>) {
+        if let Some(c) = &self.body_capture {
+            tracing::Span::current().record("response_body", c.render(response.body()).as_str());
+        }
+    }
+
+    /// Records the rate limit state of the given response, if configured.
+    pub(crate) fn record_rate_limit<B>(&self, uri: &Uri, response: &Response<B>) {
+        if let Some(tracker) = &self.rate_limits {
+            tracker.record(uri, response.headers());
+        }
+    }
+
+    /// Adds the headers set by the configured [Interceptors] to the given
+    /// request.
+    pub(crate) fn apply_state(&self, req: &mut Request<Vec<u8>>) {
+        if let Some(interceptors) = &self.interceptors {
+            interceptors.apply(req);
+        }
+    }
+
+    /// Passes the given response to the configured [Interceptors].
+    pub(crate) fn intercept_response(&self, uri: &Uri, response: &Response<Vec<u8>>) {
+        if let Some(interceptors) = &self.interceptors {
+            interceptors.intercept(uri, response);
+        }
+    }
+
+    /// Builds the request of the given [Endpoint] with the settings of this
+    /// configuration which apply while serializing it.
+    pub(crate) fn build_request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        base: &str,
+    ) -> Result<Request<Vec<u8>>, ClientError> {
+        self.building(|| endpoint.request(base))
+    }
+
+    /// Runs the given function, which builds a request, with the settings of
+    /// this configuration available through [Building::current].
+    pub(crate) fn building<R>(&self, f: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Building>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                let previous = self.0.take();
+                BUILDING.with(|b| *b.borrow_mut() = previous);
+            }
+        }
+
+        let building = Building {
+            field_cipher: self.field_cipher.clone(),
+            trailing_slash: self.trailing_slash,
+        };
+        let _restore = Restore(BUILDING.with(|b| b.replace(Some(building))));
+        f()
+    }
+
+    /// Returns whether responses must be buffered because the configured
+    /// middleware processes them or failed requests may be retried.
+    pub(crate) fn requires_buffering(&self) -> bool {
+        self.middleware.is_some() || self.retry.is_some() || self.stale_retry
+    }
+
+    /// Returns a copy of the given request to resend if its connection turns
+    /// out to be stale, if configured and the request is idempotent.
+    pub(crate) fn stale_retry(&self, req: &Request<Vec<u8>>) -> Option<Request<Vec<u8>>> {
+        let idempotent = matches!(
+            *req.method(),
+            Method::GET
+                | Method::HEAD
+                | Method::OPTIONS
+                | Method::TRACE
+                | Method::PUT
+                | Method::DELETE
+        );
+        match self.stale_retry && idempotent {
+            true => Some(crate::http::copy_request(req)),
+            false => None,
+        }
+    }
+
+    /// Starts the [Deadline] of the given request, if configured and the
+    /// request doesn't carry one already.
+    pub(crate) fn start_deadline(&self, req: &mut Request<Vec<u8>>) {
+        if let Some(budget) = self.deadline {
+            if Deadline::of(req.extensions()).is_none() {
+                req.extensions_mut().insert(Deadline::after(budget));
+            }
+        }
+    }
+
+    /// Sets the [Timeouts] and [AttemptTimeout] of the given request before
+    /// it's sent, failing if its [Deadline] has expired.
+    pub(crate) fn prepare_attempt(&self, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let timeouts = Timeouts::of(req.extensions()).or(self.timeouts());
+        let timeout = match Deadline::of(req.extensions()) {
+            Some(d) => Some(d.attempt_timeout(timeouts.total)?),
+            None => timeouts.total,
+        };
+        if let Some(t) = timeout {
+            req.extensions_mut().insert(AttemptTimeout(t));
+        }
+        req.extensions_mut().insert(timeouts);
+        Ok(())
+    }
+
+    /// Marks a request as in flight, failing if the client has been shut
+    /// down.
+    pub(crate) fn enter(&self) -> Result<Option<Active>, ClientError> {
+        self.shutdown.as_ref().map(Shutdown::enter).transpose()
+    }
+
+    /// Consults the [Gatekeeper] about the given request, if configured.
+    pub(crate) fn check_gate(&self, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        match &self.gatekeeper {
+            Some(gate) => gate.apply(req),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the URL of the given request against the [UrlGuard], if
+    /// configured.
+    pub(crate) fn check_url(&self, req: &Request<Vec<u8>>) -> Result<(), ClientError> {
+        match &self.url_guard {
+            Some(guard) => guard.check(req.uri()),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds the [ApiMode] and, if configured, the attempt header to the given
+    /// request.
+    pub(crate) fn annotate_attempt(&self, req: &mut Request<Vec<u8>>) {
+        req.extensions_mut().insert(self.mode);
+        if let Some(name) = &self.attempt_header {
+            let attempt = Attempt::of(req.extensions());
+            req.headers_mut()
+                .insert(name.clone(), HeaderValue::from(attempt));
+        }
+    }
+
+    /// Emits [Event::RequestStarted] for the given request.
+    pub(crate) fn emit_started(&self, req: &Request<Vec<u8>>) {
+        if let Some(events) = &self.events {
+            events.emit(&Event::RequestStarted {
+                operation: Operation::of(req.extensions()),
+                attempt: Attempt::of(req.extensions()),
+                method: req.method(),
+                url: req.uri(),
+            });
+        }
+    }
+
+    /// Waits for a token from every bucket of the configured [RateLimiter]
+    /// which applies to the given [Endpoint].
+    pub(crate) async fn wait_rate_limit<E: Endpoint>(&self, endpoint: &E, req: &Request<Vec<u8>>) {
+        if let Some(limiter) = &self.rate_limiter {
+            for bucket in limiter.buckets(endpoint) {
+                self.admit(req, Limiter::RateLimit, bucket.queued(), bucket.acquire())
+                    .await;
+            }
+        }
+    }
+
+    /// Like [ClientConfig::wait_rate_limit] but blocks the current thread.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn wait_rate_limit_blocking<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &Request<Vec<u8>>,
+    ) {
+        if let Some(limiter) = &self.rate_limiter {
+            for bucket in limiter.buckets(endpoint) {
+                self.admit_blocking(req, Limiter::RateLimit, bucket.queued(), || {
+                    bucket.acquire_blocking()
+                });
+            }
+        }
+    }
+
+    /// Waits for the permit of the given [Limiter], emitting
+    /// [Event::Admitted] with the number of requests queued ahead of this one
+    /// and the time spent waiting.
+    pub(crate) async fn admit<T>(
+        &self,
+        req: &Request<Vec<u8>>,
+        limiter: Limiter,
+        queued: usize,
+        acquire: impl Future<Output = T>,
+    ) -> T {
+        let start = tokio::time::Instant::now();
+        let permit = acquire.await;
+        self.emit_admitted(req, limiter, queued, start.elapsed());
+        permit
+    }
+
+    /// Like [ClientConfig::admit] but runs a blocking `acquire`.
+    #[cfg(feature = "blocking")]
+    pub(crate) fn admit_blocking<T>(
+        &self,
+        req: &Request<Vec<u8>>,
+        limiter: Limiter,
+        queued: usize,
+        acquire: impl FnOnce() -> T,
+    ) -> T {
+        let start = std::time::Instant::now();
+        let permit = acquire();
+        self.emit_admitted(req, limiter, queued, start.elapsed());
+        permit
+    }
+
+    fn emit_admitted(
+        &self,
+        req: &Request<Vec<u8>>,
+        limiter: Limiter,
+        queued: usize,
+        waited: Duration,
+    ) {
+        if let Some(events) = &self.events {
+            events.emit(&Event::Admitted {
+                operation: Operation::of(req.extensions()),
+                limiter,
+                queued,
+                waited,
+            });
+        }
+    }
+
+    /// Emits [Event::RetryScheduled] for the given attempt.
+    pub(crate) fn emit_retry(
+        &self,
+        operation: Option<&'static str>,
+        attempt: u32,
+        delay: Duration,
+    ) {
+        if let Some(events) = &self.events {
+            events.emit(&Event::RetryScheduled {
+                operation,
+                attempt,
+                delay,
+            });
+        }
+    }
+
+    /// Emits [Event::Failed] for the given error.
+    pub(crate) fn emit_failed(&self, operation: Option<&'static str>, error: &ClientError) {
+        if let Some(events) = &self.events {
+            events.emit(&Event::Failed { operation, error });
+        }
+    }
+}
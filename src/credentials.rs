@@ -0,0 +1,271 @@
+//! Contains the [CredentialStore] trait for persisting [Credentials] between
+//! runs, i.e. so a CLI tool doesn't have to log in every time it starts.
+//!
+//! Credentials are kept under a key chosen by the caller, i.e. the name of a
+//! profile or account. Three stores are provided:
+//!
+//! * [MemoryStore] keeps credentials for the lifetime of the process.
+//! * [FileStore] keeps credentials in a JSON file readable only by the
+//!   current user.
+//! * [KeyringStore] keeps credentials in the keyring of the operating system
+//!   and requires the `keyring` feature.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::credentials::{CredentialStore, Credentials, FileStore};
+//! use std::time::Duration;
+//!
+//! let store = FileStore::new("/home/user/.config/mycli/credentials.json");
+//! let credentials = match store.load("default").unwrap() {
+//!     Some(c) if !c.is_expired() => c,
+//!     _ => {
+//!         let c = Credentials::new("token").expires_in(Duration::from_secs(3600));
+//!         store.save("default", &c).unwrap();
+//!         c
+//!     }
+//! };
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::errors::ClientError;
+use serde::{Deserialize, Serialize};
+
+/// An access token along with an optional refresh token and expiry.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    pub access_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// The number of seconds since the Unix epoch at which the access token
+    /// expires
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+impl Credentials {
+    /// Returns new [Credentials] with the given access token which never
+    /// expire.
+    pub fn new(access_token: &str) -> Self {
+        Credentials {
+            access_token: access_token.to_string(),
+            refresh_token: None,
+            expires_at: None,
+        }
+    }
+
+    /// Sets the refresh token.
+    pub fn refresh_token(mut self, token: &str) -> Self {
+        self.refresh_token = Some(token.to_string());
+        self
+    }
+
+    /// Sets the access token to expire after the given duration from now.
+    pub fn expires_in(mut self, duration: Duration) -> Self {
+        self.expires_at = Some((now() + duration).as_secs());
+        self
+    }
+
+    /// Returns whether the access token has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .map(|e| now().as_secs() >= e)
+            .unwrap_or(false)
+    }
+}
+
+/// Tokens are left out so they don't end up in logs.
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_token", &"<redacted>")
+            .field(
+                "refresh_token",
+                &self.refresh_token.as_ref().map(|_| "<redacted>"),
+            )
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+fn now() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Represents a store which persists [Credentials] under a key.
+pub trait CredentialStore: Send + Sync {
+    /// Returns the credentials stored under the given key, if any.
+    fn load(&self, key: &str) -> Result<Option<Credentials>, ClientError>;
+
+    /// Stores credentials under the given key, replacing any existing
+    /// credentials.
+    fn save(&self, key: &str, credentials: &Credentials) -> Result<(), ClientError>;
+
+    /// Removes the credentials stored under the given key, i.e. after they
+    /// were rejected or the user logged out.
+    fn invalidate(&self, key: &str) -> Result<(), ClientError>;
+}
+
+/// A [CredentialStore] which keeps credentials in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    credentials: Mutex<BTreeMap<String, Credentials>>,
+}
+
+impl MemoryStore {
+    /// Returns a new, empty [MemoryStore].
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Credentials>> {
+        self.credentials.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl CredentialStore for MemoryStore {
+    fn load(&self, key: &str) -> Result<Option<Credentials>, ClientError> {
+        Ok(self.lock().get(key).cloned())
+    }
+
+    fn save(&self, key: &str, credentials: &Credentials) -> Result<(), ClientError> {
+        self.lock().insert(key.to_string(), credentials.clone());
+        Ok(())
+    }
+
+    fn invalidate(&self, key: &str) -> Result<(), ClientError> {
+        self.lock().remove(key);
+        Ok(())
+    }
+}
+
+/// A [CredentialStore] which persists credentials to a JSON file.
+///
+/// The file is replaced atomically on every change and, on Unix, is only
+/// readable and writable by the current user.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Returns a new [FileStore] backed by the file at the given path. The
+    /// file is created when the first credentials are saved.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStore {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Reads all credentials currently in the file.
+    fn read(&self) -> Result<BTreeMap<String, Credentials>, ClientError> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(ClientError::GenericError { source: e.into() }),
+        };
+        serde_json::from_reader(file).map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+
+    /// Replaces the contents of the file with the given credentials.
+    fn write(&self, credentials: &BTreeMap<String, Credentials>) -> Result<(), ClientError> {
+        let data = serde_json::to_vec_pretty(credentials)
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        let tmp = self.path.with_extension("tmp");
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options
+            .open(&tmp)
+            .and_then(|mut f| f.write_all(&data).and_then(|_| f.sync_all()))
+            .and_then(|_| std::fs::rename(&tmp, &self.path))
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn load(&self, key: &str) -> Result<Option<Credentials>, ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self.read()?.remove(key))
+    }
+
+    fn save(&self, key: &str, credentials: &Credentials) -> Result<(), ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut all = self.read()?;
+        all.insert(key.to_string(), credentials.clone());
+        self.write(&all)
+    }
+
+    fn invalidate(&self, key: &str) -> Result<(), ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut all = self.read()?;
+        match all.remove(key) {
+            Some(_) => self.write(&all),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A [CredentialStore] which keeps credentials in the keyring of the
+/// operating system, using the key as the account name of each entry.
+#[cfg(feature = "keyring")]
+#[derive(Clone, Debug)]
+pub struct KeyringStore {
+    service: String,
+}
+
+#[cfg(feature = "keyring")]
+impl KeyringStore {
+    /// Returns a new [KeyringStore] which stores entries under the given
+    /// service name, i.e. the name of the application.
+    pub fn new(service: &str) -> Self {
+        KeyringStore {
+            service: service.to_string(),
+        }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, ClientError> {
+        keyring::Entry::new(&self.service, key)
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+#[cfg(feature = "keyring")]
+impl CredentialStore for KeyringStore {
+    fn load(&self, key: &str) -> Result<Option<Credentials>, ClientError> {
+        match self.entry(key)?.get_password() {
+            Ok(p) => serde_json::from_str(&p)
+                .map(Some)
+                .map_err(|e| ClientError::GenericError { source: e.into() }),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(ClientError::GenericError { source: e.into() }),
+        }
+    }
+
+    fn save(&self, key: &str, credentials: &Credentials) -> Result<(), ClientError> {
+        let data = serde_json::to_string(credentials)
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        self.entry(key)?
+            .set_password(&data)
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+
+    fn invalidate(&self, key: &str) -> Result<(), ClientError> {
+        match self.entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(ClientError::GenericError { source: e.into() }),
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! Contains helpers for serializing date and time values into requests.
+//!
+//! Date and time values are serialized according to a [Format]. Fields can be
+//! formatted with the derive macro by tagging them with a `datetime` option:
+//!
+//! ```ignore
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "events")]
+//! struct ListEvents {
+//!     #[endpoint(query, datetime = "epoch")]
+//!     pub since: Option<chrono::DateTime<chrono::Utc>>,
+//!     #[endpoint(query, datetime = "%Y-%m-%d")]
+//!     pub day: chrono::DateTime<chrono::Utc>,
+//! }
+//! ```
+//!
+//! The [rfc3339], [epoch_seconds], and [epoch_millis] functions can also be
+//! used directly with `#[serde(serialize_with = "...")]`. Support for the
+//! `chrono` and `time` crates is enabled with the features of the same name.
+
+use serde::{Serialize, Serializer};
+
+/// The format used when serializing a date and time value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Format<'a> {
+    /// An RFC 3339 string, i.e. `2021-09-27T12:00:00+00:00`
+    Rfc3339,
+    /// The number of seconds since the Unix epoch
+    EpochSeconds,
+    /// The number of milliseconds since the Unix epoch
+    EpochMillis,
+    /// A custom format string using the syntax of the backing crate (i.e.
+    /// `strftime` for `chrono` and format descriptions for `time`)
+    Custom(&'a str),
+}
+
+impl<'a> Format<'a> {
+    /// Parses a [Format] from the value given to the `datetime` option.
+    ///
+    /// The values `rfc3339`, `epoch`, and `epoch_millis` are recognized and
+    /// any other value is treated as a custom format string.
+    pub fn parse(value: &'a str) -> Self {
+        match value {
+            "rfc3339" => Format::Rfc3339,
+            "epoch" | "epoch_seconds" => Format::EpochSeconds,
+            "epoch_millis" => Format::EpochMillis,
+            _ => Format::Custom(value),
+        }
+    }
+}
+
+/// Represents a date and time value which can be serialized using a [Format].
+pub trait FormatTimestamp {
+    /// Serializes this value using the given [Format].
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: &Format<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>;
+
+    /// Returns whether this value is absent and should be skipped.
+    fn is_none(&self) -> bool {
+        false
+    }
+}
+
+impl<T: FormatTimestamp + ?Sized> FormatTimestamp for &T {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: &Format<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        (**self).serialize_as(format, serializer)
+    }
+
+    fn is_none(&self) -> bool {
+        (**self).is_none()
+    }
+}
+
+impl<T: FormatTimestamp> FormatTimestamp for Option<T> {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: &Format<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(v) => v.serialize_as(format, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    fn is_none(&self) -> bool {
+        self.is_none()
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz> FormatTimestamp for chrono::DateTime<Tz>
+where
+    Tz: chrono::TimeZone,
+    Tz::Offset: std::fmt::Display,
+{
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: &Format<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+        use std::fmt::Write;
+
+        match format {
+            Format::Rfc3339 => serializer.serialize_str(&self.to_rfc3339()),
+            Format::EpochSeconds => serializer.serialize_i64(self.timestamp()),
+            Format::EpochMillis => serializer.serialize_i64(self.timestamp_millis()),
+            Format::Custom(f) => {
+                let mut out = String::new();
+                write!(out, "{}", self.format(f))
+                    .map_err(|_| S::Error::custom(format!("Invalid datetime format: {}", f)))?;
+                serializer.serialize_str(&out)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+impl FormatTimestamp for time::OffsetDateTime {
+    fn serialize_as<S: Serializer>(
+        &self,
+        format: &Format<'_>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        use serde::ser::Error;
+
+        match format {
+            Format::Rfc3339 => serializer.serialize_str(
+                &self
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .map_err(S::Error::custom)?,
+            ),
+            Format::EpochSeconds => serializer.serialize_i64(self.unix_timestamp()),
+            Format::EpochMillis => {
+                serializer.serialize_i64((self.unix_timestamp_nanos() / 1_000_000) as i64)
+            }
+            Format::Custom(f) => {
+                let desc =
+                    time::format_description::parse_borrowed::<2>(f).map_err(S::Error::custom)?;
+                serializer.serialize_str(&self.format(&desc).map_err(S::Error::custom)?)
+            }
+        }
+    }
+}
+
+/// A reference to a date and time value paired with the [Format] it should be
+/// serialized with.
+///
+/// This is used by the derive macro for fields tagged with a `datetime`
+/// option.
+pub struct Formatted<'a, T: ?Sized> {
+    value: &'a T,
+    format: Format<'a>,
+}
+
+impl<'a, T: FormatTimestamp + ?Sized> Formatted<'a, T> {
+    /// Returns a new [Formatted].
+    pub fn new(value: &'a T, format: Format<'a>) -> Self {
+        Formatted { value, format }
+    }
+
+    /// Returns whether the underlying value is absent and should be skipped.
+    pub fn is_none(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+impl<T: FormatTimestamp + ?Sized> Serialize for Formatted<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.value.serialize_as(&self.format, serializer)
+    }
+}
+
+/// Serializes a date and time value as an RFC 3339 string.
+pub fn rfc3339<T: FormatTimestamp, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.serialize_as(&Format::Rfc3339, serializer)
+}
+
+/// Serializes a date and time value as the number of seconds since the Unix
+/// epoch.
+pub fn epoch_seconds<T: FormatTimestamp, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.serialize_as(&Format::EpochSeconds, serializer)
+}
+
+/// Serializes a date and time value as the number of milliseconds since the
+/// Unix epoch.
+pub fn epoch_millis<T: FormatTimestamp, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.serialize_as(&Format::EpochMillis, serializer)
+}
@@ -0,0 +1,205 @@
+//! Contains the [Deadline] type for bounding the total time spent on an
+//! operation, including every retry and redirect it takes to complete.
+//!
+//! A per-attempt timeout bounds a single request, so an operation which is
+//! retried can take many times as long as the timeout. A [Deadline] is set
+//! once per operation with [ClientConfig::deadline][crate::config::ClientConfig::deadline]
+//! or by inserting it into the extensions of a request, and every attempt
+//! sent on its behalf is given an [AttemptTimeout] of at most the time which
+//! is left. Attempts which would start after the deadline fail with a
+//! [ClientError::DeadlineError] instead of being sent.
+//!
+//! An attempt can also be bounded more precisely by its [Timeouts]: how long
+//! establishing the connection may take, how long the connection may be idle
+//! while waiting for the response, and how long the attempt may take in
+//! total. Slow connections and slow responses usually call for very different
+//! limits, so each is configured separately per client with
+//! [ClientConfig::connect_timeout][crate::config::ClientConfig::connect_timeout],
+//! [ClientConfig::read_timeout][crate::config::ClientConfig::read_timeout], and
+//! [ClientConfig::attempt_timeout][crate::config::ClientConfig::attempt_timeout],
+//! and can be overridden per endpoint with
+//! [Endpoint::timeouts][crate::endpoint::Endpoint::timeouts].
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig};
+//! use std::time::Duration;
+//!
+//! // Each attempt may take up to 5 seconds, but the whole operation no more
+//! // than 8 seconds
+//! let client = Client::default("http://localhost").with_config(
+//!     ClientConfig::new()
+//!         .attempt_timeout(Duration::from_secs(5))
+//!         .deadline(Duration::from_secs(8))
+//!         .retry_stale_connections(),
+//! );
+//! ```
+//!
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig};
+//! use std::time::Duration;
+//!
+//! // Give up quickly on unreachable hosts, but allow slow responses
+//! let client = Client::default("http://localhost").with_config(
+//!     ClientConfig::new()
+//!         .connect_timeout(Duration::from_secs(2))
+//!         .read_timeout(Duration::from_secs(30)),
+//! );
+//! ```
+//!
+//! Endpoints override the timeouts of the client with the `connect_timeout`,
+//! `read_timeout`, and `total_timeout` parameters of the derive macro:
+//! ```
+//! use rustify_derive::Endpoint;
+//!
+//! // Exports take a while to produce, so allow more time than usual
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "export", read_timeout = "5m", total_timeout = "10m")]
+//! struct Export {}
+//! ```
+
+use std::time::{Duration, Instant};
+
+use http::Extensions;
+
+use crate::errors::ClientError;
+
+/// The point in time by which an operation must complete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Deadline {
+    at: Instant,
+    budget: Duration,
+}
+
+impl Deadline {
+    /// Returns a [Deadline] which expires after the given budget from now.
+    pub fn after(budget: Duration) -> Self {
+        Deadline {
+            at: Instant::now() + budget,
+            budget,
+        }
+    }
+
+    /// Returns the [Deadline] stored in the given extensions, if any.
+    pub fn of(extensions: &Extensions) -> Option<Self> {
+        extensions.get::<Deadline>().copied()
+    }
+
+    /// Returns the total budget the deadline was created with.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Returns the time left before the deadline expires.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns whether the deadline has expired.
+    pub fn is_expired(&self) -> bool {
+        self.remaining() == Duration::ZERO
+    }
+
+    /// Returns the timeout for the next attempt, which is the given
+    /// per-attempt timeout shortened to the time left, or fails if the
+    /// deadline has expired.
+    pub fn attempt_timeout(&self, timeout: Option<Duration>) -> Result<Duration, ClientError> {
+        let remaining = self.remaining();
+        if remaining == Duration::ZERO {
+            return Err(ClientError::DeadlineError {
+                budget: self.budget,
+            });
+        }
+        Ok(timeout.map(|t| t.min(remaining)).unwrap_or(remaining))
+    }
+}
+
+/// The maximum time a single attempt may take.
+///
+/// This is inserted into the extensions of every request sent by the default
+/// `execute` implementation of both [Client][crate::client::Client] traits
+/// when a timeout or [Deadline] is configured. Backing clients should apply
+/// it as the timeout of the request, the bundled reqwest clients do so in
+/// place of the timeout configured on the reqwest client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AttemptTimeout(pub Duration);
+
+impl AttemptTimeout {
+    /// Returns the timeout stored in the given extensions, if any.
+    pub fn of(extensions: &Extensions) -> Option<Duration> {
+        extensions.get::<AttemptTimeout>().map(|t| t.0)
+    }
+}
+
+/// The connect, read, and total timeouts of a single attempt.
+///
+/// The timeouts returned by
+/// [Endpoint::timeouts][crate::endpoint::Endpoint::timeouts] are stored in
+/// the extensions of its [Request][http::Request]. Before each attempt, any
+/// timeout the endpoint leaves unset is taken from the
+/// [ClientConfig][crate::config::ClientConfig], and the total timeout is
+/// applied as the [AttemptTimeout]. The bundled reqwest client applies all
+/// three, the blocking reqwest client ignores the read timeout, and the
+/// reqwest-middleware client only applies the total timeout. reqwest only
+/// supports connect and read timeouts when building a client, so the reqwest
+/// clients only apply them when created with `Client::from_builder` or
+/// `Client::default`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Timeouts {
+    /// The maximum time to establish a connection
+    pub connect: Option<Duration>,
+    /// The maximum time to wait for each read from the connection
+    pub read: Option<Duration>,
+    /// The maximum time the attempt may take, see [AttemptTimeout]
+    pub total: Option<Duration>,
+}
+
+impl Timeouts {
+    /// Returns new [Timeouts] without any timeouts set.
+    pub const fn new() -> Self {
+        Timeouts {
+            connect: None,
+            read: None,
+            total: None,
+        }
+    }
+
+    /// Returns the [Timeouts] stored in the given extensions, if any.
+    pub fn of(extensions: &Extensions) -> Self {
+        extensions.get::<Timeouts>().copied().unwrap_or_default()
+    }
+
+    /// Sets the maximum time to establish a connection.
+    pub fn connect(mut self, timeout: Duration) -> Self {
+        self.connect = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time to wait for each read from the connection.
+    pub fn read(mut self, timeout: Duration) -> Self {
+        self.read = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum time the attempt may take.
+    pub fn total(mut self, timeout: Duration) -> Self {
+        self.total = Some(timeout);
+        self
+    }
+
+    /// Returns these timeouts with any unset timeout taken from the given
+    /// defaults.
+    pub fn or(self, defaults: Timeouts) -> Self {
+        Timeouts {
+            connect: self.connect.or(defaults.connect),
+            read: self.read.or(defaults.read),
+            total: self.total.or(defaults.total),
+        }
+    }
+
+    /// Returns whether a connect or read timeout is set, which the backing
+    /// client has to be built with.
+    pub fn is_connection_level(&self) -> bool {
+        self.connect.is_some() || self.read.is_some()
+    }
+}
@@ -0,0 +1,341 @@
+//! Contains the [DeferredQueue] type for deferring requests while the network
+//! is unavailable and replaying them once connectivity returns.
+
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{client::Client, errors::ClientError};
+use http::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+/// A serializable representation of a built [Request].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeferredRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl DeferredRequest {
+    /// Converts this back into a [Request] which can be executed.
+    pub fn into_request(self) -> Result<Request<Vec<u8>>, ClientError> {
+        let mut builder = Request::builder()
+            .method(self.method.as_str())
+            .uri(self.url.as_str());
+        for (key, value) in self.headers.iter() {
+            builder = builder.header(key.as_str(), value.as_str());
+        }
+        builder
+            .body(self.body)
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+impl From<&Request<Vec<u8>>> for DeferredRequest {
+    fn from(req: &Request<Vec<u8>>) -> Self {
+        DeferredRequest {
+            method: req.method().to_string(),
+            url: req.uri().to_string(),
+            headers: req
+                .headers()
+                .iter()
+                .map(|(k, v)| {
+                    (
+                        k.to_string(),
+                        String::from_utf8_lossy(v.as_bytes()).to_string(),
+                    )
+                })
+                .collect(),
+            body: req.body().clone(),
+        }
+    }
+}
+
+/// Represents a store which persists [DeferredRequest]s in the order they
+/// were added.
+pub trait DeferredStore: Send + Sync {
+    /// Adds a request to the back of the store.
+    fn push(&self, req: DeferredRequest) -> Result<(), ClientError>;
+
+    /// Returns the request at the front of the store without removing it.
+    fn peek(&self) -> Result<Option<DeferredRequest>, ClientError>;
+
+    /// Removes the request at the front of the store.
+    fn pop(&self) -> Result<(), ClientError>;
+
+    /// Returns the number of requests in the store.
+    fn len(&self) -> Result<usize, ClientError>;
+
+    /// Returns whether the store is empty.
+    fn is_empty(&self) -> Result<bool, ClientError> {
+        self.len().map(|l| l == 0)
+    }
+}
+
+/// A [DeferredStore] which keeps requests in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    requests: Mutex<VecDeque<DeferredRequest>>,
+}
+
+impl MemoryStore {
+    /// Returns a new, empty [MemoryStore].
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<DeferredRequest>> {
+        self.requests.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl DeferredStore for MemoryStore {
+    fn push(&self, req: DeferredRequest) -> Result<(), ClientError> {
+        self.lock().push_back(req);
+        Ok(())
+    }
+
+    fn peek(&self) -> Result<Option<DeferredRequest>, ClientError> {
+        Ok(self.lock().front().cloned())
+    }
+
+    fn pop(&self) -> Result<(), ClientError> {
+        self.lock().pop_front();
+        Ok(())
+    }
+
+    fn len(&self) -> Result<usize, ClientError> {
+        Ok(self.lock().len())
+    }
+}
+
+/// A [DeferredStore] which persists requests to a file with one JSON encoded
+/// request per line, allowing deferred requests to survive restarts.
+#[derive(Debug)]
+pub struct FileStore {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileStore {
+    /// Returns a new [FileStore] backed by the file at the given path. The
+    /// file is created when the first request is added.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FileStore {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Reads all requests currently in the file.
+    fn read(&self) -> Result<Vec<DeferredRequest>, ClientError> {
+        let file = match File::open(&self.path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(ClientError::GenericError { source: e.into() }),
+        };
+        BufReader::new(file)
+            .lines()
+            .filter(|l| !matches!(l, Ok(l) if l.trim().is_empty()))
+            .map(|l| {
+                let line = l.map_err(|e| ClientError::GenericError { source: e.into() })?;
+                serde_json::from_str(&line)
+                    .map_err(|e| ClientError::GenericError { source: e.into() })
+            })
+            .collect()
+    }
+
+    /// Replaces the contents of the file with the given requests.
+    fn write(&self, requests: &[DeferredRequest]) -> Result<(), ClientError> {
+        let mut file =
+            File::create(&self.path).map_err(|e| ClientError::GenericError { source: e.into() })?;
+        for req in requests {
+            write_line(&mut file, req)?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes a request to the given file as a single line of JSON.
+fn write_line(file: &mut File, req: &DeferredRequest) -> Result<(), ClientError> {
+    let mut line =
+        serde_json::to_vec(req).map_err(|e| ClientError::GenericError { source: e.into() })?;
+    line.push(b'\n');
+    file.write_all(&line)
+        .map_err(|e| ClientError::GenericError { source: e.into() })
+}
+
+impl DeferredStore for FileStore {
+    fn push(&self, req: DeferredRequest) -> Result<(), ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        write_line(&mut file, &req)
+    }
+
+    fn peek(&self) -> Result<Option<DeferredRequest>, ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self.read()?.into_iter().next())
+    }
+
+    fn pop(&self) -> Result<(), ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let requests = self.read()?;
+        match requests.split_first() {
+            Some((_, rest)) => self.write(rest),
+            None => Ok(()),
+        }
+    }
+
+    fn len(&self) -> Result<usize, ClientError> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(self.read()?.len())
+    }
+}
+
+/// Executes requests and defers them to a [DeferredStore] when they fail to
+/// reach the server.
+///
+/// A request is deferred when sending it fails with
+/// [ClientError::RequestError], which is returned by the built-in clients when
+/// the server couldn't be reached. Deferred requests are replayed in the order
+/// they were added by calling `flush`. To preserve ordering, new requests are
+/// deferred without being sent while earlier requests remain in the store.
+///
+/// # Example
+/// ```
+/// use rustify::{clients::reqwest::Client, deferred::{DeferredQueue, MemoryStore}};
+/// use rustify::Endpoint;
+/// use rustify_derive::Endpoint;
+///
+/// #[derive(Endpoint)]
+/// #[endpoint(path = "my/endpoint", method = "POST")]
+/// struct MyEndpoint {}
+///
+/// # tokio_test::block_on(async {
+/// let client = Client::default("http://myapi.com");
+/// let queue = DeferredQueue::new(MemoryStore::new());
+/// let req = MyEndpoint {}.request(&client.base).unwrap();
+///
+/// // Returns `Ok(None)` when the request was deferred
+/// let result = queue.execute(&client, req).await;
+///
+/// // Replays deferred requests once the network is available again
+/// let sent = queue.flush(&client).await;
+/// # })
+/// ```
+pub struct DeferredQueue<S: DeferredStore> {
+    pub store: S,
+}
+
+impl<S: DeferredStore> DeferredQueue<S> {
+    /// Returns a new [DeferredQueue] backed by the given [DeferredStore].
+    pub fn new(store: S) -> Self {
+        DeferredQueue { store }
+    }
+
+    /// Executes the given [Request], deferring it if the server couldn't be
+    /// reached. Returns [None] when the request was deferred.
+    pub async fn execute(
+        &self,
+        client: &impl Client,
+        req: Request<Vec<u8>>,
+    ) -> Result<Option<Response<Vec<u8>>>, ClientError> {
+        if !self.store.is_empty()? {
+            self.flush(client).await?;
+            if !self.store.is_empty()? {
+                return self.defer(&req).map(|_| None);
+            }
+        }
+
+        let deferred = DeferredRequest::from(&req);
+        match client.execute(req).await {
+            Err(ClientError::RequestError { .. }) => self.defer_owned(deferred).map(|_| None),
+            r => r.map(Some),
+        }
+    }
+
+    /// Replays deferred requests in order until the store is empty or a
+    /// request fails to reach the server. Returns the number of requests
+    /// which were sent.
+    ///
+    /// Requests which reach the server are removed from the store regardless
+    /// of the response.
+    pub async fn flush(&self, client: &impl Client) -> Result<usize, ClientError> {
+        let mut sent = 0;
+        while let Some(deferred) = self.store.peek()? {
+            match client.execute(deferred.into_request()?).await {
+                Err(ClientError::RequestError { .. }) => break,
+                r => self.complete(r)?,
+            }
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// Executes the given [Request], deferring it if the server couldn't be
+    /// reached. Returns [None] when the request was deferred.
+    #[cfg(feature = "blocking")]
+    pub fn execute_block(
+        &self,
+        client: &impl BlockingClient,
+        req: Request<Vec<u8>>,
+    ) -> Result<Option<Response<Vec<u8>>>, ClientError> {
+        if !self.store.is_empty()? {
+            self.flush_block(client)?;
+            if !self.store.is_empty()? {
+                return self.defer(&req).map(|_| None);
+            }
+        }
+
+        let deferred = DeferredRequest::from(&req);
+        match client.execute(req) {
+            Err(ClientError::RequestError { .. }) => self.defer_owned(deferred).map(|_| None),
+            r => r.map(Some),
+        }
+    }
+
+    /// Replays deferred requests in order until the store is empty or a
+    /// request fails to reach the server. Returns the number of requests
+    /// which were sent.
+    #[cfg(feature = "blocking")]
+    pub fn flush_block(&self, client: &impl BlockingClient) -> Result<usize, ClientError> {
+        let mut sent = 0;
+        while let Some(deferred) = self.store.peek()? {
+            match client.execute(deferred.into_request()?) {
+                Err(ClientError::RequestError { .. }) => break,
+                r => self.complete(r)?,
+            }
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    fn defer(&self, req: &Request<Vec<u8>>) -> Result<(), ClientError> {
+        self.defer_owned(DeferredRequest::from(req))
+    }
+
+    fn defer_owned(&self, req: DeferredRequest) -> Result<(), ClientError> {
+        info!("Deferring {} request to {}", req.method, req.url);
+        self.store.push(req)
+    }
+
+    /// Removes a replayed request from the store.
+    fn complete(&self, result: Result<Response<Vec<u8>>, ClientError>) -> Result<(), ClientError> {
+        if let Err(e) = result {
+            warn!("Deferred request failed: {}", e);
+        }
+        self.store.pop()
+    }
+}
@@ -0,0 +1,93 @@
+//! Contains a DNS resolver for controlling which IP versions clients connect
+//! over.
+//!
+//! By default, reqwest connects to dual-stack hosts using Happy Eyeballs: the
+//! first address family returned by the system resolver is tried first and
+//! the other family is raced against it after 300ms. Hosts with broken IPv6
+//! connectivity can still cause noticeable delays, so [IpResolver] allows
+//! reordering or restricting the resolved addresses.
+//!
+//! # Example
+//! ```
+//! use std::sync::Arc;
+//! use rustify::{clients::reqwest::Client, dns::{IpPreference, IpResolver}};
+//!
+//! let client = Client::from_builder("https://myapi.com", || {
+//!     reqwest::Client::builder()
+//!         .dns_resolver(Arc::new(IpResolver::new(IpPreference::PreferV4)))
+//! })
+//! .unwrap();
+//! ```
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Which IP versions are used when connecting to a host.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IpPreference {
+    /// Uses the order returned by the system resolver and races both
+    /// families
+    #[default]
+    Any,
+    /// Tries IPv4 addresses first and races IPv6 addresses against them
+    PreferV4,
+    /// Tries IPv6 addresses first and races IPv4 addresses against them
+    PreferV6,
+    /// Only connects over IPv4
+    V4Only,
+    /// Only connects over IPv6
+    V6Only,
+}
+
+impl IpPreference {
+    /// Filters and orders the given addresses according to this preference.
+    pub fn apply(self, addrs: impl IntoIterator<Item = SocketAddr>) -> Vec<SocketAddr> {
+        let addrs = addrs.into_iter();
+        let (v4, v6): (Vec<_>, Vec<_>) = match self {
+            IpPreference::Any => return addrs.collect(),
+            _ => addrs.partition(SocketAddr::is_ipv4),
+        };
+        match self {
+            IpPreference::Any | IpPreference::PreferV4 => v4.into_iter().chain(v6).collect(),
+            IpPreference::PreferV6 => v6.into_iter().chain(v4).collect(),
+            IpPreference::V4Only => v4,
+            IpPreference::V6Only => v6,
+        }
+    }
+}
+
+/// A reqwest DNS resolver which resolves hosts using the system resolver and
+/// then applies an [IpPreference] to the results.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct IpResolver {
+    pub preference: IpPreference,
+}
+
+impl IpResolver {
+    /// Returns a new [IpResolver] using the given [IpPreference].
+    pub fn new(preference: IpPreference) -> Self {
+        IpResolver { preference }
+    }
+}
+
+impl Resolve for IpResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let preference = self.preference;
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs =
+                tokio::task::spawn_blocking(move || (host.as_str(), 0).to_socket_addrs()).await??;
+            let addrs = preference.apply(addrs);
+            if addrs.is_empty() {
+                return Err(format!(
+                    "No addresses matching {:?} found for {}",
+                    preference,
+                    name.as_str()
+                )
+                .into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
@@ -0,0 +1,247 @@
+//! Contains [decode] for decompressing response bodies according to their
+//! `Content-Encoding`, and the [GzipBody] middleware for compressing request
+//! bodies.
+//!
+//! ## Responses
+//!
+//! The default `execute` implementation of both
+//! [Client][crate::client::Client] traits decodes every response before it's
+//! passed to middleware or parsed, so endpoints can be parsed no matter
+//! whether the client backend decompresses responses itself. The `gzip`,
+//! `x-gzip`, `deflate`, and `br` encodings are supported, including several
+//! applied in sequence such as `gzip, br`. Responses with an encoding which
+//! isn't supported, and empty bodies such as those of `HEAD` requests or
+//! `304 Not Modified` responses, are returned unchanged.
+//!
+//! Decoded bodies are limited to [MAX_DECODED_BYTES] to protect against
+//! compression bombs, responses which decode to a larger body fail with a
+//! [ClientError::ResponseError]. Use [decode_with_limit] for other limits.
+//!
+//! Streamed responses, see [crate::stream], aren't decoded.
+//!
+//! The size of the body as received and after decoding are available in the
+//! [BodySizes][crate::events::BodySizes] stored in the response extensions.
+//!
+//! ## Requests
+//!
+//! Request bodies are sent as built unless the [GzipBody] middleware is
+//! configured, which compresses bodies above a size threshold. Servers must
+//! accept gzip compressed requests for this to work, so it's opt-in:
+//!
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, encoding::GzipBody};
+//!
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().middleware(GzipBody::new().min_bytes(64 * 1024)));
+//! ```
+
+use std::io::{Read, Write};
+
+use flate2::{
+    read::{DeflateDecoder, GzDecoder, ZlibDecoder},
+    write::GzEncoder,
+    Compression,
+};
+use http::{header, HeaderValue, Request, Response};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+
+/// The size of the buffer used when decoding `br` bodies.
+const BROTLI_BUFFER: usize = 4096;
+
+/// The maximum size of a body decoded by [decode], 256 MiB.
+pub const MAX_DECODED_BYTES: usize = 256 * 1024 * 1024;
+
+/// A `Content-Encoding` which can be decoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Coding {
+    Brotli,
+    Deflate,
+    Gzip,
+}
+
+impl Coding {
+    fn parse(coding: &str) -> Option<Option<Self>> {
+        match coding.trim().to_ascii_lowercase().as_str() {
+            "br" => Some(Some(Coding::Brotli)),
+            "deflate" => Some(Some(Coding::Deflate)),
+            "gzip" | "x-gzip" => Some(Some(Coding::Gzip)),
+            "identity" | "" => Some(None),
+            _ => None,
+        }
+    }
+
+    fn decode(&self, data: &[u8], limit: usize) -> std::io::Result<Vec<u8>> {
+        // Reading one byte past the limit tells a body of exactly the limit
+        // apart from a larger one
+        let take = limit as u64 + 1;
+        let mut body = Vec::new();
+        match self {
+            Coding::Brotli => {
+                brotli_decompressor::Decompressor::new(data, BROTLI_BUFFER)
+                    .take(take)
+                    .read_to_end(&mut body)?;
+            }
+            // Some servers send raw deflate data instead of the zlib wrapped
+            // data the specification requires
+            Coding::Deflate => {
+                if ZlibDecoder::new(data)
+                    .take(take)
+                    .read_to_end(&mut body)
+                    .is_err()
+                {
+                    body.clear();
+                    DeflateDecoder::new(data)
+                        .take(take)
+                        .read_to_end(&mut body)?;
+                }
+            }
+            Coding::Gzip => {
+                GzDecoder::new(data).take(take).read_to_end(&mut body)?;
+            }
+        }
+        if body.len() > limit {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("decoded body exceeds the limit of {} bytes", limit),
+            ));
+        }
+        Ok(body)
+    }
+}
+
+/// Decodes the body of the given [Response] according to its
+/// `Content-Encoding`, removing the `Content-Encoding` and `Content-Length`
+/// headers once decoded. Returns the size of the decoded body, or nothing if
+/// the response wasn't encoded, its body is empty, or its encoding isn't
+/// supported.
+///
+/// Fails if the decoded body exceeds [MAX_DECODED_BYTES].
+pub fn decode(response: &mut Response<Vec<u8>>) -> Result<Option<usize>, ClientError> {
+    decode_with_limit(response, MAX_DECODED_BYTES)
+}
+
+/// Decodes the body of the given [Response] like [decode], failing if the
+/// decoded body exceeds `max_bytes`.
+pub fn decode_with_limit(
+    response: &mut Response<Vec<u8>>,
+    max_bytes: usize,
+) -> Result<Option<usize>, ClientError> {
+    if response.body().is_empty() {
+        return Ok(None);
+    }
+    let mut codings = Vec::new();
+    for value in response.headers().get_all(header::CONTENT_ENCODING) {
+        let value = match value.to_str() {
+            Ok(v) => v,
+            Err(_) => return Ok(None),
+        };
+        for coding in value.split(',') {
+            match Coding::parse(coding) {
+                Some(Some(c)) => codings.push(c),
+                Some(None) => {}
+                None => {
+                    debug!("Not decoding response with Content-Encoding {}", value);
+                    return Ok(None);
+                }
+            }
+        }
+    }
+    if codings.is_empty() {
+        return Ok(None);
+    }
+
+    // Codings are listed in the order they were applied
+    let mut body = std::mem::take(response.body_mut());
+    for coding in codings.iter().rev() {
+        body = coding
+            .decode(&body, max_bytes)
+            .map_err(|e| ClientError::ResponseError { source: e.into() })?;
+    }
+    debug!(
+        "Decoded {:?} response body into {} bytes",
+        codings,
+        body.len()
+    );
+    let headers = response.headers_mut();
+    headers.remove(header::CONTENT_ENCODING);
+    headers.remove(header::CONTENT_LENGTH);
+    let len = body.len();
+    *response.body_mut() = body;
+    Ok(Some(len))
+}
+
+/// A [MiddleWare] which gzip compresses request bodies of at least
+/// [GzipBody::min_bytes], 1 KiB by default, and sets their `Content-Encoding`
+/// to `gzip`.
+///
+/// Requests which already have a `Content-Encoding` are left unchanged.
+/// Middleware which signs the request body, such as
+/// [HmacSigner][crate::hmac::HmacSigner], must be applied after this one so
+/// the signature covers the compressed body.
+#[derive(Clone, Debug)]
+pub struct GzipBody {
+    min_bytes: usize,
+    level: Compression,
+}
+
+impl Default for GzipBody {
+    fn default() -> Self {
+        GzipBody {
+            min_bytes: 1024,
+            level: Compression::default(),
+        }
+    }
+}
+
+impl GzipBody {
+    /// Returns a new [GzipBody] which compresses bodies of at least 1 KiB.
+    pub fn new() -> Self {
+        GzipBody::default()
+    }
+
+    /// Sets the minimum size in bytes of the bodies which are compressed.
+    pub fn min_bytes(mut self, min_bytes: usize) -> Self {
+        self.min_bytes = min_bytes;
+        self
+    }
+
+    /// Sets the compression level from 0 (none) to 9 (best), 6 by default.
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = Compression::new(level.min(9));
+        self
+    }
+}
+
+impl MiddleWare for GzipBody {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        if req.body().len() < self.min_bytes || req.headers().contains_key(header::CONTENT_ENCODING)
+        {
+            return Ok(());
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), self.level);
+        let body = encoder
+            .write_all(req.body())
+            .and_then(|_| encoder.finish())
+            .map_err(|e| ClientError::MiddlewareError { source: e.into() })?;
+        debug!(
+            "Compressed request body of {} bytes into {} bytes",
+            req.body().len(),
+            body.len()
+        );
+        let headers = req.headers_mut();
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        if headers.contains_key(header::CONTENT_LENGTH) {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+        }
+        *req.body_mut() = body;
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(&self, _: &E, _: &mut Response<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
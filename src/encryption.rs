@@ -0,0 +1,276 @@
+//! Contains the [Encryption] middleware for encrypting request bodies and
+//! decrypting response bodies with a pluggable [Cipher].
+//!
+//! The crate doesn't ship any ciphers. Implementations of [Cipher] typically
+//! wrap an AEAD such as AES-GCM, encrypting each body with a data key and
+//! identifying the key through [Cipher::key_id].
+//!
+//! # Example
+//! ```
+//! use rustify::{
+//!     encryption::{Cipher, Encryption},
+//!     errors::ClientError,
+//! };
+//!
+//! struct Reverse;
+//!
+//! impl Cipher for Reverse {
+//!     fn key_id(&self) -> String {
+//!         "reverse-1".to_string()
+//!     }
+//!
+//!     fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError> {
+//!         Ok(plaintext.iter().rev().copied().collect())
+//!     }
+//!
+//!     fn decrypt(&self, _: Option<&str>, ciphertext: &[u8]) -> Result<Vec<u8>, ClientError> {
+//!         Ok(ciphertext.iter().rev().copied().collect())
+//!     }
+//! }
+//!
+//! let middleware = Encryption::new(Reverse);
+//! ```
+//!
+//! # Field Encryption
+//! Individual fields can be encrypted before the request is serialized by
+//! tagging them with the `encrypt` option. Each field is serialized as JSON,
+//! encrypted with the cipher configured through
+//! [ClientConfig::field_cipher][crate::config::ClientConfig::field_cipher] on
+//! the client executing the endpoint, and sent as the string returned by
+//! [Cipher::encrypt_field], which defaults to the base64 encoded ciphertext:
+//!
+//! ```ignore
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "customers", method = "POST")]
+//! struct CreateCustomer {
+//!     pub name: String,
+//!     #[endpoint(encrypt)]
+//!     pub ssn: String,
+//! }
+//!
+//! let config = ClientConfig::new().field_cipher(MyCipher::new(key));
+//! ```
+//!
+//! Encrypted fields returned by an API can be decrypted with [decrypt_field].
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    config::Building,
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE},
+    HeaderName, HeaderValue, Request, Response,
+};
+use serde::{de::DeserializeOwned, ser::Error as _, Serialize, Serializer};
+
+/// The default header used to send the identifier of the encryption key.
+pub const KEY_ID_HEADER: &str = "x-encryption-key-id";
+
+/// The default header used to preserve the content type of the plaintext.
+pub const PLAINTEXT_TYPE_HEADER: &str = "x-plaintext-content-type";
+
+/// The default content type of encrypted bodies.
+pub const ENCRYPTED_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Represents a cipher used to encrypt and decrypt bodies.
+pub trait Cipher: Send + Sync {
+    /// Returns the identifier of the key used by [Cipher::encrypt].
+    fn key_id(&self) -> String;
+
+    /// Encrypts the given plaintext.
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError>;
+
+    /// Decrypts the given ciphertext using the key with the given identifier,
+    /// if the server reported one.
+    fn decrypt(&self, key_id: Option<&str>, ciphertext: &[u8]) -> Result<Vec<u8>, ClientError>;
+
+    /// Encrypts the given plaintext of a single field into the string sent in
+    /// its place. Defaults to the base64 encoded result of [Cipher::encrypt].
+    fn encrypt_field(&self, plaintext: &[u8]) -> Result<String, ClientError> {
+        Ok(STANDARD.encode(self.encrypt(plaintext)?))
+    }
+
+    /// Decrypts a string produced by [Cipher::encrypt_field].
+    fn decrypt_field(&self, token: &str) -> Result<Vec<u8>, ClientError> {
+        let ciphertext = STANDARD
+            .decode(token)
+            .map_err(|e| ClientError::DataParseError { source: e.into() })?;
+        self.decrypt(None, &ciphertext)
+    }
+}
+
+/// A shared [Cipher] used to encrypt fields tagged with the `encrypt` option,
+/// see [ClientConfig::field_cipher][crate::config::ClientConfig::field_cipher].
+#[derive(Clone)]
+pub struct FieldCipher {
+    cipher: Arc<dyn Cipher>,
+}
+
+impl FieldCipher {
+    /// Returns a new [FieldCipher] which uses the given [Cipher].
+    pub fn new(cipher: impl Cipher + 'static) -> Self {
+        FieldCipher {
+            cipher: Arc::new(cipher),
+        }
+    }
+}
+
+impl fmt::Debug for FieldCipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldCipher")
+            .field("key_id", &self.cipher.key_id())
+            .finish()
+    }
+}
+
+/// Decrypts a field encrypted with the given [Cipher] and deserializes it
+/// into `T`.
+pub fn decrypt_field<T: DeserializeOwned>(
+    cipher: &dyn Cipher,
+    token: &str,
+) -> Result<T, ClientError> {
+    let plaintext = cipher.decrypt_field(token)?;
+    serde_json::from_slice(&plaintext).map_err(|e| ClientError::DataParseError { source: e.into() })
+}
+
+/// A reference to a value which is serialized as JSON and encrypted with the
+/// field cipher of the client building the request.
+///
+/// This is used by the derive macro for fields tagged with the `encrypt`
+/// option. Values which serialize to `null`, i.e. [Option::None], are sent
+/// unencrypted.
+pub struct Encrypted<'a, T: ?Sized> {
+    value: &'a T,
+}
+
+impl<'a, T: Serialize + ?Sized> Encrypted<'a, T> {
+    /// Returns a new [Encrypted].
+    pub fn new(value: &'a T) -> Self {
+        Encrypted { value }
+    }
+}
+
+impl<T> Encrypted<'_, Option<T>> {
+    /// Returns whether the underlying value is [Option::None] and should be
+    /// skipped.
+    pub fn is_none(&self) -> bool {
+        self.value.is_none()
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for Encrypted<'_, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let plaintext = serde_json::to_vec(self.value).map_err(S::Error::custom)?;
+        if plaintext == b"null" {
+            return serializer.serialize_none();
+        }
+        let cipher = Building::current()
+            .and_then(|b| b.field_cipher)
+            .ok_or_else(|| {
+                S::Error::custom(
+                    "No field cipher has been configured, see ClientConfig::field_cipher",
+                )
+            })?;
+        let token = cipher
+            .cipher
+            .encrypt_field(&plaintext)
+            .map_err(S::Error::custom)?;
+        serializer.serialize_str(&token)
+    }
+}
+
+/// A [MiddleWare] which encrypts request bodies and decrypts response bodies
+/// using a [Cipher].
+///
+/// Non-empty request bodies are encrypted and sent with the encrypted content
+/// type, the identifier of the key, and the original content type, if any,
+/// in [PLAINTEXT_TYPE_HEADER]. Responses returned with the encrypted content
+/// type are decrypted and have their content type restored from
+/// [PLAINTEXT_TYPE_HEADER]; other responses are left untouched.
+pub struct Encryption<C: Cipher> {
+    cipher: C,
+    content_type: HeaderValue,
+    key_id_header: HeaderName,
+}
+
+impl<C: Cipher> Encryption<C> {
+    /// Returns a new [Encryption] middleware using the given [Cipher] and the
+    /// default headers.
+    pub fn new(cipher: C) -> Self {
+        Encryption {
+            cipher,
+            content_type: HeaderValue::from_static(ENCRYPTED_CONTENT_TYPE),
+            key_id_header: HeaderName::from_static(KEY_ID_HEADER),
+        }
+    }
+
+    /// Sets the content type sent with encrypted bodies, i.e.
+    /// `application/jose`.
+    pub fn content_type(mut self, content_type: HeaderValue) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Sets the header used to send the identifier of the encryption key.
+    pub fn key_id_header(mut self, name: HeaderName) -> Self {
+        self.key_id_header = name;
+        self
+    }
+}
+
+impl<C: Cipher> MiddleWare for Encryption<C> {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        if req.body().is_empty() {
+            return Ok(());
+        }
+
+        let ciphertext = self.cipher.encrypt(req.body())?;
+        let key_id = crate::http::build_header_value(&self.key_id_header, &self.cipher.key_id())?;
+        let headers = req.headers_mut();
+        if let Some(original) = headers.remove(CONTENT_TYPE) {
+            headers.insert(HeaderName::from_static(PLAINTEXT_TYPE_HEADER), original);
+        }
+        headers.insert(CONTENT_TYPE, self.content_type.clone());
+        headers.insert(self.key_id_header.clone(), key_id);
+        headers.remove(CONTENT_LENGTH);
+        *req.body_mut() = ciphertext;
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        _: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        if resp.headers().get(CONTENT_TYPE) != Some(&self.content_type) {
+            return Ok(());
+        }
+
+        let key_id = resp
+            .headers()
+            .get(&self.key_id_header)
+            .and_then(|v| v.to_str().ok());
+        let plaintext = self.cipher.decrypt(key_id, resp.body())?;
+        let headers = resp.headers_mut();
+        match headers.remove(PLAINTEXT_TYPE_HEADER) {
+            Some(original) => headers.insert(CONTENT_TYPE, original),
+            None => headers.remove(CONTENT_TYPE),
+        };
+        headers.remove(CONTENT_LENGTH);
+        *resp.body_mut() = plaintext;
+        Ok(())
+    }
+}
+
+impl<C: Cipher> fmt::Debug for Encryption<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption")
+            .field("content_type", &self.content_type)
+            .field("key_id_header", &self.key_id_header)
+            .finish()
+    }
+}
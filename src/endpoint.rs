@@ -1,16 +1,34 @@
 //! Contains the [Endpoint] trait and supporting traits/functions.
 
-use std::marker::PhantomData;
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
 
 #[cfg(feature = "blocking")]
 use crate::blocking::client::Client as BlockingClient;
 use crate::{
+    cache::CachePolicy,
     client::Client,
-    enums::{RequestMethod, RequestType, ResponseType},
+    config::ClientConfig,
+    deadline::Timeouts,
+    enums::{RequestMethod, RequestPriority, RequestType, ResponseType},
     errors::ClientError,
+    events::{Attempt, Events, Operation},
+    journal::Retries,
+    matcher::{Matcher, OnlyFor},
+    metadata::EndpointMetadata,
+    middleware::CatchPanic,
+    ratelimit::RateLimitInfo,
+    redirect::{Redirect, Redirects},
+    retry::RetryPolicy,
+    stream::{ExecMode, ExecOutput},
+    tls::TlsInfo,
 };
 use async_trait::async_trait;
-use http::{Request, Response};
+use bytes::Bytes;
+use http::{header::CONTENT_TYPE, HeaderValue, Request, Response};
 use serde::de::DeserializeOwned;
 
 /// Represents a generic wrapper that can be applied to [Endpoint] results.
@@ -21,16 +39,58 @@ use serde::de::DeserializeOwned;
 /// [EndpointResult::wrap] to automatically wrap the [Endpoint::Response] in the
 /// wrapper. The only requirement is that the [Wrapper::Value] must enclose
 /// the [Endpoint::Response].
+///
+/// The `rustify_derive` crate provides a `Wrapper` derive macro which
+/// implements this trait using the field tagged with `#[wrapper(value)]`:
+///
+/// ```
+/// use rustify_derive::Wrapper;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, Wrapper)]
+/// struct Envelope<T> {
+///     pub status: String,
+///     #[wrapper(value)]
+///     pub data: T,
+/// }
+/// ```
+///
+/// The derive macro also implements [Unwrap] for extracting the enclosed
+/// value, which endpoints declaring a wrapper require.
 pub trait Wrapper: DeserializeOwned + Send + Sync {
     type Value;
 }
 
+/// A [Wrapper] whose enclosed [Wrapper::Value] can be extracted.
+///
+/// Endpoints which are always wrapped can declare their wrapper using
+/// `#[endpoint(wrapper = "Envelope")]`. Calling `parse()` on the result of
+/// such an endpoint deserializes the wrapper and returns the output of
+/// [Unwrap::into_result]. Tagging a field of the wrapper with
+/// `#[wrapper(error)]` causes [Unwrap::into_result] to return
+/// [ClientError::ApiError] whenever that field contains a value.
+///
+/// Nested envelopes, i.e. `{"response": {"data": ...}}`, are supported by
+/// declaring `#[endpoint(wrapper = "Outer<Inner>")]`, which unwraps through
+/// both layers, or by calling [EndpointResult::unwrap_nested].
+pub trait Unwrap: Wrapper {
+    /// Consumes the wrapper and returns the enclosed [Wrapper::Value].
+    fn into_value(self) -> Self::Value;
+
+    /// Consumes the wrapper and returns the enclosed [Wrapper::Value] or an
+    /// error if the wrapper indicates the request failed. The default
+    /// implementation always returns the enclosed value.
+    fn into_result(self) -> Result<Self::Value, ClientError> {
+        Ok(self.into_value())
+    }
+}
+
 /// Represents an [Endpoint] that has had [MiddleWare] applied to it.
 ///
 /// This type wraps [Endpoint] by implementng it. The primary difference is
 /// when `exec` is called the request and response will potentially be mutated
 /// before processing. Only one [MiddleWare] can be applied to a single
-/// [Endpoint].
+/// [Endpoint], multiple middlewares are combined with a [MiddlewareStack].
 pub struct MutatedEndpoint<'a, E: Endpoint, M: MiddleWare> {
     endpoint: E,
     middleware: &'a M,
@@ -68,6 +128,41 @@ impl<E: Endpoint, M: MiddleWare> Endpoint for MutatedEndpoint<'_, E, M> {
         self.endpoint.body()
     }
 
+    fn priority(&self) -> RequestPriority {
+        self.endpoint.priority()
+    }
+
+    fn cache_policy(&self) -> CachePolicy {
+        self.endpoint.cache_policy()
+    }
+
+    fn timeouts(&self) -> Timeouts {
+        self.endpoint.timeouts()
+    }
+
+    fn name(&self) -> &'static str {
+        self.endpoint.name()
+    }
+
+    fn deprecated(&self) -> Option<&'static str> {
+        self.endpoint.deprecated()
+    }
+
+    fn metadata(&self) -> EndpointMetadata {
+        self.endpoint.metadata()
+    }
+
+    fn allow_body_on_get(&self) -> bool {
+        self.endpoint.allow_body_on_get()
+    }
+
+    fn parse_response(
+        resp: &Response<Vec<u8>>,
+        ty: ResponseType,
+    ) -> Result<Self::Response, ClientError> {
+        E::parse_response(resp, ty)
+    }
+
     #[instrument(skip(self), err)]
     fn url(&self, base: &str) -> Result<http::Uri, ClientError> {
         self.endpoint.url(base)
@@ -82,6 +177,12 @@ impl<E: Endpoint, M: MiddleWare> Endpoint for MutatedEndpoint<'_, E, M> {
             self.query()?,
             self.body()?,
         )?;
+        set_content_type::<Self>(&mut req);
+        req.extensions_mut().insert(self.priority());
+        req.extensions_mut().insert(self.cache_policy());
+        req.extensions_mut().insert(self.timeouts());
+        req.extensions_mut().insert(Operation(self.name()));
+        req.extensions_mut().insert(self.metadata());
 
         self.middleware.request(self, &mut req)?;
         Ok(req)
@@ -90,28 +191,67 @@ impl<E: Endpoint, M: MiddleWare> Endpoint for MutatedEndpoint<'_, E, M> {
     // TODO: remove the allow when the upstream clippy issue is fixed:
     // <https://github.com/rust-lang/rust-clippy/issues/12281>
     #[allow(clippy::blocks_in_conditions)]
-    #[instrument(skip(self, client), err)]
+    #[instrument(
+        skip(self, client),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
     async fn exec(
         &self,
         client: &impl Client,
     ) -> Result<EndpointResult<Self::Response>, ClientError> {
         debug!("Executing endpoint");
 
-        let req = self.request(client.base())?;
+        warn_deprecated(self);
+        let req = client.config().build_request(self, client.base())?;
         let resp = exec_mut(client, self, req, self.middleware).await?;
-        Ok(EndpointResult::new(resp, Self::RESPONSE_BODY_TYPE))
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
+    }
+
+    /// Always buffers the response so the middleware can process it, see
+    /// [Endpoint::exec_output].
+    async fn exec_output(
+        &self,
+        client: &impl Client,
+        _: ExecMode,
+    ) -> Result<ExecOutput<Self::Response>, ClientError> {
+        self.exec(client).await.map(ExecOutput::Buffered)
     }
 
     #[cfg(feature = "blocking")]
+    #[instrument(
+        skip(self, client),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
     fn exec_block(
         &self,
         client: &impl BlockingClient,
     ) -> Result<EndpointResult<Self::Response>, ClientError> {
         debug!("Executing endpoint");
 
-        let req = self.request(client.base())?;
+        warn_deprecated(self);
+        let req = client.config().build_request(self, client.base())?;
         let resp = exec_block_mut(client, self, req, self.middleware)?;
-        Ok(EndpointResult::new(resp, Self::RESPONSE_BODY_TYPE))
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
     }
 }
 
@@ -142,6 +282,17 @@ impl<E: Endpoint, M: MiddleWare> Endpoint for MutatedEndpoint<'_, E, M> {
 /// It's worth noting that fields which have the [Option] type and whose value,
 /// at runtime, is [Option::None] will not be serialized. This avoids defining
 /// data parameters which were not specified when the endpoint was created.
+/// Endpoints which need to distinguish between omitted and `null` values (i.e.
+/// for `PATCH` requests) can be tagged with
+/// `#[endpoint(serialize_none_as_null)]`, which can also be applied to or
+/// overridden by individual fields using `#[endpoint(skip_none)]`.
+///
+/// Endpoints tagged with `#[endpoint(eq)]` implement [PartialEq], [Eq], and
+/// [Hash][std::hash::Hash] using only the fields which make up the request:
+/// those interpolated into the path, the query fields, and the body fields.
+/// Two such endpoints compare equal when they would send the same request,
+/// so they can be used directly as keys for caching or deduplicating
+/// requests.
 ///
 /// A number of useful methods are provided for obtaining information about an
 /// endpoint including its URL, HTTP method, and request data. The `request`
@@ -204,6 +355,68 @@ pub trait Endpoint: Send + Sync + Sized {
         Ok(None)
     }
 
+    /// The priority used when scheduling this Endpoint's request. The
+    /// priority is stored as an extension of the [Request] and is only used by
+    /// clients configured with a [Scheduler][crate::scheduler::Scheduler].
+    fn priority(&self) -> RequestPriority {
+        RequestPriority::NORMAL
+    }
+
+    /// The [CachePolicy] applied to this Endpoint's response. The policy is
+    /// stored as an extension of the [Request] for use by caching middleware.
+    fn cache_policy(&self) -> CachePolicy {
+        CachePolicy::Default
+    }
+
+    /// The [Timeouts] of each attempt to execute this Endpoint. Timeouts
+    /// which are left unset are taken from the
+    /// [ClientConfig][crate::config::ClientConfig] of the client.
+    fn timeouts(&self) -> Timeouts {
+        Timeouts::new()
+    }
+
+    /// A name identifying this Endpoint in logs and journals. Defaults to the
+    /// name of the implementing type.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// A note explaining why this Endpoint is deprecated and what to use
+    /// instead, if it is. A warning including the note is logged the first
+    /// time each deprecated Endpoint is executed.
+    fn deprecated(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The [EndpointMetadata] describing this Endpoint. The metadata is
+    /// stored as an extension of the [Request] for use by middleware.
+    fn metadata(&self) -> EndpointMetadata {
+        EndpointMetadata::default()
+    }
+
+    /// Whether this Endpoint intentionally sends a body with `GET` requests,
+    /// as some APIs (i.e. Elasticsearch) require.
+    ///
+    /// Bodies are sent with every method regardless, this only declares the
+    /// intent so middleware which would otherwise strip or reject such bodies,
+    /// see [EndpointInfo][crate::middleware::EndpointInfo], can leave them be.
+    fn allow_body_on_get(&self) -> bool {
+        false
+    }
+
+    /// Parses a [Response] from executing this Endpoint into
+    /// [Endpoint::Response].
+    ///
+    /// The default implementation deserializes the response body directly.
+    /// Endpoints declaring a `wrapper` with the derive macro override this to
+    /// first deserialize the body into the [Wrapper] and then unwrap it.
+    fn parse_response(
+        resp: &Response<Vec<u8>>,
+        ty: ResponseType,
+    ) -> Result<Self::Response, ClientError> {
+        crate::http::parse(resp.body(), ty)
+    }
+
     /// Returns the full URL address of the endpoint using the base address.
     #[instrument(skip(self), err)]
     fn url(&self, base: &str) -> Result<http::Uri, ClientError> {
@@ -214,50 +427,432 @@ pub trait Endpoint: Send + Sync + Sized {
     /// this endpoint.
     #[instrument(skip(self), err)]
     fn request(&self, base: &str) -> Result<Request<Vec<u8>>, ClientError> {
-        crate::http::build_request(
+        let mut req = crate::http::build_request(
             base,
             &self.path(),
             self.method(),
             self.query()?,
             self.body()?,
-        )
+        )?;
+        set_content_type::<Self>(&mut req);
+        req.extensions_mut().insert(self.priority());
+        req.extensions_mut().insert(self.cache_policy());
+        req.extensions_mut().insert(self.timeouts());
+        req.extensions_mut().insert(Operation(self.name()));
+        req.extensions_mut().insert(self.metadata());
+        Ok(req)
     }
 
     /// Executes the Endpoint using the given [Client].
     // TODO: remove the allow when the upstream clippy issue is fixed:
     // <https://github.com/rust-lang/rust-clippy/issues/12281>
     #[allow(clippy::blocks_in_conditions)]
-    #[instrument(skip(self, client), err)]
+    #[instrument(
+        skip(self, client),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
     async fn exec(
         &self,
         client: &impl Client,
     ) -> Result<EndpointResult<Self::Response>, ClientError> {
         debug!("Executing endpoint");
 
-        let req = self.request(client.base())?;
-        let resp = exec(client, req).await?;
-        Ok(EndpointResult::new(resp, Self::RESPONSE_BODY_TYPE))
+        warn_deprecated(self);
+        let req = client.config().build_request(self, client.base())?;
+        let resp = exec(client, self, req).await?;
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
+    }
+
+    /// Executes the Endpoint using the given [Client] and returns a
+    /// [LazyResult] which only deserializes the response when it's accessed.
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip(self, client), err)]
+    async fn exec_lazy(
+        &self,
+        client: &impl Client,
+    ) -> Result<LazyResult<Self::Response>, ClientError> {
+        self.exec(client).await.map(EndpointResult::into_lazy)
+    }
+
+    /// Executes the Endpoint using the given [Client] and discards the
+    /// response.
+    ///
+    /// The response status is still checked, but the body is never
+    /// deserialized. This is useful for endpoints like `DELETE` where the
+    /// response content is irrelevant.
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip(self, client), err)]
+    async fn exec_void(&self, client: &impl Client) -> Result<(), ClientError> {
+        self.exec(client).await.map(|_| ())
+    }
+
+    /// Executes the Endpoint using the given [Client] and parses the response
+    /// as the given [ResponseType] into `T` instead of [Endpoint::Response].
+    ///
+    /// This is useful for endpoints which return a different format depending
+    /// on their parameters, i.e. CSV instead of JSON when exporting. Custom
+    /// response parsing of the endpoint, such as its wrapper, is bypassed.
+    ///
+    /// ```
+    /// use rustify::{clients::reqwest::Client, enums::ResponseType, Endpoint};
+    /// use rustify_derive::Endpoint;
+    ///
+    /// #[derive(Endpoint)]
+    /// #[endpoint(path = "reports", response = "Vec<serde_json::Value>")]
+    /// struct ListReports {
+    ///     #[endpoint(query)]
+    ///     export: Option<String>,
+    /// }
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::default("http://api.example.com");
+    /// let csv = ListReports { export: Some("csv".to_string()) }
+    ///     .exec_as::<String>(&client, ResponseType::TEXT)
+    ///     .await;
+    /// # });
+    /// ```
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip(self, client), err)]
+    async fn exec_as<T: DeserializeOwned + Send + Sync>(
+        &self,
+        client: &impl Client,
+        ty: ResponseType,
+    ) -> Result<EndpointResult<T>, ClientError> {
+        let result = self.exec(client).await?;
+        Ok(EndpointResult::new(result.response, ty))
     }
 
-    fn with_middleware<M: MiddleWare>(self, middleware: &M) -> MutatedEndpoint<Self, M> {
+    /// Executes the Endpoint using the given [Client] until its parsed
+    /// response satisfies the given predicate, waiting for the delays of the
+    /// given [RetryPolicy] in between. The response is returned as a
+    /// [LazyResult] holding the value the predicate was checked against, so
+    /// it isn't parsed again.
+    ///
+    /// This covers reads which aren't consistent with a preceding write yet,
+    /// i.e. a listing which doesn't contain a resource that was just created.
+    /// Every repeated execution emits an
+    /// [Event::RetryScheduled][crate::events::Event::RetryScheduled]. Once
+    /// the policy runs out of retries the last response is returned even
+    /// though it doesn't satisfy the predicate. Errors, including responses
+    /// which fail to parse, are returned immediately, while failed requests
+    /// are still retried according to the policy configured on the client.
+    ///
+    /// ```
+    /// use rustify::{clients::reqwest::Client, retry::RetryPolicy, Endpoint};
+    /// use rustify_derive::Endpoint;
+    ///
+    /// #[derive(Endpoint)]
+    /// #[endpoint(path = "users", response = "Vec<String>")]
+    /// struct ListUsers {}
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::default("http://api.example.com");
+    /// let users = ListUsers {}
+    ///     .exec_read_repair(&client, &RetryPolicy::new(5), |users| {
+    ///         users.iter().any(|u| u == "new-user")
+    ///     })
+    ///     .await;
+    /// # });
+    /// ```
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(skip(self, client, policy, ready), err)]
+    async fn exec_read_repair<F>(
+        &self,
+        client: &impl Client,
+        policy: &RetryPolicy,
+        ready: F,
+    ) -> Result<LazyResult<Self::Response>, ClientError>
+    where
+        F: Fn(&Self::Response) -> bool + Send + Sync,
+    {
+        let mut retries = 0;
+        loop {
+            let result = self.exec(client).await?.into_lazy();
+            if ready(result.get()?) {
+                return Ok(result);
+            }
+            retries += 1;
+            let delay = match retries > policy.max_retries() {
+                true => return Ok(unrepaired(self, result)),
+                false => policy.delay(retries),
+            };
+            schedule_repair(client.config(), self, retries, delay);
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Executes the Endpoint against the given absolute, pre-signed URL using
+    /// the given [Client].
+    ///
+    /// The URL is used exactly as given: the base URL of the client, the path
+    /// and query of the Endpoint, and any [MiddleWare] are bypassed and
+    /// credentials aren't attached by a [Session][crate::session::Session].
+    /// The method, body, and response parsing of the Endpoint are still used.
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(
+        skip(self, client, url),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
+    async fn exec_presigned(
+        &self,
+        client: &impl Client,
+        url: &str,
+    ) -> Result<EndpointResult<Self::Response>, ClientError> {
+        debug!("Executing endpoint against pre-signed URL");
+
+        warn_deprecated(self);
+        let req = presigned_request(client.config(), self, url)?;
+        let start = Instant::now();
+        let resp = record_outcome(start, client.execute(req).await)?;
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
+    }
+
+    /// Executes the Endpoint using the given [Client] in the given
+    /// [ExecMode], streaming the response body if requested and supported by
+    /// the client, see [crate::stream].
+    ///
+    /// Streamed responses skip response middleware and can't be retried, so
+    /// the response is buffered instead when the client is configured with
+    /// [middleware][ClientConfig::middleware], a
+    /// [RetryPolicy][ClientConfig::retry], or
+    /// [stale connection retries][ClientConfig::retry_stale_connections], and
+    /// when executed through [Endpoint::with_middleware].
+    // TODO: remove the allow when the upstream clippy issue is fixed:
+    // <https://github.com/rust-lang/rust-clippy/issues/12281>
+    #[allow(clippy::blocks_in_conditions)]
+    #[instrument(
+        skip(self, client),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
+    async fn exec_output(
+        &self,
+        client: &impl Client,
+        mode: ExecMode,
+    ) -> Result<ExecOutput<Self::Response>, ClientError> {
+        if mode == ExecMode::Buffered
+            || !client.supports_streaming()
+            || client.config().requires_buffering()
+        {
+            return self.exec(client).await.map(ExecOutput::Buffered);
+        }
+
+        debug!("Executing endpoint with a streamed response");
+        warn_deprecated(self);
+        let req = client.config().build_request(self, client.base())?;
+        client.config().wait_rate_limit(self, &req).await;
+        let start = Instant::now();
+        record_outcome(start, client.execute_stream(req).await).map(ExecOutput::Stream)
+    }
+
+    fn with_middleware<M: MiddleWare>(self, middleware: &M) -> MutatedEndpoint<'_, Self, M> {
         MutatedEndpoint::new(self, middleware)
     }
 
     /// Executes the Endpoint using the given [Client].
     #[cfg(feature = "blocking")]
-    #[instrument(skip(self, client), err)]
+    #[instrument(
+        skip(self, client),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
     fn exec_block(
         &self,
         client: &impl BlockingClient,
     ) -> Result<EndpointResult<Self::Response>, ClientError> {
         debug!("Executing endpoint");
 
-        let req = self.request(client.base())?;
-        let resp = exec_block(client, req)?;
-        Ok(EndpointResult::new(resp, Self::RESPONSE_BODY_TYPE))
+        warn_deprecated(self);
+        let req = client.config().build_request(self, client.base())?;
+        let resp = exec_block(client, self, req)?;
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
+    }
+
+    /// Executes the Endpoint against the given absolute, pre-signed URL using
+    /// the given [Client], see [Endpoint::exec_presigned].
+    #[cfg(feature = "blocking")]
+    #[instrument(
+        skip(self, client, url),
+        fields(
+            path = %self.path(),
+            method = ?self.method(),
+            status = tracing::field::Empty,
+            latency_ms = tracing::field::Empty,
+        ),
+        err
+    )]
+    fn exec_presigned_block(
+        &self,
+        client: &impl BlockingClient,
+        url: &str,
+    ) -> Result<EndpointResult<Self::Response>, ClientError> {
+        debug!("Executing endpoint against pre-signed URL");
+
+        warn_deprecated(self);
+        let req = presigned_request(client.config(), self, url)?;
+        let start = Instant::now();
+        let resp = record_outcome(start, client.execute(req))?;
+        Ok(EndpointResult::with_parser(
+            resp,
+            Self::RESPONSE_BODY_TYPE,
+            Self::parse_response,
+        ))
+    }
+
+    /// Executes the Endpoint using the given [Client] and returns a
+    /// [LazyResult] which only deserializes the response when it's accessed.
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client), err)]
+    fn exec_lazy_block(
+        &self,
+        client: &impl BlockingClient,
+    ) -> Result<LazyResult<Self::Response>, ClientError> {
+        self.exec_block(client).map(EndpointResult::into_lazy)
+    }
+
+    /// Executes the Endpoint using the given [Client] and discards the
+    /// response.
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client), err)]
+    fn exec_void_block(&self, client: &impl BlockingClient) -> Result<(), ClientError> {
+        self.exec_block(client).map(|_| ())
+    }
+
+    /// Executes the Endpoint using the given [Client] and parses the response
+    /// as the given [ResponseType], see [Endpoint::exec_as].
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client), err)]
+    fn exec_as_block<T: DeserializeOwned + Send + Sync>(
+        &self,
+        client: &impl BlockingClient,
+        ty: ResponseType,
+    ) -> Result<EndpointResult<T>, ClientError> {
+        let result = self.exec_block(client)?;
+        Ok(EndpointResult::new(result.response, ty))
+    }
+
+    /// Executes the Endpoint using the given [Client] until its parsed
+    /// response satisfies the given predicate, see
+    /// [Endpoint::exec_read_repair].
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client, policy, ready), err)]
+    fn exec_read_repair_block<F>(
+        &self,
+        client: &impl BlockingClient,
+        policy: &RetryPolicy,
+        ready: F,
+    ) -> Result<LazyResult<Self::Response>, ClientError>
+    where
+        F: Fn(&Self::Response) -> bool,
+    {
+        let mut retries = 0;
+        loop {
+            let result = self.exec_block(client)?.into_lazy();
+            if ready(result.get()?) {
+                return Ok(result);
+            }
+            retries += 1;
+            let delay = match retries > policy.max_retries() {
+                true => return Ok(unrepaired(self, result)),
+                false => policy.delay(retries),
+            };
+            schedule_repair(client.config(), self, retries, delay);
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+/// Logs a warning the first time the given [Endpoint] is executed if it's
+/// deprecated.
+fn warn_deprecated<E: Endpoint>(endpoint: &E) {
+    static WARNED: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    if let Some(note) = endpoint.deprecated() {
+        let mut warned = WARNED
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if warned.insert(endpoint.name()) {
+            warn!(
+                endpoint = endpoint.name(),
+                "Executing deprecated endpoint: {}", note
+            );
+        }
     }
 }
 
+/// Builds a [Request] for the given [Endpoint] to a pre-signed URL.
+fn presigned_request<E: Endpoint>(
+    config: &ClientConfig,
+    endpoint: &E,
+    url: &str,
+) -> Result<Request<Vec<u8>>, ClientError> {
+    let body = config.building(|| endpoint.body())?;
+    let mut req = crate::http::build_presigned_request(url, endpoint.method(), body)?;
+    set_content_type::<E>(&mut req);
+    req.extensions_mut().insert(endpoint.priority());
+    req.extensions_mut().insert(endpoint.cache_policy());
+    req.extensions_mut().insert(endpoint.timeouts());
+    req.extensions_mut().insert(Operation(endpoint.name()));
+    req.extensions_mut().insert(endpoint.metadata());
+    Ok(req)
+}
+
+/// Sets the `Content-Type` of a non-empty request body using the
+/// [Endpoint::REQUEST_BODY_TYPE], if it has one.
+fn set_content_type<E: Endpoint>(req: &mut Request<Vec<u8>>) {
+    if let (Some(ty), false) = (E::REQUEST_BODY_TYPE.content_type(), req.body().is_empty()) {
+        req.headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static(ty));
+    }
+}
+
+/// A function which parses a [Response] into the final result type.
+pub type ResponseParser<T> = fn(&Response<Vec<u8>>, ResponseType) -> Result<T, ClientError>;
+
 /// A response from executing an [Endpoint].
 ///
 /// All [Endpoint] executions will result in an [EndpointResult] which wraps
@@ -267,37 +862,92 @@ pub trait Endpoint: Send + Sync + Sized {
 pub struct EndpointResult<T: DeserializeOwned + Send + Sync> {
     pub response: Response<Vec<u8>>,
     pub ty: ResponseType,
-    inner: PhantomData<T>,
+    parser: ResponseParser<T>,
 }
 
 impl<T: DeserializeOwned + Send + Sync> EndpointResult<T> {
     /// Returns a new [EndpointResult].
     pub fn new(response: Response<Vec<u8>>, ty: ResponseType) -> Self {
+        EndpointResult::with_parser(response, ty, |resp, ty| crate::http::parse(resp.body(), ty))
+    }
+
+    /// Returns a new [EndpointResult] which uses the given [ResponseParser]
+    /// when `parse()` is called.
+    pub fn with_parser(
+        response: Response<Vec<u8>>,
+        ty: ResponseType,
+        parser: ResponseParser<T>,
+    ) -> Self {
         EndpointResult {
             response,
             ty,
-            inner: PhantomData,
+            parser,
         }
     }
 
     /// Parses the response into the final result type.
     #[instrument(skip(self), err)]
     pub fn parse(&self) -> Result<T, ClientError> {
-        match self.ty {
-            ResponseType::JSON => serde_json::from_slice(self.response.body()).map_err(|e| {
-                ClientError::ResponseParseError {
-                    source: e.into(),
-                    content: String::from_utf8(self.response.body().to_vec()).ok(),
-                }
-            }),
-        }
+        let result = (self.parser)(&self.response, self.ty.clone());
+        Events::parsed(&self.response, &result);
+        result
+    }
+
+    /// Parses the response into the final result type and invokes the given
+    /// callback with the path of every field in the response which wasn't
+    /// captured by the result.
+    ///
+    /// Each unknown field is also emitted as a tracing event. Fields are only
+    /// reported when the response is successfully parsed.
+    #[instrument(skip(self, report), err)]
+    pub fn parse_reporting<F: FnMut(&str)>(&self, report: F) -> Result<T, ClientError> {
+        crate::http::report_unknown_fields(|| self.parse(), report)
     }
 
-    /// Returns the raw response body from the HTTP [Response].
+    /// Returns the details of the TLS session the response was received
+    /// over, if available. See [crate::tls] for when this is populated.
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.response.extensions().get::<TlsInfo>()
+    }
+
+    /// Returns the rate limit state reported in the response headers, if
+    /// any.
+    pub fn rate_limit(&self) -> Option<RateLimitInfo> {
+        RateLimitInfo::from_headers(self.response.headers())
+    }
+
+    /// Returns the redirects followed before receiving the response, see
+    /// [crate::redirect].
+    pub fn redirects(&self) -> &[Redirect] {
+        self.response
+            .extensions()
+            .get::<Redirects>()
+            .map(|r| r.0.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Returns a copy of the raw response body from the HTTP [Response].
     pub fn raw(&self) -> Vec<u8> {
         self.response.body().clone()
     }
 
+    /// Returns the raw response body from the HTTP [Response] without
+    /// copying it.
+    pub fn into_raw(self) -> Vec<u8> {
+        self.response.into_body()
+    }
+
+    /// Converts this result into a [LazyResult] which caches the parsed
+    /// response.
+    pub fn into_lazy(self) -> LazyResult<T> {
+        LazyResult {
+            response: self.response,
+            ty: self.ty,
+            parser: self.parser,
+            value: OnceLock::new(),
+        }
+    }
+
     /// Parses the response into the final result type and then wraps it in the
     /// given [Wrapper].
     #[instrument(skip(self), err)]
@@ -305,14 +955,77 @@ impl<T: DeserializeOwned + Send + Sync> EndpointResult<T> {
     where
         W: Wrapper<Value = T>,
     {
-        match self.ty {
-            ResponseType::JSON => serde_json::from_slice(self.response.body()).map_err(|e| {
-                ClientError::ResponseParseError {
-                    source: e.into(),
-                    content: String::from_utf8(self.response.body().to_vec()).ok(),
-                }
-            }),
+        crate::http::parse(self.response.body(), self.ty.clone())
+    }
+
+    /// Parses the response as an outer [Wrapper] enclosing an inner
+    /// [Wrapper], i.e. `{"response": {"data": ...}}`, and unwraps both layers
+    /// into the final result type using [Unwrap::into_result].
+    ///
+    /// The enclosed type can be inferred, so `Outer<Inner<_>>` is enough.
+    #[instrument(skip(self), err)]
+    pub fn unwrap_nested<W>(&self) -> Result<T, ClientError>
+    where
+        W: Unwrap,
+        W::Value: Unwrap<Value = T>,
+    {
+        let outer: W = crate::http::parse(self.response.body(), self.ty.clone())?;
+        outer.into_result()?.into_result()
+    }
+}
+
+/// A response from executing an [Endpoint] which is deserialized on demand.
+///
+/// The response body is held as raw bytes and is only deserialized the first
+/// time `get()` is called, after which the result is cached. Callers which
+/// end up discarding the response never pay for deserialization.
+pub struct LazyResult<T: DeserializeOwned + Send + Sync> {
+    pub response: Response<Vec<u8>>,
+    pub ty: ResponseType,
+    parser: ResponseParser<T>,
+    value: OnceLock<T>,
+}
+
+impl<T: DeserializeOwned + Send + Sync> LazyResult<T> {
+    /// Returns a reference to the deserialized response, deserializing it if
+    /// this is the first access.
+    #[instrument(skip(self), err)]
+    pub fn get(&self) -> Result<&T, ClientError> {
+        if let Some(v) = self.value.get() {
+            return Ok(v);
         }
+
+        let value = (self.parser)(&self.response, self.ty.clone());
+        Events::parsed(&self.response, &value);
+        let value = value?;
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Consumes the result and returns the deserialized response.
+    pub fn into_inner(self) -> Result<T, ClientError> {
+        match self.value.into_inner() {
+            Some(v) => Ok(v),
+            None => {
+                let value = (self.parser)(&self.response, self.ty);
+                Events::parsed(&self.response, &value);
+                value
+            }
+        }
+    }
+
+    /// Returns whether the response has already been deserialized.
+    pub fn is_parsed(&self) -> bool {
+        self.value.get().is_some()
+    }
+
+    /// Returns the raw response body.
+    pub fn raw(&self) -> &[u8] {
+        self.response.body()
+    }
+
+    /// Consumes the result and returns the raw response body without copying.
+    pub fn into_bytes(self) -> Bytes {
+        Bytes::from(self.response.into_body())
     }
 }
 
@@ -336,13 +1049,291 @@ pub trait MiddleWare: Sync + Send {
         endpoint: &E,
         resp: &mut Response<Vec<u8>>,
     ) -> Result<(), ClientError>;
+
+    /// Returns a [MiddleWare] which is only applied to endpoints matched by
+    /// the given [Matcher].
+    fn only_for(self, matcher: Matcher) -> OnlyFor<Self>
+    where
+        Self: Sized,
+    {
+        OnlyFor {
+            middleware: self,
+            matcher,
+        }
+    }
+
+    /// Returns a [MiddlewareStack] which applies this middleware followed by
+    /// the given one.
+    fn and<M: MiddleWare>(self, next: M) -> MiddlewareStack<Self, M>
+    where
+        Self: Sized,
+    {
+        MiddlewareStack::new(self, next)
+    }
+
+    /// Returns a [MiddleWare] which converts panics of this middleware into
+    /// [ClientError::MiddlewarePanic] errors, see [CatchPanic].
+    fn catch_panics(self) -> CatchPanic<Self>
+    where
+        Self: Sized,
+    {
+        CatchPanic { middleware: self }
+    }
+
+    /// Returns a [MiddleWare] which wraps errors returned by this middleware
+    /// in a [ClientError::Middleware] with the given layer name.
+    fn named(self, layer: &str) -> Named<Self>
+    where
+        Self: Sized,
+    {
+        Named {
+            middleware: self,
+            layer: layer.to_string(),
+        }
+    }
+}
+
+impl<M: MiddleWare> MiddleWare for &M {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        (**self).request(endpoint, req)
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        (**self).response(endpoint, resp)
+    }
+}
+
+/// Composes two [MiddleWare] into one which can be used anywhere a single
+/// [MiddleWare] is accepted.
+///
+/// Requests are passed to the `outer` middleware first and then to the
+/// `inner` one, while responses are passed through them in reverse order, so
+/// the outer middleware sees the request last modified and the response
+/// first received by the inner one. Processing stops at the first middleware
+/// which returns an error. Stacks of more than two middlewares are built by
+/// nesting, i.e. with [MiddlewareStack::push] or [MiddleWare::and]:
+///
+/// ```
+/// use rustify::{
+///     endpoint::{MiddleWare, MiddlewareStack},
+///     journal::{Journal, JsonLines},
+///     skew::ClockSkew,
+/// };
+///
+/// let skew = ClockSkew::new();
+/// let journal = Journal::new(JsonLines::new(Vec::new()));
+///
+/// // The journal records requests after the clock skew has been applied
+/// let stack = MiddlewareStack::new(&skew, &journal);
+/// let same = (&skew).and(&journal);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MiddlewareStack<A: MiddleWare, B: MiddleWare> {
+    pub outer: A,
+    pub inner: B,
+}
+
+impl<A: MiddleWare, B: MiddleWare> MiddlewareStack<A, B> {
+    /// Returns a new [MiddlewareStack] which applies `outer` to requests
+    /// before `inner`.
+    pub fn new(outer: A, inner: B) -> Self {
+        MiddlewareStack { outer, inner }
+    }
+
+    /// Returns a [MiddlewareStack] which applies the given middleware to
+    /// requests after all middleware in this stack.
+    pub fn push<M: MiddleWare>(self, middleware: M) -> MiddlewareStack<Self, M> {
+        MiddlewareStack::new(self, middleware)
+    }
+}
+
+impl<A: MiddleWare, B: MiddleWare> MiddleWare for MiddlewareStack<A, B> {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.outer.request(endpoint, req)?;
+        self.inner.request(endpoint, req)
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.inner.response(endpoint, resp)?;
+        self.outer.response(endpoint, resp)
+    }
+}
+
+/// A [MiddleWare] which identifies the layer its errors come from.
+///
+/// Errors returned by the wrapped middleware are wrapped in a
+/// [ClientError::Middleware] carrying the layer name, so a failure in a deep
+/// [MiddlewareStack] can be attributed to the middleware which caused it.
+/// Named layers nested inside other named layers produce nested errors,
+/// see [ClientError::layers]. This is created by calling [MiddleWare::named]:
+///
+/// ```
+/// use rustify::{endpoint::MiddleWare, skew::ClockSkew};
+///
+/// let stack = ClockSkew::new().named("skew").and(ClockSkew::new().named("auth"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Named<M: MiddleWare> {
+    pub middleware: M,
+    pub layer: String,
+}
+
+impl<M: MiddleWare> Named<M> {
+    fn wrap(&self, result: Result<(), ClientError>) -> Result<(), ClientError> {
+        result.map_err(|e| ClientError::Middleware {
+            layer: self.layer.clone(),
+            source: Box::new(e),
+        })
+    }
+}
+
+impl<M: MiddleWare> MiddleWare for Named<M> {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.wrap(self.middleware.request(endpoint, req))
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.wrap(self.middleware.response(endpoint, resp))
+    }
 }
 
+/// Executes the given [Request] of an [Endpoint], applying the middleware
+/// configured with [ClientConfig::middleware][crate::config::ClientConfig::middleware]
+/// and retrying according to the configured
+/// [RetryPolicy][crate::retry::RetryPolicy].
 async fn exec(
     client: &impl Client,
-    req: Request<Vec<u8>>,
+    endpoint: &impl Endpoint,
+    mut req: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let config = client.config();
+    let start = Instant::now();
+    let mut retries = 0;
+    loop {
+        let result = exec_attempt(client, endpoint, req).await;
+        let delay = match (&result, &config.retry) {
+            (Err(e), Some(policy)) if policy.should_retry(e, retries) => {
+                retries += 1;
+                policy.delay(retries)
+            }
+            _ => return record_outcome(start, with_retries(result, retries)),
+        };
+        req = retry_request(config, client.base(), endpoint, retries, delay)?;
+        tokio::time::sleep(delay).await;
+    }
+}
+
+async fn exec_attempt(
+    client: &impl Client,
+    endpoint: &impl Endpoint,
+    mut req: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let config = client.config();
+    config.wait_rate_limit(endpoint, &req).await;
+    config.middleware_request(endpoint, &mut req)?;
+    let mut resp = client.execute(req).await?;
+    config.middleware_response(endpoint, &mut resp)?;
+    Ok(resp)
+}
+
+/// Rebuilds the request of an [Endpoint] for the given retry and emits
+/// [Event::RetryScheduled][crate::events::Event::RetryScheduled].
+fn retry_request(
+    config: &ClientConfig,
+    base: &str,
+    endpoint: &impl Endpoint,
+    retries: u32,
+    delay: Duration,
+) -> Result<Request<Vec<u8>>, ClientError> {
+    let attempt = retries + 1;
+    warn!(
+        endpoint = endpoint.name(),
+        "Retrying failed request in {:?}, attempt {}", delay, attempt
+    );
+    config.emit_retry(Some(endpoint.name()), attempt, delay);
+    let mut req = config.build_request(endpoint, base)?;
+    req.extensions_mut().insert(Attempt(attempt));
+    Ok(req)
+}
+
+/// Logs and emits [Event::RetryScheduled][crate::events::Event::RetryScheduled]
+/// for the given read repair of an [Endpoint].
+fn schedule_repair(config: &ClientConfig, endpoint: &impl Endpoint, retries: u32, delay: Duration) {
+    let attempt = retries + 1;
+    warn!(
+        endpoint = endpoint.name(),
+        "Repeating read which didn't satisfy its predicate in {:?}, attempt {}", delay, attempt
+    );
+    config.emit_retry(Some(endpoint.name()), attempt, delay);
+}
+
+/// Returns the last result of a read repair which never satisfied its
+/// predicate.
+fn unrepaired<E: Endpoint>(
+    endpoint: &E,
+    result: LazyResult<E::Response>,
+) -> LazyResult<E::Response> {
+    warn!(
+        endpoint = endpoint.name(),
+        "Read still didn't satisfy its predicate after running out of retries"
+    );
+    result
+}
+
+/// Records the status and latency of an execution on the current span.
+///
+/// The latency of streamed responses only covers receiving the headers.
+fn record_outcome<B>(
+    start: Instant,
+    result: Result<Response<B>, ClientError>,
+) -> Result<Response<B>, ClientError> {
+    let span = tracing::Span::current();
+    let status = match &result {
+        Ok(resp) => Some(resp.status().as_u16()),
+        Err(e) => e.status(),
+    };
+    if let Some(status) = status {
+        span.record("status", status);
+    }
+    span.record("latency_ms", start.elapsed().as_millis() as u64);
+    result
+}
+
+/// Records the number of retries in the extensions of a successful response.
+fn with_retries(
+    result: Result<Response<Vec<u8>>, ClientError>,
+    retries: u32,
 ) -> Result<Response<Vec<u8>>, ClientError> {
-    client.execute(req).await
+    result.map(|mut resp| {
+        if retries > 0 {
+            resp.extensions_mut().insert(Retries(retries));
+        }
+        resp
+    })
 }
 
 async fn exec_mut(
@@ -351,7 +1342,7 @@ async fn exec_mut(
     req: Request<Vec<u8>>,
     middle: &impl MiddleWare,
 ) -> Result<Response<Vec<u8>>, ClientError> {
-    let mut resp = client.execute(req).await?;
+    let mut resp = exec(client, endpoint, req).await?;
     middle.response(endpoint, &mut resp)?;
     Ok(resp)
 }
@@ -359,9 +1350,38 @@ async fn exec_mut(
 #[cfg(feature = "blocking")]
 fn exec_block(
     client: &impl BlockingClient,
-    req: Request<Vec<u8>>,
+    endpoint: &impl Endpoint,
+    mut req: Request<Vec<u8>>,
 ) -> Result<Response<Vec<u8>>, ClientError> {
-    client.execute(req)
+    let config = client.config();
+    let start = Instant::now();
+    let mut retries = 0;
+    loop {
+        let result = exec_block_attempt(client, endpoint, req);
+        let delay = match (&result, &config.retry) {
+            (Err(e), Some(policy)) if policy.should_retry(e, retries) => {
+                retries += 1;
+                policy.delay(retries)
+            }
+            _ => return record_outcome(start, with_retries(result, retries)),
+        };
+        req = retry_request(config, client.base(), endpoint, retries, delay)?;
+        std::thread::sleep(delay);
+    }
+}
+
+#[cfg(feature = "blocking")]
+fn exec_block_attempt(
+    client: &impl BlockingClient,
+    endpoint: &impl Endpoint,
+    mut req: Request<Vec<u8>>,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let config = client.config();
+    config.wait_rate_limit_blocking(endpoint, &req);
+    config.middleware_request(endpoint, &mut req)?;
+    let mut resp = client.execute(req)?;
+    config.middleware_response(endpoint, &mut resp)?;
+    Ok(resp)
 }
 
 #[cfg(feature = "blocking")]
@@ -371,7 +1391,7 @@ fn exec_block_mut(
     req: Request<Vec<u8>>,
     middle: &impl MiddleWare,
 ) -> Result<Response<Vec<u8>>, ClientError> {
-    let mut resp = client.execute(req)?;
+    let mut resp = exec_block(client, endpoint, req)?;
     middle.response(endpoint, &mut resp)?;
     Ok(resp)
 }
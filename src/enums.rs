@@ -33,14 +33,80 @@ impl Into<http::Method> for RequestMethod {
     }
 }
 
+/// Represents the priority of a HTTP request
+///
+/// Priorities are only taken into account by clients configured with a
+/// [Scheduler][crate::scheduler::Scheduler].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RequestPriority {
+    LOW,
+    #[default]
+    NORMAL,
+    HIGH,
+}
+
+/// Determines whether a trailing slash is added to or removed from request
+/// paths
+///
+/// Some frameworks redirect between the two forms of a path, so normalizing
+/// them avoids an extra round trip for every request. Only applied by clients
+/// configured with
+/// [ClientConfig::trailing_slash][crate::config::ClientConfig::trailing_slash].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TrailingSlash {
+    /// Sends paths exactly as built
+    #[default]
+    PRESERVE,
+    /// Adds a trailing slash to every path, i.e. `/users/`
+    ALWAYS,
+    /// Removes trailing slashes from every path, i.e. `/users`
+    NEVER,
+}
+
 /// Represents the type of a HTTP request body
 #[derive(Clone, Debug)]
+#[allow(non_camel_case_types)]
 pub enum RequestType {
     JSON,
+    /// A JSON Merge Patch, see [MergePatch][crate::patch::MergePatch]
+    JSON_MERGE_PATCH,
+    /// A JSON Patch, see [JsonPatch][crate::patch::JsonPatch]
+    JSON_PATCH,
+}
+
+impl RequestType {
+    /// Returns the `Content-Type` sent with bodies of this type, if any.
+    ///
+    /// Plain JSON bodies don't send a content type since endpoints may also
+    /// send raw bodies of any format.
+    pub fn content_type(&self) -> Option<&'static str> {
+        match self {
+            RequestType::JSON => None,
+            RequestType::JSON_MERGE_PATCH => Some("application/merge-patch+json"),
+            RequestType::JSON_PATCH => Some("application/json-patch+json"),
+        }
+    }
 }
 
 /// Represents the type of a HTTP response body
 #[derive(Clone, Debug)]
 pub enum ResponseType {
     JSON,
+    /// A UTF-8 text body, parsed into types which deserialize from a string
+    /// such as [String]
+    TEXT,
+}
+
+/// Determines how blank response bodies are handled when parsing
+///
+/// Proxies frequently pad otherwise empty responses with whitespace, so by
+/// default bodies which are empty, whitespace-only, or `null` are all parsed
+/// as `null`. This allows them to be parsed into an `Option` or `()`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BodyStrictness {
+    /// Parses blank bodies as `null`
+    #[default]
+    LENIENT,
+    /// Parses bodies exactly as received, failing on blank bodies
+    STRICT,
 }
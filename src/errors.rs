@@ -6,12 +6,43 @@ use crate::enums::RequestMethod;
 /// The general error type returned by this crate
 #[derive(Error, Debug)]
 pub enum ClientError {
+    #[error("API response wrapper contained an error")]
+    ApiError { message: String },
+    #[error("Request to {url} blocked: {reason}")]
+    BlockedUrlError { url: String, reason: String },
+    #[error("Request did not complete within its deadline of {budget:?}")]
+    DeadlineError { budget: std::time::Duration },
+    #[error("Operation {operation} is disabled: {reason}")]
+    DisabledError { operation: String, reason: String },
     #[error("Error parsing endpoint into data")]
     DataParseError { source: anyhow::Error },
     #[error("Error building endpoint request")]
     EndpointBuildError { source: anyhow::Error },
     #[error("An error occurred in processing the request")]
     GenericError { source: anyhow::Error },
+    #[error("Invalid value for header {name}")]
+    HeaderError { source: anyhow::Error, name: String },
+    #[error("Middleware layer {layer} failed")]
+    Middleware {
+        layer: String,
+        source: Box<ClientError>,
+    },
+    #[error("Middleware failed to process the request")]
+    MiddlewareError { source: anyhow::Error },
+    #[error("Middleware panicked: {message}")]
+    MiddlewarePanic { message: String },
+    #[error("No endpoint registered for operation {name}")]
+    OperationError { name: String },
+    #[error("Health check of {url} failed: {diagnosis}")]
+    ProbeError {
+        source: anyhow::Error,
+        url: String,
+        diagnosis: String,
+    },
+    #[error("Client profile not found: {name}")]
+    ProfileError { name: String },
+    #[error("Error configuring proxy {url}")]
+    ProxyError { source: anyhow::Error, url: String },
     #[error("Error sending HTTP request")]
     RequestError {
         source: anyhow::Error,
@@ -33,13 +64,36 @@ pub enum ClientError {
         source: anyhow::Error,
         content: Vec<u8>,
     },
+    #[error("Error determining response variant from discriminator")]
+    ResponseDiscriminatorError {
+        field: String,
+        value: Option<String>,
+        content: Option<String>,
+    },
+    #[error("Error deserializing HTTP response at line {line}, column {column}: `{excerpt}`")]
+    ResponseDeserializeError {
+        source: serde_json::Error,
+        line: usize,
+        column: usize,
+        excerpt: String,
+        content: Option<String>,
+    },
     #[error("Error parsing HTTP response")]
     ResponseParseError {
         source: anyhow::Error,
         content: Option<String>,
     },
+    #[error("Server returned an unexpected status code")]
+    ResponseStatusError { code: u16, content: Option<String> },
+    #[error("Token is missing scopes required by {operation}: {}", missing.join(", "))]
+    ScopeError {
+        operation: String,
+        missing: Vec<String>,
+    },
     #[error("Server returned error")]
     ServerResponseError { code: u16, content: Option<String> },
+    #[error("Client is shutting down")]
+    ShutdownError,
     #[error("Error building URL")]
     UrlBuildError { source: http::uri::InvalidUri },
     #[error("Error serializing URL query parameters")]
@@ -47,3 +101,205 @@ pub enum ClientError {
     #[error("Error parsing URL")]
     UrlParseError { source: url::ParseError },
 }
+
+/// The broad category of a [ClientError].
+///
+/// Matching on the kind of an error is more stable than matching on
+/// individual variants, which may be added over time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The request could not be built from the endpoint or configuration
+    Build,
+    /// The request could not be sent or the response could not be received
+    Transport,
+    /// The server responded with an unsuccessful status code
+    Http { status: u16 },
+    /// The response body could not be parsed into the response type
+    Deserialization,
+    /// A middleware layer failed
+    Middleware,
+    /// The API reported an error inside an otherwise successful response
+    Api,
+    /// Any other error
+    Other,
+}
+
+impl ClientError {
+    /// Returns the [ErrorKind] of this error.
+    ///
+    /// A [ClientError::ProbeError] has the kind of the error which caused the
+    /// probe to fail, and a [ClientError::Middleware] has the kind of the
+    /// error returned by the middleware layer.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ClientError::ApiError { .. } => ErrorKind::Api,
+            ClientError::BlockedUrlError { .. }
+            | ClientError::DataParseError { .. }
+            | ClientError::DisabledError { .. }
+            | ClientError::EndpointBuildError { .. }
+            | ClientError::HeaderError { .. }
+            | ClientError::OperationError { .. }
+            | ClientError::ProfileError { .. }
+            | ClientError::ProxyError { .. }
+            | ClientError::RequestBuildError { .. }
+            | ClientError::ReqwestBuildError { .. }
+            | ClientError::ScopeError { .. }
+            | ClientError::UrlBuildError { .. }
+            | ClientError::UrlQueryParseError { .. }
+            | ClientError::UrlParseError { .. } => ErrorKind::Build,
+            ClientError::GenericError { .. } | ClientError::ShutdownError => ErrorKind::Other,
+            ClientError::Middleware { source, .. } => source.kind(),
+            ClientError::MiddlewareError { .. } | ClientError::MiddlewarePanic { .. } => {
+                ErrorKind::Middleware
+            }
+            ClientError::ProbeError { source, .. } => source
+                .downcast_ref::<ClientError>()
+                .map(ClientError::kind)
+                .unwrap_or(ErrorKind::Build),
+            ClientError::DeadlineError { .. }
+            | ClientError::RequestError { .. }
+            | ClientError::ResponseError { .. } => ErrorKind::Transport,
+            ClientError::ResponseConversionError { .. }
+            | ClientError::ResponseDiscriminatorError { .. }
+            | ClientError::ResponseDeserializeError { .. }
+            | ClientError::ResponseParseError { .. } => ErrorKind::Deserialization,
+            ClientError::ResponseStatusError { code, .. }
+            | ClientError::ServerResponseError { code, .. } => ErrorKind::Http { status: *code },
+        }
+    }
+
+    /// Returns the status code of the response, if the server responded with
+    /// an unsuccessful status code.
+    pub fn status(&self) -> Option<u16> {
+        match self.kind() {
+            ErrorKind::Http { status } => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Returns the names of the [named][crate::endpoint::MiddleWare::named]
+    /// middleware layers this error was returned through, from the outermost
+    /// to the layer which failed.
+    pub fn layers(&self) -> Vec<&str> {
+        let mut layers = Vec::new();
+        let mut error = self;
+        while let ClientError::Middleware { layer, source } = error {
+            layers.push(layer.as_str());
+            error = source;
+        }
+        layers
+    }
+
+    /// Returns the error returned by the innermost middleware layer, or this
+    /// error if it wasn't returned by a named layer.
+    pub fn root(&self) -> &ClientError {
+        let mut error = self;
+        while let ClientError::Middleware { source, .. } = error {
+            error = source;
+        }
+        error
+    }
+
+    /// Returns whether the request failed because the connection it was sent
+    /// on was closed by the other side, i.e. a connection reset or broken
+    /// pipe on an idle keep-alive connection.
+    pub fn is_stale_connection(&self) -> bool {
+        let source = match self {
+            ClientError::RequestError { source, .. } => source,
+            _ => return false,
+        };
+        source.chain().any(|e| {
+            let closed = e.downcast_ref::<std::io::Error>().map(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::BrokenPipe
+                        | std::io::ErrorKind::UnexpectedEof
+                )
+            });
+            // Raised by hyper when the connection closes before a response
+            closed.unwrap_or_else(|| {
+                e.to_string()
+                    .contains("connection closed before message completed")
+            })
+        })
+    }
+}
+
+#[cfg(feature = "diagnostics")]
+impl miette::Diagnostic for ClientError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let code = match self.kind() {
+            ErrorKind::Build => "rustify::build",
+            ErrorKind::Transport => "rustify::transport",
+            ErrorKind::Http { .. } => "rustify::http",
+            ErrorKind::Deserialization => "rustify::deserialization",
+            ErrorKind::Middleware => "rustify::middleware",
+            ErrorKind::Api => "rustify::api",
+            ErrorKind::Other => "rustify::other",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let help = match self {
+            ClientError::ApiError { message } => format!("the API returned: {}", message),
+            ClientError::ProbeError { diagnosis, .. } => diagnosis.clone(),
+            ClientError::RequestError { method, url, .. } => {
+                format!("the {} request to {} could not be sent", method, url)
+            }
+            ClientError::ResponseDeserializeError { .. }
+            | ClientError::ResponseParseError { .. } => {
+                "the response body doesn't match the expected response type".to_string()
+            }
+            _ => match self.status() {
+                Some(status) => format!("the server responded with status {}", status),
+                None => return None,
+            },
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn miette::SourceCode> {
+        match self {
+            ClientError::ResponseDeserializeError { content, .. }
+            | ClientError::ResponseParseError { content, .. }
+            | ClientError::ResponseStatusError { content, .. }
+            | ClientError::ServerResponseError { content, .. } => {
+                content.as_ref().map(|c| c as &dyn miette::SourceCode)
+            }
+            _ => None,
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = miette::LabeledSpan> + '_>> {
+        let (source, content) = match self {
+            ClientError::ResponseDeserializeError {
+                source,
+                content: Some(content),
+                ..
+            } => (source, content),
+            ClientError::ResponseParseError {
+                source,
+                content: Some(content),
+            } => (source.downcast_ref::<serde_json::Error>()?, content),
+            _ => return None,
+        };
+        if source.line() == 0 {
+            return None;
+        }
+
+        // Lines and columns are one-based, convert them into a byte offset
+        let line: usize = content
+            .split_inclusive('\n')
+            .take(source.line() - 1)
+            .map(str::len)
+            .sum();
+        let offset = (line + source.column().saturating_sub(1)).min(content.len());
+        let len = usize::from(offset < content.len());
+        let label = miette::LabeledSpan::new(Some(source.to_string()), offset, len);
+        Some(Box::new(std::iter::once(label)))
+    }
+}
@@ -0,0 +1,212 @@
+//! Contains the typed [Event]s emitted while executing
+//! [Endpoints][crate::endpoint::Endpoint] and the [EventSink] which receives
+//! them.
+
+use std::{fmt, sync::Arc, time::Duration};
+
+use crate::errors::ClientError;
+use http::{Method, Response, Uri};
+
+/// The name of the [Endpoint][crate::endpoint::Endpoint] a request was built
+/// from, as returned by
+/// [Endpoint::name][crate::endpoint::Endpoint::name].
+///
+/// This is stored in the extensions of every request built by an endpoint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Operation(pub &'static str);
+
+impl Operation {
+    /// Returns the operation stored in the given extensions, if any.
+    pub fn of(extensions: &http::Extensions) -> Option<&'static str> {
+        extensions.get::<Operation>().map(|o| o.0)
+    }
+}
+
+/// The 1-based number of the attempt a request belongs to.
+///
+/// Code which retries requests should insert this into the extensions of each
+/// retried request. Requests without an [Attempt] are treated as the first
+/// attempt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Attempt(pub u32);
+
+impl Attempt {
+    /// Returns the attempt number stored in the given extensions, defaulting
+    /// to 1.
+    pub fn of(extensions: &http::Extensions) -> u32 {
+        extensions.get::<Attempt>().map(|a| a.0).unwrap_or(1)
+    }
+}
+
+/// The sizes of the bodies sent and received for a request.
+///
+/// This is stored in the extensions of every response returned by the
+/// default `execute` implementation of both [Client][crate::client::Client]
+/// traits. When the response was compressed, `response` is the compressed
+/// size as received and `decoded` is the uncompressed size if the body was
+/// decoded before being returned.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BodySizes {
+    /// The size of the request body in bytes
+    pub request: usize,
+    /// The size of the response body in bytes as received
+    pub response: usize,
+    /// The `Content-Encoding` of the response, if any
+    pub encoding: Option<String>,
+    /// The size of the response body in bytes after decoding, if it was
+    /// received compressed and decoded
+    pub decoded: Option<usize>,
+}
+
+impl BodySizes {
+    /// Returns the sizes of the given request body length and response.
+    ///
+    /// Responses which still carry a `Content-Encoding` weren't decoded, so
+    /// their body length is the compressed size.
+    pub fn new<T: AsRef<[u8]>>(request: usize, response: &Response<T>) -> Self {
+        let encoding = response
+            .headers()
+            .get(http::header::CONTENT_ENCODING)
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.eq_ignore_ascii_case("identity"))
+            .map(str::to_string);
+        BodySizes {
+            request,
+            response: response.body().as_ref().len(),
+            encoding,
+            decoded: None,
+        }
+    }
+
+    /// Returns the sizes stored in the given extensions, if any.
+    pub fn of(extensions: &http::Extensions) -> Option<&BodySizes> {
+        extensions.get::<BodySizes>()
+    }
+}
+
+/// An event which occurred while executing an
+/// [Endpoint][crate::endpoint::Endpoint].
+///
+/// The `operation` of each event is the name of the endpoint, if the request
+/// was built by one.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// A request is about to be sent
+    RequestStarted {
+        operation: Option<&'static str>,
+        attempt: u32,
+        method: &'a Method,
+        url: &'a Uri,
+    },
+    /// A failed request will be retried after a delay
+    RetryScheduled {
+        operation: Option<&'static str>,
+        attempt: u32,
+        delay: Duration,
+    },
+    /// A response was successfully parsed into the endpoint's response type
+    ResponseParsed {
+        operation: Option<&'static str>,
+        status: u16,
+    },
+    /// A request was admitted by a limiter after waiting behind the given
+    /// number of queued requests for the given time
+    Admitted {
+        operation: Option<&'static str>,
+        limiter: Limiter,
+        queued: usize,
+        waited: Duration,
+    },
+    /// Sending a request or parsing its response failed
+    Failed {
+        operation: Option<&'static str>,
+        error: &'a ClientError,
+    },
+}
+
+/// A limiter which may queue requests before they're sent, as reported by
+/// [Event::Admitted].
+///
+/// Every request passing through a configured limiter is reported, including
+/// those admitted without waiting, so the time spent in each queue can be
+/// recorded as a histogram. Operators can tell the client is the bottleneck,
+/// not the server, when requests spend significant time queued.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Limiter {
+    /// The [ConcurrencyLimit][crate::limit::ConcurrencyLimit]
+    Concurrency,
+    /// The [Scheduler][crate::scheduler::Scheduler]
+    Scheduler,
+    /// The [AdaptiveLimit][crate::adaptive::AdaptiveLimit]
+    Adaptive,
+    /// A [TokenBucket][crate::bucket::TokenBucket] of the
+    /// [RateLimiter][crate::bucket::RateLimiter]
+    RateLimit,
+}
+
+/// Receives the [Event]s emitted by a [Client][crate::client::Client].
+///
+/// Sinks are invoked synchronously and should avoid blocking. Any type
+/// implementing `Fn(&Event)` can be used as a sink.
+pub trait EventSink: Send + Sync {
+    /// Handles the given event.
+    fn emit(&self, event: &Event<'_>);
+}
+
+impl<F> EventSink for F
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    fn emit(&self, event: &Event<'_>) {
+        self(event)
+    }
+}
+
+/// A shared handle to an [EventSink].
+///
+/// This is configured using
+/// [ClientConfig::event_sink][crate::config::ClientConfig::event_sink] and is
+/// stored in the extensions of every response so that parsing the response
+/// also emits events.
+#[derive(Clone)]
+pub struct Events {
+    sink: Arc<dyn EventSink>,
+}
+
+impl Events {
+    /// Returns a new [Events] which emits to the given sink.
+    pub fn new(sink: impl EventSink + 'static) -> Self {
+        Events {
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Emits the given event to the sink.
+    pub fn emit(&self, event: &Event<'_>) {
+        self.sink.emit(event)
+    }
+
+    /// Emits the outcome of parsing a [Response] to the [Events] stored in its
+    /// extensions, if any.
+    pub(crate) fn parsed<T, B>(resp: &Response<B>, result: &Result<T, ClientError>) {
+        let events = match resp.extensions().get::<Events>() {
+            Some(e) => e,
+            None => return,
+        };
+        let operation = Operation::of(resp.extensions());
+        match result {
+            Ok(_) => events.emit(&Event::ResponseParsed {
+                operation,
+                status: resp.status().as_u16(),
+            }),
+            Err(error) => events.emit(&Event::Failed { operation, error }),
+        }
+    }
+}
+
+impl fmt::Debug for Events {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events").finish()
+    }
+}
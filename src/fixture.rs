@@ -0,0 +1,394 @@
+//! Contains the [Fixture] type, a versioned file format for recording
+//! interactions with a server and replaying them in tests.
+//!
+//! Interactions are recorded by wrapping a client in a [Recorder] and
+//! replayed by a [MockClient] created with [MockClient::from_fixture]. Saved
+//! fixtures are pretty-printed JSON with sorted headers, so they diff cleanly
+//! when checked in:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "interactions": [
+//!     {
+//!       "request": {
+//!         "method": "GET",
+//!         "url": "http://api.example.com/users",
+//!         "headers": {},
+//!         "body": { "text": "" }
+//!       },
+//!       "response": {
+//!         "status": 200,
+//!         "headers": { "content-type": ["application/json"] },
+//!         "body": { "text": "[\"jane\"]" }
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Bodies which aren't valid UTF-8 are stored as `{ "base64": "..." }`.
+//! Recorders configured with [Recorder::compress_bodies] store large bodies
+//! gzip compressed as `{ "gzip": "..." }` instead, keeping fixtures of large
+//! responses small. They're decompressed transparently when replayed.
+//!
+//! # Versioning
+//!
+//! Every fixture records the [FIXTURE_VERSION] it was written with. When the
+//! format changes, the version is bumped and a migration from the previous
+//! version is added, so fixtures written by older releases are upgraded
+//! transparently when loaded and never have to be re-recorded. Fixtures
+//! written by newer releases are rejected instead of being misread.
+//!
+//! This module requires the `mock` feature.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::{clients::reqwest::Client, fixture::{Fixture, Recorder}, mock::MockClient};
+//!
+//! # tokio_test::block_on(async {
+//! // Record once against the real server...
+//! let recorder = Recorder::new(Client::default("http://api.example.com"));
+//! // ...execute endpoints using the recorder...
+//! recorder.fixture().save("tests/fixtures/users.json").unwrap();
+//!
+//! // ...then replay the recorded responses in tests
+//! let fixture = Fixture::load("tests/fixtures/users.json").unwrap();
+//! let client = MockClient::from_fixture("http://api.example.com", &fixture).unwrap();
+//! # });
+//! ```
+//!
+//! [MockClient]: crate::mock::MockClient
+//! [MockClient::from_fixture]: crate::mock::MockClient::from_fixture
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{client::Client, config::ClientConfig, errors::ClientError};
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use http::{HeaderMap, HeaderName, Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The version of the fixture format written by this release.
+pub const FIXTURE_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+/// Upgrades a fixture from the version at its index plus one to the next
+/// version. New formats are introduced by appending a migration here.
+const MIGRATIONS: [fn(Value) -> Value; 1] = [
+    // Version 2 adds gzip compressed bodies, which version 1 fixtures never
+    // contain
+    |v| v,
+];
+
+/// A recorded sequence of interactions with a server.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fixture {
+    pub version: u32,
+    pub interactions: Vec<Interaction>,
+}
+
+/// A single request and the response it received.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub request: RecordedRequest,
+    pub response: RecordedResponse,
+}
+
+/// A recorded request.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub url: String,
+    pub headers: BTreeMap<String, Vec<String>>,
+    pub body: Body,
+}
+
+/// A recorded response.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub status: u16,
+    pub headers: BTreeMap<String, Vec<String>>,
+    pub body: Body,
+}
+
+/// A recorded body.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Body {
+    /// A body which is valid UTF-8
+    Text(String),
+    /// Any other body, encoded with standard base64
+    Base64(String),
+    /// A gzip compressed body, encoded with standard base64
+    Gzip(String),
+}
+
+impl Body {
+    /// Returns a [Body] holding the given bytes.
+    pub fn new(bytes: &[u8]) -> Self {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Body::Text(s.to_string()),
+            Err(_) => Body::Base64(STANDARD.encode(bytes)),
+        }
+    }
+
+    /// Returns a [Body] holding the given bytes gzip compressed.
+    pub fn gzip(bytes: &[u8]) -> Result<Self, ClientError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(bytes)
+            .and_then(|_| encoder.finish())
+            .map(|data| Body::Gzip(STANDARD.encode(data)))
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+
+    /// Returns the bytes of the body, decompressing it if needed.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ClientError> {
+        match self {
+            Body::Text(s) => Ok(s.as_bytes().to_vec()),
+            Body::Base64(s) => STANDARD
+                .decode(s)
+                .map_err(|e| ClientError::GenericError { source: e.into() }),
+            Body::Gzip(s) => {
+                let data = STANDARD
+                    .decode(s)
+                    .map_err(|e| ClientError::GenericError { source: e.into() })?;
+                let mut body = Vec::new();
+                GzDecoder::new(data.as_slice())
+                    .read_to_end(&mut body)
+                    .map_err(|e| ClientError::GenericError { source: e.into() })?;
+                Ok(body)
+            }
+        }
+    }
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Fixture {
+            version: FIXTURE_VERSION,
+            interactions: Vec::new(),
+        }
+    }
+}
+
+impl Fixture {
+    /// Returns a new, empty [Fixture].
+    pub fn new() -> Self {
+        Fixture::default()
+    }
+
+    /// Parses a fixture, migrating it from an older version if needed.
+    pub fn from_slice(data: &[u8]) -> Result<Self, ClientError> {
+        let value: Value = serde_json::from_slice(data)
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        serde_json::from_value(migrate(value)?)
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+
+    /// Serializes the fixture to pretty-printed JSON.
+    pub fn to_vec(&self) -> Result<Vec<u8>, ClientError> {
+        serde_json::to_vec_pretty(self).map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+
+    /// Reads a fixture from the file at the given path, see
+    /// [Fixture::from_slice].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ClientError> {
+        let data =
+            std::fs::read(path).map_err(|e| ClientError::GenericError { source: e.into() })?;
+        Fixture::from_slice(&data)
+    }
+
+    /// Writes the fixture to the file at the given path.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ClientError> {
+        std::fs::write(path, self.to_vec()?)
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+/// Upgrades the given fixture to the current [FIXTURE_VERSION].
+fn migrate(mut value: Value) -> Result<Value, ClientError> {
+    let version = value
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| ClientError::GenericError {
+            source: anyhow::anyhow!("Fixture is missing its format version"),
+        })?;
+    if version == 0 || version > FIXTURE_VERSION as u64 {
+        return Err(ClientError::GenericError {
+            source: anyhow::anyhow!(
+                "Unsupported fixture version {}, this release reads versions up to {}",
+                version,
+                FIXTURE_VERSION
+            ),
+        });
+    }
+    for migration in MIGRATIONS.iter().skip(version as usize - 1) {
+        value = migration(value);
+    }
+    value["version"] = Value::from(FIXTURE_VERSION);
+    Ok(value)
+}
+
+fn record_headers(headers: &HeaderMap) -> BTreeMap<String, Vec<String>> {
+    let mut recorded = BTreeMap::<String, Vec<String>>::new();
+    for (name, value) in headers {
+        recorded
+            .entry(name.to_string())
+            .or_default()
+            .push(String::from_utf8_lossy(value.as_bytes()).into_owned());
+    }
+    recorded
+}
+
+impl RecordedResponse {
+    /// Returns the headers of the response.
+    pub fn header_map(&self) -> Result<HeaderMap, ClientError> {
+        let mut headers = HeaderMap::new();
+        for (name, values) in &self.headers {
+            for value in values {
+                let (name, value) = crate::http::build_header(name, value)?;
+                headers.append::<HeaderName>(name, value);
+            }
+        }
+        Ok(headers)
+    }
+}
+
+fn interaction(req: &Request<Vec<u8>>, resp: &Response<Vec<u8>>) -> Interaction {
+    Interaction {
+        request: RecordedRequest {
+            method: req.method().to_string(),
+            url: req.uri().to_string(),
+            headers: record_headers(req.headers()),
+            body: Body::new(req.body()),
+        },
+        response: RecordedResponse {
+            status: resp.status().as_u16(),
+            headers: record_headers(resp.headers()),
+            body: Body::new(resp.body()),
+        },
+    }
+}
+
+/// A [Client] which wraps another [Client] and records every request it
+/// sends along with the response, see the [module documentation][self].
+///
+/// Requests which fail without a response aren't recorded. Headers carrying
+/// credentials (`Authorization`, `Cookie`, `Set-Cookie`, and
+/// `Proxy-Authorization`) are recorded as `<redacted>`.
+#[derive(Debug)]
+pub struct Recorder<C> {
+    pub client: C,
+    compress: Option<usize>,
+    fixture: Mutex<Fixture>,
+}
+
+impl<C> Recorder<C> {
+    /// Returns a new [Recorder] which sends requests using the given client.
+    pub fn new(client: C) -> Self {
+        Recorder {
+            client,
+            compress: None,
+            fixture: Mutex::new(Fixture::new()),
+        }
+    }
+
+    /// Stores bodies of at least the given number of bytes gzip compressed.
+    pub fn compress_bodies(mut self, min_bytes: usize) -> Self {
+        self.compress = Some(min_bytes);
+        self
+    }
+
+    /// Returns a copy of the interactions recorded so far.
+    pub fn fixture(&self) -> Fixture {
+        self.fixture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    fn record(&self, req: &Request<Vec<u8>>, resp: &Response<Vec<u8>>) {
+        let mut interaction = interaction(req, resp);
+        if let Some(min) = self.compress {
+            for (body, bytes) in [
+                (&mut interaction.request.body, req.body()),
+                (&mut interaction.response.body, resp.body()),
+            ] {
+                if bytes.len() >= min {
+                    match Body::gzip(bytes) {
+                        Ok(b) => *body = b,
+                        Err(e) => warn!("Failed compressing recorded body: {}", e),
+                    }
+                }
+            }
+        }
+        for headers in [
+            &mut interaction.request.headers,
+            &mut interaction.response.headers,
+        ] {
+            for name in [
+                "authorization",
+                "cookie",
+                "proxy-authorization",
+                "set-cookie",
+            ] {
+                if let Some(values) = headers.get_mut(name) {
+                    values
+                        .iter_mut()
+                        .for_each(|v| *v = "<redacted>".to_string());
+                }
+            }
+        }
+        self.fixture
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .interactions
+            .push(interaction);
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Recorder<C> {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let sent = crate::http::copy_request(&req);
+        let resp = self.client.send(req).await?;
+        self.record(&sent, &resp);
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<C: BlockingClient> BlockingClient for Recorder<C> {
+    fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let sent = crate::http::copy_request(&req);
+        let resp = self.client.send(req)?;
+        self.record(&sent, &resp);
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
@@ -0,0 +1,204 @@
+//! Contains the [Gatekeeper] trait for disabling or rerouting operations at
+//! runtime.
+//!
+//! A gatekeeper configured with
+//! [ClientConfig::gatekeeper][crate::config::ClientConfig::gatekeeper] is
+//! consulted before every request is sent, with the name of the operation it
+//! belongs to. This is the `operation_id` of the endpoint if it has one and
+//! its [name][crate::endpoint::Endpoint::name] otherwise. Denied requests
+//! fail with a [ClientError::DisabledError] without being sent, and rerouted
+//! requests are sent to a different base URL, i.e. a fallback region during
+//! an upstream incident.
+//!
+//! The [KillSwitch] gatekeeper keeps the disabled and rerouted operations in
+//! state shared by all of its clones, so they can be changed while the client
+//! is in use, for example from a feature flag service or an admin endpoint.
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, gate::KillSwitch};
+//!
+//! let switch = KillSwitch::new();
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().gatekeeper(switch.clone()));
+//!
+//! // During an incident...
+//! switch.disable("createPayment", "Payments are paused during maintenance");
+//! switch.reroute("listPayments", "https://eu.api.example.com".parse().unwrap());
+//!
+//! // ...and once it's resolved
+//! switch.reset();
+//! ```
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use http::{uri::PathAndQuery, Request, Uri};
+
+use crate::{errors::ClientError, events::Operation, metadata::EndpointMetadata};
+
+/// The decision of a [Gatekeeper] about a single request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Send the request as usual
+    Allow,
+    /// Fail the request with the given reason without sending it
+    Deny(String),
+    /// Send the request to the given base URL, keeping its path and query
+    Reroute(Uri),
+}
+
+/// Decides whether requests are sent, see the [module documentation][self].
+///
+/// Any type implementing `Fn(&str, &Request<Vec<u8>>) -> Decision` can be
+/// used as a gatekeeper.
+pub trait Gatekeeper: Send + Sync {
+    /// Returns the [Decision] for the given request of the given operation.
+    fn check(&self, operation: &str, req: &Request<Vec<u8>>) -> Decision;
+}
+
+impl<F> Gatekeeper for F
+where
+    F: Fn(&str, &Request<Vec<u8>>) -> Decision + Send + Sync,
+{
+    fn check(&self, operation: &str, req: &Request<Vec<u8>>) -> Decision {
+        self(operation, req)
+    }
+}
+
+/// A shared handle to a [Gatekeeper].
+#[derive(Clone)]
+pub struct Gate {
+    gatekeeper: Arc<dyn Gatekeeper>,
+}
+
+impl Gate {
+    /// Returns a new [Gate] which consults the given gatekeeper.
+    pub fn new(gatekeeper: impl Gatekeeper + 'static) -> Self {
+        Gate {
+            gatekeeper: Arc::new(gatekeeper),
+        }
+    }
+
+    /// Consults the gatekeeper about the given request, rerouting it if
+    /// asked to and failing with a [ClientError::DisabledError] if it's
+    /// denied.
+    pub fn apply(&self, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let operation = EndpointMetadata::of(req.extensions())
+            .operation_id
+            .or_else(|| Operation::of(req.extensions()))
+            .unwrap_or_default();
+        match self.gatekeeper.check(operation, req) {
+            Decision::Allow => Ok(()),
+            Decision::Deny(reason) => Err(ClientError::DisabledError {
+                operation: operation.to_string(),
+                reason,
+            }),
+            Decision::Reroute(base) => {
+                *req.uri_mut() = reroute(&base, req.uri())?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Gate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gate").finish()
+    }
+}
+
+/// Returns the given [Uri] with its scheme and authority replaced by those of
+/// the given base, and its path prefixed with the path of the base.
+fn reroute(base: &Uri, uri: &Uri) -> Result<Uri, ClientError> {
+    let prefix = base.path().trim_end_matches('/');
+    let path = uri
+        .path_and_query()
+        .map(PathAndQuery::as_str)
+        .unwrap_or("/");
+    let rerouted = format!(
+        "{}://{}{}{}",
+        base.scheme_str().unwrap_or("https"),
+        base.authority().map(|a| a.as_str()).unwrap_or_default(),
+        prefix,
+        path
+    );
+    rerouted
+        .parse::<Uri>()
+        .map_err(|e| ClientError::UrlBuildError { source: e })
+}
+
+#[derive(Debug, Default)]
+struct Switches {
+    disabled: HashMap<String, String>,
+    rerouted: HashMap<String, Uri>,
+}
+
+/// A [Gatekeeper] which disables or reroutes operations by name.
+///
+/// All clones share the same state, so changes are visible to every client
+/// configured with one of them.
+#[derive(Clone, Debug, Default)]
+pub struct KillSwitch {
+    switches: Arc<RwLock<Switches>>,
+}
+
+impl KillSwitch {
+    /// Returns a new [KillSwitch] which allows every operation.
+    pub fn new() -> Self {
+        KillSwitch::default()
+    }
+
+    /// Denies requests of the given operation with the given reason.
+    pub fn disable(&self, operation: &str, reason: &str) {
+        self.write()
+            .disabled
+            .insert(operation.to_string(), reason.to_string());
+    }
+
+    /// Sends requests of the given operation to the given base URL.
+    pub fn reroute(&self, operation: &str, base: Uri) {
+        self.write().rerouted.insert(operation.to_string(), base);
+    }
+
+    /// Allows and stops rerouting requests of the given operation.
+    pub fn enable(&self, operation: &str) {
+        let mut switches = self.write();
+        switches.disabled.remove(operation);
+        switches.rerouted.remove(operation);
+    }
+
+    /// Allows and stops rerouting requests of every operation.
+    pub fn reset(&self) {
+        *self.write() = Switches::default();
+    }
+
+    /// Returns whether requests of the given operation are denied.
+    pub fn is_disabled(&self, operation: &str) -> bool {
+        self.read().disabled.contains_key(operation)
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Switches> {
+        self.switches.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Switches> {
+        self.switches.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Gatekeeper for KillSwitch {
+    fn check(&self, operation: &str, _: &Request<Vec<u8>>) -> Decision {
+        let switches = self.read();
+        if let Some(reason) = switches.disabled.get(operation) {
+            return Decision::Deny(reason.clone());
+        }
+        match switches.rerouted.get(operation) {
+            Some(base) => Decision::Reroute(base.clone()),
+            None => Decision::Allow,
+        }
+    }
+}
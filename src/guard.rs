@@ -0,0 +1,201 @@
+//! Contains the [UrlGuard] type for restricting which URLs a
+//! [Client][crate::client::Client] may send requests to.
+//!
+//! A guard is useful when parts of a request URL, i.e. the base URL or a path
+//! segment, come from user input in server-side code, where an attacker could
+//! otherwise direct requests at internal services (SSRF).
+//!
+//! When configured with [ClientConfig::url_guard][crate::config::ClientConfig::url_guard]
+//! every request is checked before it's sent. The guard only sees the host
+//! named in the URL, so hosts which resolve to private addresses and
+//! redirects are only caught by also installing the guard as the DNS resolver
+//! and redirect policy of the backing reqwest client. Creating the client with
+//! `Client::from_builder` ensures the clients derived for proxies and
+//! connection timeouts use them too.
+//!
+//! # Example
+//! ```
+//! use std::sync::Arc;
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, guard::UrlGuard};
+//!
+//! let guard = UrlGuard::new()
+//!     .allow_scheme("https")
+//!     .allow_host("*.example.com")
+//!     .block_private_ips();
+//! let resolver = guard.clone();
+//! let client = Client::from_builder("https://api.example.com", move || {
+//!     reqwest::Client::builder()
+//!         .dns_resolver(Arc::new(resolver.clone()))
+//!         .redirect(resolver.redirect_policy())
+//! })
+//! .unwrap()
+//! .with_config(ClientConfig::new().url_guard(guard));
+//! ```
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+
+use crate::errors::ClientError;
+use http::Uri;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Restricts requests to the allowed schemes and hosts and optionally blocks
+/// private, loopback, and link-local IP addresses.
+///
+/// A new guard allows `http` and `https` URLs to any host. Once a scheme or
+/// host is explicitly allowed only the explicitly allowed values pass.
+#[derive(Clone, Debug, Default)]
+pub struct UrlGuard {
+    schemes: Vec<String>,
+    hosts: Vec<String>,
+    block_private: bool,
+}
+
+impl UrlGuard {
+    /// Returns a new [UrlGuard] which allows `http` and `https` URLs to any
+    /// host.
+    pub fn new() -> Self {
+        UrlGuard::default()
+    }
+
+    /// Allows URLs using the given scheme.
+    pub fn allow_scheme(mut self, scheme: &str) -> Self {
+        self.schemes.push(scheme.to_ascii_lowercase());
+        self
+    }
+
+    /// Allows URLs to the given host. A leading `*.` matches any subdomain,
+    /// i.e. `*.example.com` matches `api.example.com` but not `example.com`.
+    pub fn allow_host(mut self, host: &str) -> Self {
+        self.hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    /// Blocks hosts which are, or resolve to, private, loopback, link-local,
+    /// or otherwise non-public IP addresses.
+    pub fn block_private_ips(mut self) -> Self {
+        self.block_private = true;
+        self
+    }
+
+    /// Checks the given [Uri] is allowed, returning a
+    /// [ClientError::BlockedUrlError] if it isn't.
+    pub fn check(&self, uri: &Uri) -> Result<(), ClientError> {
+        let blocked = |reason: String| ClientError::BlockedUrlError {
+            url: uri.to_string(),
+            reason,
+        };
+        let scheme = uri.scheme_str().unwrap_or_default().to_ascii_lowercase();
+        let allowed = match self.schemes.is_empty() {
+            true => scheme == "http" || scheme == "https",
+            false => self.schemes.contains(&scheme),
+        };
+        if !allowed {
+            return Err(blocked(format!("scheme `{}` is not allowed", scheme)));
+        }
+
+        let host = uri.host().unwrap_or_default().to_ascii_lowercase();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if !self.hosts.is_empty() && !self.hosts.iter().any(|h| matches_host(h, host)) {
+            return Err(blocked(format!("host `{}` is not allowed", host)));
+        }
+        if self.block_private {
+            let private = match host.parse::<IpAddr>() {
+                Ok(ip) => !is_public(ip),
+                Err(_) => host == "localhost" || host.ends_with(".localhost"),
+            };
+            if private {
+                return Err(blocked(format!("host `{}` is not a public address", host)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a reqwest redirect policy which follows up to 10 redirects and
+    /// stops at any redirect to a URL this guard blocks.
+    pub fn redirect_policy(&self) -> reqwest::redirect::Policy {
+        let guard = self.clone();
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= 10 {
+                return attempt.error("too many redirects");
+            }
+            let uri = attempt.url().as_str().parse::<Uri>();
+            match uri.map(|u| guard.check(&u)) {
+                Ok(Ok(())) => attempt.follow(),
+                Ok(Err(e)) => attempt.error(e),
+                Err(e) => attempt.error(e),
+            }
+        })
+    }
+}
+
+/// Resolves hosts using the system resolver, failing if private IP addresses
+/// are blocked and any resolved address is private.
+impl Resolve for UrlGuard {
+    fn resolve(&self, name: Name) -> Resolving {
+        let block_private = self.block_private;
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs = tokio::task::spawn_blocking(move || {
+                (host.as_str(), 0)
+                    .to_socket_addrs()
+                    .map(|a| a.collect::<Vec<_>>())
+            })
+            .await??;
+            if block_private && addrs.iter().any(|a| !is_public(a.ip())) {
+                return Err(format!("{} resolves to a non-public address", name.as_str()).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+/// Returns whether the given host matches an allowed host pattern.
+fn matches_host(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .map(|h| h.ends_with('.'))
+            .unwrap_or(false),
+        None => pattern == host,
+    }
+}
+
+/// Returns whether the given address is publicly routable.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || a == 0
+        // Shared address space (RFC 6598)
+        || (a == 100 && (64..128).contains(&b))
+        // Benchmarking (RFC 2544)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local (fc00::/7)
+        || (first & 0xfe00) == 0xfc00
+        // Link-local (fe80::/10)
+        || (first & 0xffc0) == 0xfe80
+        // Documentation (2001:db8::/32)
+        || (first == 0x2001 && ip.segments()[1] == 0xdb8))
+}
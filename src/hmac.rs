@@ -0,0 +1,236 @@
+//! Contains the [HmacSigner] middleware for signing requests with an
+//! HMAC-SHA256 signature over a configurable canonical string.
+//!
+//! Many payment and webhook APIs authenticate requests with a shared secret
+//! by signing a string built from parts of the request, such as the method,
+//! path, body, and a timestamp, and sending the result in a header. The parts
+//! and their order differ between vendors, so the canonical string is built
+//! from a list of [Components][Component] joined with a separator. By default
+//! it contains the method, path, body, and timestamp separated by newlines,
+//! and the signature is sent hex encoded.
+//!
+//! The timestamp is the number of seconds since the Unix epoch at which the
+//! request was signed. It's sent in its own header when one is configured
+//! with [HmacSigner::timestamp_header], so the server can rebuild the
+//! canonical string.
+//!
+//! # Example
+//! ```
+//! use http::HeaderName;
+//! use rustify::{
+//!     clients::reqwest::Client,
+//!     config::ClientConfig,
+//!     hmac::{Component, HmacSigner},
+//! };
+//!
+//! let signer = HmacSigner::new(b"secret", HeaderName::from_static("x-signature"))
+//!     .components(vec![
+//!         Component::Timestamp,
+//!         Component::Method,
+//!         Component::PathAndQuery,
+//!         Component::Body,
+//!     ])
+//!     .separator(".")
+//!     .timestamp_header(HeaderName::from_static("x-timestamp"))
+//!     .base64();
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().middleware(signer));
+//! ```
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use http::{HeaderName, Request, Response};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+    http::build_header_value,
+    signing,
+    skew::ClockSkew,
+};
+
+/// A part of the request included in the canonical string signed by an
+/// [HmacSigner].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Component {
+    /// The request method, i.e. `POST`
+    Method,
+    /// The path of the request as sent, i.e. `/v1/charges`
+    Path,
+    /// The path and query of the request as sent, i.e. `/v1/charges?limit=10`
+    PathAndQuery,
+    /// The raw request body
+    Body,
+    /// The lowercase hex encoded SHA-256 hash of the request body
+    BodyHash,
+    /// The signing time in seconds since the Unix epoch
+    Timestamp,
+    /// The value of the given header, or nothing if it's missing
+    Header(HeaderName),
+    /// The given string
+    Literal(String),
+}
+
+/// How the signature of an [HmacSigner] is encoded in its header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Hex,
+    Base64,
+}
+
+/// A [MiddleWare] which signs requests with an HMAC-SHA256 signature, see
+/// the [module documentation][self].
+#[derive(Clone)]
+pub struct HmacSigner {
+    clock: Option<ClockSkew>,
+    components: Vec<Component>,
+    encoding: Encoding,
+    header: HeaderName,
+    key: Vec<u8>,
+    prefix: String,
+    separator: String,
+    timestamp_header: Option<HeaderName>,
+}
+
+impl HmacSigner {
+    /// Returns a new [HmacSigner] which signs requests with the given key and
+    /// sends the signature in the given header.
+    pub fn new(key: &[u8], header: HeaderName) -> Self {
+        HmacSigner {
+            clock: None,
+            components: vec![
+                Component::Method,
+                Component::Path,
+                Component::Body,
+                Component::Timestamp,
+            ],
+            encoding: Encoding::Hex,
+            header,
+            key: key.to_vec(),
+            prefix: String::new(),
+            separator: "\n".to_string(),
+            timestamp_header: None,
+        }
+    }
+
+    /// Sets the components of the canonical string, in order.
+    pub fn components(mut self, components: Vec<Component>) -> Self {
+        self.components = components;
+        self
+    }
+
+    /// Sets the separator placed between components.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Sends the signing timestamp in the given header.
+    pub fn timestamp_header(mut self, header: HeaderName) -> Self {
+        self.timestamp_header = Some(header);
+        self
+    }
+
+    /// Sends the signature base64 encoded instead of hex encoded.
+    pub fn base64(mut self) -> Self {
+        self.encoding = Encoding::Base64;
+        self
+    }
+
+    /// Places the given prefix in front of the signature, i.e. `sha256=`.
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// Uses the given [ClockSkew] for the signing time instead of the local
+    /// clock.
+    pub fn clock(mut self, clock: ClockSkew) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Returns the canonical string of the given request signed at the given
+    /// timestamp.
+    pub fn canonical_string(&self, req: &Request<Vec<u8>>, timestamp: u64) -> Vec<u8> {
+        let mut result = Vec::new();
+        for (i, component) in self.components.iter().enumerate() {
+            if i > 0 {
+                result.extend_from_slice(self.separator.as_bytes());
+            }
+            match component {
+                Component::Method => result.extend_from_slice(req.method().as_str().as_bytes()),
+                Component::Path => result.extend_from_slice(req.uri().path().as_bytes()),
+                Component::PathAndQuery => {
+                    let target = req.uri().path_and_query().map(|p| p.as_str());
+                    result.extend_from_slice(target.unwrap_or("/").as_bytes())
+                }
+                Component::Body => result.extend_from_slice(req.body()),
+                Component::BodyHash => {
+                    result.extend_from_slice(signing::hash_body(req.body()).as_bytes())
+                }
+                Component::Timestamp => result.extend_from_slice(timestamp.to_string().as_bytes()),
+                Component::Header(name) => {
+                    if let Some(value) = req.headers().get(name) {
+                        result.extend_from_slice(value.as_bytes())
+                    }
+                }
+                Component::Literal(s) => result.extend_from_slice(s.as_bytes()),
+            }
+        }
+        result
+    }
+
+    /// Signs the given request as if it was sent at the given time.
+    pub fn sign_at(&self, req: &mut Request<Vec<u8>>, time: SystemTime) -> Result<(), ClientError> {
+        let timestamp = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        if let Some(name) = &self.timestamp_header {
+            let value = build_header_value(name, &timestamp.to_string())?;
+            req.headers_mut().insert(name.clone(), value);
+        }
+
+        let mac = signing::hmac_sha256(&self.key, &self.canonical_string(req, timestamp));
+        let signature = match self.encoding {
+            Encoding::Hex => signing::hex(&mac),
+            Encoding::Base64 => STANDARD.encode(mac),
+        };
+        let mut value = build_header_value(&self.header, &format!("{}{}", self.prefix, signature))?;
+        value.set_sensitive(true);
+        req.headers_mut().insert(self.header.clone(), value);
+        Ok(())
+    }
+}
+
+impl MiddleWare for HmacSigner {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        };
+        self.sign_at(req, now)
+    }
+
+    fn response<E: Endpoint>(&self, _: &E, _: &mut Response<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for HmacSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HmacSigner")
+            .field("components", &self.components)
+            .field("encoding", &self.encoding)
+            .field("header", &self.header)
+            .field("prefix", &self.prefix)
+            .field("separator", &self.separator)
+            .field("timestamp_header", &self.timestamp_header)
+            .finish()
+    }
+}
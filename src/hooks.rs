@@ -0,0 +1,102 @@
+//! Contains the [Hooks] type for observing requests executed by a
+//! [Client][crate::client::Client].
+
+use std::{fmt, sync::Arc};
+
+use crate::errors::ClientError;
+use http::{Request, Response};
+
+type RequestHook = Arc<dyn Fn(&Request<Vec<u8>>) + Send + Sync>;
+type ResponseHook = Arc<dyn Fn(&Response<Vec<u8>>) + Send + Sync>;
+type ErrorHook = Arc<dyn Fn(&ClientError) + Send + Sync>;
+
+/// A set of callbacks which are invoked while a
+/// [Client][crate::client::Client] executes a request.
+///
+/// Unlike [MiddleWare][crate::endpoint::MiddleWare], hooks only ever receive
+/// immutable references and therefore cannot alter the request or response.
+/// They are intended purely for observability purposes like logging or
+/// collecting metrics. Multiple callbacks can be registered for each event and
+/// they are invoked in the order they were added.
+///
+/// # Example
+/// ```
+/// use rustify::{clients::reqwest::Client, config::ClientConfig, hooks::Hooks};
+///
+/// let hooks = Hooks::new()
+///     .on_request(|req| println!("Sending request to {}", req.uri()))
+///     .on_response(|resp| println!("Received {}", resp.status()))
+///     .on_error(|err| println!("Request failed: {}", err));
+/// let client = Client::default("http://myapi.com")
+///     .with_config(ClientConfig::new().hooks(hooks));
+/// ```
+#[derive(Clone, Default)]
+pub struct Hooks {
+    request: Vec<RequestHook>,
+    response: Vec<ResponseHook>,
+    error: Vec<ErrorHook>,
+}
+
+impl Hooks {
+    /// Returns a new [Hooks] without any callbacks registered.
+    pub const fn new() -> Self {
+        Hooks {
+            request: Vec::new(),
+            response: Vec::new(),
+            error: Vec::new(),
+        }
+    }
+
+    /// Registers a callback which is invoked with each [Request] before it's
+    /// sent.
+    pub fn on_request<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Request<Vec<u8>>) + Send + Sync + 'static,
+    {
+        self.request.push(Arc::new(f));
+        self
+    }
+
+    /// Registers a callback which is invoked with each [Response] received,
+    /// including responses with an unsuccessful status code. Streamed
+    /// responses are passed with an empty body.
+    pub fn on_response<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Response<Vec<u8>>) + Send + Sync + 'static,
+    {
+        self.response.push(Arc::new(f));
+        self
+    }
+
+    /// Registers a callback which is invoked with any [ClientError] that
+    /// occurs while executing a request.
+    pub fn on_error<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&ClientError) + Send + Sync + 'static,
+    {
+        self.error.push(Arc::new(f));
+        self
+    }
+
+    pub(crate) fn request(&self, req: &Request<Vec<u8>>) {
+        self.request.iter().for_each(|f| f(req));
+    }
+
+    pub(crate) fn response(&self, resp: &Response<Vec<u8>>) {
+        self.response.iter().for_each(|f| f(resp));
+    }
+
+    pub(crate) fn error(&self, err: &ClientError) {
+        self.error.iter().for_each(|f| f(err));
+    }
+}
+
+impl fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks")
+            .field("request", &self.request.len())
+            .field("response", &self.response.len())
+            .field("error", &self.error.len())
+            .finish()
+    }
+}
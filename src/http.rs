@@ -1,27 +1,43 @@
 //! Contains helper functions for working with HTTP requests and responses.
 
 use crate::{
-    enums::{RequestMethod, RequestType},
+    config::Building,
+    enums::{BodyStrictness, RequestMethod, RequestType, ResponseType},
     errors::ClientError,
 };
-use http::{Request, Uri};
-use serde::Serialize;
+use http::{HeaderName, HeaderValue, Request, Uri};
+use serde::{
+    de::{DeserializeOwned, IntoDeserializer},
+    Serialize,
+};
+use serde_json::Value;
+use std::cell::RefCell;
 use url::Url;
 
+/// The number of characters shown on either side of a deserialization error.
+const EXCERPT_WINDOW: usize = 40;
+
+thread_local! {
+    /// The paths of unknown fields found while collection is enabled.
+    static UNKNOWN_FIELDS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
 /// Builds a request body by serializing an object using a serializer determined
 /// by the [RequestType].
 #[instrument(skip(object), err)]
 pub fn build_body(object: &impl Serialize, ty: RequestType) -> Result<Vec<u8>, ClientError> {
     match ty {
         RequestType::JSON => {
-            let parse_data = serde_json::to_string(object)
+            let data = serde_json::to_vec(object)
                 .map_err(|e| ClientError::DataParseError { source: e.into() })?;
-            Ok(match parse_data.as_str() {
-                "null" => "".as_bytes().to_vec(),
-                "{}" => "".as_bytes().to_vec(),
-                _ => parse_data.as_bytes().to_vec(),
+            Ok(match data.as_slice() {
+                b"null" | b"{}" => Vec::new(),
+                _ => data,
             })
         }
+        RequestType::JSON_MERGE_PATCH | RequestType::JSON_PATCH => {
+            serde_json::to_vec(object).map_err(|e| ClientError::DataParseError { source: e.into() })
+        }
     }
 }
 
@@ -33,6 +49,10 @@ pub fn build_query(object: &impl Serialize) -> Result<String, ClientError> {
 }
 
 /// Builds a [Request] using the given [Endpoint][crate::Endpoint] and base URL.
+///
+/// When called while a client builds the request of an endpoint, the path is
+/// normalized according to its
+/// [TrailingSlash][crate::enums::TrailingSlash] policy.
 #[instrument(skip(query, data), err)]
 pub fn build_request(
     base: &str,
@@ -42,10 +62,14 @@ pub fn build_request(
     data: Option<Vec<u8>>,
 ) -> Result<Request<Vec<u8>>, ClientError> {
     debug!("Building endpoint request");
-    let uri = build_url(base, path, query)?;
+    let mut uri = build_url(base, path, query)?;
+    if let Some(building) = Building::current() {
+        uri = building.normalize_path(uri)?;
+    }
 
+    // The URL is only formatted when building the request fails
+    let uri_err = uri.clone();
     let method_err = method.clone();
-    let uri_err = uri.to_string();
     Request::builder()
         .uri(uri)
         .method(method)
@@ -53,16 +77,95 @@ pub fn build_request(
         .map_err(|e| ClientError::RequestBuildError {
             source: e,
             method: method_err,
-            url: uri_err,
+            url: uri_err.to_string(),
         })
 }
 
+/// Marks a [Request] as being sent to a pre-signed URL.
+///
+/// Pre-signed URLs carry their own credentials, so clients which attach
+/// credentials to requests, i.e. [Session][crate::session::Session], leave
+/// requests with this extension untouched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Presigned;
+
+/// Builds a [Request] to the given absolute, pre-signed URL which is used
+/// exactly as given. The request is marked with the [Presigned] extension.
+#[instrument(skip(url, data), err)]
+pub fn build_presigned_request(
+    url: &str,
+    method: RequestMethod,
+    data: Option<Vec<u8>>,
+) -> Result<Request<Vec<u8>>, ClientError> {
+    debug!("Building pre-signed request");
+    let parsed = Url::parse(url).map_err(|e| ClientError::UrlParseError { source: e })?;
+    if !parsed.has_host() {
+        return Err(ClientError::UrlParseError {
+            source: url::ParseError::EmptyHost,
+        });
+    }
+    let uri = url
+        .parse::<Uri>()
+        .map_err(|e| ClientError::UrlBuildError { source: e })?;
+
+    let method_err = method.clone();
+    let mut req = Request::builder()
+        .uri(uri)
+        .method(method)
+        .body(data.unwrap_or_default())
+        .map_err(|e| ClientError::RequestBuildError {
+            source: e,
+            method: method_err,
+            url: url.to_string(),
+        })?;
+    req.extensions_mut().insert(Presigned);
+    Ok(req)
+}
+
+/// Returns a copy of the given [Request], including its extensions.
+pub(crate) fn copy_request(req: &Request<Vec<u8>>) -> Request<Vec<u8>> {
+    let mut copy = Request::new(req.body().clone());
+    *copy.method_mut() = req.method().clone();
+    *copy.uri_mut() = req.uri().clone();
+    *copy.version_mut() = req.version();
+    *copy.headers_mut() = req.headers().clone();
+    *copy.extensions_mut() = req.extensions().clone();
+    copy
+}
+
+/// Parses the given header name and value, returning a
+/// [ClientError::HeaderError] if either contains characters which aren't
+/// permitted in an HTTP header, i.e. newlines.
+pub fn build_header(name: &str, value: &str) -> Result<(HeaderName, HeaderValue), ClientError> {
+    let header = HeaderName::from_bytes(name.as_bytes()).map_err(|e| ClientError::HeaderError {
+        source: e.into(),
+        name: name.to_string(),
+    })?;
+    let value = build_header_value(&header, value)?;
+    Ok((header, value))
+}
+
+/// Parses the value of the given header, returning a
+/// [ClientError::HeaderError] if it contains characters which aren't
+/// permitted in an HTTP header. The value itself is left out of the error in
+/// case it contains a secret.
+pub fn build_header_value(name: &HeaderName, value: &str) -> Result<HeaderValue, ClientError> {
+    HeaderValue::from_str(value).map_err(|e| ClientError::HeaderError {
+        source: e.into(),
+        name: name.to_string(),
+    })
+}
+
 /// Combines the given base URL, relative path, and optional query parameters
 /// into a single [Uri].
 #[instrument(skip(query), err)]
 pub fn build_url(base: &str, path: &str, query: Option<String>) -> Result<Uri, ClientError> {
     let mut url = Url::parse(base).map_err(|e| ClientError::UrlParseError { source: e })?;
-    url.path_segments_mut().unwrap().extend(path.split('/'));
+    url.path_segments_mut()
+        .map_err(|_| ClientError::UrlParseError {
+            source: url::ParseError::RelativeUrlWithCannotBeABaseBase,
+        })?
+        .extend(path.split('/'));
     if let Some(q) = query {
         url.set_query(Some(q.as_str()));
     }
@@ -71,3 +174,207 @@ pub fn build_url(base: &str, path: &str, query: Option<String>) -> Result<Uri, C
         .parse::<Uri>()
         .map_err(|e| ClientError::UrlBuildError { source: e })
 }
+
+/// Parses a response body into an object using a deserializer determined by
+/// the [ResponseType].
+///
+/// Empty and whitespace-only JSON bodies are parsed as `null`, see
+/// [BodyStrictness::LENIENT]. On failure the returned
+/// [ClientError::ResponseDeserializeError] contains the line and column where
+/// deserialization failed along with an excerpt of the body surrounding that
+/// position.
+#[instrument(skip(body), err)]
+pub fn parse<T: DeserializeOwned>(body: &[u8], ty: ResponseType) -> Result<T, ClientError> {
+    parse_with(body, ty, BodyStrictness::default())
+}
+
+/// Parses a response body like [parse] using the given [BodyStrictness].
+#[instrument(skip(body), err)]
+pub fn parse_with<T: DeserializeOwned>(
+    body: &[u8],
+    ty: ResponseType,
+    strictness: BodyStrictness,
+) -> Result<T, ClientError> {
+    let blank = body.iter().all(u8::is_ascii_whitespace);
+    let input = match (strictness, blank) {
+        (BodyStrictness::LENIENT, true) => "null".as_bytes(),
+        _ => body,
+    };
+    match ty {
+        ResponseType::TEXT => {
+            let text = std::str::from_utf8(body).map_err(|e| ClientError::ResponseParseError {
+                source: e.into(),
+                content: None,
+            })?;
+            deserialize(text.into_deserializer()).map_err(|e: serde::de::value::Error| {
+                ClientError::ResponseParseError {
+                    source: e.into(),
+                    content: Some(text.to_string()),
+                }
+            })
+        }
+        ResponseType::JSON => {
+            let mut de = serde_json::Deserializer::from_slice(input);
+            deserialize(&mut de)
+                .and_then(|v| de.end().map(|_| v))
+                .map_err(|e| {
+                    let (line, column) = (e.line(), e.column());
+                    ClientError::ResponseDeserializeError {
+                        source: e,
+                        line,
+                        column,
+                        excerpt: excerpt(input, line, column),
+                        content: String::from_utf8(body.to_vec()).ok(),
+                    }
+                })
+        }
+    }
+}
+
+/// Parses a response body like [parse] and invokes the given callback with
+/// the path of every field in the body which wasn't captured by the result.
+///
+/// Fields are only reported when the body is successfully parsed. This is
+/// useful for noticing when an API begins returning fields which are missing
+/// from the response type.
+pub fn parse_reporting<T, F>(body: &[u8], ty: ResponseType, report: F) -> Result<T, ClientError>
+where
+    T: DeserializeOwned,
+    F: FnMut(&str),
+{
+    report_unknown_fields(|| parse(body, ty), report)
+}
+
+/// Invokes the given function and reports the path of every unknown field
+/// encountered by [parse] or [from_value] while it runs.
+///
+/// Each field is reported through a tracing event and the given callback, but
+/// only if the function returns successfully.
+pub(crate) fn report_unknown_fields<T, F, R>(f: F, mut report: R) -> Result<T, ClientError>
+where
+    F: FnOnce() -> Result<T, ClientError>,
+    R: FnMut(&str),
+{
+    let previous = UNKNOWN_FIELDS.with(|u| u.replace(Some(Vec::new())));
+    let result = f();
+    let fields = UNKNOWN_FIELDS
+        .with(|u| u.replace(previous))
+        .unwrap_or_default();
+
+    if result.is_ok() {
+        for field in fields.iter() {
+            warn!(field = %field, "Response contained an unknown field");
+            report(field);
+        }
+    }
+    result
+}
+
+/// Deserializes an object, recording the path of any unknown fields if
+/// collection has been enabled by [report_unknown_fields].
+fn deserialize<'de, T, D>(de: D) -> Result<T, D::Error>
+where
+    T: DeserializeOwned,
+    D: serde::Deserializer<'de>,
+{
+    let collecting = UNKNOWN_FIELDS.with(|u| u.borrow().is_some());
+    match collecting {
+        true => serde_ignored::deserialize(de, |path| {
+            UNKNOWN_FIELDS.with(|u| {
+                if let Some(fields) = u.borrow_mut().as_mut() {
+                    fields.push(path.to_string());
+                }
+            })
+        }),
+        false => T::deserialize(de),
+    }
+}
+
+/// Returns the region of a body surrounding the given line and column.
+///
+/// Both the line and column are one-based, matching [serde_json::Error]. The
+/// excerpt contains at most [EXCERPT_WINDOW] characters on either side of the
+/// position and is prefixed or suffixed with `...` when truncated.
+pub fn excerpt(body: &[u8], line: usize, column: usize) -> String {
+    let body = String::from_utf8_lossy(body);
+    let text = match body.lines().nth(line.saturating_sub(1)) {
+        Some(l) => l,
+        None => return String::new(),
+    };
+
+    // Columns are byte offsets, so convert to a character position
+    let offset = column.min(text.len());
+    let position = text.char_indices().take_while(|(i, _)| *i < offset).count();
+    let start = position.saturating_sub(EXCERPT_WINDOW);
+    let total = text.chars().count();
+    let end = (position + EXCERPT_WINDOW).min(total);
+
+    let mut result = String::new();
+    if start > 0 {
+        result.push_str("...");
+    }
+    result.extend(text.chars().skip(start).take(end - start));
+    if end < total {
+        result.push_str("...");
+    }
+    result
+}
+
+/// Parses a response body into a [serde_json::Value] and returns it along with
+/// the string value of its discriminator field.
+///
+/// This is used for endpoints which return structurally different objects
+/// identified by a common field, for example `{"type": "user", ...}`.
+#[instrument(skip(body), err)]
+pub fn parse_discriminated(
+    body: &[u8],
+    ty: ResponseType,
+    field: &str,
+) -> Result<(Value, String), ClientError> {
+    let value: Value = parse(body, ty)?;
+    match value.get(field).and_then(Value::as_str) {
+        Some(v) => {
+            let variant = v.to_string();
+            Ok((value, variant))
+        }
+        None => Err(ClientError::ResponseDiscriminatorError {
+            field: field.to_string(),
+            value: None,
+            content: String::from_utf8(body.to_vec()).ok(),
+        }),
+    }
+}
+
+/// Parses a response body into a [serde_json::Value] and deserializes only the
+/// subtree at the given dotted path, i.e. `data.items`, into an object.
+///
+/// Numeric segments index into arrays, so `items.0.id` selects the `id` of
+/// the first item. A [ClientError::ResponseParseError] is returned if no
+/// value exists at the path.
+#[instrument(skip(body), err)]
+pub fn parse_extract<T: DeserializeOwned>(
+    body: &[u8],
+    ty: ResponseType,
+    path: &str,
+) -> Result<T, ClientError> {
+    let mut value: Value = parse(body, ty)?;
+    let pointer: String = path
+        .split('.')
+        .map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1")))
+        .collect();
+    match value.pointer_mut(&pointer) {
+        Some(v) => from_value(v.take()),
+        None => Err(ClientError::ResponseParseError {
+            source: anyhow::anyhow!("No value found at `{}`", path),
+            content: String::from_utf8(body.to_vec()).ok(),
+        }),
+    }
+}
+
+/// Deserializes a [serde_json::Value] into an object.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, ClientError> {
+    deserialize(&value).map_err(|e| ClientError::ResponseParseError {
+        source: e.into(),
+        content: Some(value.to_string()),
+    })
+}
@@ -0,0 +1,207 @@
+//! Contains the [Interceptors] type for adjusting the state of a
+//! [Client][crate::client::Client] based on the responses it receives.
+//!
+//! Some servers announce changes through response headers, i.e. that an
+//! endpoint is deprecated and will be removed at a `Sunset` date, or that a
+//! newer API version should be requested. Interceptors receive every
+//! response along with the [ClientState] shared by all clones of a
+//! [ClientConfig][crate::config::ClientConfig], and headers set on the state
+//! are added to every subsequent request which doesn't already carry them.
+//!
+//! Interceptors which update rate limits for other header names can hold a
+//! clone of a [RateLimitTracker][crate::ratelimit::RateLimitTracker] and
+//! record into it.
+//!
+//! # Example
+//! ```
+//! use http::HeaderName;
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, intercept::Interceptors};
+//!
+//! let interceptors = Interceptors::new()
+//!     .honor_deprecation()
+//!     .switch_version(
+//!         HeaderName::from_static("x-api-version-latest"),
+//!         HeaderName::from_static("x-api-version"),
+//!     );
+//! let client = Client::default("http://myapi.com")
+//!     .with_config(ClientConfig::new().intercept(interceptors.clone()));
+//!
+//! // ...execute endpoints...
+//!
+//! for (path, deprecation) in interceptors.state().deprecations() {
+//!     println!("{} is deprecated, sunset at {:?}", path, deprecation.sunset);
+//! }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Response, Uri};
+use tracing::warn;
+
+type Interceptor = Arc<dyn Fn(&Uri, &Response<Vec<u8>>, &ClientState) + Send + Sync>;
+
+/// The deprecation of an endpoint announced by a server with the
+/// `Deprecation` and `Sunset` headers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The value of the `Deprecation` header, i.e. `true` or the date the
+    /// endpoint was deprecated
+    pub deprecation: Option<String>,
+    /// The time the endpoint will stop responding, from the `Sunset` header
+    pub sunset: Option<SystemTime>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    headers: HeaderMap,
+    deprecations: BTreeMap<String, Deprecation>,
+}
+
+/// Client state which [Interceptors] adjust and subsequent requests observe.
+///
+/// The state is shared by all clones, so it can be inspected through the
+/// [Interceptors] passed to a configuration.
+#[derive(Clone, Debug, Default)]
+pub struct ClientState {
+    state: Arc<RwLock<State>>,
+}
+
+impl ClientState {
+    /// Sets a header which is added to every subsequent request which doesn't
+    /// already carry it.
+    pub fn set_header(&self, name: HeaderName, value: HeaderValue) {
+        self.write().headers.insert(name, value);
+    }
+
+    /// Stops adding the given header to subsequent requests.
+    pub fn remove_header(&self, name: &HeaderName) {
+        self.write().headers.remove(name);
+    }
+
+    /// Returns the headers added to subsequent requests.
+    pub fn headers(&self) -> HeaderMap {
+        self.read().headers.clone()
+    }
+
+    /// Records that the endpoint at the given host and path is deprecated.
+    pub fn set_deprecation(&self, path: &str, deprecation: Deprecation) {
+        self.write()
+            .deprecations
+            .insert(path.to_string(), deprecation);
+    }
+
+    /// Returns the deprecations announced so far, keyed by host and path.
+    pub fn deprecations(&self) -> BTreeMap<String, Deprecation> {
+        self.read().deprecations.clone()
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, State> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, State> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A set of callbacks which adjust the [ClientState] based on each response
+/// received, see the [module documentation][self].
+///
+/// Interceptors are invoked in the order they were added, before
+/// [Hooks][crate::hooks::Hooks], and for responses with an unsuccessful
+/// status code as well.
+#[derive(Clone, Default)]
+pub struct Interceptors {
+    interceptors: Vec<Interceptor>,
+    state: ClientState,
+}
+
+impl Interceptors {
+    /// Returns a new [Interceptors] without any callbacks registered.
+    pub fn new() -> Self {
+        Interceptors::default()
+    }
+
+    /// Registers a callback which is invoked with the [Uri] of each request
+    /// and the [Response] it received.
+    pub fn on_response<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Uri, &Response<Vec<u8>>, &ClientState) + Send + Sync + 'static,
+    {
+        self.interceptors.push(Arc::new(f));
+        self
+    }
+
+    /// Records the `Deprecation` and `Sunset` headers of responses in the
+    /// [ClientState] and logs a warning the first time each deprecated
+    /// endpoint responds.
+    pub fn honor_deprecation(self) -> Self {
+        self.on_response(|uri, resp, state| {
+            let header = |name| resp.headers().get(name).and_then(|v| v.to_str().ok());
+            let deprecation = Deprecation {
+                deprecation: header("deprecation").map(String::from),
+                sunset: header("sunset").and_then(|v| httpdate::parse_http_date(v).ok()),
+            };
+            if deprecation == Deprecation::default() {
+                return;
+            }
+            let path = format!(
+                "{}{}",
+                uri.authority().map(|a| a.as_str()).unwrap_or_default(),
+                uri.path()
+            );
+            if !state.read().deprecations.contains_key(&path) {
+                match header("sunset") {
+                    Some(sunset) => warn!("Endpoint {} is deprecated, sunset at {}", path, sunset),
+                    None => warn!("Endpoint {} is deprecated", path),
+                }
+            }
+            state.set_deprecation(&path, deprecation);
+        })
+    }
+
+    /// Sends the version announced by the server in the `announced` response
+    /// header as the `request` header of every subsequent request.
+    pub fn switch_version(self, announced: HeaderName, request: HeaderName) -> Self {
+        self.on_response(move |_, resp, state| {
+            if let Some(version) = resp.headers().get(&announced) {
+                state.set_header(request.clone(), version.clone());
+            }
+        })
+    }
+
+    /// Returns the [ClientState] adjusted by these interceptors.
+    pub fn state(&self) -> &ClientState {
+        &self.state
+    }
+
+    /// Adds the headers of the [ClientState] to the given request.
+    pub(crate) fn apply(&self, req: &mut Request<Vec<u8>>) {
+        let state = self.state.read();
+        for (name, value) in &state.headers {
+            if !req.headers().contains_key(name) {
+                req.headers_mut().insert(name, value.clone());
+            }
+        }
+    }
+
+    pub(crate) fn intercept(&self, uri: &Uri, resp: &Response<Vec<u8>>) {
+        self.interceptors
+            .iter()
+            .for_each(|f| f(uri, resp, &self.state));
+    }
+}
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Interceptors")
+            .field("interceptors", &self.interceptors.len())
+            .field("state", &self.state)
+            .finish()
+    }
+}
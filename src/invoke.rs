@@ -0,0 +1,123 @@
+//! Contains the [Registry] type for executing [Endpoints][Endpoint] by name
+//! with a JSON payload.
+//!
+//! Tools built on an SDK often need a generic "call any operation" command
+//! which takes the name of an operation and its arguments as JSON, for
+//! example from the command line. Endpoints are registered under a name and
+//! invoked with a [serde_json::Value] which is deserialized into the endpoint
+//! struct, so invalid payloads are rejected by serde before any request is
+//! sent. The parsed response is returned as a [serde_json::Value] as well.
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, invoke::Registry};
+//! use rustify_derive::Endpoint;
+//! use serde::Deserialize;
+//! use serde_json::json;
+//!
+//! #[derive(Deserialize, Endpoint)]
+//! #[endpoint(path = "users/{self.id}", response = "serde_json::Value")]
+//! struct GetUser {
+//!     #[endpoint(skip)]
+//!     id: u64,
+//! }
+//!
+//! let registry = Registry::<Client>::new().register::<GetUser>("getUser");
+//! assert_eq!(registry.names().collect::<Vec<_>>(), vec!["getUser"]);
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("http://api.example.com");
+//! let user = registry.invoke(&client, "getUser", json!({ "id": 1 })).await;
+//! # });
+//! ```
+
+use std::collections::BTreeMap;
+
+use futures_util::future::BoxFuture;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{client::Client, endpoint::Endpoint, errors::ClientError};
+
+type Operation<C> =
+    Box<dyn for<'a> Fn(&'a C, Value) -> BoxFuture<'a, Result<Value, ClientError>> + Send + Sync>;
+
+/// A set of [Endpoints][Endpoint] which can be executed by name against a
+/// [Client] of type `C`, see the [module documentation][self].
+pub struct Registry<C> {
+    operations: BTreeMap<String, Operation<C>>,
+}
+
+impl<C> std::fmt::Debug for Registry<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Registry")
+            .field("operations", &self.operations.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<C: Client> Default for Registry<C> {
+    fn default() -> Self {
+        Registry {
+            operations: BTreeMap::new(),
+        }
+    }
+}
+
+impl<C: Client> Registry<C> {
+    /// Returns a new, empty [Registry].
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Registers the [Endpoint] `E` under the given name, replacing any
+    /// endpoint previously registered under it.
+    pub fn register<E>(mut self, name: &str) -> Self
+    where
+        E: Endpoint + DeserializeOwned + Send + Sync + 'static,
+        E::Response: Serialize,
+    {
+        let operation: Operation<C> = Box::new(|client, payload| {
+            Box::pin(async move {
+                let endpoint: E = serde_json::from_value(payload)
+                    .map_err(|e| ClientError::EndpointBuildError { source: e.into() })?;
+                let result = endpoint.exec(client).await?.parse()?;
+                serde_json::to_value(result)
+                    .map_err(|e| ClientError::DataParseError { source: e.into() })
+            })
+        });
+        self.operations.insert(name.to_string(), operation);
+        self
+    }
+
+    /// Returns the names of all registered endpoints in alphabetical order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.operations.keys().map(String::as_str)
+    }
+
+    /// Returns whether an endpoint is registered under the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.operations.contains_key(name)
+    }
+
+    /// Executes the endpoint registered under the given name using the given
+    /// payload as its fields and returns the parsed response.
+    ///
+    /// Fails with a [ClientError::OperationError] if no endpoint is
+    /// registered under the name and with a [ClientError::EndpointBuildError]
+    /// if the payload can't be deserialized into the endpoint.
+    pub async fn invoke(
+        &self,
+        client: &C,
+        name: &str,
+        payload: Value,
+    ) -> Result<Value, ClientError> {
+        let operation = self
+            .operations
+            .get(name)
+            .ok_or_else(|| ClientError::OperationError {
+                name: name.to_string(),
+            })?;
+        operation(client, payload).await
+    }
+}
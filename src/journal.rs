@@ -0,0 +1,177 @@
+//! Contains the [Journal] middleware for recording every execution of an
+//! [Endpoint] to a [JournalSink].
+
+use std::{
+    io::Write,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+    events::Attempt,
+};
+use http::{Request, Response};
+use serde::{Deserialize, Serialize};
+
+/// The number of times a request was retried before the response was
+/// received.
+///
+/// Middleware which retries requests should insert this into the extensions
+/// of the final [Response] so it can be recorded by the [Journal]. When it's
+/// missing, the [Attempt] of the request is used instead.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Retries(pub u32);
+
+/// A single execution recorded by the [Journal].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The name of the executed [Endpoint] as returned by [Endpoint::name]
+    pub operation: String,
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    /// The time from sending the request to receiving the response
+    pub duration_ms: u64,
+    pub retries: u32,
+    /// The time the response was received in milliseconds since the Unix
+    /// epoch
+    pub timestamp_ms: u64,
+}
+
+/// Represents a destination which [JournalEntry]s are appended to.
+pub trait JournalSink: Send + Sync {
+    /// Appends the given entry to the sink.
+    fn append(&self, entry: &JournalEntry) -> Result<(), ClientError>;
+}
+
+/// A [JournalSink] which writes each entry to a [Write] as a single line of
+/// JSON (JSONL).
+pub struct JsonLines<W: Write + Send> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> JsonLines<W> {
+    /// Returns a new [JsonLines] sink which writes to the given writer.
+    pub fn new(writer: W) -> Self {
+        JsonLines {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Consumes the sink and returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send> JournalSink for JsonLines<W> {
+    fn append(&self, entry: &JournalEntry) -> Result<(), ClientError> {
+        let mut line = serde_json::to_vec(entry)
+            .map_err(|e| ClientError::GenericError { source: e.into() })?;
+        line.push(b'\n');
+
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer
+            .write_all(&line)
+            .and_then(|_| writer.flush())
+            .map_err(|e| ClientError::GenericError { source: e.into() })
+    }
+}
+
+impl<F> JournalSink for F
+where
+    F: Fn(&JournalEntry) + Send + Sync,
+{
+    fn append(&self, entry: &JournalEntry) -> Result<(), ClientError> {
+        self(entry);
+        Ok(())
+    }
+}
+
+/// The details of a request captured before it's sent.
+#[derive(Clone)]
+struct Started {
+    method: String,
+    url: String,
+    at: Instant,
+}
+
+/// A [MiddleWare] which records every execution of an [Endpoint] to a
+/// [JournalSink].
+///
+/// Executions which fail before a response is passed to middleware, for
+/// example due to a connection error or an unsuccessful status code, are not
+/// recorded. Failures to write to the sink are logged and otherwise ignored
+/// so they never fail the request itself.
+///
+/// # Example
+/// ```
+/// use rustify::{clients::reqwest::Client, journal::{Journal, JsonLines}};
+/// use rustify::Endpoint;
+/// use rustify_derive::Endpoint;
+///
+/// #[derive(Endpoint)]
+/// #[endpoint(path = "my/endpoint")]
+/// struct MyEndpoint {}
+///
+/// # tokio_test::block_on(async {
+/// let client = Client::default("http://myapi.com");
+/// let journal = Journal::new(JsonLines::new(std::io::stdout()));
+/// let result = MyEndpoint {}.with_middleware(&journal).exec(&client).await;
+/// # })
+/// ```
+pub struct Journal<S: JournalSink> {
+    pub sink: S,
+}
+
+impl<S: JournalSink> Journal<S> {
+    /// Returns a new [Journal] which appends entries to the given sink.
+    pub fn new(sink: S) -> Self {
+        Journal { sink }
+    }
+}
+
+impl<S: JournalSink> MiddleWare for Journal<S> {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let started = Started {
+            method: req.method().to_string(),
+            url: req.uri().to_string(),
+            at: Instant::now(),
+        };
+        req.extensions_mut().insert(started);
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let started = match resp.extensions().get::<Started>() {
+            Some(s) => s.clone(),
+            None => return Ok(()),
+        };
+        let entry = JournalEntry {
+            operation: endpoint.name().to_string(),
+            method: started.method,
+            url: started.url,
+            status: resp.status().as_u16(),
+            duration_ms: started.at.elapsed().as_millis() as u64,
+            retries: match resp.extensions().get::<Retries>() {
+                Some(r) => r.0,
+                None => Attempt::of(resp.extensions()).saturating_sub(1),
+            },
+            timestamp_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or_default(),
+        };
+
+        if let Err(e) = self.sink.append(&entry) {
+            warn!("Failed writing journal entry: {}", e);
+        }
+        Ok(())
+    }
+}
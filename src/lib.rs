@@ -75,6 +75,8 @@
 //! // below example. If no `body` attribute is found anywhere then rustify defaults
 //! // to serializing all "untagged" fields as part of the body. Fields can be opted
 //! // out of this behavior by tagging them with #[endpoint(skip)].
+//! // Fields which must be sent in both the query and the body can be tagged with
+//! // #[endpoint(dual)].
 //! #[derive(Builder, Endpoint)]
 //! #[endpoint(path = "test/path/{self.name}", method = "POST", builder = "true")]
 //! #[builder(setter(into))] // Improves the building process
@@ -177,6 +179,11 @@
 //! # });
 //! ```
 //!
+//! Responses which enclose the result in a one-off envelope can deserialize
+//! only the relevant subtree using `extract`, i.e.
+//! `#[endpoint(path = "users", response = "Vec<User>", extract = "data.items")]`
+//! parses the array at `{"data": {"items": [...]}}`.
+//!
 //! ## Examples
 //!
 //! You can find example usage in the [examples](examples) directory. They can
@@ -194,13 +201,45 @@
 //! The following features are available for this crate:
 //!
 //! * `blocking`: Enables the blocking variants of `Client`s as well as the blocking
-//!    `exec()` functions in `Endpoint`s.
+//!   `exec()` functions in `Endpoint`s.
+//! * `mmap`: Enables spilling large response bodies from the blocking `Client`
+//!   to memory-mapped temporary files.
+//! * `arbitrary_precision`: Enables `serde_json/arbitrary_precision` so numbers
+//!   in responses can be deserialized without being converted to `f64`, for
+//!   example into `serde_json::Number` or a decimal type.
+//! * `float_roundtrip`: Enables `serde_json/float_roundtrip` so floats are
+//!   parsed with full accuracy at the cost of some performance.
+//! * `chrono`: Enables formatting `chrono::DateTime` values in requests using
+//!   the `datetime` field option.
+//! * `time`: Enables formatting `time::OffsetDateTime` values in requests using
+//!   the `datetime` field option.
+//! * `socks`: Enables routing requests through SOCKS5 proxies.
+//! * `callback`: Enables the callback server for APIs which deliver results
+//!   to a callback URL.
+//! * `diagnostics`: Implements `miette::Diagnostic` for `ClientError`, which
+//!   labels the position of deserialization failures in the response body.
+//! * `docs`: Includes the doc comments of derived endpoints in their
+//!   `EndpointMetadata`.
+//! * `keyring`: Enables storing credentials in the keyring of the operating
+//!   system.
+//! * `metrics`: Enables recording request counts, errors, and latency through
+//!   the `metrics` facade.
+//! * `mock`: Enables the [MockClient][crate::mock::MockClient] for testing
+//!   without a server.
+//! * `oauth`: Enables the OAuth 2.0 device authorization flow for command line
+//!   tools and bearer token middleware which refreshes rejected tokens.
+//! * `sigv4`: Enables middleware signing requests with AWS Signature Version 4.
 //!
 //! ## Error Handling
 //!
 //! All errors generated by this crate are wrapped in the `ClientError` enum
 //! provided by the crate.
 //!
+//! Use `ClientError::kind` to match on the category of an error (build,
+//! transport, HTTP status, deserialization, middleware, or API) instead of its
+//! individual variants, and `ClientError::status` to get the status code of
+//! unsuccessful responses.
+//!
 //! ## Testing
 //!
 //! See the the [tests](tests) directory for tests. Run tests with `cargo test`.
@@ -221,14 +260,73 @@
 #[macro_use]
 extern crate tracing;
 
+pub mod adaptive;
+pub mod auth;
+pub mod bench;
 #[cfg(feature = "blocking")]
 pub mod blocking;
+pub mod bucket;
+pub mod cache;
+#[cfg(feature = "callback")]
+pub mod callback;
+pub mod capture;
 pub mod client;
 pub mod clients;
+pub mod conditional;
+pub mod config;
+pub mod credentials;
+pub mod datetime;
+pub mod deadline;
+pub mod deferred;
+pub mod dns;
+pub mod encoding;
+pub mod encryption;
 pub mod endpoint;
 pub mod enums;
 pub mod errors;
+pub mod events;
+#[cfg(feature = "mock")]
+pub mod fixture;
+pub mod gate;
+pub mod guard;
+pub mod hmac;
+pub mod hooks;
 pub mod http;
+pub mod intercept;
+pub mod invoke;
+pub mod journal;
+pub mod limit;
+pub mod logging;
+pub mod matcher;
+pub mod metadata;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod middleware;
+#[cfg(feature = "mock")]
+pub mod mock;
+pub mod mode;
+#[cfg(feature = "oauth")]
+pub mod oauth;
+pub mod patch;
+pub mod probe;
+pub mod profiles;
+pub mod proxy;
+pub mod ratelimit;
+pub mod redirect;
+pub mod resilience;
+pub mod retry;
+pub mod scheduler;
+pub mod segmented;
+pub mod session;
+pub mod shutdown;
+pub mod signing;
+#[cfg(feature = "sigv4")]
+pub mod sigv4;
+pub mod skew;
+pub mod stream;
+pub mod throttle;
+pub mod tls;
+pub mod validate;
 
 #[doc(hidden)]
 #[path = "private/mod.rs"]
@@ -236,5 +334,5 @@ pub mod __private;
 
 pub use crate::{
     clients::reqwest::Client,
-    endpoint::{Endpoint, MiddleWare, Wrapper},
+    endpoint::{Endpoint, MiddleWare, Unwrap, Wrapper},
 };
@@ -0,0 +1,157 @@
+//! Contains the [ConcurrencyLimit] type for capping the number of requests a
+//! [Client][crate::client::Client] has in flight at once.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_lock::{Semaphore, SemaphoreGuardArc};
+use http::Uri;
+
+/// The semaphores used for limiting requests to each host.
+type HostSemaphores = Arc<Mutex<HashMap<String, Arc<Semaphore>>>>;
+
+/// Limits the number of concurrent in-flight requests, either in total or for
+/// each individual host.
+///
+/// A [ConcurrencyLimit] is shared by all clones of the
+/// [ClientConfig][crate::config::ClientConfig] it belongs to. Requests which
+/// exceed the limit wait until an earlier request completes before being
+/// sent.
+#[derive(Clone, Default)]
+pub struct ConcurrencyLimit {
+    total: Option<(usize, Arc<Semaphore>)>,
+    per_host: Option<(usize, HostSemaphores)>,
+    in_flight: Arc<AtomicUsize>,
+    queued: Arc<AtomicUsize>,
+}
+
+/// The permits held by a request while it's in flight. The permits are
+/// released when this is dropped.
+pub struct Permit {
+    _total: Option<SemaphoreGuardArc>,
+    _host: Option<SemaphoreGuardArc>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl ConcurrencyLimit {
+    /// Returns a new [ConcurrencyLimit] without any limits.
+    pub fn new() -> Self {
+        ConcurrencyLimit::default()
+    }
+
+    /// Limits the total number of concurrent requests.
+    pub fn total(mut self, max: usize) -> Self {
+        self.total = Some((max, Arc::new(Semaphore::new(max))));
+        self
+    }
+
+    /// Limits the number of concurrent requests sent to each host.
+    pub fn per_host(mut self, max: usize) -> Self {
+        self.per_host = Some((max, Arc::new(Mutex::new(HashMap::new()))));
+        self
+    }
+
+    /// Waits until the request to the given [Uri] is permitted to be sent.
+    pub async fn acquire(&self, uri: &Uri) -> Permit {
+        let _queued = Queued::new(&self.queued);
+        let total = match &self.total {
+            Some((_, s)) => Some(s.acquire_arc().await),
+            None => None,
+        };
+        let host = match self.host(uri) {
+            Some(s) => Some(s.acquire_arc().await),
+            None => None,
+        };
+        self.permit(total, host)
+    }
+
+    /// Blocks until the request to the given [Uri] is permitted to be sent.
+    pub fn acquire_blocking(&self, uri: &Uri) -> Permit {
+        let _queued = Queued::new(&self.queued);
+        let total = self.total.as_ref().map(|(_, s)| s.acquire_arc_blocking());
+        let host = self.host(uri).map(|s| s.acquire_arc_blocking());
+        self.permit(total, host)
+    }
+
+    /// Returns the maximum total number of concurrent requests, if limited.
+    pub fn max_total(&self) -> Option<usize> {
+        self.total.as_ref().map(|(max, _)| *max)
+    }
+
+    /// Returns the maximum number of concurrent requests to each host, if
+    /// limited.
+    pub fn max_per_host(&self) -> Option<usize> {
+        self.per_host.as_ref().map(|(max, _)| *max)
+    }
+
+    /// Returns the number of requests currently holding a permit.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of requests waiting for a permit.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    fn permit(&self, total: Option<SemaphoreGuardArc>, host: Option<SemaphoreGuardArc>) -> Permit {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Permit {
+            _total: total,
+            _host: host,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Returns the semaphore for the host of the given [Uri], if a per host
+    /// limit is configured.
+    fn host(&self, uri: &Uri) -> Option<Arc<Semaphore>> {
+        let (max, hosts) = self.per_host.as_ref()?;
+        let key = uri.authority().map(|a| a.to_string()).unwrap_or_default();
+        let mut hosts = hosts.lock().unwrap_or_else(|e| e.into_inner());
+        Some(
+            hosts
+                .entry(key)
+                .or_insert_with(|| Arc::new(Semaphore::new(*max)))
+                .clone(),
+        )
+    }
+}
+
+/// Counts a request as queued until it's dropped, including when the
+/// request is cancelled while waiting.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl<'a> Queued<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        Queued(queued)
+    }
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimit")
+            .field("total", &self.max_total())
+            .field("per_host", &self.per_host.as_ref().map(|(max, _)| max))
+            .finish()
+    }
+}
@@ -0,0 +1,251 @@
+//! Contains the [Logger] middleware for logging every execution of an
+//! [Endpoint].
+//!
+//! Each response is logged as a single `info` event containing the method,
+//! URL, status, and duration of the request. Headers and bodies are only
+//! logged when enabled. The values of headers carrying credentials
+//! (`Authorization`, `Cookie`, `Set-Cookie`, and `Proxy-Authorization`) are
+//! replaced with `<redacted>`, along with any headers added with
+//! [Logger::redact]. Bodies are rendered by a [BodyCapture], which redacts
+//! credential fields and truncates long bodies.
+//!
+//! # Example
+//! ```
+//! use rustify::{capture::BodyCapture, clients::reqwest::Client, config::ClientConfig};
+//! use rustify::logging::Logger;
+//!
+//! let logger = Logger::new()
+//!     .headers(true)
+//!     .redact("X-Api-Key")
+//!     .bodies(BodyCapture::new().max_bytes(256));
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().middleware(logger));
+//! ```
+
+use std::{
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use http::{HeaderMap, Request, Response};
+
+use crate::{
+    capture::BodyCapture,
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+
+/// The headers which are always redacted.
+const SENSITIVE_HEADERS: [&str; 4] = [
+    "authorization",
+    "cookie",
+    "proxy-authorization",
+    "set-cookie",
+];
+
+const REDACTED: &str = "<redacted>";
+
+type Sink = Arc<dyn Fn(&RequestLog) + Send + Sync>;
+
+/// A single execution logged by the [Logger].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestLog {
+    /// The name of the executed [Endpoint] as returned by [Endpoint::name]
+    pub operation: String,
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    /// The time from sending the request to receiving the response
+    pub duration: Duration,
+    /// The request headers, only populated if enabled with [Logger::headers]
+    pub request_headers: Vec<(String, String)>,
+    /// The response headers, only populated if enabled with
+    /// [Logger::headers]
+    pub response_headers: Vec<(String, String)>,
+    /// The rendered request body, only populated if enabled with
+    /// [Logger::bodies] and the request was sampled
+    pub request_body: Option<String>,
+    /// The rendered response body, only populated if enabled with
+    /// [Logger::bodies] and the request was sampled
+    pub response_body: Option<String>,
+}
+
+impl fmt::Display for RequestLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} ({}ms)",
+            self.method,
+            self.url,
+            self.status,
+            self.duration.as_millis()
+        )
+    }
+}
+
+/// The details of a request captured before it's sent.
+#[derive(Clone)]
+struct Started {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+    at: Instant,
+}
+
+/// A [MiddleWare] which logs every execution of an [Endpoint], see the
+/// [module documentation][self].
+///
+/// Executions which fail before a response is passed to middleware, for
+/// example due to a connection error or an unsuccessful status code, are not
+/// logged.
+#[derive(Clone)]
+pub struct Logger {
+    headers: bool,
+    redact: Vec<String>,
+    bodies: Option<BodyCapture>,
+    sink: Option<Sink>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Logger {
+            headers: false,
+            redact: SENSITIVE_HEADERS.iter().map(|h| h.to_string()).collect(),
+            bodies: None,
+            sink: None,
+        }
+    }
+}
+
+impl Logger {
+    /// Returns a new [Logger] which logs the method, URL, status, and
+    /// duration of every request.
+    pub fn new() -> Self {
+        Logger::default()
+    }
+
+    /// Sets whether request and response headers are logged.
+    pub fn headers(mut self, enabled: bool) -> Self {
+        self.headers = enabled;
+        self
+    }
+
+    /// Redacts the values of headers with the given name, compared case
+    /// insensitively.
+    pub fn redact(mut self, header: &str) -> Self {
+        self.redact.push(header.to_ascii_lowercase());
+        self
+    }
+
+    /// Logs the request and response bodies of the requests sampled by the
+    /// given [BodyCapture].
+    pub fn bodies(mut self, capture: BodyCapture) -> Self {
+        self.bodies = Some(capture);
+        self
+    }
+
+    /// Passes every [RequestLog] to the given function instead of emitting it
+    /// as a tracing event.
+    pub fn sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(&RequestLog) + Send + Sync + 'static,
+    {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Returns the given headers with the values of sensitive headers
+    /// redacted, or nothing if headers aren't logged.
+    fn render_headers(&self, headers: &HeaderMap) -> Vec<(String, String)> {
+        if !self.headers {
+            return Vec::new();
+        }
+        headers
+            .iter()
+            .map(|(name, value)| {
+                let value = match self.redact.iter().any(|r| r == name.as_str()) {
+                    true => REDACTED.to_string(),
+                    false => String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                };
+                (name.to_string(), value)
+            })
+            .collect()
+    }
+
+    fn emit(&self, log: &RequestLog) {
+        if let Some(sink) = &self.sink {
+            return sink(log);
+        }
+        info!(
+            operation = %log.operation,
+            method = %log.method,
+            url = %log.url,
+            status = log.status,
+            duration_ms = log.duration.as_millis() as u64,
+            request_headers = ?log.request_headers,
+            response_headers = ?log.response_headers,
+            request_body = ?log.request_body,
+            response_body = ?log.response_body,
+            "{}",
+            log
+        );
+    }
+}
+
+impl MiddleWare for Logger {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let body = match &self.bodies {
+            Some(capture) if capture.sample() => Some(capture.render(req.body())),
+            _ => None,
+        };
+        let started = Started {
+            method: req.method().to_string(),
+            url: req.uri().to_string(),
+            headers: self.render_headers(req.headers()),
+            body,
+            at: Instant::now(),
+        };
+        req.extensions_mut().insert(started);
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        let started = match resp.extensions().get::<Started>() {
+            Some(s) => s.clone(),
+            None => return Ok(()),
+        };
+        let response_body = match (&self.bodies, &started.body) {
+            (Some(capture), Some(_)) => Some(capture.render(resp.body())),
+            _ => None,
+        };
+        let log = RequestLog {
+            operation: endpoint.name().to_string(),
+            method: started.method,
+            url: started.url,
+            status: resp.status().as_u16(),
+            duration: started.at.elapsed(),
+            request_headers: started.headers,
+            response_headers: self.render_headers(resp.headers()),
+            request_body: started.body,
+            response_body,
+        };
+        self.emit(&log);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Logger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Logger")
+            .field("headers", &self.headers)
+            .field("redact", &self.redact)
+            .field("bodies", &self.bodies)
+            .finish()
+    }
+}
@@ -0,0 +1,176 @@
+//! Contains the [Matcher] type for conditionally applying
+//! [MiddleWare][crate::endpoint::MiddleWare] to some endpoints.
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+use http::{Method, Request, Response};
+
+type Predicate = Arc<dyn Fn(&Target<'_>) -> bool + Send + Sync>;
+
+/// The details of an [Endpoint] which a [Matcher] is evaluated against.
+#[derive(Clone, Debug)]
+pub struct Target<'a> {
+    pub method: Method,
+    pub path: &'a str,
+    pub name: &'static str,
+}
+
+impl<'a> Target<'a> {
+    /// Returns a new [Target] describing the given path of an [Endpoint].
+    pub fn new<E: Endpoint>(endpoint: &E, path: &'a str) -> Self {
+        Target {
+            method: endpoint.method().into(),
+            path,
+            name: endpoint.name(),
+        }
+    }
+}
+
+/// Determines which endpoints a [MiddleWare] applies to.
+///
+/// An endpoint matches if its method is one of the given methods, its path
+/// matches one of the given glob patterns, and every predicate returns true.
+/// Criteria which were never given always match, so an empty [Matcher]
+/// matches every endpoint.
+///
+/// Path patterns are matched against [Endpoint::path] and support `*`, which
+/// matches any characters within a path segment, and `**`, which matches any
+/// number of segments.
+///
+/// # Example
+/// ```
+/// use rustify::matcher::Matcher;
+/// use http::Method;
+///
+/// let matcher = Matcher::new()
+///     .method(Method::DELETE)
+///     .path("users/*")
+///     .when(|t| !t.path.ends_with("/self"));
+/// ```
+#[derive(Clone, Default)]
+pub struct Matcher {
+    methods: Vec<Method>,
+    paths: Vec<String>,
+    predicates: Vec<Predicate>,
+}
+
+impl Matcher {
+    /// Returns a new [Matcher] which matches every endpoint.
+    pub fn new() -> Self {
+        Matcher::default()
+    }
+
+    /// Matches endpoints using the given HTTP method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Matches endpoints whose path matches the given glob pattern.
+    pub fn path(mut self, pattern: &str) -> Self {
+        self.paths.push(pattern.trim_matches('/').to_string());
+        self
+    }
+
+    /// Matches endpoints for which the given predicate returns true.
+    pub fn when<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&Target<'_>) -> bool + Send + Sync + 'static,
+    {
+        self.predicates.push(Arc::new(f));
+        self
+    }
+
+    /// Returns whether the given [Target] matches.
+    pub fn matches(&self, target: &Target<'_>) -> bool {
+        let path = target.path.trim_matches('/');
+        (self.methods.is_empty() || self.methods.contains(&target.method))
+            && (self.paths.is_empty() || self.paths.iter().any(|p| glob(p, path)))
+            && self.predicates.iter().all(|p| p(target))
+    }
+
+    /// Returns whether the given [Endpoint] matches.
+    pub fn matches_endpoint<E: Endpoint>(&self, endpoint: &E) -> bool {
+        let path = endpoint.path();
+        self.matches(&Target::new(endpoint, &path))
+    }
+}
+
+impl fmt::Debug for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Matcher")
+            .field("methods", &self.methods)
+            .field("paths", &self.paths)
+            .field("predicates", &self.predicates.len())
+            .finish()
+    }
+}
+
+/// Returns whether a path matches a glob pattern.
+fn glob(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.split('/').collect::<Vec<_>>();
+    let path = path.split('/').collect::<Vec<_>>();
+    glob_segments(&pattern, &path)
+}
+
+fn glob_segments(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| glob_segments(rest, &path[i..])),
+        Some((p, rest)) => match path.split_first() {
+            Some((s, path)) => {
+                glob_segment(p.as_bytes(), s.as_bytes()) && glob_segments(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+fn glob_segment(pattern: &[u8], segment: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => segment.is_empty(),
+        Some((b'*', rest)) => (0..=segment.len()).any(|i| glob_segment(rest, &segment[i..])),
+        Some((c, rest)) => match segment.split_first() {
+            Some((s, segment)) => c == s && glob_segment(rest, segment),
+            None => false,
+        },
+    }
+}
+
+/// A [MiddleWare] which is only applied to endpoints matched by a [Matcher].
+///
+/// This is created by calling
+/// [MiddleWare::only_for][crate::endpoint::MiddleWare::only_for].
+#[derive(Clone, Debug)]
+pub struct OnlyFor<M: MiddleWare> {
+    pub middleware: M,
+    pub matcher: Matcher,
+}
+
+impl<M: MiddleWare> MiddleWare for OnlyFor<M> {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        match self.matcher.matches_endpoint(endpoint) {
+            true => self.middleware.request(endpoint, req),
+            false => Ok(()),
+        }
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        match self.matcher.matches_endpoint(endpoint) {
+            true => self.middleware.response(endpoint, resp),
+            false => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Contains the [EndpointMetadata] type describing an
+//! [Endpoint][crate::endpoint::Endpoint] beyond what's needed to build its
+//! request.
+//!
+//! The metadata of an endpoint is stored as an extension of its request so
+//! middleware can use it, i.e. to check that a token carries the scopes an
+//! endpoint requires before sending a request which is bound to be rejected.
+//! It can be set with the derive macro using the `operation_id` and `scopes`
+//! parameters, where `scopes` is a list of string literals. With the `docs`
+//! feature enabled, the doc comments of the struct are included as well:
+//!
+//! ```
+//! use rustify::{errors::ClientError, Endpoint};
+//! use rustify_derive::Endpoint;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "repos", operation_id = "listRepos", scopes = ["repo:read", "org:read"])]
+//! struct ListRepos {}
+//!
+//! let metadata = ListRepos {}.metadata();
+//! assert_eq!(metadata.operation_id, Some("listRepos"));
+//! assert!(matches!(
+//!     metadata.check_scopes(&["repo:read"]),
+//!     Err(ClientError::ScopeError { .. })
+//! ));
+//! ```
+
+use http::Extensions;
+
+use crate::errors::ClientError;
+
+/// Describes an [Endpoint][crate::endpoint::Endpoint] for use by middleware.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct EndpointMetadata {
+    /// A stable identifier for the operation, i.e. the `operationId` of an
+    /// OpenAPI specification
+    pub operation_id: Option<&'static str>,
+    /// The scopes a token must carry to be allowed to execute the endpoint
+    pub scopes: &'static [&'static str],
+    /// The doc comments of a derived endpoint, for tools which show help
+    /// text for each operation. Only set when the `docs` feature is enabled.
+    pub doc: Option<&'static str>,
+}
+
+impl EndpointMetadata {
+    /// Returns the [EndpointMetadata] stored in the given extensions,
+    /// defaulting to empty metadata.
+    pub fn of(extensions: &Extensions) -> Self {
+        extensions
+            .get::<EndpointMetadata>()
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the required scopes which aren't in the given granted scopes.
+    pub fn missing_scopes<S: AsRef<str>>(&self, granted: &[S]) -> Vec<&'static str> {
+        self.scopes
+            .iter()
+            .filter(|s| !granted.iter().any(|g| g.as_ref() == **s))
+            .copied()
+            .collect()
+    }
+
+    /// Fails with a [ClientError::ScopeError] if any required scope isn't in
+    /// the given granted scopes.
+    pub fn check_scopes<S: AsRef<str>>(&self, granted: &[S]) -> Result<(), ClientError> {
+        let missing = self.missing_scopes(granted);
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(ClientError::ScopeError {
+                operation: self.operation_id.unwrap_or("endpoint").to_string(),
+                missing: missing.into_iter().map(String::from).collect(),
+            }),
+        }
+    }
+}
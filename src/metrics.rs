@@ -0,0 +1,103 @@
+//! Contains the [Metrics] type for recording requests through the
+//! [metrics](https://docs.rs/metrics) facade.
+//!
+//! This requires enabling the `metrics` feature. Once configured with
+//! [ClientConfig::metrics][crate::config::ClientConfig::metrics], every
+//! request sent by the client is recorded to the globally installed metrics
+//! recorder:
+//!
+//! * `rustify_requests_total`: a counter of all requests
+//! * `rustify_errors_total`: a counter of requests which failed or received
+//!   an unsuccessful status
+//! * `rustify_request_duration_seconds`: a histogram of the time from sending
+//!   a request to receiving its response
+//!
+//! Each metric is labeled with the name of the `endpoint` which built the
+//! request and the `status` class of the response, i.e. `2xx` or `5xx`, or
+//! `none` when no response was received. Retried requests are recorded once
+//! per attempt.
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, metrics::Metrics};
+//!
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().metrics(Metrics::new().prefix("myapi")));
+//! ```
+
+use std::time::Duration;
+
+use http::Response;
+
+use crate::{client::HTTP_SUCCESS_CODES, errors::ClientError};
+
+/// Records requests to the installed metrics recorder, see the
+/// [module documentation][self].
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    requests: String,
+    errors: String,
+    duration: String,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    /// Returns a new [Metrics] whose metric names start with `rustify`.
+    pub fn new() -> Self {
+        Metrics::named("rustify")
+    }
+
+    /// Sets the prefix of the metric names, i.e. `myapi` records
+    /// `myapi_requests_total`.
+    pub fn prefix(self, prefix: &str) -> Self {
+        Metrics::named(prefix)
+    }
+
+    fn named(prefix: &str) -> Self {
+        Metrics {
+            requests: format!("{}_requests_total", prefix),
+            errors: format!("{}_errors_total", prefix),
+            duration: format!("{}_request_duration_seconds", prefix),
+        }
+    }
+
+    /// Records the outcome of a request sent by the given operation which
+    /// took the given time.
+    pub(crate) fn record<B>(
+        &self,
+        operation: Option<&'static str>,
+        result: &Result<Response<B>, ClientError>,
+        elapsed: Duration,
+    ) {
+        let status = match result {
+            Ok(resp) => Some(resp.status().as_u16()),
+            Err(e) => e.status(),
+        };
+        let labels = [
+            ("endpoint", operation.unwrap_or("unknown").to_string()),
+            ("status", status_class(status).to_string()),
+        ];
+        ::metrics::counter!(self.requests.clone(), &labels).increment(1);
+        if !status.is_some_and(|s| HTTP_SUCCESS_CODES.contains(&s)) {
+            ::metrics::counter!(self.errors.clone(), &labels).increment(1);
+        }
+        ::metrics::histogram!(self.duration.clone(), &labels).record(elapsed.as_secs_f64());
+    }
+}
+
+/// Returns the class of the given status code, i.e. `4xx` for `404`.
+fn status_class(status: Option<u16>) -> &'static str {
+    match status {
+        Some(100..=199) => "1xx",
+        Some(200..=299) => "2xx",
+        Some(300..=399) => "3xx",
+        Some(400..=499) => "4xx",
+        Some(500..=599) => "5xx",
+        _ => "none",
+    }
+}
@@ -0,0 +1,251 @@
+//! Contains the [SharedMiddleware] type for applying [MiddleWare] to every
+//! [Endpoint] executed by a [Client][crate::client::Client].
+//!
+//! Middleware configured with
+//! [ClientConfig::middleware][crate::config::ClientConfig::middleware] is
+//! applied by `exec` and its variants without calling
+//! [Endpoint::with_middleware] on each endpoint. Middleware added to a single
+//! execution with [Endpoint::with_middleware] still applies on top: it sees
+//! the request before and the response after the client middleware, as if
+//! both were combined in a [MiddlewareStack][crate::endpoint::MiddlewareStack]
+//! with the per-call middleware outermost. Endpoints executed against a
+//! pre-signed URL bypass both.
+//!
+//! Since the client isn't generic over the endpoints it executes, client
+//! middleware receives an [EndpointInfo] describing the endpoint instead of
+//! the endpoint itself.
+//!
+//! # Example
+//! ```
+//! use rustify::{
+//!     clients::reqwest::Client,
+//!     config::ClientConfig,
+//!     endpoint::MiddleWare,
+//!     journal::{Journal, JsonLines},
+//!     skew::ClockSkew,
+//! };
+//!
+//! let middleware = ClockSkew::new().and(Journal::new(JsonLines::new(std::io::stderr())));
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().middleware(middleware));
+//! ```
+
+use std::{
+    any::Any,
+    fmt,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::Arc,
+};
+
+use http::{Request, Response};
+
+use crate::{
+    cache::CachePolicy,
+    endpoint::{Endpoint, MiddleWare},
+    enums::{RequestMethod, RequestPriority, RequestType, ResponseType},
+    errors::ClientError,
+    metadata::EndpointMetadata,
+};
+
+/// A description of an [Endpoint] passed to [SharedMiddleware] in place of
+/// the endpoint itself.
+///
+/// The path, method, name, and other attributes are those of the described
+/// endpoint. Its query and body are already part of the request, and its
+/// request and response body types are always JSON.
+#[derive(Clone, Debug)]
+pub struct EndpointInfo {
+    pub allow_body_on_get: bool,
+    pub cache_policy: CachePolicy,
+    pub deprecated: Option<&'static str>,
+    pub metadata: EndpointMetadata,
+    pub method: RequestMethod,
+    pub name: &'static str,
+    pub path: String,
+    pub priority: RequestPriority,
+}
+
+impl EndpointInfo {
+    /// Returns the [EndpointInfo] describing the given [Endpoint].
+    pub fn of<E: Endpoint>(endpoint: &E) -> Self {
+        EndpointInfo {
+            allow_body_on_get: endpoint.allow_body_on_get(),
+            cache_policy: endpoint.cache_policy(),
+            deprecated: endpoint.deprecated(),
+            metadata: endpoint.metadata(),
+            method: endpoint.method(),
+            name: endpoint.name(),
+            path: endpoint.path(),
+            priority: endpoint.priority(),
+        }
+    }
+}
+
+impl Endpoint for EndpointInfo {
+    type Response = ();
+    const REQUEST_BODY_TYPE: RequestType = RequestType::JSON;
+    const RESPONSE_BODY_TYPE: ResponseType = ResponseType::JSON;
+
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn method(&self) -> RequestMethod {
+        self.method.clone()
+    }
+
+    fn priority(&self) -> RequestPriority {
+        self.priority
+    }
+
+    fn cache_policy(&self) -> CachePolicy {
+        self.cache_policy
+    }
+
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn deprecated(&self) -> Option<&'static str> {
+        self.deprecated
+    }
+
+    fn metadata(&self) -> EndpointMetadata {
+        self.metadata
+    }
+
+    fn allow_body_on_get(&self) -> bool {
+        self.allow_body_on_get
+    }
+}
+
+/// An object safe version of [MiddleWare].
+trait ErasedMiddleware: Send + Sync {
+    fn request(
+        &self,
+        endpoint: &EndpointInfo,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError>;
+
+    fn response(
+        &self,
+        endpoint: &EndpointInfo,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError>;
+}
+
+impl<M: MiddleWare> ErasedMiddleware for M {
+    fn request(
+        &self,
+        endpoint: &EndpointInfo,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        MiddleWare::request(self, endpoint, req)
+    }
+
+    fn response(
+        &self,
+        endpoint: &EndpointInfo,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        MiddleWare::response(self, endpoint, resp)
+    }
+}
+
+/// A shared handle to a [MiddleWare] which can be stored in a
+/// [ClientConfig][crate::config::ClientConfig], see the
+/// [module documentation][self].
+#[derive(Clone)]
+pub struct SharedMiddleware {
+    middleware: Arc<dyn ErasedMiddleware>,
+}
+
+impl SharedMiddleware {
+    /// Returns a new [SharedMiddleware] which applies the given middleware.
+    pub fn new(middleware: impl MiddleWare + 'static) -> Self {
+        SharedMiddleware {
+            middleware: Arc::new(middleware),
+        }
+    }
+}
+
+impl MiddleWare for SharedMiddleware {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.middleware.request(&EndpointInfo::of(endpoint), req)
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.middleware.response(&EndpointInfo::of(endpoint), resp)
+    }
+}
+
+impl fmt::Debug for SharedMiddleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedMiddleware").finish()
+    }
+}
+
+/// A [MiddleWare] which converts panics of the wrapped middleware into
+/// [ClientError::MiddlewarePanic] errors, so a buggy middleware fails the
+/// request it panicked on instead of the task executing it.
+///
+/// This is created by calling [MiddleWare::catch_panics], or applied to the
+/// client middleware with
+/// [ClientConfig::catch_middleware_panics][crate::config::ClientConfig::catch_middleware_panics].
+/// State the middleware shares between requests may be left inconsistent by
+/// the panic, and panics can't be caught when compiling with `panic =
+/// "abort"`.
+#[derive(Clone, Debug)]
+pub struct CatchPanic<M: MiddleWare> {
+    pub middleware: M,
+}
+
+impl<M: MiddleWare> MiddleWare for CatchPanic<M> {
+    fn request<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        req: &mut Request<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        catch_panic(|| self.middleware.request(endpoint, req))
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        catch_panic(|| self.middleware.response(endpoint, resp))
+    }
+}
+
+/// Runs the given middleware function, converting a panic into a
+/// [ClientError::MiddlewarePanic].
+pub(crate) fn catch_panic<F>(f: F) -> Result<(), ClientError>
+where
+    F: FnOnce() -> Result<(), ClientError>,
+{
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = panic_message(payload.as_ref());
+        error!("Middleware panicked: {}", message);
+        Err(ClientError::MiddlewarePanic { message })
+    })
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s.to_string(),
+        None => payload
+            .downcast_ref::<String>()
+            .cloned()
+            .unwrap_or_else(|| "unknown panic".to_string()),
+    }
+}
@@ -0,0 +1,228 @@
+//! Contains the [MockClient] type for testing code which executes
+//! [Endpoints][crate::endpoint::Endpoint] without a server.
+//!
+//! A [MockClient] replies to requests with scripted [MockResponses][MockResponse]
+//! in the order they were queued and records every request it receives. The
+//! configured [ClientConfig] is applied as usual, so limits, deadlines, and
+//! retries behave as they would against a real server.
+//!
+//! Delays are simulated with [tokio::time::sleep], as are the delays of
+//! [ThrottledClient][crate::throttle::ThrottledClient] and the polling of the
+//! [oauth][crate::oauth] flow. Tests using `tokio::time::pause` (or
+//! `#[tokio::test(start_paused = true)]`) therefore run instantly and
+//! deterministically regardless of the delays involved.
+//!
+//! This module requires the `mock` feature.
+//!
+//! # Example
+//! ```
+//! use rustify::{mock::{MockClient, MockResponse}, Endpoint};
+//! use rustify_derive::Endpoint;
+//! use std::time::Duration;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "users", response = "Vec<String>")]
+//! struct ListUsers {}
+//!
+//! # tokio_test::block_on(async {
+//! let client = MockClient::new("http://api.example.com");
+//! client.push(MockResponse::new(200).body(r#"["jane"]"#).delay(Duration::from_millis(5)));
+//!
+//! let users = ListUsers {}.exec(&client).await.unwrap().parse().unwrap();
+//! assert_eq!(users, vec!["jane".to_string()]);
+//! assert_eq!(client.requests()[0].uri(), "http://api.example.com/users");
+//! # });
+//! ```
+
+use std::{
+    collections::VecDeque,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{
+    client::Client, config::ClientConfig, errors::ClientError, fixture::Fixture, http::copy_request,
+};
+use async_trait::async_trait;
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Response};
+
+/// A scripted reply to a single request sent to a [MockClient].
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: u16,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    delay: Duration,
+    disconnect: bool,
+}
+
+impl MockResponse {
+    /// Returns a new [MockResponse] with the given status code and an empty
+    /// body.
+    pub fn new(status: u16) -> Self {
+        MockResponse {
+            status,
+            headers: HeaderMap::new(),
+            body: Vec::new(),
+            delay: Duration::ZERO,
+            disconnect: false,
+        }
+    }
+
+    /// Returns a [MockResponse] which fails the request as if the server
+    /// closed the connection, see [ClientError::is_stale_connection].
+    pub fn disconnect() -> Self {
+        MockResponse {
+            disconnect: true,
+            ..MockResponse::new(0)
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Sets the body of the response.
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Delays the reply by the given duration.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    fn into_response(self, req: &Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        if self.disconnect {
+            return Err(ClientError::RequestError {
+                source: std::io::Error::from(std::io::ErrorKind::ConnectionReset).into(),
+                url: req.uri().to_string(),
+                method: req.method().to_string(),
+            });
+        }
+        let mut resp = Response::builder()
+            .status(self.status)
+            .body(self.body)
+            .map_err(|e| ClientError::ResponseError { source: e.into() })?;
+        *resp.headers_mut() = self.headers;
+        Ok(resp)
+    }
+}
+
+/// A [Client] which replies to requests with scripted responses, see the
+/// [module documentation][self].
+///
+/// Requests sent after all responses were used fail with a
+/// [ClientError::RequestError].
+#[derive(Debug)]
+pub struct MockClient {
+    base: String,
+    config: ClientConfig,
+    responses: Mutex<VecDeque<MockResponse>>,
+    requests: Mutex<Vec<Request<Vec<u8>>>>,
+}
+
+impl MockClient {
+    /// Returns a new [MockClient] with the given base URL and no responses.
+    pub fn new(base: &str) -> Self {
+        MockClient {
+            base: base.to_string(),
+            config: ClientConfig::default(),
+            responses: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns a new [MockClient] with the given base URL which replies with
+    /// the responses recorded in the given [Fixture], in order.
+    pub fn from_fixture(base: &str, fixture: &Fixture) -> Result<Self, ClientError> {
+        let client = MockClient::new(base);
+        for interaction in &fixture.interactions {
+            let recorded = &interaction.response;
+            let mut response = MockResponse::new(recorded.status).body(recorded.body.to_bytes()?);
+            response.headers = recorded.header_map()?;
+            client.push(response);
+        }
+        Ok(client)
+    }
+
+    /// Sets the [ClientConfig] applied to requests sent by this client.
+    pub fn with_config(mut self, config: ClientConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Queues a response for a future request.
+    pub fn push(&self, response: MockResponse) -> &Self {
+        lock(&self.responses).push_back(response);
+        self
+    }
+
+    /// Returns copies of all requests received so far.
+    pub fn requests(&self) -> Vec<Request<Vec<u8>>> {
+        lock(&self.requests).iter().map(copy_request).collect()
+    }
+
+    /// Returns the number of queued responses which haven't been used yet.
+    pub fn remaining(&self) -> usize {
+        lock(&self.responses).len()
+    }
+
+    /// Records the given request and returns the next scripted response.
+    fn next(&self, req: &Request<Vec<u8>>) -> Result<MockResponse, ClientError> {
+        lock(&self.requests).push(copy_request(req));
+        lock(&self.responses)
+            .pop_front()
+            .ok_or_else(|| ClientError::RequestError {
+                source: anyhow::anyhow!("No response scripted for request"),
+                url: req.uri().to_string(),
+                method: req.method().to_string(),
+            })
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[async_trait]
+impl Client for MockClient {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let response = self.next(&req)?;
+        if !response.delay.is_zero() {
+            tokio::time::sleep(response.delay).await;
+        }
+        response.into_response(&req)
+    }
+
+    fn base(&self) -> &str {
+        self.base.as_str()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl BlockingClient for MockClient {
+    fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let response = self.next(&req)?;
+        std::thread::sleep(response.delay);
+        response.into_response(&req)
+    }
+
+    fn base(&self) -> &str {
+        self.base.as_str()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+}
@@ -0,0 +1,152 @@
+//! Contains the [ApiMode] type for telling requests to a sandbox apart from
+//! requests to production, along with middleware which adjusts requests in
+//! sandbox mode.
+//!
+//! The mode of a client is configured with
+//! [ClientConfig::mode][crate::config::ClientConfig::mode] and stored as an
+//! extension of every request it executes. Middleware configured on the
+//! client sees the mode in the extensions of both the request and the
+//! response, while middleware added to a single execution with
+//! [Endpoint::with_middleware][crate::endpoint::Endpoint::with_middleware]
+//! only sees it in the extensions of the response. The mode is also
+//! available on the response of an
+//! [EndpointResult][crate::endpoint::EndpointResult].
+//!
+//! # Example
+//! ```
+//! use rustify::{
+//!     clients::reqwest::Client,
+//!     config::ClientConfig,
+//!     endpoint::MiddleWare,
+//!     mode::{ApiMode, SandboxHeader, SandboxRoute},
+//! };
+//!
+//! let middleware = SandboxHeader::new()
+//!     .and(SandboxRoute::new("https://api.example.com", "https://sandbox.example.com"));
+//! let client = Client::default("https://api.example.com").with_config(
+//!     ClientConfig::new()
+//!         .mode(ApiMode::Sandbox)
+//!         .middleware(middleware),
+//! );
+//! ```
+
+use http::{Extensions, HeaderName, HeaderValue, Request, Response, Uri};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+
+/// Whether a client executes requests against a sandbox or against
+/// production.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ApiMode {
+    /// Requests have real effects
+    #[default]
+    Production,
+    /// Requests are executed against a test environment
+    Sandbox,
+}
+
+impl ApiMode {
+    /// Returns the [ApiMode] stored in the given extensions, defaulting to
+    /// [ApiMode::Production].
+    pub fn of(extensions: &Extensions) -> Self {
+        extensions.get::<ApiMode>().copied().unwrap_or_default()
+    }
+
+    /// Returns whether this is [ApiMode::Sandbox].
+    pub fn is_sandbox(&self) -> bool {
+        *self == ApiMode::Sandbox
+    }
+}
+
+/// A [MiddleWare] which adds a header to every request executed in
+/// [ApiMode::Sandbox], `X-Sandbox: true` by default.
+#[derive(Clone, Debug)]
+pub struct SandboxHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl Default for SandboxHeader {
+    fn default() -> Self {
+        SandboxHeader {
+            name: HeaderName::from_static("x-sandbox"),
+            value: HeaderValue::from_static("true"),
+        }
+    }
+}
+
+impl SandboxHeader {
+    /// Returns a new [SandboxHeader] which adds `X-Sandbox: true`.
+    pub fn new() -> Self {
+        SandboxHeader::default()
+    }
+
+    /// Returns a new [SandboxHeader] which adds the given header.
+    pub fn with(name: HeaderName, value: HeaderValue) -> Self {
+        SandboxHeader { name, value }
+    }
+}
+
+impl MiddleWare for SandboxHeader {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        if ApiMode::of(req.extensions()).is_sandbox() {
+            req.headers_mut()
+                .insert(self.name.clone(), self.value.clone());
+        }
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(&self, _: &E, _: &mut Response<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+/// A [MiddleWare] which sends requests executed in [ApiMode::Sandbox] to a
+/// different base URL.
+///
+/// The production base at the start of the request URL is replaced with the
+/// sandbox base, i.e. with a production base of `https://api.example.com/v1`
+/// and a sandbox base of `https://sandbox.example.com/v1`, a request to
+/// `https://api.example.com/v1/charges` is sent to
+/// `https://sandbox.example.com/v1/charges`. Requests to other URLs are left
+/// unchanged.
+#[derive(Clone, Debug)]
+pub struct SandboxRoute {
+    pub production: String,
+    pub sandbox: String,
+}
+
+impl SandboxRoute {
+    /// Returns a new [SandboxRoute] which replaces the given production base
+    /// with the given sandbox base.
+    pub fn new(production: &str, sandbox: &str) -> Self {
+        SandboxRoute {
+            production: production.trim_end_matches('/').to_string(),
+            sandbox: sandbox.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl MiddleWare for SandboxRoute {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        if !ApiMode::of(req.extensions()).is_sandbox() {
+            return Ok(());
+        }
+        let url = req.uri().to_string();
+        let rest = match url.strip_prefix(&self.production) {
+            Some(r) if r.is_empty() || r.starts_with(['/', '?']) => r,
+            _ => return Ok(()),
+        };
+        *req.uri_mut() = format!("{}{}", self.sandbox, rest)
+            .parse::<Uri>()
+            .map_err(|e| ClientError::UrlBuildError { source: e })?;
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(&self, _: &E, _: &mut Response<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+//! Contains the [DeviceFlow] type implementing the OAuth 2.0 device
+//! authorization grant ([RFC 8628]) for command line tools, and the
+//! [OAuth2Middleware] client for sending bearer tokens which are refreshed
+//! when they expire.
+//!
+//! The flow starts by requesting a device code, whose verification URL and
+//! user code are shown to the user. The token endpoint is then polled at the
+//! interval requested by the server, backing off when asked to slow down,
+//! until the user approves or denies the request or the code expires.
+//!
+//! This module requires the `oauth` feature.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::{clients::reqwest::Client, oauth::DeviceFlow};
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("https://auth.example.com");
+//! let flow = DeviceFlow::new(
+//!     "my-cli",
+//!     "https://auth.example.com/device/code",
+//!     "https://auth.example.com/token",
+//! )
+//! .scope("read");
+//!
+//! let auth = flow.start(&client).await.unwrap();
+//! println!("Visit {} and enter {}", auth.verification_uri, auth.user_code);
+//! let credentials = flow.poll(&client, &auth).await.unwrap();
+//! # })
+//! ```
+//!
+//! [RFC 8628]: https://datatracker.ietf.org/doc/html/rfc8628
+//!
+//! ## Bearer Tokens
+//!
+//! An [OAuth2Middleware] wraps a client and sends every request with an
+//! `Authorization: Bearer` header containing the current access token. The
+//! token is fetched from a [TokenSource] before the first request and again
+//! once it expires. Requests rejected with `401 Unauthorized` invalidate the
+//! token and are resent once with a fresh token from the source. Concurrent
+//! requests share a single refresh, see [RefreshLock].
+//!
+//! ```
+//! use rustify::{
+//!     clients::reqwest::Client, credentials::Credentials, errors::ClientError,
+//!     oauth::OAuth2Middleware,
+//! };
+//!
+//! async fn fetch_token() -> Result<Credentials, ClientError> {
+//!     // Exchange a refresh token or client credentials here...
+//!     Ok(Credentials::new("token"))
+//! }
+//!
+//! let client = OAuth2Middleware::new(Client::default("https://api.example.com"), fetch_token);
+//! ```
+
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{
+    auth::RefreshLock,
+    client::Client,
+    config::ClientConfig,
+    credentials::Credentials,
+    enums::{RequestMethod, ResponseType},
+    errors::ClientError,
+    http::{build_header_value, build_presigned_request, copy_request, parse},
+};
+use async_trait::async_trait;
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    HeaderValue, Request, Response, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// The interval added to the polling interval when the server responds with
+/// `slow_down`.
+const SLOW_DOWN: Duration = Duration::from_secs(5);
+
+/// The device code returned when starting a [DeviceFlow].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct DeviceAuthorization {
+    pub device_code: String,
+    /// The code the user enters at the verification URL
+    pub user_code: String,
+    /// The URL the user visits to approve the request
+    pub verification_uri: String,
+    /// The verification URL with the user code already filled in, if the
+    /// server provides one
+    #[serde(default)]
+    pub verification_uri_complete: Option<String>,
+    /// The number of seconds the device code is valid for
+    pub expires_in: u64,
+    /// The minimum number of seconds to wait between polls
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+}
+
+fn default_interval() -> u64 {
+    5
+}
+
+#[derive(Serialize)]
+struct DeviceRequest<'a> {
+    client_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TokenRequest<'a> {
+    grant_type: &'a str,
+    device_code: &'a str,
+    client_id: &'a str,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    #[serde(default)]
+    error_description: Option<String>,
+}
+
+/// The result of polling the token endpoint once.
+enum Poll {
+    Done(Credentials),
+    Pending,
+    SlowDown,
+}
+
+/// Implements the OAuth 2.0 device authorization grant.
+#[derive(Clone, Debug)]
+pub struct DeviceFlow {
+    client_id: String,
+    device_url: String,
+    token_url: String,
+    scopes: Vec<String>,
+}
+
+impl DeviceFlow {
+    /// Returns a new [DeviceFlow] for the given client ID which requests
+    /// device codes from `device_url` and tokens from `token_url`. Both URLs
+    /// must be absolute.
+    pub fn new(client_id: &str, device_url: &str, token_url: &str) -> Self {
+        DeviceFlow {
+            client_id: client_id.to_string(),
+            device_url: device_url.to_string(),
+            token_url: token_url.to_string(),
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Adds a scope to request.
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_string());
+        self
+    }
+
+    /// Requests a device code using the given [Client].
+    #[instrument(skip(self, client), err)]
+    pub async fn start(&self, client: &impl Client) -> Result<DeviceAuthorization, ClientError> {
+        let resp = client.execute(self.device_request()?).await?;
+        parse(resp.body(), ResponseType::JSON)
+    }
+
+    /// Polls the token endpoint using the given [Client] until the user
+    /// approves or denies the request or the device code expires.
+    ///
+    /// A denied or expired request is returned as a [ClientError::ApiError]
+    /// containing the error code returned by the server.
+    #[instrument(skip(self, client, auth), err)]
+    pub async fn poll(
+        &self,
+        client: &impl Client,
+        auth: &DeviceAuthorization,
+    ) -> Result<Credentials, ClientError> {
+        let deadline = Instant::now() + Duration::from_secs(auth.expires_in);
+        let mut interval = Duration::from_secs(auth.interval);
+        loop {
+            let result = client.execute(self.token_request(auth)?).await;
+            match poll_result(result)? {
+                Poll::Done(c) => return Ok(c),
+                Poll::Pending => {}
+                Poll::SlowDown => interval += SLOW_DOWN,
+            }
+            check_deadline(deadline, interval)?;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Requests a device code using the given [BlockingClient].
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client), err)]
+    pub fn start_block(
+        &self,
+        client: &impl BlockingClient,
+    ) -> Result<DeviceAuthorization, ClientError> {
+        let resp = client.execute(self.device_request()?)?;
+        parse(resp.body(), ResponseType::JSON)
+    }
+
+    /// Polls the token endpoint using the given [BlockingClient], see
+    /// [DeviceFlow::poll].
+    #[cfg(feature = "blocking")]
+    #[instrument(skip(self, client, auth), err)]
+    pub fn poll_block(
+        &self,
+        client: &impl BlockingClient,
+        auth: &DeviceAuthorization,
+    ) -> Result<Credentials, ClientError> {
+        let deadline = Instant::now() + Duration::from_secs(auth.expires_in);
+        let mut interval = Duration::from_secs(auth.interval);
+        loop {
+            let result = client.execute(self.token_request(auth)?);
+            match poll_result(result)? {
+                Poll::Done(c) => return Ok(c),
+                Poll::Pending => {}
+                Poll::SlowDown => interval += SLOW_DOWN,
+            }
+            check_deadline(deadline, interval)?;
+            std::thread::sleep(interval);
+        }
+    }
+
+    fn device_request(&self) -> Result<Request<Vec<u8>>, ClientError> {
+        let scope = match self.scopes.is_empty() {
+            true => None,
+            false => Some(self.scopes.join(" ")),
+        };
+        form_request(
+            &self.device_url,
+            &DeviceRequest {
+                client_id: &self.client_id,
+                scope,
+            },
+        )
+    }
+
+    fn token_request(&self, auth: &DeviceAuthorization) -> Result<Request<Vec<u8>>, ClientError> {
+        form_request(
+            &self.token_url,
+            &TokenRequest {
+                grant_type: DEVICE_CODE_GRANT,
+                device_code: &auth.device_code,
+                client_id: &self.client_id,
+            },
+        )
+    }
+}
+
+/// Builds a form encoded `POST` request to the given URL.
+fn form_request(url: &str, form: &impl Serialize) -> Result<Request<Vec<u8>>, ClientError> {
+    let body = serde_urlencoded::to_string(form)
+        .map_err(|e| ClientError::DataParseError { source: e.into() })?;
+    let mut req = build_presigned_request(url, RequestMethod::POST, Some(body.into_bytes()))?;
+    req.headers_mut().insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static("application/x-www-form-urlencoded"),
+    );
+    Ok(req)
+}
+
+/// Interprets the result of polling the token endpoint.
+fn poll_result(result: Result<Response<Vec<u8>>, ClientError>) -> Result<Poll, ClientError> {
+    let content = match result {
+        Ok(resp) => {
+            let token: TokenResponse = parse(resp.body(), ResponseType::JSON)?;
+            let mut credentials = Credentials::new(&token.access_token);
+            credentials.refresh_token = token.refresh_token;
+            if let Some(e) = token.expires_in {
+                credentials = credentials.expires_in(Duration::from_secs(e));
+            }
+            return Ok(Poll::Done(credentials));
+        }
+        Err(ClientError::ServerResponseError {
+            code: 400 | 401,
+            content: Some(content),
+        }) => content,
+        Err(e) => return Err(e),
+    };
+    let error: ErrorResponse =
+        serde_json::from_str(&content).map_err(|e| ClientError::ResponseParseError {
+            source: e.into(),
+            content: Some(content.clone()),
+        })?;
+    match error.error.as_str() {
+        "authorization_pending" => Ok(Poll::Pending),
+        "slow_down" => Ok(Poll::SlowDown),
+        _ => Err(ClientError::ApiError {
+            message: match error.error_description {
+                Some(d) => format!("{}: {}", error.error, d),
+                None => error.error,
+            },
+        }),
+    }
+}
+
+/// Fails if the device code expires before the next poll.
+fn check_deadline(deadline: Instant, interval: Duration) -> Result<(), ClientError> {
+    match Instant::now() + interval > deadline {
+        true => Err(ClientError::ApiError {
+            message: "expired_token: The device code expired before it was approved".to_string(),
+        }),
+        false => Ok(()),
+    }
+}
+
+/// Provides the access tokens sent by an [OAuth2Middleware].
+///
+/// Any type implementing `Fn() -> Future<Output = Result<Credentials,
+/// ClientError>>` can be used as a token source.
+#[async_trait]
+pub trait TokenSource: Send + Sync {
+    /// Returns new [Credentials], i.e. by exchanging a refresh token.
+    async fn token(&self) -> Result<Credentials, ClientError>;
+}
+
+#[async_trait]
+impl<F, Fut> TokenSource for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<Credentials, ClientError>> + Send,
+{
+    async fn token(&self) -> Result<Credentials, ClientError> {
+        self().await
+    }
+}
+
+/// A [Client] which wraps another [Client] and authenticates every request
+/// with a bearer token from a [TokenSource], see the
+/// [module documentation][self#bearer-tokens].
+#[derive(Debug)]
+pub struct OAuth2Middleware<C, S> {
+    client: C,
+    credentials: RefreshLock<Credentials>,
+    source: S,
+}
+
+impl<C: Client, S: TokenSource> OAuth2Middleware<C, S> {
+    /// Returns a new [OAuth2Middleware] which sends requests using the given
+    /// client and fetches tokens from the given source.
+    pub fn new(client: C, source: S) -> Self {
+        OAuth2Middleware {
+            client,
+            credentials: RefreshLock::new(),
+            source,
+        }
+    }
+
+    /// Uses the given [Credentials] until they expire or are rejected instead
+    /// of fetching a token before the first request.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = RefreshLock::with_value(credentials);
+        self
+    }
+
+    /// Returns the wrapped client.
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// Returns the current credentials, if any, without refreshing them.
+    pub async fn credentials(&self) -> Option<Credentials> {
+        self.credentials.get().await
+    }
+
+    /// Returns the current credentials, fetching new ones from the source if
+    /// there are none or they're expired.
+    async fn current(&self) -> Result<Credentials, ClientError> {
+        self.credentials
+            .get_or_refresh(|c| !c.is_expired(), || self.source.token())
+            .await
+    }
+
+    /// Sends a copy of the given request authenticated with the given
+    /// credentials.
+    async fn send_with(
+        &self,
+        req: &Request<Vec<u8>>,
+        credentials: &Credentials,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let mut req = copy_request(req);
+        let mut value = build_header_value(
+            &AUTHORIZATION,
+            &format!("Bearer {}", credentials.access_token),
+        )?;
+        value.set_sensitive(true);
+        req.headers_mut().insert(AUTHORIZATION, value);
+        self.client.send(req).await
+    }
+}
+
+#[async_trait]
+impl<C: Client, S: TokenSource> Client for OAuth2Middleware<C, S> {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        let credentials = self.current().await?;
+        let resp = self.send_with(&req, &credentials).await?;
+        if resp.status() != StatusCode::UNAUTHORIZED {
+            return Ok(resp);
+        }
+        info!(
+            "Refreshing the access token after {} {} was rejected",
+            req.method(),
+            req.uri()
+        );
+        self.credentials.invalidate(&credentials).await;
+        let credentials = self.current().await?;
+        self.send_with(&req, &credentials).await
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
@@ -0,0 +1,169 @@
+//! Contains helper types for sending JSON Merge Patch ([RFC 7396][1]) and
+//! JSON Patch ([RFC 6902][2]) request bodies.
+//!
+//! When the only body field of an endpoint is a [MergePatch] or [JsonPatch],
+//! the `Endpoint` derive macro serializes the field as the entire request body
+//! and defaults the request type to
+//! [RequestType::JSON_MERGE_PATCH][crate::enums::RequestType::JSON_MERGE_PATCH]
+//! or [RequestType::JSON_PATCH][crate::enums::RequestType::JSON_PATCH]
+//! respectively, which sends the matching `Content-Type` header.
+//!
+//! # Example
+//! ```
+//! use rustify::patch::JsonPatch;
+//! use rustify_derive::Endpoint;
+//! use serde_json::json;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "users/{self.id}", method = "PATCH")]
+//! struct PatchUser {
+//!     #[endpoint(skip)]
+//!     id: u64,
+//!     patch: JsonPatch,
+//! }
+//!
+//! let endpoint = PatchUser {
+//!     id: 1,
+//!     patch: JsonPatch::new()
+//!         .test("/version", json!(3))
+//!         .replace("/name", json!("test"))
+//!         .remove("/nickname"),
+//! };
+//! ```
+//!
+//! [1]: https://datatracker.ietf.org/doc/html/rfc7396
+//! [2]: https://datatracker.ietf.org/doc/html/rfc6902
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A JSON Merge Patch document.
+///
+/// The wrapped value is serialized as-is. Fields set to `null` are removed
+/// from the target and missing fields are left unchanged, so the wrapped type
+/// must only serialize `None` values as `null` for fields which should be
+/// removed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct MergePatch<T>(pub T);
+
+impl MergePatch<Value> {
+    /// Returns the merge patch which transforms `original` into `updated`.
+    pub fn diff(original: &Value, updated: &Value) -> Self {
+        MergePatch(diff(original, updated))
+    }
+}
+
+fn diff(original: &Value, updated: &Value) -> Value {
+    match (original, updated) {
+        (Value::Object(o), Value::Object(u)) => {
+            let mut patch = Map::new();
+            for key in o.keys().filter(|k| !u.contains_key(*k)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, value) in u.iter() {
+                match o.get(key) {
+                    Some(v) if v == value => {}
+                    Some(v) => {
+                        patch.insert(key.clone(), diff(v, value));
+                    }
+                    None => {
+                        patch.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(patch)
+        }
+        (_, u) => u.clone(),
+    }
+}
+
+/// A single JSON Patch operation.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+/// A JSON Patch document consisting of an ordered list of
+/// [PatchOperation]s.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct JsonPatch(pub Vec<PatchOperation>);
+
+impl JsonPatch {
+    /// Returns a new, empty [JsonPatch].
+    pub fn new() -> Self {
+        JsonPatch::default()
+    }
+
+    /// Adds the given value at the given path.
+    pub fn add(self, path: &str, value: impl Into<Value>) -> Self {
+        self.op(PatchOperation::Add {
+            path: path.to_string(),
+            value: value.into(),
+        })
+    }
+
+    /// Removes the value at the given path.
+    pub fn remove(self, path: &str) -> Self {
+        self.op(PatchOperation::Remove {
+            path: path.to_string(),
+        })
+    }
+
+    /// Replaces the value at the given path.
+    pub fn replace(self, path: &str, value: impl Into<Value>) -> Self {
+        self.op(PatchOperation::Replace {
+            path: path.to_string(),
+            value: value.into(),
+        })
+    }
+
+    /// Moves the value at `from` to `path`.
+    pub fn move_to(self, from: &str, path: &str) -> Self {
+        self.op(PatchOperation::Move {
+            from: from.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Copies the value at `from` to `path`.
+    pub fn copy(self, from: &str, path: &str) -> Self {
+        self.op(PatchOperation::Copy {
+            from: from.to_string(),
+            path: path.to_string(),
+        })
+    }
+
+    /// Tests that the value at the given path equals the given value. The
+    /// entire patch fails to apply if the test fails.
+    pub fn test(self, path: &str, value: impl Into<Value>) -> Self {
+        self.op(PatchOperation::Test {
+            path: path.to_string(),
+            value: value.into(),
+        })
+    }
+
+    /// Appends the given operation.
+    pub fn op(mut self, op: PatchOperation) -> Self {
+        self.0.push(op);
+        self
+    }
+}
+
+/// Returns a JSON Pointer ([RFC 6901][1]) to the value at the given path,
+/// escaping `~` and `/` in each segment.
+///
+/// [1]: https://datatracker.ietf.org/doc/html/rfc6901
+pub fn pointer(segments: &[&str]) -> String {
+    segments
+        .iter()
+        .map(|s| format!("/{}", s.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
@@ -1 +1,22 @@
+pub use http;
 pub use serde;
+
+use serde::de::DeserializeOwned;
+
+/// Implemented by every type which can be used as the response of an
+/// [Endpoint][crate::endpoint::Endpoint].
+///
+/// This exists to replace the errors about unsatisfied `Deserialize` bounds
+/// emitted for invalid response types with an explanation.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be used as the response of an endpoint",
+    label = "the response must be deserializable without borrowing from the response body",
+    note = "derive `Deserialize` for `{Self}` and use owned types, i.e. `String` instead of `&str`"
+)]
+pub trait EndpointResponse: DeserializeOwned {}
+
+impl<T: DeserializeOwned> EndpointResponse for T {}
+
+/// Fails to compile if `T` can't be used as the response of an endpoint.
+/// This is never called, it's only referenced by the derive macro.
+pub fn assert_response<T: EndpointResponse>() {}
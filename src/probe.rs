@@ -0,0 +1,174 @@
+//! Contains the [HealthCheckEndpoint] and [ProbeReport] types used by
+//! [Client::probe][crate::client::Client::probe] to verify a client is
+//! correctly configured before sending real traffic.
+//!
+//! A probe sends a single request to the given path and translates any
+//! failure into a [ClientError::ProbeError] describing the likely cause, i.e.
+//! an invalid base URL, an unreachable host, a TLS failure, or rejected
+//! credentials.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::{client::Client as _, clients::reqwest::Client};
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("https://myapi.com");
+//! match client.probe("health").await {
+//!     Ok(report) => println!("{} responded in {:?}", report.url, report.latency),
+//!     Err(e) => panic!("{}", e),
+//! }
+//! # })
+//! ```
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    endpoint::Endpoint,
+    enums::{RequestMethod, RequestType, ResponseType},
+    errors::ClientError,
+    tls::TlsInfo,
+};
+use http::{Request, Response};
+
+/// An [Endpoint] which sends an empty request to a fixed path and ignores the
+/// response body.
+#[derive(Clone, Debug)]
+pub struct HealthCheckEndpoint {
+    pub path: String,
+    pub method: RequestMethod,
+}
+
+impl HealthCheckEndpoint {
+    /// Returns a new [HealthCheckEndpoint] which sends a `GET` request to the
+    /// given path.
+    pub fn new(path: &str) -> Self {
+        HealthCheckEndpoint {
+            path: path.to_string(),
+            method: RequestMethod::GET,
+        }
+    }
+
+    /// Sets the HTTP method used for the health check, i.e. `HEAD`.
+    pub fn method(mut self, method: RequestMethod) -> Self {
+        self.method = method;
+        self
+    }
+}
+
+impl Endpoint for HealthCheckEndpoint {
+    type Response = ();
+    const REQUEST_BODY_TYPE: RequestType = RequestType::JSON;
+    const RESPONSE_BODY_TYPE: ResponseType = ResponseType::JSON;
+
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn method(&self) -> RequestMethod {
+        self.method.clone()
+    }
+
+    fn name(&self) -> &'static str {
+        "health_check"
+    }
+}
+
+/// The result of a successful probe.
+#[derive(Clone, Debug)]
+pub struct ProbeReport {
+    /// The URL which was probed
+    pub url: String,
+    /// The status code the server responded with
+    pub status: u16,
+    /// The time taken to receive the response
+    pub latency: Duration,
+    /// The TLS session the response was received over, if available
+    pub tls: Option<TlsInfo>,
+}
+
+/// Validates the base URL and builds the request sent by a probe.
+pub(crate) fn prepare(
+    base: &str,
+    endpoint: &HealthCheckEndpoint,
+) -> Result<Request<Vec<u8>>, ClientError> {
+    let fail = |diagnosis: String, source: anyhow::Error| ClientError::ProbeError {
+        url: base.to_string(),
+        diagnosis,
+        source,
+    };
+    let url = url::Url::parse(base).map_err(|e| {
+        fail(
+            format!("the base URL `{}` is not a valid absolute URL", base),
+            e.into(),
+        )
+    })?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(fail(
+            format!("the base URL uses an unsupported `{}` scheme", url.scheme()),
+            anyhow::anyhow!("expected http or https"),
+        ));
+    }
+    endpoint.request(base).map_err(|e| {
+        fail(
+            "the health check request could not be built".into(),
+            e.into(),
+        )
+    })
+}
+
+/// Converts the result of a probe into a [ProbeReport], diagnosing the cause
+/// of any failure.
+pub(crate) fn report(
+    req_url: String,
+    start: Instant,
+    result: Result<Response<Vec<u8>>, ClientError>,
+) -> Result<ProbeReport, ClientError> {
+    match result {
+        Ok(resp) => Ok(ProbeReport {
+            url: req_url,
+            status: resp.status().as_u16(),
+            latency: start.elapsed(),
+            tls: resp.extensions().get::<TlsInfo>().cloned(),
+        }),
+        Err(e) => Err(ClientError::ProbeError {
+            diagnosis: diagnose(&e),
+            url: req_url,
+            source: e.into(),
+        }),
+    }
+}
+
+/// Returns a description of the likely cause of a failed probe.
+fn diagnose(err: &ClientError) -> String {
+    match err {
+        ClientError::ServerResponseError { code: 401, .. } => {
+            "the server rejected the credentials (401), check the configured authentication".into()
+        }
+        ClientError::ServerResponseError { code: 403, .. } => {
+            "the credentials lack permission (403), check the configured scopes or roles".into()
+        }
+        ClientError::ServerResponseError { code: 404, .. } => {
+            "the path was not found (404), check the base URL includes any path prefix".into()
+        }
+        ClientError::ServerResponseError { code, .. } if *code >= 500 => {
+            format!("the server is unhealthy ({})", code)
+        }
+        ClientError::ServerResponseError { code, .. } => {
+            format!("the server responded with an unexpected status ({})", code)
+        }
+        ClientError::RequestError { source, .. } => {
+            let chain = format!("{:#}", source).to_lowercase();
+            let reqwest = source.downcast_ref::<reqwest::Error>();
+            if chain.contains("certificate") || chain.contains("tls") || chain.contains("ssl") {
+                "the TLS handshake failed, check the server certificate and configured roots".into()
+            } else if reqwest.map(|e| e.is_timeout()).unwrap_or(false) {
+                "the request timed out, check the host is reachable".into()
+            } else if reqwest.map(|e| e.is_connect()).unwrap_or(false) {
+                "could not connect to the host, check the base URL and network".into()
+            } else {
+                "the request could not be sent".into()
+            }
+        }
+        _ => "the request failed".into(),
+    }
+}
@@ -0,0 +1,201 @@
+//! Contains the [Profiles] type for grouping client settings into named
+//! environments like `prod`, `staging`, or `local`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::{
+    clients::reqwest::Client,
+    dns::{IpPreference, IpResolver},
+    errors::ClientError,
+};
+use http::{HeaderMap, HeaderName, HeaderValue};
+
+/// The TLS options applied to clients built from a [Profile].
+///
+/// These options are only applied when a TLS backend is enabled through the
+/// `default` or `rustls-tls` features.
+#[derive(Clone, Debug, Default)]
+pub struct TlsOptions {
+    /// Accept invalid certificates, for example self-signed certificates in a
+    /// local environment. This should never be enabled in production.
+    pub accept_invalid_certs: bool,
+    /// Additional PEM encoded root certificates to trust
+    pub root_certificates: Vec<Vec<u8>>,
+}
+
+/// The settings for a single environment.
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub base: String,
+    pub headers: HeaderMap,
+    pub ip: IpPreference,
+    pub tls: TlsOptions,
+}
+
+impl Profile {
+    /// Returns a new [Profile] with the given base URL.
+    pub fn new(base: &str) -> Self {
+        Profile {
+            base: base.to_string(),
+            headers: HeaderMap::new(),
+            ip: IpPreference::default(),
+            tls: TlsOptions::default(),
+        }
+    }
+
+    /// Adds a header which is sent with every request.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets which IP versions clients built from this profile connect over.
+    pub fn ip_preference(mut self, ip: IpPreference) -> Self {
+        self.ip = ip;
+        self
+    }
+
+    /// Sets the [TlsOptions] used by clients built from this profile.
+    pub fn tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Builds a new [Client] configured with this profile.
+    pub fn client(&self) -> Result<Client, ClientError> {
+        #[cfg(any(feature = "default", feature = "rustls-tls"))]
+        let (certs, accept_invalid_certs) = (self.certificates()?, self.tls.accept_invalid_certs);
+        let (headers, ip) = (self.headers.clone(), self.ip);
+        Client::from_builder(&self.base, move || {
+            let mut builder = reqwest::Client::builder().default_headers(headers.clone());
+            if ip != IpPreference::Any {
+                builder = builder.dns_resolver(Arc::new(IpResolver::new(ip)));
+            }
+            #[cfg(any(feature = "default", feature = "rustls-tls"))]
+            let builder = certs.iter().fold(
+                builder.danger_accept_invalid_certs(accept_invalid_certs),
+                |builder, cert| builder.add_root_certificate(cert.clone()),
+            );
+            builder
+        })
+    }
+
+    /// Builds a new blocking [Client][crate::blocking::clients::reqwest::Client]
+    /// configured with this profile.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client(
+        &self,
+    ) -> Result<crate::blocking::clients::reqwest::Client, ClientError> {
+        #[cfg(any(feature = "default", feature = "rustls-tls"))]
+        let (certs, accept_invalid_certs) = (self.certificates()?, self.tls.accept_invalid_certs);
+        let (headers, ip) = (self.headers.clone(), self.ip);
+        crate::blocking::clients::reqwest::Client::from_builder(&self.base, move || {
+            let mut builder = reqwest::blocking::Client::builder().default_headers(headers.clone());
+            if ip != IpPreference::Any {
+                builder = builder.dns_resolver(Arc::new(IpResolver::new(ip)));
+            }
+            #[cfg(any(feature = "default", feature = "rustls-tls"))]
+            let builder = certs.iter().fold(
+                builder.danger_accept_invalid_certs(accept_invalid_certs),
+                |builder, cert| builder.add_root_certificate(cert.clone()),
+            );
+            builder
+        })
+    }
+
+    /// Parses the root certificates of this profile.
+    #[cfg(any(feature = "default", feature = "rustls-tls"))]
+    fn certificates(&self) -> Result<Vec<reqwest::Certificate>, ClientError> {
+        self.tls
+            .root_certificates
+            .iter()
+            .map(|cert| {
+                reqwest::Certificate::from_pem(cert)
+                    .map_err(|e| ClientError::ReqwestBuildError { source: e })
+            })
+            .collect()
+    }
+}
+
+/// A set of named [Profile]s which clients can be built from.
+///
+/// SDKs can ship their known environments as profiles and let users pick one
+/// by name, optionally through an environment variable.
+///
+/// # Example
+/// ```
+/// use rustify::profiles::{Profile, Profiles};
+///
+/// let profiles = Profiles::new()
+///     .profile("prod", Profile::new("https://api.example.com"))
+///     .profile("staging", Profile::new("https://staging.api.example.com"))
+///     .profile("local", Profile::new("http://localhost:8080"))
+///     .default_profile("prod");
+///
+/// // Uses the profile named by `EXAMPLE_ENV`, falling back to "prod"
+/// let client = profiles.client_from_env("EXAMPLE_ENV").unwrap();
+/// assert_eq!(client.base, "https://api.example.com");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Profiles {
+    profiles: BTreeMap<String, Profile>,
+    default: Option<String>,
+}
+
+impl Profiles {
+    /// Returns a new, empty [Profiles].
+    pub fn new() -> Self {
+        Profiles::default()
+    }
+
+    /// Adds a [Profile] with the given name.
+    pub fn profile(mut self, name: &str, profile: Profile) -> Self {
+        self.profiles.insert(name.to_string(), profile);
+        self
+    }
+
+    /// Sets the name of the profile used when none is selected.
+    pub fn default_profile(mut self, name: &str) -> Self {
+        self.default = Some(name.to_string());
+        self
+    }
+
+    /// Returns the [Profile] with the given name.
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Returns the names of all profiles.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.profiles.keys().map(String::as_str)
+    }
+
+    /// Returns the [Profile] with the given name or the default profile if
+    /// no name is given.
+    pub fn select(&self, name: Option<&str>) -> Result<&Profile, ClientError> {
+        let name = match name.or(self.default.as_deref()) {
+            Some(n) => n,
+            None => {
+                return Err(ClientError::ProfileError {
+                    name: String::new(),
+                })
+            }
+        };
+        self.get(name).ok_or_else(|| ClientError::ProfileError {
+            name: name.to_string(),
+        })
+    }
+
+    /// Builds a [Client] from the profile with the given name.
+    pub fn client(&self, name: &str) -> Result<Client, ClientError> {
+        self.select(Some(name))?.client()
+    }
+
+    /// Builds a [Client] from the profile named by the given environment
+    /// variable, or the default profile if the variable isn't set.
+    pub fn client_from_env(&self, var: &str) -> Result<Client, ClientError> {
+        let name = std::env::var(var).ok();
+        self.select(name.as_deref())?.client()
+    }
+}
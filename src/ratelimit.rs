@@ -0,0 +1,182 @@
+//! Contains the [RateLimitInfo] type for reading the rate limit state
+//! reported by a server.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant, SystemTime},
+};
+
+use http::{header::RETRY_AFTER, HeaderMap, Uri};
+
+/// Reset values above this are treated as Unix timestamps instead of a number
+/// of seconds.
+const EPOCH_THRESHOLD: u64 = 1_000_000_000;
+
+/// The rate limit state reported in the headers of a response.
+///
+/// The following header families are supported:
+/// * `X-RateLimit-Limit`, `X-RateLimit-Remaining`, and `X-RateLimit-Reset`
+/// * `RateLimit-Limit`, `RateLimit-Remaining`, and `RateLimit-Reset` from the
+///   IETF draft
+/// * A combined `RateLimit` header, i.e. `limit=100, remaining=50, reset=30`
+///   or `r=50;t=30`
+/// * `Retry-After`, either as a number of seconds or an HTTP date
+///
+/// Reset values may either be a number of seconds or a Unix timestamp, both
+/// of which are converted into the time remaining until the reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    /// The maximum number of requests allowed in the current window
+    pub limit: Option<u64>,
+    /// The number of requests remaining in the current window
+    pub remaining: Option<u64>,
+    /// The time until the current window resets
+    pub reset: Option<Duration>,
+    /// The time the server asked clients to wait before retrying
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    /// Parses the rate limit state from the given headers, returning [None]
+    /// if no rate limit headers are present.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let get = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|n| headers.get(*n).and_then(|v| v.to_str().ok()))
+                .and_then(|v| v.trim().parse::<u64>().ok())
+        };
+        let mut info = RateLimitInfo {
+            limit: get(&["x-ratelimit-limit", "ratelimit-limit"]),
+            remaining: get(&["x-ratelimit-remaining", "ratelimit-remaining"]),
+            reset: get(&["x-ratelimit-reset", "ratelimit-reset"]).map(reset),
+            retry_after: headers
+                .get(RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .and_then(retry_after),
+        };
+
+        if let Some(combined) = headers.get("ratelimit").and_then(|v| v.to_str().ok()) {
+            for param in combined.split([',', ';']) {
+                let (key, value) = match param.split_once('=') {
+                    Some((k, v)) => (k.trim(), v.trim().trim_matches('"')),
+                    None => continue,
+                };
+                let value = match value.parse::<u64>() {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+                match key {
+                    "limit" => info.limit = info.limit.or(Some(value)),
+                    "remaining" | "r" => info.remaining = info.remaining.or(Some(value)),
+                    "reset" | "t" => info.reset = info.reset.or(Some(reset(value))),
+                    _ => {}
+                }
+            }
+        }
+
+        match info == RateLimitInfo::default() {
+            true => None,
+            false => Some(info),
+        }
+    }
+
+    /// Returns whether the server reported no requests remaining.
+    pub fn is_exhausted(&self) -> bool {
+        self.remaining == Some(0)
+    }
+
+    /// Returns how long to wait before sending another request, if the
+    /// server asked clients to wait or the current window is exhausted.
+    pub fn wait_time(&self) -> Option<Duration> {
+        match (self.retry_after, self.is_exhausted()) {
+            (Some(d), _) => Some(d),
+            (None, true) => self.reset,
+            (None, false) => None,
+        }
+    }
+}
+
+/// The rate limit state last reported by each host, along with when it was
+/// reported.
+type Hosts = Arc<Mutex<HashMap<String, (RateLimitInfo, Instant)>>>;
+
+/// Keeps the most recent [RateLimitInfo] reported by each host.
+///
+/// A [RateLimitTracker] configured with
+/// [ClientConfig::track_rate_limits][crate::config::ClientConfig::track_rate_limits]
+/// records the rate limit headers of every response and is shared by all
+/// clones of the configuration.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitTracker {
+    hosts: Hosts,
+}
+
+impl RateLimitTracker {
+    /// Returns a new, empty [RateLimitTracker].
+    pub fn new() -> Self {
+        RateLimitTracker::default()
+    }
+
+    /// Records the rate limit state in the given headers for the host of the
+    /// given [Uri]. Headers without any rate limit state are ignored.
+    pub fn record(&self, uri: &Uri, headers: &HeaderMap) {
+        if let Some(info) = RateLimitInfo::from_headers(headers) {
+            self.lock().insert(host(uri), (info, Instant::now()));
+        }
+    }
+
+    /// Returns the rate limit state last reported by the given host, with
+    /// the reset and retry times adjusted for the time since it was reported.
+    pub fn get(&self, host: &str) -> Option<RateLimitInfo> {
+        self.lock().get(host).map(|(i, at)| elapse(i, at.elapsed()))
+    }
+
+    /// Returns the rate limit state last reported by every host.
+    pub fn snapshot(&self) -> HashMap<String, RateLimitInfo> {
+        self.lock()
+            .iter()
+            .map(|(h, (i, at))| (h.clone(), elapse(i, at.elapsed())))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, (RateLimitInfo, Instant)>> {
+        self.hosts.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the key used for the host of the given [Uri].
+fn host(uri: &Uri) -> String {
+    uri.authority().map(|a| a.to_string()).unwrap_or_default()
+}
+
+/// Returns the given state with its durations reduced by the elapsed time.
+fn elapse(info: &RateLimitInfo, elapsed: Duration) -> RateLimitInfo {
+    RateLimitInfo {
+        reset: info.reset.map(|d| d.saturating_sub(elapsed)),
+        retry_after: info.retry_after.map(|d| d.saturating_sub(elapsed)),
+        ..*info
+    }
+}
+
+/// Converts a reset value into the time remaining until the reset.
+fn reset(value: u64) -> Duration {
+    match value > EPOCH_THRESHOLD {
+        true => until(SystemTime::UNIX_EPOCH + Duration::from_secs(value)),
+        false => Duration::from_secs(value),
+    }
+}
+
+/// Parses a `Retry-After` value.
+fn retry_after(value: &str) -> Option<Duration> {
+    match value.trim().parse::<u64>() {
+        Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => httpdate::parse_http_date(value.trim()).ok().map(until),
+    }
+}
+
+/// Returns the time remaining until the given time, or zero if it's passed.
+fn until(time: SystemTime) -> Duration {
+    time.duration_since(SystemTime::now()).unwrap_or_default()
+}
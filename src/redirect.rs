@@ -0,0 +1,142 @@
+//! Contains the [Redirect] type for capturing the redirects followed while
+//! executing a request.
+//!
+//! Redirects are followed by rustify itself when configured with
+//! [ClientConfig::follow_redirects][crate::config::ClientConfig::follow_redirects],
+//! which records every hop in the [Redirects] extension of the final
+//! response. Use [EndpointResult::redirects][crate::endpoint::EndpointResult::redirects]
+//! to access them. The backing HTTP client must not follow redirects itself,
+//! otherwise rustify never sees them, i.e. build the reqwest client with
+//! `redirect(reqwest::redirect::Policy::none())`.
+//!
+//! Each redirect target is checked against the configured
+//! [UrlGuard][crate::guard::UrlGuard]. The `Authorization`, `Cookie`, and
+//! `Proxy-Authorization` headers are dropped when a redirect leads to a
+//! different host or downgrades from `https` to `http`.
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{client::Client, config::ClientConfig, errors::ClientError, http::copy_request};
+use http::{
+    header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, LOCATION, PROXY_AUTHORIZATION},
+    Method, Request, Response, StatusCode, Uri,
+};
+use url::Url;
+
+/// A single redirect which was followed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redirect {
+    /// The URL which responded with the redirect
+    pub url: String,
+    /// The status code of the redirect response
+    pub status: u16,
+    /// The URL the redirect pointed to
+    pub location: String,
+}
+
+/// The redirects followed before receiving a response, in the order they
+/// were followed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Redirects(pub Vec<Redirect>);
+
+/// Sends the given [Request] using the given [Client], following up to `max`
+/// redirects.
+pub(crate) async fn follow<C: Client + ?Sized>(
+    client: &C,
+    mut req: Request<Vec<u8>>,
+    max: usize,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let mut chain = Vec::new();
+    loop {
+        let sent = copy_request(&req);
+        let resp = crate::client::send(client, req).await?;
+        match next(client.config(), &sent, &resp, &mut chain, max)? {
+            Some(r) => req = r,
+            None => return Ok(finish(resp, chain)),
+        }
+    }
+}
+
+/// Sends the given [Request] using the given [BlockingClient], following up
+/// to `max` redirects.
+#[cfg(feature = "blocking")]
+pub(crate) fn follow_blocking<C: BlockingClient + ?Sized>(
+    client: &C,
+    mut req: Request<Vec<u8>>,
+    max: usize,
+) -> Result<Response<Vec<u8>>, ClientError> {
+    let mut chain = Vec::new();
+    loop {
+        let sent = copy_request(&req);
+        let resp = crate::blocking::client::send(client, req)?;
+        match next(client.config(), &sent, &resp, &mut chain, max)? {
+            Some(r) => req = r,
+            None => return Ok(finish(resp, chain)),
+        }
+    }
+}
+
+fn finish(mut resp: Response<Vec<u8>>, chain: Vec<Redirect>) -> Response<Vec<u8>> {
+    if !chain.is_empty() {
+        resp.extensions_mut().insert(Redirects(chain));
+    }
+    resp
+}
+
+/// Returns the [Request] to send next if the response is a redirect,
+/// recording the redirect in the chain.
+fn next(
+    config: &ClientConfig,
+    sent: &Request<Vec<u8>>,
+    resp: &Response<Vec<u8>>,
+    chain: &mut Vec<Redirect>,
+    max: usize,
+) -> Result<Option<Request<Vec<u8>>>, ClientError> {
+    let status = resp.status();
+    if !matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308) {
+        return Ok(None);
+    }
+    let location = match resp.headers().get(LOCATION).and_then(|l| l.to_str().ok()) {
+        Some(l) => l,
+        None => return Ok(None),
+    };
+    let url = sent.uri().to_string();
+    let target = Url::parse(&url)
+        .and_then(|u| u.join(location))
+        .map_err(|e| ClientError::UrlParseError { source: e })?;
+    if chain.len() >= max {
+        return Err(ClientError::ResponseError {
+            source: anyhow::anyhow!("Too many redirects, stopped at {}", target),
+        });
+    }
+    debug!("Following {} redirect to {}", status.as_u16(), target);
+    chain.push(Redirect {
+        url,
+        status: status.as_u16(),
+        location: target.to_string(),
+    });
+
+    let uri = target
+        .as_str()
+        .parse::<Uri>()
+        .map_err(|e| ClientError::UrlBuildError { source: e })?;
+    let mut req = copy_request(sent);
+    // Only 307 and 308 preserve the method and body of a non-GET request
+    let to_get = status == StatusCode::SEE_OTHER && sent.method() != Method::HEAD
+        || matches!(status.as_u16(), 301 | 302) && sent.method() == Method::POST;
+    if to_get {
+        *req.method_mut() = Method::GET;
+        req.body_mut().clear();
+        req.headers_mut().remove(CONTENT_TYPE);
+        req.headers_mut().remove(CONTENT_LENGTH);
+    }
+    let downgrade = sent.uri().scheme_str() == Some("https") && uri.scheme_str() == Some("http");
+    if downgrade || uri.authority() != sent.uri().authority() {
+        req.headers_mut().remove(AUTHORIZATION);
+        req.headers_mut().remove(COOKIE);
+        req.headers_mut().remove(PROXY_AUTHORIZATION);
+    }
+    *req.uri_mut() = uri;
+    config.check_url(&req)?;
+    Ok(Some(req))
+}
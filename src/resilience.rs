@@ -0,0 +1,62 @@
+//! Contains the [ResilienceState] type describing the state of the limits a
+//! [Client][crate::client::Client] applies to its requests.
+//!
+//! A snapshot is returned by
+//! [Client::resilience_state][crate::client::Client::resilience_state] and is
+//! intended for exporting metrics or implementing admission control, i.e.
+//! shedding background work while requests are queued.
+
+use std::collections::HashMap;
+
+use crate::ratelimit::RateLimitInfo;
+
+/// A snapshot of the limits applied by a [ClientConfig][crate::config::ClientConfig].
+///
+/// Each field is [None] or empty when the corresponding limit isn't
+/// configured.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResilienceState {
+    /// The state of the [AdaptiveLimit][crate::adaptive::AdaptiveLimit]
+    pub adaptive: Option<AdaptiveState>,
+    /// The state of the [ConcurrencyLimit][crate::limit::ConcurrencyLimit]
+    pub concurrency: Option<ConcurrencyState>,
+    /// The state of the [Scheduler][crate::scheduler::Scheduler]
+    pub scheduler: Option<SchedulerState>,
+    /// The rate limit state last reported by each host, keyed by authority,
+    /// when tracked by a [RateLimitTracker][crate::ratelimit::RateLimitTracker]
+    pub rate_limits: HashMap<String, RateLimitInfo>,
+}
+
+impl ResilienceState {
+    /// Returns the total number of requests waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.adaptive.as_ref().map(|a| a.queued).unwrap_or(0)
+            + self.concurrency.as_ref().map(|c| c.queued).unwrap_or(0)
+            + self.scheduler.as_ref().map(|s| s.queued).unwrap_or(0)
+    }
+}
+
+/// The state of an [AdaptiveLimit][crate::adaptive::AdaptiveLimit].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AdaptiveState {
+    pub limit: usize,
+    pub in_flight: usize,
+    pub queued: usize,
+}
+
+/// The state of a [ConcurrencyLimit][crate::limit::ConcurrencyLimit].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConcurrencyState {
+    pub max_total: Option<usize>,
+    pub max_per_host: Option<usize>,
+    pub in_flight: usize,
+    pub queued: usize,
+}
+
+/// The state of a [Scheduler][crate::scheduler::Scheduler].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SchedulerState {
+    pub max: usize,
+    pub available: usize,
+    pub queued: usize,
+}
@@ -0,0 +1,157 @@
+//! Contains the [RetryPolicy] type for retrying failed executions of
+//! [Endpoints][crate::endpoint::Endpoint].
+//!
+//! A policy configured with
+//! [ClientConfig::retry][crate::config::ClientConfig::retry] is consulted
+//! whenever executing an endpoint fails. Retried executions rebuild the
+//! request from the endpoint and pass it through all middleware again, so
+//! signatures, timestamps, and tokens added by middleware are fresh for every
+//! attempt. Each retry is sent with the next
+//! [Attempt][crate::events::Attempt] number, waits for an
+//! exponentially increasing delay with jitter, and emits an
+//! [Event::RetryScheduled][crate::events::Event::RetryScheduled].
+//!
+//! By default connection errors and responses with a `5xx` status code are
+//! retried, see [RetryPolicy::retry_if] for retrying other errors. Note that
+//! this includes requests which aren't idempotent, such as `POST` requests.
+//!
+//! ## Read repair
+//!
+//! Reads which succeed but aren't consistent with a preceding write yet, such
+//! as a listing which doesn't contain a resource that was just created, can be
+//! repeated with
+//! [Endpoint::exec_read_repair][crate::endpoint::Endpoint::exec_read_repair].
+//! The endpoint is executed again, waiting for the delays of the given policy,
+//! until its parsed response satisfies a predicate.
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, config::ClientConfig, retry::RetryPolicy};
+//! use std::time::Duration;
+//!
+//! let policy = RetryPolicy::new(3)
+//!     .base_delay(Duration::from_millis(200))
+//!     .max_delay(Duration::from_secs(5));
+//! let client = Client::default("https://api.example.com")
+//!     .with_config(ClientConfig::new().retry(policy));
+//! ```
+
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::errors::ClientError;
+
+type Condition = Arc<dyn Fn(&ClientError) -> bool + Send + Sync>;
+
+/// Determines which failed executions are retried and how long to wait
+/// between attempts, see the [module documentation][self].
+#[derive(Clone)]
+pub struct RetryPolicy {
+    base_delay: Duration,
+    condition: Option<Condition>,
+    jitter: bool,
+    max_delay: Duration,
+    max_retries: u32,
+}
+
+impl RetryPolicy {
+    /// Returns a new [RetryPolicy] which retries failed executions up to the
+    /// given number of times, starting with a delay of 100 milliseconds which
+    /// doubles for every retry up to 10 seconds.
+    pub fn new(max_retries: u32) -> Self {
+        RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            condition: None,
+            jitter: true,
+            max_delay: Duration::from_secs(10),
+            max_retries,
+        }
+    }
+
+    /// Sets the delay before the first retry, which doubles for every
+    /// subsequent retry.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Sets the maximum delay between two attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Waits exactly the exponential delay between attempts instead of a
+    /// random delay between half of it and all of it.
+    pub fn without_jitter(mut self) -> Self {
+        self.jitter = false;
+        self
+    }
+
+    /// Retries only errors for which the given predicate returns true instead
+    /// of connection errors and `5xx` responses.
+    pub fn retry_if<F>(mut self, condition: F) -> Self
+    where
+        F: Fn(&ClientError) -> bool + Send + Sync + 'static,
+    {
+        self.condition = Some(Arc::new(condition));
+        self
+    }
+
+    /// Returns the maximum number of retries.
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries
+    }
+
+    /// Returns whether an execution which failed with the given error after
+    /// the given number of retries should be retried.
+    pub fn should_retry(&self, error: &ClientError, retries: u32) -> bool {
+        retries < self.max_retries
+            && match &self.condition {
+                Some(condition) => condition(error),
+                None => is_transient(error),
+            }
+    }
+
+    /// Returns the delay before the given retry, starting at 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        let delay = self.base_delay.saturating_mul(factor).min(self.max_delay);
+        match self.jitter {
+            true => delay / 2 + delay.mul_f64(random() / 2.0),
+            false => delay,
+        }
+    }
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("base_delay", &self.base_delay)
+            .field("jitter", &self.jitter)
+            .field("max_delay", &self.max_delay)
+            .field("max_retries", &self.max_retries)
+            .finish()
+    }
+}
+
+/// Returns whether the given error is a connection error or a response with a
+/// `5xx` status code.
+pub fn is_transient(error: &ClientError) -> bool {
+    match error {
+        ClientError::RequestError { .. } => true,
+        ClientError::ServerResponseError { code, .. } => (500..=599).contains(code),
+        _ => false,
+    }
+}
+
+/// Returns a random number between 0 and 1.
+fn random() -> f64 {
+    let n = RandomState::new().build_hasher().finish();
+    (n >> 11) as f64 / (1u64 << 53) as f64
+}
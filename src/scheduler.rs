@@ -0,0 +1,222 @@
+//! Contains the [Scheduler] type for ordering requests sent by a
+//! [Client][crate::client::Client] by their priority.
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    task::{Context, Poll, Waker},
+};
+
+use crate::enums::RequestPriority;
+
+/// Limits the number of concurrent in-flight requests and, when the limit is
+/// reached, sends waiting requests in order of their [RequestPriority].
+///
+/// Requests with the same priority are sent in the order they were received.
+/// The priority of a request is determined by
+/// [Endpoint::priority][crate::endpoint::Endpoint::priority], which can be
+/// set using `#[endpoint(priority = "HIGH")]` with the derive macro. This
+/// prevents interactive requests from being starved by large batches of
+/// background requests sharing the same client.
+///
+/// A [Scheduler] is shared by all clones of the
+/// [ClientConfig][crate::config::ClientConfig] it belongs to.
+#[derive(Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    max: usize,
+    state: Mutex<State>,
+    ready: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    available: usize,
+    next: u64,
+    queue: BinaryHeap<(RequestPriority, Reverse<u64>)>,
+    wakers: HashMap<u64, Option<Waker>>,
+    granted: Vec<u64>,
+}
+
+impl State {
+    /// Grants permits to the highest priority waiting requests.
+    fn grant(&mut self) {
+        while self.available > 0 {
+            let Reverse(id) = match self.queue.pop() {
+                Some((_, id)) => id,
+                None => break,
+            };
+            self.available -= 1;
+            self.granted.push(id);
+            if let Some(Some(waker)) = self.wakers.remove(&id) {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Removes the permit granted to the given request, if any.
+    fn take(&mut self, id: u64) -> bool {
+        match self.granted.iter().position(|g| *g == id) {
+            Some(i) => {
+                self.granted.swap_remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// A permit held by a request while it's in flight. The permit is released
+/// when this is dropped.
+pub struct Permit {
+    inner: Arc<Inner>,
+}
+
+/// A future which resolves once a request is permitted to be sent.
+struct Acquire {
+    inner: Arc<Inner>,
+    id: u64,
+    done: bool,
+}
+
+impl Scheduler {
+    /// Returns a new [Scheduler] which allows at most `max` requests to be in
+    /// flight at once.
+    pub fn new(max: usize) -> Self {
+        Scheduler {
+            inner: Arc::new(Inner {
+                max,
+                state: Mutex::new(State {
+                    available: max,
+                    ..State::default()
+                }),
+                ready: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Returns the maximum number of concurrent requests.
+    pub fn max(&self) -> usize {
+        self.inner.max
+    }
+
+    /// Returns the number of requests waiting to be sent.
+    pub fn queued(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    /// Returns the number of requests which may currently be sent without
+    /// waiting.
+    pub fn available(&self) -> usize {
+        self.inner.lock().available
+    }
+
+    /// Waits until a request with the given priority is permitted to be sent.
+    pub async fn acquire(&self, priority: RequestPriority) -> Permit {
+        let id = self.enqueue(priority);
+        Acquire {
+            inner: self.inner.clone(),
+            id,
+            done: false,
+        }
+        .await
+    }
+
+    /// Blocks until a request with the given priority is permitted to be
+    /// sent.
+    pub fn acquire_blocking(&self, priority: RequestPriority) -> Permit {
+        let id = self.enqueue(priority);
+        let mut state = self.inner.lock();
+        while !state.take(id) {
+            state = self
+                .inner
+                .ready
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        Permit {
+            inner: self.inner.clone(),
+        }
+    }
+
+    /// Adds a request to the queue and returns its identifier.
+    fn enqueue(&self, priority: RequestPriority) -> u64 {
+        let mut state = self.inner.lock();
+        let id = state.next;
+        state.next += 1;
+        state.queue.push((priority, Reverse(id)));
+        state.wakers.insert(id, None);
+        state.grant();
+        id
+    }
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Returns a permit and hands it to the next waiting request.
+    fn release(&self) {
+        let mut state = self.lock();
+        state.available += 1;
+        state.grant();
+        self.ready.notify_all();
+    }
+}
+
+impl Future for Acquire {
+    type Output = Permit;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.inner.lock();
+        if state.take(self.id) {
+            drop(state);
+            self.done = true;
+            return Poll::Ready(Permit {
+                inner: self.inner.clone(),
+            });
+        }
+        state.wakers.insert(self.id, Some(cx.waker().clone()));
+        Poll::Pending
+    }
+}
+
+impl Drop for Acquire {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        // Give up our place in the queue or return a permit granted to us
+        let mut state = self.inner.lock();
+        state.wakers.remove(&self.id);
+        let id = self.id;
+        state.queue.retain(|(_, Reverse(q))| *q != id);
+        if state.take(id) {
+            drop(state);
+            self.inner.release();
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.inner.release();
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("max", &self.inner.max)
+            .finish()
+    }
+}
@@ -0,0 +1,200 @@
+//! Contains the [SegmentedDownload] type for downloading large resources
+//! using concurrent `Range` requests.
+//!
+//! The first segment is requested on its own to discover the total size of
+//! the resource from the `Content-Range` header. The remaining segments are
+//! then requested concurrently and reassembled in order into a single `200 OK`
+//! response. Servers which ignore the `Range` header have their full response
+//! returned as-is.
+//!
+//! Every segment is built from the endpoint, so request middleware is applied
+//! to each segment. When the first segment contains an `ETag` or
+//! `Last-Modified` header, the remaining segments are sent with `If-Range` so
+//! a resource which changes mid-download is returned in full instead of being
+//! spliced together from different versions.
+//!
+//! # Example
+//! ```no_run
+//! use rustify::{clients::reqwest::Client, segmented::SegmentedDownload};
+//! use rustify_derive::Endpoint;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "files/large.bin")]
+//! struct DownloadFile {}
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("https://myapi.com");
+//! let download = SegmentedDownload::new(8 * 1024 * 1024).parallelism(8);
+//! let result = download.exec(&DownloadFile {}, &client).await.unwrap();
+//! let bytes = result.into_raw();
+//! # })
+//! ```
+
+use crate::{
+    client::Client,
+    endpoint::{Endpoint, EndpointResult},
+    errors::ClientError,
+};
+use futures_util::stream::{self, StreamExt};
+use http::{
+    header::{CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    HeaderValue, Response, StatusCode,
+};
+
+/// Downloads the response of an [Endpoint] in segments of a fixed size using
+/// concurrent `Range` requests.
+#[derive(Clone, Debug)]
+pub struct SegmentedDownload {
+    segment_size: u64,
+    parallelism: usize,
+}
+
+impl SegmentedDownload {
+    /// Returns a new [SegmentedDownload] which requests segments of the given
+    /// number of bytes, four at a time.
+    pub fn new(segment_size: u64) -> Self {
+        SegmentedDownload {
+            segment_size: segment_size.max(1),
+            parallelism: 4,
+        }
+    }
+
+    /// Sets the maximum number of segments requested at once.
+    pub fn parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
+    }
+
+    /// Executes the given [Endpoint] using the given [Client], downloading the
+    /// response in segments.
+    ///
+    /// The returned result contains the reassembled response with a `200 OK`
+    /// status and a `Content-Length` of the full resource.
+    #[instrument(skip(self, endpoint, client), fields(endpoint = endpoint.name()), err)]
+    pub async fn exec<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        client: &impl Client,
+    ) -> Result<EndpointResult<E::Response>, ClientError> {
+        let first = match self.fetch(endpoint, client, 0, None).await {
+            // Empty resources can't satisfy any range
+            Err(ClientError::ServerResponseError { code: 416, .. }) => {
+                return endpoint.exec(client).await;
+            }
+            r => r?,
+        };
+        if first.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(result::<E>(first));
+        }
+
+        let (start, end, total) = content_range(&first)?;
+        if start != 0 {
+            return Err(segment_error(format!(
+                "expected the first segment to start at 0, got {}",
+                start
+            )));
+        }
+        let validator = first
+            .headers()
+            .get(ETAG)
+            .or_else(|| first.headers().get(LAST_MODIFIED))
+            .cloned();
+
+        let ranges = (end + 1..total)
+            .step_by(self.segment_size as usize)
+            .map(|s| (s, (s + self.segment_size - 1).min(total - 1)))
+            .collect::<Vec<_>>();
+        debug!(
+            "Downloading {} bytes in {} segments",
+            total,
+            ranges.len() + 1
+        );
+
+        let (mut parts, mut body) = first.into_parts();
+        let mut segments = stream::iter(ranges)
+            .map(|(s, e)| {
+                let validator = validator.clone();
+                async move { (s, e, self.fetch(endpoint, client, s, validator).await) }
+            })
+            .buffered(self.parallelism);
+        while let Some((s, e, resp)) = segments.next().await {
+            let resp = resp?;
+            // The resource changed and the server sent all of it instead
+            if resp.status() != StatusCode::PARTIAL_CONTENT {
+                return Ok(result::<E>(resp));
+            }
+            let (start, _, _) = content_range(&resp)?;
+            if start != s || resp.body().len() as u64 != e - s + 1 {
+                return Err(segment_error(format!(
+                    "expected bytes {}-{}, got {} bytes starting at {}",
+                    s,
+                    e,
+                    resp.body().len(),
+                    start
+                )));
+            }
+            body.extend_from_slice(resp.body());
+        }
+
+        parts.status = StatusCode::OK;
+        parts.headers.remove(CONTENT_RANGE);
+        parts
+            .headers
+            .insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        Ok(result::<E>(Response::from_parts(parts, body)))
+    }
+
+    /// Requests the segment starting at the given offset.
+    async fn fetch<E: Endpoint>(
+        &self,
+        endpoint: &E,
+        client: &impl Client,
+        start: u64,
+        validator: Option<HeaderValue>,
+    ) -> Result<Response<Vec<u8>>, ClientError> {
+        let mut req = client.config().build_request(endpoint, client.base())?;
+        let range = format!("bytes={}-{}", start, start + self.segment_size - 1);
+        let headers = req.headers_mut();
+        headers.insert(RANGE, crate::http::build_header_value(&RANGE, &range)?);
+        if let Some(validator) = validator {
+            headers.insert(IF_RANGE, validator);
+        }
+        client.execute(req).await
+    }
+}
+
+fn result<E: Endpoint>(resp: Response<Vec<u8>>) -> EndpointResult<E::Response> {
+    EndpointResult::with_parser(resp, E::RESPONSE_BODY_TYPE, E::parse_response)
+}
+
+fn segment_error(message: String) -> ClientError {
+    ClientError::ResponseError {
+        source: anyhow::anyhow!("Invalid segment: {}", message),
+    }
+}
+
+/// Parses the start, end, and total size from a `Content-Range` header, i.e.
+/// `bytes 0-99/1000`.
+fn content_range(resp: &Response<Vec<u8>>) -> Result<(u64, u64, u64), ClientError> {
+    let value = resp
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let parse = || {
+        let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+        let (start, end) = range.split_once('-')?;
+        Some((
+            start.trim().parse().ok()?,
+            end.trim().parse().ok()?,
+            total.trim().parse().ok()?,
+        ))
+    };
+    match parse() {
+        Some((start, end, total)) if start <= end && end < total => Ok((start, end, total)),
+        _ => Err(segment_error(format!(
+            "unsupported Content-Range `{}`",
+            value
+        ))),
+    }
+}
@@ -0,0 +1,323 @@
+//! Contains the [Session] type for modeling stateful, browser-like API flows.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard},
+};
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{
+    client::Client,
+    config::ClientConfig,
+    endpoint::{Endpoint, EndpointResult},
+    errors::ClientError,
+    http::{build_header_value, Presigned},
+    metadata::EndpointMetadata,
+};
+use async_trait::async_trait;
+use http::{
+    header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+    HeaderMap, HeaderName, HeaderValue, Method, Request, Response,
+};
+
+/// The credentials attached to every request sent by a [Session].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Auth {
+    /// Sends `Authorization: Bearer <token>`
+    Bearer(String),
+    /// Sends the given header and value
+    Header(HeaderName, HeaderValue),
+}
+
+/// The state shared by all requests sent through a [Session].
+#[derive(Debug, Default)]
+struct State {
+    auth: Option<Auth>,
+    cookies: BTreeMap<String, String>,
+    csrf: Option<String>,
+    scopes: Option<Vec<String>>,
+}
+
+/// A [Client] which wraps another [Client] and keeps cookies, authentication,
+/// default headers, and CSRF tokens together across requests.
+///
+/// Every request sent through a session has the default headers, current
+/// [Auth], and stored cookies applied. Cookies set by responses are stored
+/// automatically. When a CSRF header is configured, its value is captured
+/// from responses and echoed back on requests which modify state (any method
+/// other than `GET`, `HEAD`, `OPTIONS`, and `TRACE`).
+///
+/// # Example
+/// ```
+/// use http::HeaderName;
+/// use rustify::{clients::reqwest::Client, session::{Auth, Session}};
+/// use rustify::Endpoint;
+/// use rustify_derive::Endpoint;
+///
+/// #[derive(Endpoint)]
+/// #[endpoint(path = "login", method = "POST", response = "String")]
+/// struct Login {
+///     username: String,
+/// }
+///
+/// # tokio_test::block_on(async {
+/// let session = Session::new(Client::default("http://myapi.com"))
+///     .csrf_header(HeaderName::from_static("x-csrf-token"));
+/// let login = Login { username: "me".to_string() };
+/// let result = session
+///     .login(&login, |r| Ok(Some(Auth::Bearer(r.parse()?))))
+///     .await;
+/// # })
+/// ```
+pub struct Session<C> {
+    pub client: C,
+    headers: HeaderMap,
+    csrf_header: Option<HeaderName>,
+    state: Mutex<State>,
+}
+
+impl<C> Session<C> {
+    /// Returns a new [Session] which sends requests using the given client.
+    pub fn new(client: C) -> Self {
+        Session {
+            client,
+            headers: HeaderMap::new(),
+            csrf_header: None,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Adds a header which is sent with every request.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Sets the header used for capturing and sending CSRF tokens.
+    pub fn csrf_header(mut self, name: HeaderName) -> Self {
+        self.csrf_header = Some(name);
+        self
+    }
+
+    /// Returns the current [Auth], if any.
+    pub fn auth(&self) -> Option<Auth> {
+        self.lock().auth.clone()
+    }
+
+    /// Sets the [Auth] sent with every request.
+    pub fn set_auth(&self, auth: Option<Auth>) {
+        self.lock().auth = auth;
+    }
+
+    /// Sets the scopes granted to the current [Auth]. When set, requests to
+    /// endpoints which require other scopes, see
+    /// [EndpointMetadata::scopes], fail with a [ClientError::ScopeError]
+    /// without being sent.
+    pub fn set_scopes(&self, scopes: Option<Vec<String>>) {
+        self.lock().scopes = scopes;
+    }
+
+    /// Returns the value of a stored cookie.
+    pub fn cookie(&self, name: &str) -> Option<String> {
+        self.lock().cookies.get(name).cloned()
+    }
+
+    /// Stores a cookie which is sent with every request.
+    pub fn set_cookie(&self, name: &str, value: &str) {
+        self.lock()
+            .cookies
+            .insert(name.to_string(), value.to_string());
+    }
+
+    /// Returns the most recently captured CSRF token, if any.
+    pub fn csrf_token(&self) -> Option<String> {
+        self.lock().csrf.clone()
+    }
+
+    /// Clears all authentication, scopes, cookies, and CSRF tokens from the
+    /// session.
+    pub fn clear(&self) {
+        *self.lock() = State::default();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Applies the session state to an outgoing request. Requests to
+    /// [Presigned] URLs are left untouched.
+    fn prepare(&self, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        if req.extensions().get::<Presigned>().is_some() {
+            return Ok(());
+        }
+        let state = self.lock();
+        if let Some(scopes) = &state.scopes {
+            EndpointMetadata::of(req.extensions()).check_scopes(scopes)?;
+        }
+        let unsafe_method = !matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        let headers = req.headers_mut();
+        for (name, value) in self.headers.iter() {
+            headers.insert(name, value.clone());
+        }
+
+        match &state.auth {
+            Some(Auth::Bearer(token)) => {
+                headers.insert(
+                    AUTHORIZATION,
+                    build_header_value(&AUTHORIZATION, &format!("Bearer {}", token))?,
+                );
+            }
+            Some(Auth::Header(name, value)) => {
+                headers.insert(name, value.clone());
+            }
+            None => {}
+        }
+
+        if !state.cookies.is_empty() {
+            let cookies = state
+                .cookies
+                .iter()
+                .map(|(k, v)| format!("{}={}", k, v))
+                .collect::<Vec<_>>()
+                .join("; ");
+            headers.insert(COOKIE, build_header_value(&COOKIE, &cookies)?);
+        }
+
+        if let (Some(name), Some(token), true) = (&self.csrf_header, &state.csrf, unsafe_method) {
+            headers.insert(name, build_header_value(name, token)?);
+        }
+        Ok(())
+    }
+
+    /// Captures cookies and CSRF tokens from an incoming response.
+    fn observe(&self, resp: &Response<Vec<u8>>) {
+        let mut state = self.lock();
+        for cookie in resp.headers().get_all(SET_COOKIE) {
+            let cookie = match cookie.to_str() {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            let mut parts = cookie.split(';').map(str::trim);
+            let (name, value) = match parts.next().and_then(|p| p.split_once('=')) {
+                Some((n, v)) => (n.trim(), v.trim().trim_matches('"')),
+                None => continue,
+            };
+            let expired = parts.any(|p| p.eq_ignore_ascii_case("max-age=0"));
+            match expired || value.is_empty() {
+                true => state.cookies.remove(name),
+                false => state.cookies.insert(name.to_string(), value.to_string()),
+            };
+        }
+
+        if let Some(token) = self
+            .csrf_header
+            .as_ref()
+            .and_then(|n| resp.headers().get(n))
+            .and_then(|v| v.to_str().ok())
+        {
+            state.csrf = Some(token.to_string());
+        }
+    }
+}
+
+impl<C: Client> Session<C> {
+    /// Executes the given login [Endpoint] and sets the session [Auth] to the
+    /// value returned by `f`. Any cookies set by the response are stored
+    /// regardless of the value returned.
+    pub async fn login<E, F>(
+        &self,
+        endpoint: &E,
+        f: F,
+    ) -> Result<EndpointResult<E::Response>, ClientError>
+    where
+        E: Endpoint,
+        F: FnOnce(&EndpointResult<E::Response>) -> Result<Option<Auth>, ClientError>,
+    {
+        let result = endpoint.exec(self).await?;
+        if let Some(auth) = f(&result)? {
+            self.set_auth(Some(auth));
+        }
+        Ok(result)
+    }
+
+    /// Executes the given logout [Endpoint] and then clears the session.
+    pub async fn logout<E: Endpoint>(
+        &self,
+        endpoint: &E,
+    ) -> Result<EndpointResult<E::Response>, ClientError> {
+        let result = endpoint.exec(self).await;
+        self.clear();
+        result
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for Session<C> {
+    async fn send(&self, mut req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        self.prepare(&mut req)?;
+        let resp = self.client.send(req).await?;
+        self.observe(&resp);
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<C: BlockingClient> Session<C> {
+    /// Executes the given login [Endpoint] and sets the session [Auth] to the
+    /// value returned by `f`.
+    pub fn login_block<E, F>(
+        &self,
+        endpoint: &E,
+        f: F,
+    ) -> Result<EndpointResult<E::Response>, ClientError>
+    where
+        E: Endpoint,
+        F: FnOnce(&EndpointResult<E::Response>) -> Result<Option<Auth>, ClientError>,
+    {
+        let result = endpoint.exec_block(self)?;
+        if let Some(auth) = f(&result)? {
+            self.set_auth(Some(auth));
+        }
+        Ok(result)
+    }
+
+    /// Executes the given logout [Endpoint] and then clears the session.
+    pub fn logout_block<E: Endpoint>(
+        &self,
+        endpoint: &E,
+    ) -> Result<EndpointResult<E::Response>, ClientError> {
+        let result = endpoint.exec_block(self);
+        self.clear();
+        result
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<C: BlockingClient> BlockingClient for Session<C> {
+    fn send(&self, mut req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        self.prepare(&mut req)?;
+        let resp = self.client.send(req)?;
+        self.observe(&resp);
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
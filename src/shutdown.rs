@@ -0,0 +1,187 @@
+//! Contains the [Shutdown] type for shutting down a
+//! [Client][crate::client::Client] without dropping requests on the floor.
+//!
+//! Once [Client::shutdown][crate::client::Client::shutdown] is called, new
+//! executions fail with a [ClientError::ShutdownError] while requests already
+//! in flight are given a grace period to complete. Requests which are still
+//! in flight when the grace period ends are cancelled and fail with a
+//! [ClientError::ShutdownError] as well. Requests executed by a blocking
+//! client can't be cancelled, so shutting down only stops waiting for them.
+//!
+//! # Example
+//! ```
+//! use rustify::{client::Client as _, clients::reqwest::Client, config::ClientConfig};
+//! use std::time::Duration;
+//!
+//! # tokio_test::block_on(async {
+//! let client =
+//!     Client::default("http://localhost").with_config(ClientConfig::new().graceful_shutdown());
+//!
+//! // Waits up to 10 seconds for in-flight requests before cancelling them
+//! let cancelled = client.shutdown(Duration::from_secs(10)).await;
+//! assert_eq!(cancelled, 0);
+//! # });
+//! ```
+
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use event_listener::{Event, Listener};
+
+use crate::errors::ClientError;
+
+/// Tracks the requests a client has in flight so it can be shut down
+/// gracefully.
+///
+/// A [Shutdown] is shared by all clones of the
+/// [ClientConfig][crate::config::ClientConfig] it belongs to, so shutting down
+/// one clone shuts down all of them.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    closed: AtomicBool,
+    cancelled: AtomicBool,
+    in_flight: AtomicUsize,
+    idle: Event,
+    cancel: Event,
+}
+
+/// Marks a request as in flight until it's dropped.
+pub struct Active {
+    inner: Arc<Inner>,
+}
+
+impl Shutdown {
+    /// Returns a new [Shutdown] which accepts requests.
+    pub fn new() -> Self {
+        Shutdown::default()
+    }
+
+    /// Returns whether the client has started shutting down.
+    pub fn is_shut_down(&self) -> bool {
+        self.inner.closed.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of requests currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// Marks a request as in flight, failing if the client has started
+    /// shutting down.
+    pub fn enter(&self) -> Result<Active, ClientError> {
+        self.inner.in_flight.fetch_add(1, Ordering::SeqCst);
+        let active = Active {
+            inner: self.inner.clone(),
+        };
+        // Checked after counting the request so shutdown can't miss it
+        match self.is_shut_down() {
+            true => Err(ClientError::ShutdownError),
+            false => Ok(active),
+        }
+    }
+
+    /// Runs the given future to completion unless the client is shut down
+    /// before it completes, in which case it's dropped and a
+    /// [ClientError::ShutdownError] is returned.
+    pub async fn cancellable<T>(
+        &self,
+        fut: impl Future<Output = Result<T, ClientError>>,
+    ) -> Result<T, ClientError> {
+        let fut = std::pin::pin!(fut);
+        let cancelled = std::pin::pin!(self.cancelled());
+        match futures_util::future::select(fut, cancelled).await {
+            futures_util::future::Either::Left((result, _)) => result,
+            futures_util::future::Either::Right(_) => Err(ClientError::ShutdownError),
+        }
+    }
+
+    /// Stops accepting requests and waits up to the given grace period for
+    /// the requests in flight to complete, then cancels the rest. Returns the
+    /// number of requests which were cancelled.
+    pub async fn shutdown(&self, grace: Duration) -> usize {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        if tokio::time::timeout(grace, self.idle()).await.is_ok() {
+            return 0;
+        }
+        let cancelled = self.cancel();
+        self.idle().await;
+        cancelled
+    }
+
+    /// Like [Shutdown::shutdown] but blocks the current thread. Requests
+    /// which are still in flight when the grace period ends are left running
+    /// and are counted as cancelled.
+    pub fn shutdown_blocking(&self, grace: Duration) -> usize {
+        self.inner.closed.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + grace;
+        loop {
+            let listener = self.inner.idle.listen();
+            if self.in_flight() == 0 {
+                return 0;
+            }
+            if listener.wait_deadline(deadline).is_none() && self.in_flight() > 0 {
+                return self.cancel();
+            }
+        }
+    }
+
+    /// Cancels the requests in flight, returning how many there were.
+    fn cancel(&self) -> usize {
+        let in_flight = self.in_flight();
+        warn!("Cancelling {} requests in flight after shutdown", in_flight);
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.cancel.notify(usize::MAX);
+        in_flight
+    }
+
+    /// Waits until no requests are in flight.
+    async fn idle(&self) {
+        loop {
+            let listener = self.inner.idle.listen();
+            if self.in_flight() == 0 {
+                return;
+            }
+            listener.await;
+        }
+    }
+
+    /// Waits until the requests in flight are cancelled.
+    pub(crate) async fn cancelled(&self) {
+        loop {
+            let listener = self.inner.cancel.listen();
+            if self.inner.cancelled.load(Ordering::SeqCst) {
+                return;
+            }
+            listener.await;
+        }
+    }
+}
+
+impl Drop for Active {
+    fn drop(&mut self) {
+        if self.inner.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.inner.idle.notify(usize::MAX);
+        }
+    }
+}
+
+impl fmt::Debug for Shutdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shutdown")
+            .field("shut_down", &self.is_shut_down())
+            .field("in_flight", &self.in_flight())
+            .finish()
+    }
+}
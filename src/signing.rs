@@ -0,0 +1,185 @@
+//! Contains canonicalization helpers shared by request signing schemes.
+//!
+//! Most vendor signature schemes (for example AWS SigV4 or HMAC based
+//! webhooks) sign a canonical representation of the request: a normalized
+//! path, sorted and strictly encoded query parameters, lowercase headers, and
+//! a hash of the body. These helpers produce those building blocks so custom
+//! signing middleware don't need to reimplement them, along with
+//! [hmac_sha256] for computing signatures.
+//!
+//! # Example
+//! ```
+//! use rustify::signing;
+//!
+//! let req = http::Request::builder()
+//!     .uri("https://example.com/a%20b?b=2&a=1")
+//!     .header("Host", "example.com")
+//!     .header("X-Date", "20210927T120000Z")
+//!     .body(Vec::new())
+//!     .unwrap();
+//! let names = ["host", "x-date"];
+//! let canonical = [
+//!     req.method().as_str().to_string(),
+//!     signing::canonical_path(req.uri()),
+//!     signing::canonical_query(req.uri()),
+//!     signing::canonical_headers(req.headers(), &names),
+//!     signing::signed_headers(&names),
+//!     signing::hash_body(req.body()),
+//! ]
+//! .join("\n");
+//! ```
+
+use http::{HeaderMap, Uri};
+use sha2::{Digest, Sha256};
+
+/// Percent-encodes a string using the unreserved characters of RFC 3986,
+/// which is the strict encoding required by most signature schemes.
+///
+/// When `encode_slash` is false, `/` is left as-is so the result can be used
+/// for paths.
+pub fn encode(value: &str, encode_slash: bool) -> String {
+    let mut result = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                result.push(b as char)
+            }
+            b'/' if !encode_slash => result.push('/'),
+            _ => result.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    result
+}
+
+/// Decodes a percent-encoded string, leaving invalid escapes untouched.
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                result.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                result.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+/// Returns the path of a [Uri] with each segment decoded and then strictly
+/// re-encoded. An empty path is returned as `/`.
+///
+/// Segments are split before decoding, so an encoded slash (`%2F`) stays part
+/// of its segment.
+pub fn canonical_path(uri: &Uri) -> String {
+    match uri.path() {
+        "" => "/".to_string(),
+        p => p
+            .split('/')
+            .map(|segment| encode(&decode(segment), true))
+            .collect::<Vec<_>>()
+            .join("/"),
+    }
+}
+
+/// Returns the query parameters of a [Uri] strictly encoded and sorted by
+/// name and then value, joined with `&`.
+///
+/// Parameters without a value are encoded with an empty value, i.e. `a=`.
+pub fn canonical_query(uri: &Uri) -> String {
+    let query = match uri.query() {
+        Some(q) => q,
+        None => return String::new(),
+    };
+    let mut params = query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let (k, v) = p.split_once('=').unwrap_or((p, ""));
+            let decode = |s: &str| decode(&s.replace('+', " "));
+            (encode(&decode(k), true), encode(&decode(v), true))
+        })
+        .collect::<Vec<_>>();
+    params.sort();
+    params
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Returns the given headers as lowercase `name:value` lines, each ending
+/// with a newline, in the order of `names`.
+///
+/// Values are trimmed and sequential spaces are collapsed into a single
+/// space. Headers with multiple values are joined with `,`. Names without a
+/// matching header are included with an empty value.
+pub fn canonical_headers(headers: &HeaderMap, names: &[&str]) -> String {
+    let mut result = String::new();
+    for name in names {
+        let name = name.to_lowercase();
+        let values = headers
+            .get_all(name.as_str())
+            .iter()
+            .map(|v| {
+                String::from_utf8_lossy(v.as_bytes())
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        result.push_str(&format!("{}:{}\n", name, values));
+    }
+    result
+}
+
+/// Returns the given header names lowercased, sorted, and joined with `;`.
+pub fn signed_headers(names: &[&str]) -> String {
+    let mut names = names.iter().map(|n| n.to_lowercase()).collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names.join(";")
+}
+
+/// Returns the lowercase hex encoded SHA-256 hash of a body.
+pub fn hash_body(body: &[u8]) -> String {
+    hex(&Sha256::digest(body))
+}
+
+/// Returns the lowercase hex encoding of the given bytes.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Returns the HMAC-SHA256 ([RFC 2104]) of the given data using the given
+/// key.
+///
+/// [RFC 2104]: https://datatracker.ietf.org/doc/html/rfc2104
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut block = [0u8; BLOCK_SIZE];
+    match key.len() > BLOCK_SIZE {
+        true => block[..32].copy_from_slice(&Sha256::digest(key)),
+        false => block[..key.len()].copy_from_slice(key),
+    }
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<_>>();
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(data)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
@@ -0,0 +1,240 @@
+//! Contains the [SigV4] middleware for signing requests to AWS and
+//! AWS-compatible services with [Signature Version 4].
+//!
+//! The signature covers the method, canonical path, canonical query, the
+//! `host`, `content-type`, and `x-amz-*` headers, and the SHA-256 hash of the
+//! body of the built request. It's added in the `Authorization` header along
+//! with an `x-amz-date` header, and an `x-amz-security-token` header when
+//! using temporary credentials. Requests to S3 also include the body hash in
+//! an `x-amz-content-sha256` header.
+//!
+//! Since the request is signed by middleware, headers added after middleware
+//! runs, such as the
+//! [ClientConfig::attempt_header][crate::config::ClientConfig::attempt_header]
+//! or those added by a [Session][crate::session::Session], aren't signed.
+//! Retries configured with
+//! [ClientConfig::retry][crate::config::ClientConfig::retry] re-run
+//! middleware, so every attempt is sent with a fresh signature.
+//!
+//! This module requires the `sigv4` feature.
+//!
+//! # Example
+//! ```
+//! use rustify::{
+//!     clients::reqwest::Client, config::ClientConfig, sigv4::SigV4, skew::ClockSkew,
+//! };
+//!
+//! let signer = SigV4::new("AKIDEXAMPLE", "secret", "us-east-1", "execute-api")
+//!     .clock(ClockSkew::new());
+//! let client = Client::default("https://abc123.execute-api.us-east-1.amazonaws.com")
+//!     .with_config(ClientConfig::new().middleware(signer));
+//! ```
+//!
+//! [Signature Version 4]: https://docs.aws.amazon.com/IAM/latest/UserGuide/reference_sigv4.html
+
+use std::{
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use http::{
+    header::{AUTHORIZATION, CONTENT_TYPE, HOST},
+    HeaderName, Request, Response,
+};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+    http::build_header_value,
+    signing,
+    skew::ClockSkew,
+};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const X_AMZ_CONTENT_SHA256: HeaderName = HeaderName::from_static("x-amz-content-sha256");
+const X_AMZ_DATE: HeaderName = HeaderName::from_static("x-amz-date");
+const X_AMZ_SECURITY_TOKEN: HeaderName = HeaderName::from_static("x-amz-security-token");
+
+/// A [MiddleWare] which signs requests with AWS Signature Version 4, see the
+/// [module documentation][self].
+#[derive(Clone)]
+pub struct SigV4 {
+    access_key: String,
+    clock: Option<ClockSkew>,
+    region: String,
+    secret_key: String,
+    service: String,
+    session_token: Option<String>,
+}
+
+impl SigV4 {
+    /// Returns a new [SigV4] which signs requests to the given service in the
+    /// given region using the given access key.
+    pub fn new(access_key: &str, secret_key: &str, region: &str, service: &str) -> Self {
+        SigV4 {
+            access_key: access_key.to_string(),
+            clock: None,
+            region: region.to_string(),
+            secret_key: secret_key.to_string(),
+            service: service.to_string(),
+            session_token: None,
+        }
+    }
+
+    /// Sends the given session token of temporary credentials with every
+    /// request.
+    pub fn session_token(mut self, token: &str) -> Self {
+        self.session_token = Some(token.to_string());
+        self
+    }
+
+    /// Uses the given [ClockSkew] for the signing time instead of the local
+    /// clock.
+    pub fn clock(mut self, clock: ClockSkew) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Signs the given request as if it was sent at the given time.
+    pub fn sign_at(&self, req: &mut Request<Vec<u8>>, time: SystemTime) -> Result<(), ClientError> {
+        let timestamp = format_timestamp(time);
+        let date = &timestamp[..8];
+        let payload_hash = signing::hash_body(req.body());
+
+        if !req.headers().contains_key(HOST) {
+            if let Some(authority) = req.uri().authority() {
+                let host = build_header_value(&HOST, authority.as_str())?;
+                req.headers_mut().insert(HOST, host);
+            }
+        }
+        let headers = req.headers_mut();
+        headers.insert(X_AMZ_DATE, build_header_value(&X_AMZ_DATE, &timestamp)?);
+        if self.service == "s3" {
+            let hash = build_header_value(&X_AMZ_CONTENT_SHA256, &payload_hash)?;
+            headers.insert(X_AMZ_CONTENT_SHA256, hash);
+        }
+        if let Some(token) = &self.session_token {
+            let mut token = build_header_value(&X_AMZ_SECURITY_TOKEN, token)?;
+            token.set_sensitive(true);
+            headers.insert(X_AMZ_SECURITY_TOKEN, token);
+        }
+
+        let mut names = req
+            .headers()
+            .keys()
+            .map(HeaderName::as_str)
+            .filter(|n| *n == HOST || *n == CONTENT_TYPE || n.starts_with("x-amz-"))
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        let signed_headers = signing::signed_headers(&names);
+
+        let canonical_request = [
+            req.method().as_str(),
+            &self.canonical_path(req),
+            &signing::canonical_query(req.uri()),
+            &signing::canonical_headers(req.headers(), &names),
+            &signed_headers,
+            &payload_hash,
+        ]
+        .join("\n");
+        let scope = format!("{}/{}/{}/aws4_request", date, self.region, self.service);
+        let string_to_sign = [
+            ALGORITHM,
+            &timestamp,
+            &scope,
+            &signing::hash_body(canonical_request.as_bytes()),
+        ]
+        .join("\n");
+        let signature = signing::hex(&signing::hmac_sha256(
+            &self.signing_key(date),
+            string_to_sign.as_bytes(),
+        ));
+
+        let mut authorization = build_header_value(
+            &AUTHORIZATION,
+            &format!(
+                "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+                ALGORITHM, self.access_key, scope, signed_headers, signature
+            ),
+        )?;
+        authorization.set_sensitive(true);
+        req.headers_mut().insert(AUTHORIZATION, authorization);
+        Ok(())
+    }
+
+    /// Returns the key derived from the secret key for signing requests on
+    /// the given date.
+    pub fn signing_key(&self, date: &str) -> [u8; 32] {
+        let secret = format!("AWS4{}", self.secret_key);
+        let key = signing::hmac_sha256(secret.as_bytes(), date.as_bytes());
+        let key = signing::hmac_sha256(&key, self.region.as_bytes());
+        let key = signing::hmac_sha256(&key, self.service.as_bytes());
+        signing::hmac_sha256(&key, b"aws4_request")
+    }
+
+    /// Returns the canonical path of the given request. Every service except
+    /// S3 encodes the already encoded path a second time.
+    fn canonical_path(&self, req: &Request<Vec<u8>>) -> String {
+        let path = signing::canonical_path(req.uri());
+        match self.service.as_str() {
+            "s3" => path,
+            _ => signing::encode(&path, false),
+        }
+    }
+}
+
+impl MiddleWare for SigV4 {
+    fn request<E: Endpoint>(&self, _: &E, req: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => SystemTime::now(),
+        };
+        self.sign_at(req, now)
+    }
+
+    fn response<E: Endpoint>(&self, _: &E, _: &mut Response<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SigV4 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigV4")
+            .field("access_key", &self.access_key)
+            .field("region", &self.region)
+            .field("service", &self.service)
+            .finish()
+    }
+}
+
+/// Formats the given time as `YYYYMMDD'T'HHMMSS'Z'` in UTC.
+fn format_timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Converts days since the epoch into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
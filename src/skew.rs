@@ -0,0 +1,132 @@
+//! Contains the [ClockSkew] type for compensating for differences between the
+//! local clock and the clock of a remote server.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    endpoint::{Endpoint, MiddleWare},
+    errors::ClientError,
+};
+use http::{header::DATE, Request, Response};
+
+/// Learns the offset between the local clock and a server's clock from the
+/// `Date` header of its responses.
+///
+/// Signing middleware which include a timestamp in their signatures can use
+/// [ClockSkew::now] in place of [SystemTime::now] to avoid signatures being
+/// rejected when the local clock has drifted. Because the `Date` header only
+/// has a precision of one second, offsets smaller than one second are
+/// ignored.
+///
+/// A [ClockSkew] is cheap to clone and all clones share the same offset. It
+/// can be applied as [MiddleWare] or, to also learn from unsuccessful
+/// responses, registered as a response hook:
+///
+/// ```
+/// use rustify::{config::ClientConfig, hooks::Hooks, skew::ClockSkew};
+///
+/// let skew = ClockSkew::new();
+/// let observer = skew.clone();
+/// let config = ClientConfig::new()
+///     .hooks(Hooks::new().on_response(move |resp| {
+///         observer.observe(resp);
+///     }));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ClockSkew {
+    offset: Arc<AtomicI64>,
+    observed: Arc<AtomicBool>,
+}
+
+impl ClockSkew {
+    /// Returns a new [ClockSkew] which assumes the clocks are in sync.
+    pub fn new() -> Self {
+        ClockSkew::default()
+    }
+
+    /// Updates the offset using the `Date` header of the given [Response], if
+    /// present. Returns whether the offset was updated.
+    pub fn observe<T>(&self, resp: &Response<T>) -> bool {
+        let date = match resp
+            .headers()
+            .get(DATE)
+            .and_then(|d| d.to_str().ok())
+            .and_then(|d| httpdate::parse_http_date(d).ok())
+        {
+            Some(d) => d,
+            None => return false,
+        };
+        self.observe_at(date, SystemTime::now());
+        true
+    }
+
+    /// Updates the offset given the server's time and the local time it was
+    /// observed at.
+    pub fn observe_at(&self, server: SystemTime, local: SystemTime) {
+        let offset = millis(server) - millis(local);
+        let offset = match offset.abs() < 1000 {
+            true => 0,
+            false => offset,
+        };
+        if offset != self.offset.swap(offset, Ordering::Relaxed) {
+            debug!("Clock skew offset is now {}ms", offset);
+        }
+        self.observed.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns the offset of the server's clock from the local clock in
+    /// milliseconds. Positive values indicate the server is ahead.
+    pub fn offset_millis(&self) -> i64 {
+        self.offset.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether an offset has been learned from a response yet.
+    pub fn is_observed(&self) -> bool {
+        self.observed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the current time according to the server's clock.
+    pub fn now(&self) -> SystemTime {
+        self.adjust(SystemTime::now())
+    }
+
+    /// Adjusts the given local time by the learned offset.
+    pub fn adjust(&self, time: SystemTime) -> SystemTime {
+        let offset = self.offset_millis();
+        let delta = Duration::from_millis(offset.unsigned_abs());
+        match offset >= 0 {
+            true => time + delta,
+            false => time - delta,
+        }
+    }
+}
+
+/// Returns the number of milliseconds between the Unix epoch and the given
+/// time, which is negative for times before the epoch.
+fn millis(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+impl MiddleWare for ClockSkew {
+    fn request<E: Endpoint>(&self, _: &E, _: &mut Request<Vec<u8>>) -> Result<(), ClientError> {
+        Ok(())
+    }
+
+    fn response<E: Endpoint>(
+        &self,
+        _: &E,
+        resp: &mut Response<Vec<u8>>,
+    ) -> Result<(), ClientError> {
+        self.observe(resp);
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Contains the [ExecOutput] type for executing
+//! [Endpoints][crate::endpoint::Endpoint] with either a buffered or a
+//! streamed response body.
+//!
+//! Most responses are small enough to be buffered and parsed, but some
+//! endpoints, such as exports or log tails, are better consumed as they're
+//! received.
+//! [Endpoint::exec_output][crate::endpoint::Endpoint::exec_output] executes
+//! the same endpoint definition in the [ExecMode] chosen by the caller.
+//! Streaming is only used when the client reports
+//! [Client::supports_streaming][crate::client::Client::supports_streaming]
+//! and nothing needs the complete response, otherwise the response is
+//! buffered and an [ExecOutput::Buffered] is returned instead.
+//!
+//! Streamed responses pass through request middleware and the same hooks,
+//! limits, shutdown tracking, and accounting of the
+//! [ClientConfig][crate::config::ClientConfig] as buffered ones, see
+//! [Client::execute_stream][crate::client::Client::execute_stream]. The
+//! concurrency limit, scheduler, and shutdown tracking keep counting the
+//! request until its body is received or dropped. Redirects, decoding, and
+//! everything else which needs the complete body is skipped. Since response
+//! middleware and retries can't be skipped without changing the result,
+//! endpoints executed with
+//! [Endpoint::with_middleware][crate::endpoint::Endpoint::with_middleware]
+//! and clients configured with middleware, a retry policy, or stale
+//! connection retries always buffer the response.
+//!
+//! Streams of newline delimited JSON (NDJSON), as returned by search or
+//! export endpoints, can be parsed with [collect_ndjson]. It stops waiting
+//! after a time limit and returns the items received so far, marked as
+//! [Collected::TimedOut], so best-effort callers can use a partial result
+//! rather than none.
+//!
+//! # Example
+//! ```no_run
+//! use futures_util::StreamExt;
+//! use rustify::{
+//!     clients::reqwest::Client,
+//!     stream::{ExecMode, ExecOutput},
+//!     Endpoint,
+//! };
+//! use rustify_derive::Endpoint;
+//!
+//! #[derive(Endpoint)]
+//! #[endpoint(path = "logs", response = "Vec<String>")]
+//! struct ListLogs {}
+//!
+//! # tokio_test::block_on(async {
+//! let client = Client::default("http://api.example.com");
+//! let output = ListLogs {}.exec_output(&client, ExecMode::Stream).await;
+//! match output.unwrap() {
+//!     ExecOutput::Buffered(result) => println!("{:?}", result.parse().unwrap()),
+//!     ExecOutput::Stream(resp) => {
+//!         let mut body = resp.into_body();
+//!         while let Some(chunk) = body.next().await {
+//!             println!("Received {} bytes", chunk.unwrap().len());
+//!         }
+//!     }
+//! }
+//! # });
+//! ```
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::{
+    future::{BoxFuture, Either},
+    stream::BoxStream,
+    FutureExt, StreamExt,
+};
+use http::Response;
+use serde::de::DeserializeOwned;
+use tokio::time::Instant;
+
+use crate::{
+    client::Guards, endpoint::EndpointResult, enums::ResponseType, errors::ClientError,
+    shutdown::Shutdown,
+};
+
+/// A response body which is received in chunks.
+pub type ByteStream = BoxStream<'static, Result<Bytes, ClientError>>;
+
+/// Determines whether
+/// [Endpoint::exec_output][crate::endpoint::Endpoint::exec_output] buffers or
+/// streams the response body.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ExecMode {
+    /// Receives the complete body before returning
+    #[default]
+    Buffered,
+    /// Returns as soon as the headers are received, if the client supports it
+    Stream,
+}
+
+/// The output of executing an [Endpoint][crate::endpoint::Endpoint] with
+/// [Endpoint::exec_output][crate::endpoint::Endpoint::exec_output].
+pub enum ExecOutput<T: DeserializeOwned + Send + Sync> {
+    /// The complete response, ready to be parsed
+    Buffered(EndpointResult<T>),
+    /// The status and headers of the response with its body still streaming
+    Stream(Response<ByteStream>),
+}
+
+impl<T: DeserializeOwned + Send + Sync> ExecOutput<T> {
+    /// Returns whether the response body is streamed.
+    pub fn is_stream(&self) -> bool {
+        matches!(self, ExecOutput::Stream(_))
+    }
+
+    /// Returns the response body as a stream, yielding a buffered body as a
+    /// single chunk.
+    pub fn into_stream(self) -> ByteStream {
+        match self {
+            ExecOutput::Buffered(result) => single(result.into_raw()),
+            ExecOutput::Stream(resp) => resp.into_body(),
+        }
+    }
+}
+
+impl<T: DeserializeOwned + Send + Sync> std::fmt::Debug for ExecOutput<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExecOutput::Buffered(result) => f
+                .debug_tuple("Buffered")
+                .field(&result.response.status())
+                .finish(),
+            ExecOutput::Stream(resp) => f.debug_tuple("Stream").field(&resp.status()).finish(),
+        }
+    }
+}
+
+/// Returns a [ByteStream] which yields the given body as a single chunk.
+pub fn single(body: Vec<u8>) -> ByteStream {
+    futures_util::stream::once(async move { Ok(Bytes::from(body)) }).boxed()
+}
+
+/// Holds the given [Guards] until the given [ByteStream] ends or is dropped,
+/// failing it with a [ClientError::ShutdownError] if the requests in flight
+/// are cancelled by the given [Shutdown] first.
+pub(crate) fn guarded(body: ByteStream, guards: Guards, shutdown: Option<Shutdown>) -> ByteStream {
+    let cancelled: BoxFuture<'static, ()> = match shutdown {
+        Some(s) => async move { s.cancelled().await }.boxed(),
+        None => futures_util::future::pending().boxed(),
+    };
+    futures_util::stream::unfold(Some((body, cancelled, guards)), |state| async move {
+        let (mut body, mut cancelled, guards) = state?;
+        match futures_util::future::select(body.next(), cancelled.as_mut()).await {
+            Either::Left((Some(chunk), _)) => Some((chunk, Some((body, cancelled, guards)))),
+            Either::Left((None, _)) => None,
+            Either::Right(_) => Some((Err(ClientError::ShutdownError), None)),
+        }
+    })
+    .boxed()
+}
+
+/// Receives all chunks of the given [ByteStream].
+pub async fn collect(mut stream: ByteStream) -> Result<Vec<u8>, ClientError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        body.extend_from_slice(&chunk?);
+    }
+    Ok(body)
+}
+
+/// The items parsed by [collect_ndjson].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Collected<T> {
+    /// The stream ended and every item was parsed
+    Complete(Vec<T>),
+    /// The time limit expired and only the items received before it were
+    /// parsed
+    TimedOut(Vec<T>),
+}
+
+impl<T> Collected<T> {
+    /// Returns whether the time limit expired before the stream ended.
+    pub fn is_timed_out(&self) -> bool {
+        matches!(self, Collected::TimedOut(_))
+    }
+
+    /// Returns the parsed items.
+    pub fn items(&self) -> &[T] {
+        match self {
+            Collected::Complete(items) | Collected::TimedOut(items) => items,
+        }
+    }
+
+    /// Consumes this and returns the parsed items.
+    pub fn into_items(self) -> Vec<T> {
+        match self {
+            Collected::Complete(items) | Collected::TimedOut(items) => items,
+        }
+    }
+}
+
+/// Parses each line of the given [ByteStream] as a JSON object until the
+/// stream ends or the given time limit expires.
+///
+/// When the time limit expires the items parsed so far are returned as
+/// [Collected::TimedOut] and a trailing incomplete line is discarded. Blank
+/// lines are skipped. Errors received from the stream and lines which fail
+/// to parse are returned as errors, discarding any parsed items.
+pub async fn collect_ndjson<T: DeserializeOwned>(
+    mut stream: ByteStream,
+    limit: Duration,
+) -> Result<Collected<T>, ClientError> {
+    let deadline = Instant::now() + limit;
+    let mut items = Vec::new();
+    let mut buffer = Vec::new();
+    loop {
+        let chunk = match tokio::time::timeout_at(deadline, stream.next()).await {
+            Ok(Some(chunk)) => chunk?,
+            Ok(None) => break,
+            Err(_) => {
+                warn!(
+                    "Stopped waiting for NDJSON stream after {:?} with {} items",
+                    limit,
+                    items.len()
+                );
+                return Ok(Collected::TimedOut(items));
+            }
+        };
+        buffer.extend_from_slice(&chunk);
+        if let Some(end) = buffer.iter().rposition(|b| *b == b'\n') {
+            let rest = buffer.split_off(end + 1);
+            parse_lines(&buffer, &mut items)?;
+            buffer = rest;
+        }
+    }
+    parse_lines(&buffer, &mut items)?;
+    Ok(Collected::Complete(items))
+}
+
+/// Parses every non-blank line of the given body and appends it to `items`.
+fn parse_lines<T: DeserializeOwned>(body: &[u8], items: &mut Vec<T>) -> Result<(), ClientError> {
+    for line in body.split(|b| *b == b'\n') {
+        if !line.iter().all(u8::is_ascii_whitespace) {
+            items.push(crate::http::parse(line, ResponseType::JSON)?);
+        }
+    }
+    Ok(())
+}
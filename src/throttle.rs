@@ -0,0 +1,130 @@
+//! Contains the [ThrottledClient] type for simulating a slow network.
+//!
+//! Wrapping a client in a [ThrottledClient] delays every request by a fixed
+//! latency and by the time its bodies would take to transfer at the
+//! configured bandwidth. This makes it possible to exercise timeouts,
+//! progress reporting, and resilience settings locally against realistic
+//! network conditions.
+//!
+//! # Example
+//! ```
+//! use rustify::{clients::reqwest::Client, throttle::ThrottledClient};
+//! use std::time::Duration;
+//!
+//! // Roughly a slow 3G connection
+//! let client = ThrottledClient::new(Client::default("http://localhost"))
+//!     .latency(Duration::from_millis(300))
+//!     .upload(50_000)
+//!     .download(100_000);
+//! ```
+
+use std::time::Duration;
+
+#[cfg(feature = "blocking")]
+use crate::blocking::client::Client as BlockingClient;
+use crate::{client::Client, config::ClientConfig, errors::ClientError};
+use async_trait::async_trait;
+use http::{Request, Response};
+
+/// A [Client] which wraps another [Client] and slows down every request it
+/// sends.
+///
+/// Each request is delayed by the latency plus the time its body takes to
+/// upload, and each response by the time its body takes to download. The
+/// bandwidth applies to each request on its own rather than being shared by
+/// concurrent requests.
+#[derive(Debug)]
+pub struct ThrottledClient<C> {
+    pub client: C,
+    latency: Duration,
+    upload: Option<u64>,
+    download: Option<u64>,
+}
+
+impl<C> ThrottledClient<C> {
+    /// Returns a new [ThrottledClient] which sends requests using the given
+    /// client without slowing them down.
+    pub fn new(client: C) -> Self {
+        ThrottledClient {
+            client,
+            latency: Duration::ZERO,
+            upload: None,
+            download: None,
+        }
+    }
+
+    /// Delays every request by the given latency before it's sent.
+    pub fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Limits the speed at which request bodies are sent, in bytes per
+    /// second.
+    pub fn upload(mut self, bytes_per_second: u64) -> Self {
+        self.upload = Some(bytes_per_second.max(1));
+        self
+    }
+
+    /// Limits the speed at which response bodies are received, in bytes per
+    /// second.
+    pub fn download(mut self, bytes_per_second: u64) -> Self {
+        self.download = Some(bytes_per_second.max(1));
+        self
+    }
+
+    /// Returns the delay applied before sending the given request.
+    fn request_delay(&self, req: &Request<Vec<u8>>) -> Duration {
+        self.latency + transfer_time(req.body().len(), self.upload)
+    }
+
+    /// Returns the delay applied before returning the given response.
+    fn response_delay(&self, resp: &Response<Vec<u8>>) -> Duration {
+        transfer_time(resp.body().len(), self.download)
+    }
+}
+
+/// Returns the time it takes to transfer the given number of bytes at the
+/// given bandwidth.
+fn transfer_time(bytes: usize, bandwidth: Option<u64>) -> Duration {
+    match bandwidth {
+        Some(b) => Duration::from_secs_f64(bytes as f64 / b as f64),
+        None => Duration::ZERO,
+    }
+}
+
+#[async_trait]
+impl<C: Client> Client for ThrottledClient<C> {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        tokio::time::sleep(self.request_delay(&req)).await;
+        let resp = self.client.send(req).await?;
+        tokio::time::sleep(self.response_delay(&resp)).await;
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl<C: BlockingClient> BlockingClient for ThrottledClient<C> {
+    fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        std::thread::sleep(self.request_delay(&req));
+        let resp = self.client.send(req)?;
+        std::thread::sleep(self.response_delay(&resp));
+        Ok(resp)
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+
+    fn config(&self) -> &ClientConfig {
+        self.client.config()
+    }
+}
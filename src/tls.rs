@@ -0,0 +1,63 @@
+//! Contains the [TlsInfo] type describing the TLS session a response was
+//! received over.
+//!
+//! The built-in reqwest clients store a [TlsInfo] in the extensions of every
+//! response received over TLS when the backing reqwest client was built with
+//! `tls_info(true)`. This requires the `default` or `rustls-tls` feature.
+//!
+//! reqwest only exposes the leaf certificate of the peer. The negotiated
+//! protocol version and cipher suite are only available from custom
+//! [Client][crate::client::Client] implementations which populate them.
+//!
+//! # Example
+//! ```
+//! use rustify::clients::reqwest::Client;
+//!
+//! let client =
+//!     Client::from_builder("https://myapi.com", || reqwest::Client::builder().tls_info(true))
+//!         .unwrap();
+//! ```
+
+use sha2::{Digest, Sha256};
+
+/// The details of a negotiated TLS session.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    /// The DER encoded leaf certificate presented by the peer
+    pub peer_certificate: Option<Vec<u8>>,
+    /// The negotiated protocol version, i.e. `TLSv1.3`
+    pub protocol: Option<String>,
+    /// The negotiated cipher suite, i.e. `TLS13_AES_256_GCM_SHA384`
+    pub cipher: Option<String>,
+}
+
+impl TlsInfo {
+    /// Returns the lowercase hex encoded SHA-256 fingerprint of the peer
+    /// certificate, if any.
+    pub fn peer_fingerprint(&self) -> Option<String> {
+        self.peer_certificate
+            .as_ref()
+            .map(|c| crate::signing::hex(&Sha256::digest(c)))
+    }
+}
+
+#[cfg(any(feature = "default", feature = "rustls-tls"))]
+impl From<&reqwest::tls::TlsInfo> for TlsInfo {
+    fn from(info: &reqwest::tls::TlsInfo) -> Self {
+        TlsInfo {
+            peer_certificate: info.peer_certificate().map(<[u8]>::to_vec),
+            ..TlsInfo::default()
+        }
+    }
+}
+
+/// Returns the [TlsInfo] stored by reqwest in the given response extensions.
+pub(crate) fn from_reqwest(extensions: &http::Extensions) -> Option<TlsInfo> {
+    #[cfg(any(feature = "default", feature = "rustls-tls"))]
+    return extensions.get::<reqwest::tls::TlsInfo>().map(TlsInfo::from);
+    #[cfg(not(any(feature = "default", feature = "rustls-tls")))]
+    {
+        let _ = extensions;
+        None
+    }
+}
@@ -0,0 +1,88 @@
+//! Contains helpers for checking [Endpoint] definitions without sending any
+//! requests, typically from a test.
+//!
+//! The [validate_endpoints!][crate::validate_endpoints] macro builds the
+//! request of each given endpoint type from its [Default] value and panics
+//! with a report of every endpoint which failed to build.
+//!
+//! # Example
+//! ```
+//! use rustify::validate_endpoints;
+//! use rustify_derive::Endpoint;
+//!
+//! #[derive(Default, Endpoint)]
+//! #[endpoint(path = "users/{self.id}")]
+//! struct GetUser {
+//!     #[endpoint(skip)]
+//!     id: u64,
+//! }
+//!
+//! #[derive(Default, Endpoint)]
+//! #[endpoint(path = "users", method = "POST")]
+//! struct CreateUser {
+//!     name: String,
+//! }
+//!
+//! validate_endpoints!(GetUser, CreateUser);
+//! ```
+
+use crate::{endpoint::Endpoint, errors::ClientError};
+
+/// The base URL requests are built against during validation.
+pub const VALIDATION_BASE: &str = "http://localhost";
+
+/// Builds the request for the given [Endpoint] and checks its path, URL,
+/// query, and body can be constructed.
+///
+/// The path is rejected if it contains unresolved `{...}` placeholders or
+/// empty segments, which usually point to a placeholder referencing an empty
+/// field.
+pub fn validate<E: Endpoint>(endpoint: &E) -> Result<(), ClientError> {
+    let path = endpoint.path();
+    let fail = |message: String| ClientError::EndpointBuildError {
+        source: anyhow::anyhow!(message),
+    };
+    if path.contains('{') || path.contains('}') {
+        return Err(fail(format!("path `{}` has unresolved placeholders", path)));
+    }
+    let trimmed = path.trim_matches('/');
+    if !trimmed.is_empty() && trimmed.split('/').any(str::is_empty) {
+        return Err(fail(format!("path `{}` has empty segments", path)));
+    }
+    endpoint.request(VALIDATION_BASE).map(|_| ())
+}
+
+/// Returns the message of the given error followed by its sources.
+#[doc(hidden)]
+pub fn describe(err: &ClientError) -> String {
+    let mut message = err.to_string();
+    let mut source = std::error::Error::source(err);
+    while let Some(e) = source {
+        message.push_str(&format!(": {}", e));
+        source = e.source();
+    }
+    message
+}
+
+/// Validates the [Default] value of each given [Endpoint] type with
+/// [validate][crate::validate::validate], panicking with a report of every
+/// failure.
+#[macro_export]
+macro_rules! validate_endpoints {
+    ($($endpoint:ty),+ $(,)?) => {{
+        let mut failures: Vec<String> = Vec::new();
+        $(
+            let endpoint = <$endpoint as ::std::default::Default>::default();
+            if let Err(e) = $crate::validate::validate(&endpoint) {
+                failures.push(format!(
+                    "{}: {}",
+                    stringify!($endpoint),
+                    $crate::validate::describe(&e)
+                ));
+            }
+        )+
+        if !failures.is_empty() {
+            panic!("Endpoint validation failed:\n{}", failures.join("\n"));
+        }
+    }};
+}
@@ -0,0 +1,3068 @@
+mod common;
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use common::TestServer;
+use http::{Request, Response};
+use httpmock::prelude::*;
+use rustify::{
+    adaptive::AdaptiveLimit,
+    client::Client as RustifyClient,
+    clients::reqwest::Client,
+    config::ClientConfig,
+    deferred::{DeferredQueue, DeferredStore, MemoryStore},
+    endpoint::Endpoint,
+    enums::{RequestPriority, TrailingSlash},
+    errors::{ClientError, ErrorKind},
+    events::{BodySizes, Event},
+    guard::UrlGuard,
+    hooks::Hooks,
+    limit::ConcurrencyLimit,
+    profiles::{Profile, Profiles},
+    ratelimit::RateLimitTracker,
+    scheduler::Scheduler,
+    segmented::SegmentedDownload,
+    session::{Auth, Session},
+};
+use rustify_derive::Endpoint;
+use test_log::test;
+
+#[test(tokio::test)]
+async fn test_hooks() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let responses = Arc::new(AtomicUsize::new(0));
+    let errors = Arc::new(AtomicUsize::new(0));
+    let (req_c, resp_c, err_c) = (requests.clone(), responses.clone(), errors.clone());
+    let hooks = Hooks::new()
+        .on_request(move |_| {
+            req_c.fetch_add(1, Ordering::SeqCst);
+        })
+        .on_response(move |_| {
+            resp_c.fetch_add(1, Ordering::SeqCst);
+        })
+        .on_error(move |_| {
+            err_c.fetch_add(1, Ordering::SeqCst);
+        });
+
+    let t =
+        TestServer::with_client(Client::default("").with_config(ClientConfig::new().hooks(hooks)));
+    let m_ok = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/ok");
+        then.status(200).body("null");
+    });
+    let m_err = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/err");
+        then.status(500);
+    });
+
+    let ok = Test {
+        name: "ok".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+    let err = Test {
+        name: "err".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+
+    m_ok.assert();
+    m_err.assert();
+    assert!(ok.is_ok());
+    assert!(err.is_err());
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(responses.load(Ordering::SeqCst), 2);
+    assert_eq!(errors.load(Ordering::SeqCst), 1);
+}
+
+#[test(tokio::test)]
+async fn test_event_sink() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_c = events.clone();
+    let sink = move |e: &Event<'_>| {
+        let name = match e {
+            Event::RequestStarted { operation, .. } => {
+                assert!(operation.unwrap().ends_with("Test"));
+                "started"
+            }
+            Event::RetryScheduled { .. } => "retry",
+            Event::Admitted { .. } => "admitted",
+            Event::ResponseParsed { status, .. } => {
+                assert_eq!(*status, 200);
+                "parsed"
+            }
+            Event::Failed { operation, .. } => {
+                assert!(operation.is_some());
+                "failed"
+            }
+        };
+        events_c.lock().unwrap().push(name);
+    };
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().event_sink(sink)),
+    );
+    let m_ok = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/ok");
+        then.status(200).body("null");
+    });
+    let m_err = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/err");
+        then.status(500);
+    });
+
+    let ok = Test {
+        name: "ok".to_string(),
+    }
+    .exec(&t.client)
+    .await
+    .unwrap();
+    assert!(ok.parse().is_ok());
+    let err = Test {
+        name: "err".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+
+    m_ok.assert();
+    m_err.assert();
+    assert!(err.is_err());
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec!["started", "parsed", "started", "failed"]
+    );
+}
+
+#[test(tokio::test)]
+async fn test_slow_request_threshold() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::with_client(
+        Client::default("")
+            .with_config(ClientConfig::new().slow_request_threshold(Duration::from_millis(1))),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).delay(Duration::from_millis(10));
+    });
+    let r = Test {}.exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_attempt_header() {
+    use rustify::events::Attempt;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::with_client(Client::default("").with_config(
+        ClientConfig::new().attempt_header(http::HeaderName::from_static("x-attempt")),
+    ));
+    let m1 = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path").header("X-Attempt", "1");
+        then.status(200);
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path").header("X-Attempt", "2");
+        then.status(200);
+    });
+    let r1 = Test {}.exec(&t.client).await;
+    let mut req = Test {}.request(&t.client.base).unwrap();
+    req.extensions_mut().insert(Attempt(2));
+    let r2 = t.client.execute(req).await;
+
+    m1.assert();
+    m2.assert();
+    assert!(r1.is_ok());
+    assert_eq!(Attempt::of(r2.unwrap().extensions()), 2);
+}
+
+#[test(tokio::test)]
+async fn test_concurrency_limit() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::with_client(
+        Client::default("")
+            .with_config(ClientConfig::new().concurrency_limit(ConcurrencyLimit::new().total(1))),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).delay(Duration::from_millis(100));
+    });
+
+    let start = Instant::now();
+    let (r1, r2, r3) = tokio::join!(
+        Test {}.exec(&t.client),
+        Test {}.exec(&t.client),
+        Test {}.exec(&t.client)
+    );
+
+    m.assert_hits(3);
+    assert!(r1.is_ok() && r2.is_ok() && r3.is_ok());
+    assert!(start.elapsed() >= Duration::from_millis(300));
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_rate_limit() {
+    use rustify::{
+        bucket::{RateLimiter, TokenBucket},
+        matcher::Matcher,
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "{self.path}")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+    }
+
+    let search = TokenBucket::with_burst(1.0, 1);
+    let limiter = RateLimiter::new()
+        .limit(TokenBucket::with_burst(10.0, 2))
+        .group(Matcher::new().path("search/**"), search.clone());
+    let client = MockClient::new("https://api.example.com")
+        .with_config(ClientConfig::new().rate_limit(limiter));
+    for _ in 0..6 {
+        client.push(MockResponse::new(200));
+    }
+    let test = |path: &str| Test {
+        path: path.to_string(),
+    };
+
+    // The burst is sent at once and the remaining requests at 10 per second
+    let (users, search_users) = (test("users"), test("search/users"));
+    let start = tokio::time::Instant::now();
+    let (r1, r2, r3, r4) = tokio::join!(
+        users.exec(&client),
+        users.exec(&client),
+        users.exec(&client),
+        users.exec(&client),
+    );
+    assert!(r1.is_ok() && r2.is_ok() && r3.is_ok() && r4.is_ok());
+    assert_eq!(start.elapsed(), Duration::from_millis(200));
+
+    // Searches are additionally limited to one per second
+    let start = tokio::time::Instant::now();
+    search_users.exec(&client).await.unwrap();
+    assert_eq!(start.elapsed(), Duration::from_millis(100));
+    let (r1, r2) = tokio::join!(search_users.exec(&client), async {
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(search.queued(), 1);
+    });
+    assert!(r1.is_ok());
+    assert_eq!(
+        start.elapsed(),
+        Duration::from_secs(1) + Duration::from_millis(100)
+    );
+    assert_eq!(search.queued(), 0);
+
+    // Buckets which are never refilled only allow their burst
+    let bucket = TokenBucket::with_burst(0.0, 1);
+    bucket.acquire().await;
+    let r = tokio::time::timeout(Duration::from_secs(3600), bucket.acquire()).await;
+    assert!(r.is_err());
+    assert_eq!(bucket.queued(), 0);
+    r2
+}
+
+#[cfg(all(feature = "mock", feature = "blocking"))]
+#[test]
+fn test_rate_limit_blocking() {
+    use rustify::{
+        bucket::{RateLimiter, TokenBucket},
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let limiter = RateLimiter::new().limit(TokenBucket::with_burst(20.0, 1));
+    let client = MockClient::new("https://api.example.com")
+        .with_config(ClientConfig::new().rate_limit(limiter));
+    let start = Instant::now();
+    for _ in 0..3 {
+        client.push(MockResponse::new(200));
+        Test {}.exec_block(&client).unwrap();
+    }
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_queue_metrics() {
+    use rustify::{
+        events::Limiter,
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let admitted = Arc::new(Mutex::new(Vec::new()));
+    let admitted_c = admitted.clone();
+    let sink = move |e: &Event<'_>| {
+        if let Event::Admitted {
+            operation,
+            limiter,
+            queued,
+            waited,
+        } = e
+        {
+            assert!(operation.unwrap().ends_with("Test"));
+            assert_eq!(*limiter, Limiter::Concurrency);
+            admitted_c.lock().unwrap().push((*queued, *waited));
+        }
+    };
+    let client = MockClient::new("https://api.example.com").with_config(
+        ClientConfig::new()
+            .concurrency_limit(ConcurrencyLimit::new().total(1))
+            .event_sink(sink),
+    );
+    for _ in 0..3 {
+        client.push(MockResponse::new(200).delay(Duration::from_millis(100)));
+    }
+
+    let (r1, r2, r3) = tokio::join!(
+        Test {}.exec(&client),
+        Test {}.exec(&client),
+        Test {}.exec(&client)
+    );
+
+    // The semaphore doesn't guarantee the order queued requests are admitted
+    assert!(r1.is_ok() && r2.is_ok() && r3.is_ok());
+    let admitted = admitted.lock().unwrap();
+    let mut queued = admitted.iter().map(|a| a.0).collect::<Vec<_>>();
+    queued.sort();
+    assert_eq!(queued, [0, 0, 1]);
+    assert_eq!(
+        admitted.iter().map(|a| a.1).collect::<Vec<_>>(),
+        [
+            Duration::ZERO,
+            Duration::from_millis(100),
+            Duration::from_millis(200)
+        ]
+    );
+    assert_eq!(client.resilience_state().concurrency.unwrap().queued, 0);
+}
+
+#[test(tokio::test)]
+async fn test_adaptive_concurrency() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.path}")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+    }
+
+    let limit = AdaptiveLimit::new(4).bounds(2, 5).backoff(0.5);
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().adaptive_concurrency(limit.clone())),
+    );
+    let ok = t.server.mock(|when, then| {
+        when.method(GET).path("/test/ok");
+        then.status(200);
+    });
+    let err = t.server.mock(|when, then| {
+        when.method(GET).path("/test/err");
+        then.status(503);
+    });
+    let missing = t.server.mock(|when, then| {
+        when.method(GET).path("/test/missing");
+        then.status(404);
+    });
+    let test = |path: &str| Test {
+        path: path.to_string(),
+    };
+
+    assert!(test("err").exec(&t.client).await.is_err());
+    assert_eq!(limit.limit(), 2);
+    assert!(test("err").exec(&t.client).await.is_err());
+    assert_eq!(limit.limit(), 2);
+    assert!(test("missing").exec(&t.client).await.is_err());
+    assert_eq!(limit.limit(), 2);
+    for _ in 0..15 {
+        assert!(test("ok").exec(&t.client).await.is_ok());
+    }
+    assert_eq!(limit.limit(), 5);
+    assert_eq!(limit.in_flight(), 0);
+
+    err.assert_hits(2);
+    missing.assert();
+    ok.assert_hits(15);
+}
+
+#[test(tokio::test)]
+async fn test_probe() {
+    let t = TestServer::default();
+    let health = t.server.mock(|when, then| {
+        when.method(GET).path("/health");
+        then.status(204);
+    });
+    let secure = t.server.mock(|when, then| {
+        when.method(GET).path("/secure");
+        then.status(401);
+    });
+
+    let report = t.client.probe("health").await.unwrap();
+    assert_eq!(report.status, 204);
+    assert!(report.url.ends_with("/health"));
+    assert!(report.tls.is_none());
+
+    let diagnosis = |r: Result<_, ClientError>| match r {
+        Err(ClientError::ProbeError { diagnosis, .. }) => diagnosis,
+        r => panic!("expected probe error, got {:?}", r.map(|_| ())),
+    };
+    assert!(diagnosis(t.client.probe("secure").await).contains("credentials"));
+    assert!(diagnosis(Client::default("myapi.com").probe("health").await).contains("base URL"));
+    assert!(
+        diagnosis(Client::default("http://127.0.0.1:1").probe("health").await).contains("connect")
+    );
+
+    health.assert();
+    secure.assert();
+}
+
+#[test(tokio::test)]
+async fn test_body_sizes() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.path}", method = "POST")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+        name: String,
+    }
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().log_body_sizes()),
+    );
+    let plain = t.server.mock(|when, then| {
+        when.method(POST).path("/test/plain");
+        then.status(200).body("hello");
+    });
+    let gzip = t.server.mock(|when, then| {
+        when.method(POST).path("/test/gzip");
+        then.status(200)
+            .header("Content-Encoding", "gzip")
+            .body(gzip_bytes(b"hello world"));
+    });
+    let sizes = |path: &str| Test {
+        path: path.to_string(),
+        name: "test".to_string(),
+    };
+
+    let r1 = sizes("plain").exec(&t.client).await.unwrap();
+    let r2 = sizes("gzip").exec(&t.client).await.unwrap();
+
+    plain.assert();
+    gzip.assert();
+    assert_eq!(
+        BodySizes::of(r1.response.extensions()),
+        Some(&BodySizes {
+            request: 15,
+            response: 5,
+            encoding: None,
+            decoded: None,
+        })
+    );
+    let s2 = BodySizes::of(r2.response.extensions()).unwrap();
+    assert_eq!(s2.response, gzip_bytes(b"hello world").len());
+    assert_eq!(s2.encoding.as_deref(), Some("gzip"));
+    assert_eq!(s2.decoded, Some(11));
+}
+
+fn gzip_bytes(data: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+#[test(tokio::test)]
+async fn test_decompression() {
+    use std::io::Write;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.encoding}", response = "Vec<u32>")]
+    struct Test {
+        #[endpoint(skip)]
+        encoding: String,
+    }
+
+    let data = b"[1, 2, 3]";
+    let mut deflate = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    deflate.write_all(data).unwrap();
+    let mut br = Vec::new();
+    brotli::BrotliCompress(
+        &mut &data[..],
+        &mut br,
+        &brotli::enc::BrotliEncoderParams::default(),
+    )
+    .unwrap();
+    let encoded = [
+        ("gzip", gzip_bytes(data)),
+        ("deflate", deflate.finish().unwrap()),
+        ("br", br),
+        ("gzip, br", {
+            let mut out = Vec::new();
+            brotli::BrotliCompress(
+                &mut gzip_bytes(data).as_slice(),
+                &mut out,
+                &brotli::enc::BrotliEncoderParams::default(),
+            )
+            .unwrap();
+            out
+        }),
+    ];
+
+    let t = TestServer::default();
+    for (i, (encoding, body)) in encoded.iter().enumerate() {
+        t.server.mock(|when, then| {
+            when.method(GET).path(format!("/test/{}", i));
+            then.status(200)
+                .header("Content-Encoding", *encoding)
+                .body(body);
+        });
+    }
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/invalid");
+        then.status(200)
+            .header("Content-Encoding", "gzip")
+            .body("[1, 2, 3]");
+    });
+
+    for (i, (encoding, _)) in encoded.iter().enumerate() {
+        let r = Test {
+            encoding: i.to_string(),
+        }
+        .exec(&t.client)
+        .await
+        .unwrap();
+        assert!(r.response.headers().get("Content-Encoding").is_none());
+        assert_eq!(r.parse().unwrap(), vec![1, 2, 3], "{}", encoding);
+    }
+    let r = Test {
+        encoding: "invalid".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+    assert!(matches!(r, Err(ClientError::ResponseError { .. })));
+
+    // Empty bodies, i.e. of HEAD requests, aren't decoded
+    let mut resp = http::Response::builder()
+        .header("Content-Encoding", "gzip")
+        .body(Vec::new())
+        .unwrap();
+    assert_eq!(rustify::encoding::decode(&mut resp).unwrap(), None);
+    assert_eq!(resp.headers()["Content-Encoding"], "gzip");
+
+    // Bodies decoding to more than the limit are rejected
+    let body = gzip_bytes(&[0; 1024]);
+    let mut resp = http::Response::builder()
+        .header("Content-Encoding", "gzip")
+        .body(body.clone())
+        .unwrap();
+    assert_eq!(
+        rustify::encoding::decode_with_limit(&mut resp, 1024).unwrap(),
+        Some(1024)
+    );
+    let mut resp = http::Response::builder()
+        .header("Content-Encoding", "gzip")
+        .body(body)
+        .unwrap();
+    let r = rustify::encoding::decode_with_limit(&mut resp, 1023);
+    assert!(matches!(r, Err(ClientError::ResponseError { .. })));
+}
+
+#[test(tokio::test)]
+async fn test_resilience_state() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let scheduler = Scheduler::new(2);
+    let t = TestServer::with_client(
+        Client::default("").with_config(
+            ClientConfig::new()
+                .adaptive_concurrency(AdaptiveLimit::new(4))
+                .concurrency_limit(ConcurrencyLimit::new().per_host(3))
+                .scheduler(scheduler.clone())
+                .track_rate_limits(RateLimitTracker::new()),
+        ),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .header("X-RateLimit-Limit", "100")
+            .header("X-RateLimit-Remaining", "99");
+    });
+
+    let empty = Client::default("").resilience_state();
+    assert_eq!(empty, Default::default());
+
+    let before = t.client.resilience_state();
+    assert!(before.rate_limits.is_empty());
+    assert_eq!(before.concurrency.unwrap().max_per_host, Some(3));
+
+    Test {}.exec(&t.client).await.unwrap();
+    let _held = (
+        scheduler.acquire(RequestPriority::NORMAL).await,
+        scheduler.acquire(RequestPriority::NORMAL).await,
+    );
+    let state = t.client.resilience_state();
+
+    m.assert();
+    assert_eq!(state.adaptive.unwrap().in_flight, 0);
+    assert_eq!(state.concurrency.unwrap().in_flight, 0);
+    assert_eq!(state.scheduler.unwrap().available, 0);
+    assert_eq!(state.queued(), 0);
+    let host = t.server.address().to_string();
+    let limits = state.rate_limits.get(&host).unwrap();
+    assert_eq!((limits.limit, limits.remaining), (Some(100), Some(99)));
+}
+
+#[test(tokio::test)]
+async fn test_error_kind() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.path}", response = "u64")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+    }
+
+    let t = TestServer::default();
+    let missing = t.server.mock(|when, then| {
+        when.method(GET).path("/test/missing");
+        then.status(404);
+    });
+    let invalid = t.server.mock(|when, then| {
+        when.method(GET).path("/test/invalid");
+        then.status(200).body("not a number");
+    });
+    let test = |path: &str| Test {
+        path: path.to_string(),
+    };
+
+    let not_found = test("missing").exec(&t.client).await.err().unwrap();
+    let parse = test("invalid").exec(&t.client).await.unwrap().parse();
+    let transport = test("missing")
+        .exec(&Client::default("http://127.0.0.1:1"))
+        .await
+        .err()
+        .unwrap();
+    let build = test("missing")
+        .exec(&Client::default("not a url"))
+        .await
+        .err()
+        .unwrap();
+    let probe = t.client.probe("test/missing").await.unwrap_err();
+
+    missing.assert_hits(2);
+    invalid.assert();
+    assert_eq!(not_found.kind(), ErrorKind::Http { status: 404 });
+    assert_eq!(not_found.status(), Some(404));
+    assert_eq!(parse.unwrap_err().kind(), ErrorKind::Deserialization);
+    assert_eq!(transport.kind(), ErrorKind::Transport);
+    assert_eq!(transport.status(), None);
+    assert_eq!(build.kind(), ErrorKind::Build);
+    assert_eq!(probe.kind(), ErrorKind::Http { status: 404 });
+}
+
+#[test(tokio::test)]
+async fn test_url_guard() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let guarded = |guard: UrlGuard| {
+        Client::default(&t.server.base_url()).with_config(ClientConfig::new().url_guard(guard))
+    };
+
+    let allowed = Test {}
+        .exec(&guarded(UrlGuard::new().allow_host("127.0.0.1")))
+        .await;
+    let private = Test {}
+        .exec(&guarded(UrlGuard::new().block_private_ips()))
+        .await;
+    let host = Test {}
+        .exec(&guarded(UrlGuard::new().allow_host("*.example.com")))
+        .await;
+
+    m.assert_hits(1);
+    assert!(allowed.is_ok());
+    assert!(matches!(private, Err(ClientError::BlockedUrlError { .. })));
+    assert!(matches!(host, Err(ClientError::BlockedUrlError { .. })));
+
+    let guard = UrlGuard::new()
+        .allow_scheme("https")
+        .allow_host("*.example.com")
+        .allow_host("[::ffff:10.0.0.1]")
+        .block_private_ips();
+    let check = |url: &str| guard.check(&url.parse().unwrap()).is_ok();
+    assert!(check("https://api.example.com/path"));
+    assert!(!check("http://api.example.com/path"));
+    assert!(!check("https://example.com/path"));
+    assert!(!check("https://evilexample.com/path"));
+    assert!(!check("https://[::ffff:10.0.0.1]/path"));
+
+    let private = UrlGuard::new().block_private_ips();
+    let check = |url: &str| private.check(&url.parse().unwrap()).is_ok();
+    assert!(check("https://93.184.216.34"));
+    assert!(check("https://[2606:2800:220:1::1]"));
+    for url in [
+        "http://169.254.169.254/latest/meta-data",
+        "http://10.1.2.3",
+        "http://192.168.0.1",
+        "http://100.64.0.1",
+        "http://0.0.0.0",
+        "http://localhost:8080",
+        "http://[::1]",
+        "http://[fd00::1]",
+        "http://[fe80::1]",
+    ] {
+        assert!(!check(url), "{} should be blocked", url);
+    }
+}
+
+#[test(tokio::test)]
+async fn test_segmented_download() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "{self.path}")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+    }
+
+    let t = TestServer::default();
+    let segments = [
+        ("bytes=0-3", "bytes 0-3/10", "0123"),
+        ("bytes=4-7", "bytes 4-7/10", "4567"),
+        ("bytes=8-11", "bytes 8-9/10", "89"),
+    ];
+    let mocks = segments
+        .iter()
+        .map(|(range, content_range, body)| {
+            t.server.mock(|when, then| {
+                when.method(GET).path("/file").header("range", *range);
+                then.status(206)
+                    .header("content-range", *content_range)
+                    .header("etag", "\"v1\"")
+                    .body(*body);
+            })
+        })
+        .collect::<Vec<_>>();
+    let m_full = t.server.mock(|when, then| {
+        when.method(GET).path("/unranged");
+        then.status(200).body("0123456789");
+    });
+
+    let download = SegmentedDownload::new(4).parallelism(2);
+    let file = Test {
+        path: "file".to_string(),
+    };
+    let r = download.exec(&file, &t.client).await.unwrap();
+    let unranged = Test {
+        path: "unranged".to_string(),
+    };
+    let full = download.exec(&unranged, &t.client).await.unwrap();
+
+    mocks.iter().for_each(|m| m.assert());
+    m_full.assert();
+    assert_eq!(r.response.status(), 200);
+    assert_eq!(r.raw(), b"0123456789");
+    assert_eq!(r.response.headers()["content-length"], "10");
+    assert_eq!(full.raw(), b"0123456789");
+}
+
+#[test(tokio::test)]
+async fn test_redirect_chain() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "start", method = "POST")]
+    struct Test {
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let http = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let config = ClientConfig::new().follow_redirects(2);
+    let client = Client::new(&t.server.base_url(), http.clone()).with_config(config);
+    let m_start = t.server.mock(|when, then| {
+        when.method(POST).path("/start");
+        then.status(303).header("location", "/middle");
+    });
+    let m_middle = t.server.mock(|when, then| {
+        when.method(GET).path("/middle");
+        then.status(307)
+            .header("location", t.server.url("/end?code=abc"));
+    });
+    let m_end = t.server.mock(|when, then| {
+        when.method(GET).path("/end").query_param("code", "abc");
+        then.status(200);
+    });
+    let e = Test {
+        name: "test".to_string(),
+    };
+    let r = e.exec(&client).await.unwrap();
+
+    m_start.assert();
+    m_middle.assert();
+    m_end.assert();
+    let chain = r.redirects();
+    assert_eq!(chain.len(), 2);
+    assert_eq!(chain[0].url, t.server.url("/start"));
+    assert_eq!(chain[0].status, 303);
+    assert_eq!(chain[0].location, t.server.url("/middle"));
+    assert_eq!(chain[1].status, 307);
+    assert_eq!(chain[1].location, t.server.url("/end?code=abc"));
+
+    let config = ClientConfig::new().follow_redirects(1);
+    let client = Client::new(&t.server.base_url(), http).with_config(config);
+    let r = e.exec(&client).await;
+    assert!(matches!(r, Err(ClientError::ResponseError { .. })));
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test)]
+async fn test_redirect_credentials() {
+    use http::{
+        header::{AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION},
+        HeaderValue,
+    };
+    use rustify::{
+        endpoint::MiddleWare,
+        mock::{MockClient, MockResponse},
+    };
+
+    struct Credentials;
+
+    impl MiddleWare for Credentials {
+        fn request<E: Endpoint>(
+            &self,
+            _: &E,
+            req: &mut http::Request<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            let headers = req.headers_mut();
+            headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer token"));
+            headers.insert(COOKIE, HeaderValue::from_static("session=1"));
+            headers.insert(
+                PROXY_AUTHORIZATION,
+                HeaderValue::from_static("Basic cHJveHk="),
+            );
+            Ok(())
+        }
+
+        fn response<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut http::Response<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            Ok(())
+        }
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "start")]
+    struct Test {}
+
+    let client = MockClient::new("https://api.example.com")
+        .with_config(ClientConfig::new().follow_redirects(2));
+    let location = |url: &'static str| {
+        MockResponse::new(302).header(http::header::LOCATION, HeaderValue::from_static(url))
+    };
+    client
+        .push(location("/middle"))
+        .push(location("http://api.example.com/end"))
+        .push(MockResponse::new(200));
+    let r = Test {}.with_middleware(&Credentials).exec(&client).await;
+    assert!(r.is_ok());
+
+    // Credentials survive redirects on the same origin but not a downgrade
+    // to plain HTTP on the same host
+    let requests = client.requests();
+    assert_eq!(requests.len(), 3);
+    for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION] {
+        assert!(requests[1].headers().contains_key(&name));
+        assert!(!requests[2].headers().contains_key(&name));
+    }
+    assert_eq!(requests[2].uri(), "http://api.example.com/end");
+}
+
+#[test(tokio::test)]
+async fn test_trailing_slash() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "{self.path}")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+        #[endpoint(query)]
+        page: u8,
+    }
+
+    let t = TestServer::default();
+    let m_slash = t.server.mock(|when, then| {
+        when.method(GET).path("/users/").query_param("page", "2");
+        then.status(200);
+    });
+    let m_bare = t.server.mock(|when, then| {
+        when.method(GET).path("/teams").query_param("page", "2");
+        then.status(200);
+    });
+    let always = Client::new(&t.server.base_url(), reqwest::Client::new())
+        .with_config(ClientConfig::new().trailing_slash(TrailingSlash::ALWAYS));
+    let never = Client::new(&t.server.base_url(), reqwest::Client::new())
+        .with_config(ClientConfig::new().trailing_slash(TrailingSlash::NEVER));
+    let users = |path: &str| Test {
+        path: path.to_string(),
+        page: 2,
+    };
+
+    assert!(users("users").exec(&always).await.is_ok());
+    assert!(users("users/").exec(&always).await.is_ok());
+    assert!(users("teams/").exec(&never).await.is_ok());
+    assert!(users("teams").exec(&never).await.is_ok());
+    m_slash.assert_hits(2);
+    m_bare.assert_hits(2);
+}
+
+#[test(tokio::test)]
+async fn test_trailing_slash_signed() {
+    use http::HeaderName;
+    use rustify::hmac::{Component, HmacSigner};
+    use std::time::SystemTime;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "users")]
+    struct Test {
+        #[endpoint(query)]
+        page: u8,
+    }
+
+    let signer = || {
+        HmacSigner::new(b"secret", HeaderName::from_static("x-signature"))
+            .components(vec![Component::Method, Component::PathAndQuery])
+    };
+    let t = TestServer::default();
+    let mut sent = http::Request::get(format!("{}/users/?page=2", t.server.base_url()))
+        .body(Vec::new())
+        .unwrap();
+    signer().sign_at(&mut sent, SystemTime::now()).unwrap();
+    let signature = sent.headers()["x-signature"].to_str().unwrap().to_string();
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/users/")
+            .query_param("page", "2")
+            .header("x-signature", &signature);
+        then.status(200);
+    });
+    let client = Client::new(&t.server.base_url(), reqwest::Client::new())
+        .with_config(ClientConfig::new().trailing_slash(TrailingSlash::ALWAYS));
+    let signed = Client::new(&t.server.base_url(), reqwest::Client::new()).with_config(
+        ClientConfig::new()
+            .trailing_slash(TrailingSlash::ALWAYS)
+            .middleware(signer()),
+    );
+
+    // The signature covers the normalized path whether the signer runs per
+    // call or for every request of the client
+    let r = Test { page: 2 }
+        .with_middleware(&signer())
+        .exec(&client)
+        .await;
+    assert!(r.is_ok());
+    assert!(Test { page: 2 }.exec(&signed).await.is_ok());
+    m.assert_hits(2);
+}
+
+#[test(tokio::test)]
+async fn test_stale_connection_retry() {
+    use std::io::{Read, Write};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Get {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Post {}
+
+    // Closes every other connection without responding
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for (i, stream) in listener.incoming().take(3).enumerate() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0; 1024];
+            let _ = stream.read(&mut buf);
+            if i % 2 == 1 {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                );
+            }
+        }
+    });
+    let client = Client::new(&format!("http://{}", addr), reqwest::Client::new())
+        .with_config(ClientConfig::new().retry_stale_connections());
+
+    let r = Get {}.exec(&client).await;
+    assert!(r.is_ok());
+    let r = Post {}.exec(&client).await;
+    assert!(r.err().unwrap().is_stale_connection());
+}
+
+#[test(tokio::test)]
+async fn test_deadline() {
+    use rustify::deadline::Deadline;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(
+            ClientConfig::new()
+                .attempt_timeout(Duration::from_secs(5))
+                .deadline(Duration::from_millis(100)),
+        ),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).delay(Duration::from_millis(500));
+    });
+
+    let start = Instant::now();
+    let r = Test {}.exec(&t.client).await;
+    assert!(start.elapsed() < Duration::from_millis(400));
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+    m.assert_hits(1);
+
+    let mut req = Test {}.request(&t.client.base).unwrap();
+    req.extensions_mut()
+        .insert(Deadline::after(Duration::from_millis(0)));
+    let r = t.client.execute(req).await;
+    assert!(matches!(r, Err(ClientError::DeadlineError { .. })));
+    m.assert_hits(1);
+}
+
+#[test(tokio::test)]
+async fn test_timeouts() {
+    use rustify::deadline::Timeouts;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Default {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", connect_timeout = "1s", read_timeout = "2s")]
+    struct Patient {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", read_timeout = "2s", total_timeout = "100ms")]
+    struct Bounded {}
+
+    assert_eq!(Default {}.timeouts(), Timeouts::new());
+    assert_eq!(
+        Patient {}.timeouts(),
+        Timeouts::new()
+            .connect(Duration::from_secs(1))
+            .read(Duration::from_secs(2))
+    );
+    let req = Bounded {}.request("http://localhost").unwrap();
+    assert_eq!(
+        Timeouts::of(req.extensions()),
+        Timeouts::new()
+            .read(Duration::from_secs(2))
+            .total(Duration::from_millis(100))
+    );
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(
+            ClientConfig::new()
+                .connect_timeout(Duration::from_secs(1))
+                .read_timeout(Duration::from_millis(100)),
+        ),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).delay(Duration::from_millis(300));
+    });
+
+    // The read timeout of the client is exceeded
+    let r = Default {}.exec(&t.client).await;
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+
+    // The endpoint allows slower reads
+    let r = Patient {}.exec(&t.client).await;
+    assert!(r.is_ok());
+
+    // The endpoint allows slower reads, but not in total
+    let r = Bounded {}.exec(&t.client).await;
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+    m.assert_hits(3);
+
+    // A client is built for each combination of connect and read timeouts
+    assert_eq!(
+        format!("{:?}", t.client.proxies),
+        "ProxyPool { proxies: 2 }"
+    );
+
+    // Clients created from a built client send requests with it and only
+    // apply the total timeout
+    let mut headers = http::HeaderMap::new();
+    headers.insert("X-Client", http::HeaderValue::from_static("custom"));
+    let http = reqwest::Client::builder()
+        .default_headers(headers)
+        .build()
+        .unwrap();
+    let t = TestServer::with_client(Client::new("", http));
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("X-Client", "custom");
+        then.status(200).delay(Duration::from_millis(300));
+    });
+    let r = Patient {}.exec(&t.client).await;
+    assert!(r.is_ok());
+    let r = Bounded {}.exec(&t.client).await;
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+    m.assert_hits(2);
+    assert_eq!(
+        format!("{:?}", t.client.proxies),
+        "ProxyPool { proxies: 0 }"
+    );
+}
+
+#[test(tokio::test)]
+async fn test_http_cache() {
+    use rustify::cache::{CacheStatus, HttpCache};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "String")]
+    struct Test {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "String", cache = "no-store")]
+    struct NoStore {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/ttl", response = "String", cache = "ttl=1h")]
+    struct Ttl {}
+
+    let t = TestServer::default();
+    let revalidated = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("If-None-Match", "\"v1\"");
+        then.status(304).header("ETag", "\"v1\"");
+    });
+    let fresh = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .header("ETag", "\"v1\"")
+            .json_body(serde_json::json!("cached"));
+    });
+    let ttl = t.server.mock(|when, then| {
+        when.method(GET).path("/test/ttl");
+        then.status(200).json_body(serde_json::json!("ttl"));
+    });
+    let client = HttpCache::new(t.client);
+
+    // The first response is stored
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), "cached");
+    assert_eq!(
+        CacheStatus::of(r.response.extensions()),
+        Some(CacheStatus::Miss)
+    );
+    assert_eq!(client.len(), 1);
+
+    // The stored response is returned when it wasn't modified
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), "cached");
+    assert_eq!(
+        CacheStatus::of(r.response.extensions()),
+        Some(CacheStatus::Revalidated)
+    );
+    revalidated.assert_hits(1);
+    fresh.assert_hits(1);
+
+    // Endpoints which opt out aren't revalidated
+    let r = NoStore {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), "cached");
+    revalidated.assert_hits(1);
+    fresh.assert_hits(2);
+
+    // Responses within their TTL are returned without contacting the server
+    for status in [CacheStatus::Miss, CacheStatus::Hit] {
+        let r = Ttl {}.exec(&client).await.unwrap();
+        assert_eq!(r.parse().unwrap(), "ttl");
+        assert_eq!(CacheStatus::of(r.response.extensions()), Some(status));
+    }
+    ttl.assert_hits(1);
+    assert_eq!(client.len(), 2);
+
+    client.clear();
+    assert!(client.is_empty());
+}
+
+#[test(tokio::test)]
+async fn test_http_cache_stale() {
+    use rustify::cache::{CacheStatus, HttpCache};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "String")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let revalidated = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("If-None-Match", "\"v1\"");
+        then.status(304).header("ETag", "\"v1\"");
+    });
+    let fresh = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .header("ETag", "\"v1\"")
+            .json_body(serde_json::json!("cached"));
+    });
+    let client = HttpCache::new(t.client).stale_while_revalidate(Duration::from_secs(3600));
+
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(
+        CacheStatus::of(r.response.extensions()),
+        Some(CacheStatus::Miss)
+    );
+
+    // The stored response is returned while it's refreshed in the background
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), "cached");
+    assert_eq!(
+        CacheStatus::of(r.response.extensions()),
+        Some(CacheStatus::Stale)
+    );
+    let start = Instant::now();
+    while revalidated.hits() == 0 && start.elapsed() < Duration::from_secs(5) {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    revalidated.assert_hits(1);
+    fresh.assert_hits(1);
+}
+
+#[test(tokio::test)]
+async fn test_graceful_shutdown() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/fast");
+        then.status(200).delay(Duration::from_millis(100));
+    });
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/slow");
+        then.status(200).delay(Duration::from_secs(5));
+    });
+    async fn exec(client: &Client, name: &str) -> Result<(), ClientError> {
+        Test {
+            name: name.to_string(),
+        }
+        .exec(client)
+        .await
+        .map(|_| ())
+    }
+
+    // Requests which complete within the grace period are drained
+    let client =
+        Client::default(&t.server.base_url()).with_config(ClientConfig::new().graceful_shutdown());
+    let (r, cancelled) = futures_util::future::join(exec(&client, "fast"), async {
+        tokio::task::yield_now().await;
+        client.shutdown(Duration::from_secs(2)).await
+    })
+    .await;
+    assert!(r.is_ok());
+    assert_eq!(cancelled, 0);
+    let r = exec(&client, "fast").await;
+    assert!(matches!(r, Err(ClientError::ShutdownError)));
+
+    // Requests which don't are cancelled
+    let client =
+        Client::default(&t.server.base_url()).with_config(ClientConfig::new().graceful_shutdown());
+    let start = Instant::now();
+    let ((r1, r2), cancelled) = futures_util::future::join(
+        futures_util::future::join(exec(&client, "fast"), exec(&client, "slow")),
+        async {
+            tokio::task::yield_now().await;
+            client.shutdown(Duration::from_millis(300)).await
+        },
+    )
+    .await;
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert!(r1.is_ok());
+    assert!(matches!(r2, Err(ClientError::ShutdownError)));
+    assert_eq!(cancelled, 1);
+}
+
+#[test(tokio::test)]
+async fn test_body_capture() {
+    use rustify::capture::BodyCapture;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {
+        password: String,
+    }
+
+    let capture = BodyCapture::new().sample_rate(0.25).max_bytes(8);
+    let sampled = (0..8).filter(|_| capture.sample()).count();
+    assert_eq!(sampled, 2);
+    assert_eq!(
+        capture.render(br#"{"Password":"x"}"#),
+        r#"{"Passwo... (17 more bytes)"#
+    );
+    assert_eq!(capture.render(b"plain"), "plain");
+    assert_eq!(capture.render(&[0xff, 0xfe]), "<2 bytes of binary data>");
+    assert_eq!(
+        BodyCapture::new().render(br#"[{"token":"t","id":1}]"#),
+        r#"[{"id":1,"token":"<redacted>"}]"#
+    );
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().capture_bodies(BodyCapture::new())),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path");
+        then.status(200).body("ok");
+    });
+    let r = Test {
+        password: "hunter2".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+    m.assert();
+    assert!(r.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_refresh_lock() {
+    use rustify::auth::RefreshLock;
+
+    let lock = RefreshLock::<u32>::new();
+    let refreshes = AtomicUsize::new(0);
+    let refresh = || async {
+        let n = refreshes.fetch_add(1, Ordering::SeqCst) as u32;
+        tokio::task::yield_now().await;
+        Ok(n + 1)
+    };
+
+    let tokens =
+        futures_util::future::join_all((0..10).map(|_| lock.get_or_refresh(|_| true, refresh)))
+            .await;
+    assert!(tokens.iter().all(|t| *t.as_ref().unwrap() == 1));
+    assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+
+    // Only the first rejection of a token causes a refresh
+    lock.invalidate(&1).await;
+    assert_eq!(lock.get_or_refresh(|_| true, refresh).await.unwrap(), 2);
+    lock.invalidate(&1).await;
+    assert_eq!(lock.get().await, Some(2));
+    assert_eq!(lock.get_or_refresh(|t| *t > 2, refresh).await.unwrap(), 3);
+    assert_eq!(refreshes.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_credential_store() {
+    use rustify::credentials::{CredentialStore, Credentials, FileStore, MemoryStore};
+
+    let path =
+        std::env::temp_dir().join(format!("rustify-credentials-{}.json", std::process::id()));
+    let stores: Vec<Box<dyn CredentialStore>> = vec![
+        Box::new(MemoryStore::new()),
+        Box::new(FileStore::new(&path)),
+    ];
+    let fresh = Credentials::new("access")
+        .refresh_token("refresh")
+        .expires_in(Duration::from_secs(60));
+    let expired = Credentials {
+        expires_at: Some(0),
+        ..Credentials::new("old")
+    };
+
+    for store in stores {
+        assert_eq!(store.load("default").unwrap(), None);
+        store.save("default", &fresh).unwrap();
+        store.save("other", &expired).unwrap();
+        assert_eq!(store.load("default").unwrap(), Some(fresh.clone()));
+        assert!(store.load("other").unwrap().unwrap().is_expired());
+        store.invalidate("default").unwrap();
+        assert_eq!(store.load("default").unwrap(), None);
+        assert!(store.load("other").unwrap().is_some());
+    }
+
+    // File backed credentials survive being reopened
+    assert!(FileStore::new(&path).load("other").unwrap().is_some());
+    assert!(!format!("{:?}", fresh).contains("\"access\""));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(all(feature = "mock", feature = "oauth"))]
+#[test(tokio::test(start_paused = true))]
+async fn test_device_flow() {
+    use rustify::{
+        mock::{MockClient, MockResponse},
+        oauth::DeviceFlow,
+    };
+
+    let client = MockClient::new("https://auth.example.com");
+    client
+        .push(MockResponse::new(200).body(
+            r#"{"device_code":"dc","user_code":"ABCD-EFGH","verification_uri":"https://auth.example.com/device","expires_in":60,"interval":5}"#,
+        ))
+        .push(MockResponse::new(400).body(r#"{"error":"authorization_pending"}"#))
+        .push(MockResponse::new(400).body(r#"{"error":"slow_down"}"#))
+        .push(MockResponse::new(200).body(
+            r#"{"access_token":"token","token_type":"Bearer","refresh_token":"refresh","expires_in":3600}"#,
+        ))
+        .push(MockResponse::new(400).body(r#"{"error":"access_denied"}"#));
+    let flow = DeviceFlow::new(
+        "cli",
+        "https://auth.example.com/device/code",
+        "https://auth.example.com/token",
+    )
+    .scope("read")
+    .scope("write");
+
+    let auth = flow.start(&client).await.unwrap();
+    assert_eq!(auth.user_code, "ABCD-EFGH");
+    assert_eq!(auth.verification_uri, "https://auth.example.com/device");
+    assert_eq!(auth.verification_uri_complete, None);
+
+    // Polls after 5 seconds and again after 10 seconds once asked to slow down
+    let start = tokio::time::Instant::now();
+    let credentials = flow.poll(&client, &auth).await.unwrap();
+    assert_eq!(start.elapsed(), Duration::from_secs(15));
+    assert_eq!(credentials.access_token, "token");
+    assert_eq!(credentials.refresh_token.as_deref(), Some("refresh"));
+    assert!(!credentials.is_expired());
+
+    let bodies = client
+        .requests()
+        .iter()
+        .map(|r| String::from_utf8(r.body().clone()).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(bodies[0], "client_id=cli&scope=read+write");
+    assert_eq!(bodies.len(), 4);
+    assert!(bodies[1..].iter().all(|b| b
+        == "grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code&device_code=dc&client_id=cli"));
+
+    let res = flow.poll(&client, &auth).await;
+    assert!(matches!(res, Err(ClientError::ApiError { message }) if message == "access_denied"));
+}
+
+#[cfg(all(feature = "mock", feature = "oauth"))]
+#[test(tokio::test)]
+async fn test_oauth2_middleware() {
+    use rustify::{
+        credentials::Credentials,
+        mock::{MockClient, MockResponse},
+        oauth::OAuth2Middleware,
+    };
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "user", response = "String")]
+    struct GetUser {}
+
+    let mock = Arc::new(MockClient::new("https://api.example.com"));
+    mock.push(MockResponse::new(200).body(r#""first""#))
+        .push(MockResponse::new(401))
+        .push(MockResponse::new(200).body(r#""second""#))
+        .push(MockResponse::new(401))
+        .push(MockResponse::new(401));
+    let fetched = AtomicUsize::new(0);
+    let source = || {
+        let n = fetched.fetch_add(1, Ordering::SeqCst) + 1;
+        async move { Ok(Credentials::new(&format!("token-{}", n))) }
+    };
+    let client = OAuth2Middleware::new(mock.clone(), source);
+
+    // Fetches a token before the first request and reuses it
+    let res = GetUser {}.exec(&client).await.unwrap().parse().unwrap();
+    assert_eq!(res, "first");
+
+    // Refreshes the token and resends the request once after a 401
+    let res = GetUser {}.exec(&client).await.unwrap().parse().unwrap();
+    assert_eq!(res, "second");
+    assert_eq!(client.credentials().await.unwrap().access_token, "token-2");
+
+    // Fails if the fresh token is rejected too
+    let res = GetUser {}.exec(&client).await;
+    assert!(matches!(
+        res,
+        Err(ClientError::ServerResponseError { code: 401, .. })
+    ));
+    assert_eq!(fetched.load(Ordering::SeqCst), 3);
+
+    let tokens = mock
+        .requests()
+        .iter()
+        .map(|r| r.headers()["authorization"].to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        tokens,
+        [
+            "Bearer token-1",
+            "Bearer token-1",
+            "Bearer token-2",
+            "Bearer token-2",
+            "Bearer token-3"
+        ]
+    );
+}
+
+#[test(tokio::test)]
+async fn test_session_scopes() {
+    use rustify::metadata::EndpointMetadata;
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "repos",
+        operation_id = "listRepos",
+        scopes = ["repo:read", "org:read"]
+    )]
+    struct ListRepos {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "user")]
+    struct GetUser {}
+
+    assert_eq!(
+        ListRepos {}.metadata(),
+        EndpointMetadata {
+            operation_id: Some("listRepos"),
+            scopes: &["repo:read", "org:read"],
+            doc: None,
+        }
+    );
+    let req = ListRepos {}.request("http://localhost").unwrap();
+    assert_eq!(EndpointMetadata::of(req.extensions()).scopes.len(), 2);
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET);
+        then.status(200);
+    });
+    let session = Session::new(t.client);
+    session.set_scopes(Some(vec!["repo:read".to_string()]));
+
+    let err = ListRepos {}.exec(&session).await.err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Token is missing scopes required by listRepos: org:read"
+    );
+    assert!(GetUser {}.exec(&session).await.is_ok());
+    m.assert_hits(1);
+
+    session.set_scopes(Some(vec!["org:read".to_string(), "repo:read".to_string()]));
+    assert!(ListRepos {}.exec(&session).await.is_ok());
+    m.assert_hits(2);
+}
+
+#[cfg(feature = "docs")]
+#[test]
+fn test_metadata_doc() {
+    /// Lists repositories.
+    ///
+    /// Only repositories the token can read are returned.
+    #[derive(Endpoint)]
+    #[endpoint(path = "repos")]
+    struct ListRepos {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "user")]
+    struct GetUser {}
+
+    assert_eq!(
+        ListRepos {}.metadata().doc,
+        Some("Lists repositories.\n\nOnly repositories the token can read are returned.")
+    );
+    assert_eq!(GetUser {}.metadata().doc, None);
+}
+
+#[test(tokio::test)]
+async fn test_registry() {
+    use rustify::invoke::Registry;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, Endpoint)]
+    #[endpoint(path = "users/{self.id}", response = "Vec<String>")]
+    struct ListRoles {
+        #[endpoint(skip)]
+        id: u64,
+        #[endpoint(query)]
+        limit: Option<u32>,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/users/1").query_param("limit", "2");
+        then.status(200).json_body(json!(["admin", "dev"]));
+    });
+    let registry = Registry::new().register::<ListRoles>("listRoles");
+    assert!(registry.contains("listRoles"));
+
+    let r = registry
+        .invoke(&t.client, "listRoles", json!({ "id": 1, "limit": 2 }))
+        .await;
+    m.assert();
+    assert_eq!(r.unwrap(), json!(["admin", "dev"]));
+
+    let r = registry
+        .invoke(&t.client, "listRoles", json!({ "id": "one" }))
+        .await;
+    assert!(matches!(r, Err(ClientError::EndpointBuildError { .. })));
+    let r = registry.invoke(&t.client, "deleteUser", json!({})).await;
+    assert!(matches!(r, Err(ClientError::OperationError { name }) if name == "deleteUser"));
+    assert_eq!(m.hits(), 1);
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test)]
+async fn test_interceptors() {
+    use http::{HeaderName, HeaderValue};
+    use rustify::{
+        intercept::Interceptors,
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let interceptors = Interceptors::new().honor_deprecation().switch_version(
+        HeaderName::from_static("x-api-version-latest"),
+        HeaderName::from_static("x-api-version"),
+    );
+    let client = MockClient::new("http://localhost")
+        .with_config(ClientConfig::new().intercept(interceptors.clone()));
+    client
+        .push(
+            MockResponse::new(200)
+                .header(
+                    HeaderName::from_static("x-api-version-latest"),
+                    HeaderValue::from_static("2024-01-01"),
+                )
+                .header(
+                    HeaderName::from_static("deprecation"),
+                    HeaderValue::from_static("true"),
+                )
+                .header(
+                    HeaderName::from_static("sunset"),
+                    HeaderValue::from_static("Sat, 01 Jun 2030 00:00:00 GMT"),
+                ),
+        )
+        .push(MockResponse::new(200));
+
+    Test {}.exec(&client).await.unwrap();
+    Test {}.exec(&client).await.unwrap();
+    let requests = client.requests();
+    assert!(requests[0].headers().get("x-api-version").is_none());
+    assert_eq!(requests[1].headers()["x-api-version"], "2024-01-01");
+
+    let deprecations = interceptors.state().deprecations();
+    let deprecation = &deprecations["localhost/test/path"];
+    assert_eq!(deprecation.deprecation.as_deref(), Some("true"));
+    assert!(deprecation.sunset.is_some());
+}
+
+#[test(tokio::test)]
+async fn test_kill_switch() {
+    use rustify::gate::KillSwitch;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "payments", operation_id = "listPayments")]
+    struct ListPayments {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "payments", method = "POST")]
+    struct CreatePayment {}
+
+    let fallback = TestServer::default();
+    let m = fallback.server.mock(|when, then| {
+        when.method(GET).path("/v2/payments");
+        then.status(200);
+    });
+    let switch = KillSwitch::new();
+    let client = Client::default("http://localhost:1")
+        .with_config(ClientConfig::new().gatekeeper(switch.clone()));
+
+    let name = CreatePayment {}.name();
+    switch.disable(name, "paused");
+    let r = CreatePayment {}.exec(&client).await;
+    assert!(matches!(
+        r,
+        Err(ClientError::DisabledError { operation, reason }) if operation == name && reason == "paused"
+    ));
+
+    let base = format!("{}/v2", fallback.server.base_url());
+    switch.reroute("listPayments", base.parse().unwrap());
+    ListPayments {}.exec(&client).await.unwrap();
+    m.assert();
+
+    switch.reset();
+    assert!(!switch.is_disabled(name));
+    let r = ListPayments {}.exec(&client).await;
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_bench() {
+    use rustify::{
+        bench::Bench,
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "users", response = "Vec<String>")]
+    struct ListUsers {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "admins", response = "Vec<String>")]
+    struct ListAdmins {}
+
+    let client = MockClient::new("http://localhost");
+    for i in 0..10u64 {
+        client.push(
+            MockResponse::new(200)
+                .body("[]")
+                .delay(Duration::from_millis(10 * (i + 1))),
+        );
+    }
+    client
+        .push(MockResponse::new(500))
+        .push(MockResponse::new(200).body("[]"));
+
+    let report = Bench::new(100.0)
+        .endpoint(ListUsers {})
+        .endpoint(ListAdmins {})
+        .requests(12)
+        .run(&client)
+        .await;
+    assert_eq!(report.requests(), 12);
+    assert_eq!(report.failures(), 1);
+    assert_eq!(report.errors[&ErrorKind::Http { status: 500 }], 1);
+    assert_eq!(report.percentile(50.0), Some(Duration::from_millis(40)));
+    assert_eq!(report.percentile(100.0), Some(Duration::from_millis(100)));
+    assert_eq!(report.elapsed, Duration::from_millis(190));
+    assert_eq!(client.requests()[1].uri(), "http://localhost/admins");
+}
+
+#[test(tokio::test)]
+async fn test_throttled_client() {
+    use rustify::throttle::ThrottledClient;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {
+        data: String,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path");
+        then.status(200).body("x".repeat(100));
+    });
+    let client = ThrottledClient::new(t.client)
+        .latency(Duration::from_millis(50))
+        .upload(1_000)
+        .download(1_000);
+
+    // 50ms of latency, ~13 bytes uploaded, and 100 bytes downloaded
+    let start = Instant::now();
+    let r = Test {
+        data: "x".to_string(),
+    }
+    .exec(&client)
+    .await;
+    m.assert();
+    assert!(r.is_ok());
+    assert!(start.elapsed() >= Duration::from_millis(160));
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_mock_client() {
+    use rustify::{
+        mock::{MockClient, MockResponse},
+        throttle::ThrottledClient,
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "Vec<u32>")]
+    struct Test {}
+
+    let client = MockClient::new("http://localhost")
+        .with_config(ClientConfig::new().retry_stale_connections());
+    client
+        .push(MockResponse::disconnect())
+        .push(
+            MockResponse::new(200)
+                .body("[1, 2]")
+                .delay(Duration::from_secs(30)),
+        )
+        .push(MockResponse::new(503));
+    let client = ThrottledClient::new(client).latency(Duration::from_secs(1));
+
+    let start = tokio::time::Instant::now();
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), vec![1, 2]);
+    assert_eq!(start.elapsed(), Duration::from_secs(32));
+
+    let r = Test {}.exec(&client).await;
+    assert_eq!(r.err().unwrap().status(), Some(503));
+    let r = Test {}.exec(&client).await;
+    assert_eq!(r.err().unwrap().kind(), ErrorKind::Transport);
+    assert_eq!(client.client.requests().len(), 4);
+    assert_eq!(client.client.remaining(), 0);
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test)]
+async fn test_fixture() {
+    use rustify::{
+        fixture::{Fixture, Recorder, FIXTURE_VERSION},
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "Vec<u32>")]
+    struct Test {}
+
+    let client = MockClient::new("http://localhost");
+    client.push(
+        MockResponse::new(200)
+            .header(
+                http::header::SET_COOKIE,
+                http::HeaderValue::from_static("session=secret"),
+            )
+            .body("[1, 2]"),
+    );
+    let recorder = Recorder::new(client);
+    Test {}.exec(&recorder).await.unwrap();
+
+    let data = recorder.fixture().to_vec().unwrap();
+    assert!(!String::from_utf8_lossy(&data).contains("secret"));
+    let fixture = Fixture::from_slice(&data).unwrap();
+    assert_eq!(fixture, recorder.fixture());
+    assert_eq!(fixture.version, FIXTURE_VERSION);
+    assert_eq!(
+        fixture.interactions[0].request.url,
+        "http://localhost/test/path"
+    );
+
+    let client = MockClient::from_fixture("http://localhost", &fixture).unwrap();
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), vec![1, 2]);
+
+    let newer = format!(
+        r#"{{"version": {}, "interactions": []}}"#,
+        FIXTURE_VERSION + 1
+    );
+    assert!(Fixture::from_slice(newer.as_bytes()).is_err());
+    assert!(Fixture::from_slice(br#"{"interactions": []}"#).is_err());
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test)]
+async fn test_fixture_compressed() {
+    use rustify::{
+        fixture::{Body, Fixture, Recorder},
+        mock::{MockClient, MockResponse},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "Vec<u32>")]
+    struct Test {}
+
+    let body = serde_json::to_string(&(0..500).collect::<Vec<u32>>()).unwrap();
+    let client = MockClient::new("http://localhost");
+    client.push(MockResponse::new(200).body(body.clone()));
+    let recorder = Recorder::new(client).compress_bodies(1024);
+    Test {}.exec(&recorder).await.unwrap();
+
+    let data = recorder.fixture().to_vec().unwrap();
+    assert!(!String::from_utf8_lossy(&data).contains(&body));
+    let fixture = Fixture::from_slice(&data).unwrap();
+    let interaction = &fixture.interactions[0];
+    assert!(matches!(interaction.response.body, Body::Gzip(_)));
+    assert!(matches!(interaction.request.body, Body::Text(_)));
+    assert_eq!(
+        interaction.response.body.to_bytes().unwrap(),
+        body.as_bytes()
+    );
+
+    let client = MockClient::from_fixture("http://localhost", &fixture).unwrap();
+    let r = Test {}.exec(&client).await.unwrap();
+    assert_eq!(r.parse().unwrap(), (0..500).collect::<Vec<u32>>());
+}
+
+#[test(tokio::test)]
+async fn test_scheduler_priority() {
+    let scheduler = Scheduler::new(1);
+    let order = Mutex::new(Vec::new());
+    let permit = scheduler.acquire(RequestPriority::NORMAL).await;
+
+    tokio::join!(
+        async {
+            let _p = scheduler.acquire(RequestPriority::LOW).await;
+            order.lock().unwrap().push("low");
+        },
+        async {
+            let _p = scheduler.acquire(RequestPriority::HIGH).await;
+            order.lock().unwrap().push("high");
+        },
+        async {
+            tokio::task::yield_now().await;
+            drop(permit);
+        }
+    );
+
+    assert_eq!(*order.lock().unwrap(), vec!["high", "low"]);
+}
+
+#[test(tokio::test)]
+async fn test_scheduler() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", priority = "HIGH")]
+    struct Test {}
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().scheduler(Scheduler::new(1))),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let (r1, r2) = tokio::join!(Test {}.exec(&t.client), Test {}.exec(&t.client));
+
+    m.assert_hits(2);
+    assert!(r1.is_ok() && r2.is_ok());
+    assert_eq!(Test {}.priority(), RequestPriority::HIGH);
+}
+
+#[test(tokio::test)]
+async fn test_exec_presigned() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "uploads/{self.id}", method = "PUT")]
+    struct Upload {
+        #[endpoint(skip)]
+        id: u64,
+        #[endpoint(query)]
+        overwrite: bool,
+        #[endpoint(raw)]
+        data: Vec<u8>,
+    }
+
+    let t = TestServer::default();
+    let url = t.server.url("/bucket/file.bin?X-Amz-Signature=abc123");
+    let session = Session::new(t.client);
+    session.set_auth(Some(Auth::Bearer("token".to_string())));
+    let m = t.server.mock(|when, then| {
+        when.method(PUT)
+            .path("/bucket/file.bin")
+            .query_param("X-Amz-Signature", "abc123")
+            .body("contents")
+            .matches(|req| {
+                let headers = req.headers.clone().unwrap_or_default();
+                !headers.iter().any(|(k, _)| k == "authorization")
+            });
+        then.status(200);
+    });
+    let e = Upload {
+        id: 1,
+        overwrite: true,
+        data: b"contents".to_vec(),
+    };
+    let r = e.exec_presigned(&session, &url).await;
+    let relative = e.exec_presigned(&session, "bucket/file.bin").await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert!(matches!(
+        relative.err().unwrap(),
+        ClientError::UrlParseError { .. }
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_session() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "login", method = "POST", response = "String")]
+    struct Login {
+        username: String,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "items", method = "POST")]
+    struct CreateItem {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "logout", method = "POST")]
+    struct Logout {}
+
+    let t = TestServer::default();
+    let session = Session::new(t.client)
+        .header(
+            http::header::USER_AGENT,
+            http::HeaderValue::from_static("rustify"),
+        )
+        .csrf_header(http::HeaderName::from_static("x-csrf-token"));
+    let m_login = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/login")
+            .header("User-Agent", "rustify");
+        then.status(200)
+            .header("Set-Cookie", "session=abc; Path=/; HttpOnly")
+            .header("X-CSRF-Token", "csrf1")
+            .json_body(serde_json::json!("token1"));
+    });
+    let m_item = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/items")
+            .header("Authorization", "Bearer token1")
+            .header("Cookie", "session=abc")
+            .header("X-CSRF-Token", "csrf1");
+        then.status(200);
+    });
+    let m_logout = t.server.mock(|when, then| {
+        when.method(POST).path("/logout");
+        then.status(200).header("Set-Cookie", "session=; Max-Age=0");
+    });
+
+    let login = Login {
+        username: "test".to_string(),
+    };
+    session
+        .login(&login, |r| Ok(Some(Auth::Bearer(r.parse()?))))
+        .await
+        .unwrap();
+    assert_eq!(session.cookie("session").as_deref(), Some("abc"));
+    assert_eq!(session.csrf_token().as_deref(), Some("csrf1"));
+
+    let r = CreateItem {}.exec(&session).await;
+    assert!(r.is_ok());
+
+    session.logout(&Logout {}).await.unwrap();
+    assert!(session.auth().is_none());
+    assert!(session.cookie("session").is_none());
+
+    m_login.assert();
+    m_item.assert();
+    m_logout.assert();
+}
+
+#[test(tokio::test)]
+async fn test_profiles() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let profiles = Profiles::new()
+        .profile("prod", Profile::new("https://api.example.com"))
+        .profile(
+            "local",
+            Profile::new(t.client.base.as_str()).header(
+                http::HeaderName::from_static("x-env"),
+                http::HeaderValue::from_static("local"),
+            ),
+        )
+        .default_profile("prod");
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path").header("X-Env", "local");
+        then.status(200);
+    });
+
+    let client = profiles.client("local").unwrap();
+    let r = Test {}.exec(&client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert_eq!(
+        profiles.select(None).unwrap().base,
+        "https://api.example.com"
+    );
+    assert!(matches!(
+        profiles.client("missing"),
+        Err(ClientError::ProfileError { .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_ip_preference() {
+    use rustify::dns::IpPreference;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let v4 = "127.0.0.1:80".parse().unwrap();
+    let v6 = "[::1]:80".parse().unwrap();
+    assert_eq!(IpPreference::Any.apply([v6, v4]), vec![v6, v4]);
+    assert_eq!(IpPreference::PreferV4.apply([v6, v4]), vec![v4, v6]);
+    assert_eq!(IpPreference::PreferV6.apply([v4, v6]), vec![v6, v4]);
+    assert_eq!(IpPreference::V4Only.apply([v6, v4]), vec![v4]);
+    assert_eq!(IpPreference::V6Only.apply([v6, v4]), vec![v6]);
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let base = format!("http://localhost:{}", t.server.port());
+    let client = Profile::new(&base)
+        .ip_preference(IpPreference::V4Only)
+        .client()
+        .unwrap();
+    let r = Test {}.exec(&client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_conditional_fetcher() {
+    use rustify::conditional::{ConditionalFetcher, Fetched, MemoryStore};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "String")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m1 = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path").matches(|req| {
+            !req.headers
+                .as_ref()
+                .map(|h| {
+                    h.iter()
+                        .any(|(k, _)| k.eq_ignore_ascii_case("If-None-Match"))
+                })
+                .unwrap_or(false)
+        });
+        then.status(200).header("ETag", "\"v1\"").body("\"data\"");
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("If-None-Match", "\"v1\"");
+        then.status(304);
+    });
+    let fetcher = ConditionalFetcher::new(Test {}, MemoryStore::new());
+    let r1 = fetcher.fetch(&t.client).await;
+    let r2 = fetcher.fetch(&t.client).await;
+
+    m1.assert();
+    m2.assert();
+    assert_eq!(r1.unwrap(), Fetched::Fresh("data".to_string()));
+    assert_eq!(r2.unwrap(), Fetched::Stored("data".to_string()));
+}
+
+#[cfg(feature = "callback")]
+#[test(tokio::test)]
+async fn test_callback_server() {
+    use rustify::callback::CallbackServer;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/jobs", method = "POST")]
+    struct Test {
+        callback_url: String,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST).path("/test/jobs");
+        then.status(200);
+    });
+    let server = CallbackServer::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+    let missing = reqwest::get(format!("{}/callback/wrong", base))
+        .await
+        .unwrap();
+    let (r, callback) = server
+        .exec(&t.client, Duration::from_secs(5), |url| {
+            let url = url.to_string();
+            tokio::spawn({
+                let url = url.clone();
+                async move {
+                    let client = reqwest::Client::new();
+                    client.post(url).body("\"done\"").send().await.unwrap();
+                }
+            });
+            Test { callback_url: url }
+        })
+        .await
+        .unwrap();
+
+    m.assert();
+    assert_eq!(missing.status().as_u16(), 404);
+    assert_eq!(r.response.status().as_u16(), 200);
+    assert_eq!(callback.parse::<String>().unwrap(), "done");
+}
+
+/// A client which fails to send requests while it's offline.
+struct OfflineClient {
+    client: Client,
+    offline: AtomicBool,
+}
+
+#[async_trait]
+impl RustifyClient for OfflineClient {
+    async fn send(&self, req: Request<Vec<u8>>) -> Result<Response<Vec<u8>>, ClientError> {
+        match self.offline.load(Ordering::SeqCst) {
+            true => Err(ClientError::RequestError {
+                source: anyhow::anyhow!("offline"),
+                url: req.uri().to_string(),
+                method: req.method().to_string(),
+            }),
+            false => self.client.send(req).await,
+        }
+    }
+
+    fn base(&self) -> &str {
+        self.client.base()
+    }
+}
+
+#[test(tokio::test)]
+async fn test_deferred_queue() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}", method = "POST")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let client = OfflineClient {
+        client: Client::default(t.client.base.as_str()),
+        offline: AtomicBool::new(true),
+    };
+    let queue = DeferredQueue::new(MemoryStore::new());
+    let m1 = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path/first");
+        then.status(200);
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path/second");
+        then.status(200);
+    });
+
+    for name in ["first", "second"] {
+        let req = Test {
+            name: name.to_string(),
+        }
+        .request(client.base())
+        .unwrap();
+        assert!(queue.execute(&client, req).await.unwrap().is_none());
+    }
+    assert_eq!(queue.store.len().unwrap(), 2);
+    assert_eq!(queue.flush(&client).await.unwrap(), 0);
+
+    client.offline.store(false, Ordering::SeqCst);
+    assert_eq!(queue.flush(&client).await.unwrap(), 2);
+
+    m1.assert();
+    m2.assert();
+    assert!(queue.store.is_empty().unwrap());
+}
+
+#[cfg(feature = "metrics")]
+#[test(tokio::test)]
+async fn test_metrics() {
+    use metrics::{
+        Counter, CounterFn, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+        SharedString, Unit,
+    };
+    use rustify::metrics::Metrics;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    /// Logs the name and labels of every recorded metric
+    struct Handle(String, Log);
+
+    impl CounterFn for Handle {
+        fn increment(&self, value: u64) {
+            self.1.lock().unwrap().push(format!("{} {}", self.0, value));
+        }
+
+        fn absolute(&self, _: u64) {}
+    }
+
+    impl HistogramFn for Handle {
+        fn record(&self, _: f64) {
+            self.1.lock().unwrap().push(self.0.clone());
+        }
+    }
+
+    struct TestRecorder(Log);
+
+    impl TestRecorder {
+        fn handle(&self, key: &Key) -> Arc<Handle> {
+            let labels: Vec<String> = key
+                .labels()
+                .map(|l| format!("{}={}", l.key(), l.value()))
+                .collect();
+            let name = format!("{}{{{}}}", key.name(), labels.join(","));
+            Arc::new(Handle(name, self.0.clone()))
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            Counter::from_arc(self.handle(key))
+        }
+
+        fn register_gauge(&self, _: &Key, _: &Metadata<'_>) -> Gauge {
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            Histogram::from_arc(self.handle(key))
+        }
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().metrics(Metrics::new().prefix("api"))),
+    );
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/ok");
+        then.status(200);
+    });
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/fail");
+        then.status(503);
+    });
+    let log = Log::default();
+    let recorder = TestRecorder(log.clone());
+    let _guard = metrics::set_default_local_recorder(&recorder);
+    let test = |name: &str| Test {
+        name: name.to_string(),
+    };
+    assert!(test("ok").exec(&t.client).await.is_ok());
+    assert!(test("fail").exec(&t.client).await.is_err());
+
+    let endpoint = std::any::type_name::<Test>();
+    let labels = |status| format!("{{endpoint={},status={}}}", endpoint, status);
+    assert_eq!(
+        *log.lock().unwrap(),
+        vec![
+            format!("api_requests_total{} 1", labels("2xx")),
+            format!("api_request_duration_seconds{}", labels("2xx")),
+            format!("api_requests_total{} 1", labels("5xx")),
+            format!("api_errors_total{} 1", labels("5xx")),
+            format!("api_request_duration_seconds{}", labels("5xx")),
+        ]
+    );
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn test_execute_spilled() {
+    use rustify::blocking::clients::reqwest::Client as BlockingClient;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let server = MockServer::start();
+    let client = BlockingClient::default(server.base_url().as_str());
+    let large = "a".repeat(4096);
+    let m_small = server.mock(|when, then| {
+        when.method(GET).path("/test/path/small");
+        then.status(200).body("small");
+    });
+    let m_large = server.mock(|when, then| {
+        when.method(GET).path("/test/path/large");
+        then.status(200).body(large.as_str());
+    });
+
+    let small = Test {
+        name: "small".to_string(),
+    }
+    .request(&client.base)
+    .unwrap();
+    let small = client.execute_spilled(small, 1024).unwrap();
+    let big = Test {
+        name: "large".to_string(),
+    }
+    .request(&client.base)
+    .unwrap();
+    let big = client.execute_spilled(big, 1024).unwrap();
+
+    m_small.assert();
+    m_large.assert();
+    assert_eq!(small.body().as_ref(), b"small");
+    assert_eq!(big.body().as_ref(), large.as_bytes());
+}
+
+#[test(tokio::test)]
+async fn test_client_middleware() {
+    use common::Middle;
+    use rustify::{
+        endpoint::MiddleWare,
+        journal::{Journal, JournalEntry, JsonLines},
+        matcher::Matcher,
+    };
+    use serde_json::json;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.name}", response = "TestResponse")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct TestResponse {
+        age: u8,
+    }
+
+    let t = TestServer::with_client(Client::default("").with_config(
+        ClientConfig::new().middleware(Middle {}.only_for(Matcher::new().path("test/u*"))),
+    ));
+    let m_match = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/users")
+            .header("X-API-Token", "mytoken");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let m_skip = t.server.mock(|when, then| {
+        when.method(GET).path("/test/other");
+        then.status(200).json_body(json!({"age": 40}));
+    });
+    let test = |name: &str| Test {
+        name: name.to_string(),
+    };
+    let r1 = test("users").exec(&t.client).await;
+    let r2 = test("other").exec(&t.client).await;
+
+    let journal = Journal::new(JsonLines::new(Vec::new()));
+    let r3 = test("users")
+        .with_middleware(&journal)
+        .exec(&t.client)
+        .await;
+
+    m_match.assert_hits(2);
+    m_skip.assert();
+    assert_eq!(r1.unwrap().parse().unwrap().age, 30);
+    assert_eq!(r2.unwrap().parse().unwrap().age, 40);
+    assert_eq!(r3.unwrap().parse().unwrap().age, 30);
+    let out = journal.sink.into_inner();
+    let entry: JournalEntry = serde_json::from_slice(&out).unwrap();
+    assert!(entry.operation.ends_with("Test"));
+    assert_eq!(entry.status, 200);
+}
+
+#[test(tokio::test)]
+async fn test_api_mode() {
+    use rustify::{
+        endpoint::MiddleWare,
+        mode::{ApiMode, SandboxHeader, SandboxRoute},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "v1/charges")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let base = t.server.base_url();
+    let m_sandbox = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/sandbox/v1/charges")
+            .header("X-Sandbox", "true");
+        then.status(200);
+    });
+    let m_production = t.server.mock(|when, then| {
+        when.method(GET).path("/v1/charges");
+        then.status(200);
+    });
+    let client = |mode| {
+        let middleware =
+            SandboxHeader::new().and(SandboxRoute::new(&base, &format!("{}/sandbox", base)));
+        Client::default(&base).with_config(ClientConfig::new().mode(mode).middleware(middleware))
+    };
+
+    let r = Test {}.exec(&client(ApiMode::Sandbox)).await.unwrap();
+    assert!(ApiMode::of(r.response.extensions()).is_sandbox());
+    m_sandbox.assert();
+
+    let r = Test {}.exec(&client(ApiMode::Production)).await.unwrap();
+    assert_eq!(ApiMode::of(r.response.extensions()), ApiMode::Production);
+    m_production.assert();
+    m_sandbox.assert_hits(1);
+}
+
+#[test(tokio::test)]
+async fn test_middleware_panic() {
+    use rustify::{endpoint::MiddleWare, errors::ErrorKind};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    struct Buggy;
+
+    impl MiddleWare for Buggy {
+        fn request<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut http::Request<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            Ok(())
+        }
+
+        fn response<E: Endpoint>(
+            &self,
+            _: &E,
+            resp: &mut http::Response<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            panic!("unexpected status {}", resp.status().as_u16())
+        }
+    }
+
+    let t = TestServer::with_client(
+        Client::default("").with_config(
+            ClientConfig::new()
+                .middleware(Buggy)
+                .catch_middleware_panics(),
+        ),
+    );
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(204);
+    });
+
+    // Client middleware is guarded by the configuration
+    let res = Test {}.exec(&t.client).await;
+    m.assert();
+    let err = res.err().unwrap();
+    assert!(
+        matches!(&err, ClientError::MiddlewarePanic { message } if message == "unexpected status 204")
+    );
+    assert_eq!(err.kind(), ErrorKind::Middleware);
+
+    // Per-call middleware is guarded with catch_panics
+    let t = TestServer::default();
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(204);
+    });
+    let guarded = Buggy.catch_panics();
+    let res = Test {}.with_middleware(&guarded).exec(&t.client).await;
+    assert!(matches!(res, Err(ClientError::MiddlewarePanic { .. })));
+}
+
+#[test(tokio::test)]
+async fn test_shared_client() {
+    fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+    assert_shareable::<Client>();
+    assert_shareable::<rustify::clients::reqwest_middleware::ClientWithMiddleware>();
+    #[cfg(feature = "blocking")]
+    assert_shareable::<rustify::blocking::clients::reqwest::Client>();
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let client = t.client.shared();
+    let handles = (0..4)
+        .map(|_| {
+            let client = client.clone();
+            tokio::spawn(async move { Test {}.exec(&client).await.map(|_| ()) })
+        })
+        .collect::<Vec<_>>();
+    for handle in handles {
+        assert!(handle.await.unwrap().is_ok());
+    }
+    let cloned = (*client).clone();
+    assert!(Test {}.exec(&cloned).await.is_ok());
+
+    m.assert_hits(5);
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_retry() {
+    use rustify::{
+        endpoint::MiddleWare,
+        events::Attempt,
+        journal::{Journal, JournalEntry, JsonLines},
+        mock::{MockClient, MockResponse},
+        retry::RetryPolicy,
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {}
+
+    struct Sign(AtomicUsize);
+
+    impl MiddleWare for Sign {
+        fn request<E: Endpoint>(
+            &self,
+            _: &E,
+            req: &mut Request<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            let n = self.0.fetch_add(1, Ordering::SeqCst);
+            req.headers_mut()
+                .insert("X-Signature", http::HeaderValue::from(n));
+            Ok(())
+        }
+
+        fn response<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut Response<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            Ok(())
+        }
+    }
+
+    let delays = Arc::new(Mutex::new(Vec::new()));
+    let sink = {
+        let delays = delays.clone();
+        move |event: &Event| {
+            if let Event::RetryScheduled { attempt, delay, .. } = event {
+                delays.lock().unwrap().push((*attempt, *delay));
+            }
+        }
+    };
+    let policy = RetryPolicy::new(2)
+        .base_delay(Duration::from_secs(1))
+        .without_jitter();
+    let client = MockClient::new("https://api.example.com").with_config(
+        ClientConfig::new()
+            .retry(policy)
+            .event_sink(sink)
+            .middleware(Sign(AtomicUsize::new(0))),
+    );
+    client
+        .push(MockResponse::new(503))
+        .push(MockResponse::new(502))
+        .push(MockResponse::new(200));
+    let journal = Journal::new(JsonLines::new(Vec::new()));
+    let start = tokio::time::Instant::now();
+    let r = Test {}.with_middleware(&journal).exec(&client).await;
+
+    assert!(r.is_ok());
+    assert_eq!(start.elapsed(), Duration::from_secs(3));
+    assert_eq!(
+        *delays.lock().unwrap(),
+        vec![(2, Duration::from_secs(1)), (3, Duration::from_secs(2))]
+    );
+    let requests = client.requests();
+    let signatures = requests
+        .iter()
+        .map(|r| r.headers()["X-Signature"].to_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(signatures, vec!["0", "1", "2"]);
+    let attempts = requests
+        .iter()
+        .map(|r| Attempt::of(r.extensions()))
+        .collect::<Vec<_>>();
+    assert_eq!(attempts, vec![1, 2, 3]);
+    let entry: JournalEntry = serde_json::from_slice(&journal.sink.into_inner()).unwrap();
+    assert_eq!(entry.retries, 2);
+
+    // Retries are exhausted
+    client
+        .push(MockResponse::new(500))
+        .push(MockResponse::new(500))
+        .push(MockResponse::new(500));
+    let r = Test {}.exec(&client).await;
+    assert!(matches!(
+        r,
+        Err(ClientError::ServerResponseError { code: 500, .. })
+    ));
+    assert_eq!(client.requests().len(), 6);
+
+    // Client errors aren't retried
+    client.push(MockResponse::new(404));
+    let r = Test {}.exec(&client).await;
+    assert!(matches!(
+        r,
+        Err(ClientError::ServerResponseError { code: 404, .. })
+    ));
+    assert_eq!(client.requests().len(), 7);
+}
+
+#[cfg(feature = "mock")]
+#[test(tokio::test(start_paused = true))]
+async fn test_read_repair() {
+    use rustify::{
+        mock::{MockClient, MockResponse},
+        retry::RetryPolicy,
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "users", response = "Vec<String>")]
+    struct ListUsers {}
+
+    let delays = Arc::new(Mutex::new(Vec::new()));
+    let parsed = Arc::new(AtomicUsize::new(0));
+    let sink = {
+        let (delays, parsed) = (delays.clone(), parsed.clone());
+        move |event: &Event| match event {
+            Event::RetryScheduled { attempt, delay, .. } => {
+                delays.lock().unwrap().push((*attempt, *delay))
+            }
+            Event::ResponseParsed { .. } => {
+                parsed.fetch_add(1, Ordering::SeqCst);
+            }
+            _ => {}
+        }
+    };
+    let client = MockClient::new("https://api.example.com")
+        .with_config(ClientConfig::new().event_sink(sink));
+    client
+        .push(MockResponse::new(200).body("[]"))
+        .push(MockResponse::new(200).body(r#"["old"]"#))
+        .push(MockResponse::new(200).body(r#"["old", "new"]"#));
+    let policy = RetryPolicy::new(2)
+        .base_delay(Duration::from_secs(1))
+        .without_jitter();
+    let visible = |users: &Vec<String>| users.iter().any(|u| u == "new");
+    let start = tokio::time::Instant::now();
+    let r = ListUsers {}
+        .exec_read_repair(&client, &policy, visible)
+        .await
+        .unwrap();
+
+    // Each response is only parsed once
+    assert_eq!(*r.get().unwrap(), vec!["old", "new"]);
+    assert_eq!(parsed.load(Ordering::SeqCst), 3);
+    assert_eq!(start.elapsed(), Duration::from_secs(3));
+    assert_eq!(
+        *delays.lock().unwrap(),
+        vec![(2, Duration::from_secs(1)), (3, Duration::from_secs(2))]
+    );
+
+    // The last response is returned once retries are exhausted
+    client
+        .push(MockResponse::new(200).body("[]"))
+        .push(MockResponse::new(200).body("[]"))
+        .push(MockResponse::new(200).body(r#"["old"]"#));
+    let r = ListUsers {}
+        .exec_read_repair(&client, &policy, visible)
+        .await
+        .unwrap();
+    assert_eq!(r.into_inner().unwrap(), vec!["old"]);
+    assert_eq!(client.requests().len(), 6);
+
+    // Responses which fail to parse aren't repeated
+    client.push(MockResponse::new(200).body("{}"));
+    let r = ListUsers {}
+        .exec_read_repair(&client, &policy, visible)
+        .await;
+    assert!(r.is_err());
+    assert_eq!(client.requests().len(), 7);
+}
+
+#[test(tokio::test)]
+async fn test_exec_output() {
+    use futures_util::StreamExt;
+    use rustify::{
+        stream::{collect, ExecMode, ExecOutput},
+        throttle::ThrottledClient,
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.name}", response = "Vec<u8>")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let body = (0..=255u8).cycle().take(64 * 1024).collect::<Vec<_>>();
+    let json = serde_json::to_vec(&body).unwrap();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/data");
+        then.status(200).body(json.clone());
+    });
+    let m_err = t.server.mock(|when, then| {
+        when.method(GET).path("/test/missing");
+        then.status(404).body("not found");
+    });
+    let test = |name: &str| Test {
+        name: name.to_string(),
+    };
+
+    let r = test("data").exec_output(&t.client, ExecMode::Stream).await;
+    let resp = match r.unwrap() {
+        ExecOutput::Stream(resp) => resp,
+        o => panic!("Expected a stream, got {:?}", o),
+    };
+    assert_eq!(resp.status(), 200);
+    let chunks = resp.into_body().collect::<Vec<_>>().await;
+    let streamed = chunks
+        .into_iter()
+        .flat_map(|c| c.unwrap().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(streamed, json);
+
+    let r = test("data")
+        .exec_output(&t.client, ExecMode::Buffered)
+        .await;
+    match r.unwrap() {
+        ExecOutput::Buffered(result) => assert_eq!(result.parse().unwrap(), body),
+        o => panic!("Expected a buffered result, got {:?}", o),
+    }
+
+    // Clients which can't stream fall back to buffering
+    let throttled = ThrottledClient::new(Client::default(&t.client.base));
+    let r = test("data").exec_output(&throttled, ExecMode::Stream).await;
+    let output = r.unwrap();
+    assert!(!output.is_stream());
+    assert_eq!(collect(output.into_stream()).await.unwrap(), json);
+
+    let r = test("missing")
+        .exec_output(&t.client, ExecMode::Stream)
+        .await;
+    assert!(matches!(
+        r,
+        Err(ClientError::ServerResponseError { code: 404, content: Some(c) }) if c == "not found"
+    ));
+
+    m.assert_hits(3);
+    m_err.assert();
+}
+
+#[test(tokio::test)]
+async fn test_exec_output_pipeline() {
+    use rustify::{
+        hooks::Hooks,
+        stream::{collect, ExecMode, ExecOutput},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.name}")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let requests = Arc::new(AtomicUsize::new(0));
+    let responses = Arc::new(AtomicUsize::new(0));
+    let errors = Arc::new(AtomicUsize::new(0));
+    let hooks = {
+        let (requests, responses, errors) = (requests.clone(), responses.clone(), errors.clone());
+        Hooks::new()
+            .on_request(move |_| {
+                requests.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_response(move |resp| {
+                assert!(resp.body().is_empty());
+                responses.fetch_add(1, Ordering::SeqCst);
+            })
+            .on_error(move |_| {
+                errors.fetch_add(1, Ordering::SeqCst);
+            })
+    };
+    let t = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().hooks(hooks).graceful_shutdown()),
+    );
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/data");
+        then.status(200).body("data");
+    });
+    t.server.mock(|when, then| {
+        when.method(GET).path("/test/missing");
+        then.status(404);
+    });
+    let test = |name: &str| Test {
+        name: name.to_string(),
+    };
+    let shutdown = t.client.config.shutdown.clone().unwrap();
+
+    // Streamed executions go through the hooks and stay in flight until their
+    // body is received
+    let output = test("data")
+        .exec_output(&t.client, ExecMode::Stream)
+        .await
+        .unwrap();
+    assert!(matches!(output, ExecOutput::Stream(_)));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert_eq!(responses.load(Ordering::SeqCst), 1);
+    assert_eq!(shutdown.in_flight(), 1);
+    assert_eq!(collect(output.into_stream()).await.unwrap(), b"data");
+    assert_eq!(shutdown.in_flight(), 0);
+
+    let r = test("missing")
+        .exec_output(&t.client, ExecMode::Stream)
+        .await;
+    assert!(r.is_err());
+    assert_eq!(errors.load(Ordering::SeqCst), 1);
+    assert_eq!(shutdown.in_flight(), 0);
+
+    // Streamed executions are refused once the client is shut down
+    assert_eq!(t.client.shutdown(Duration::ZERO).await, 0);
+    let r = test("data").exec_output(&t.client, ExecMode::Stream).await;
+    assert!(matches!(r, Err(ClientError::ShutdownError)));
+}
+
+#[test(tokio::test)]
+async fn test_exec_output_buffering() {
+    use common::Middle;
+    use rustify::{
+        retry::RetryPolicy,
+        stream::{ExecMode, ExecOutput},
+    };
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "u8")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).json_body(serde_json::json!({"result": 1}));
+    });
+    let middleware = Client::new(&t.server.base_url(), reqwest::Client::new())
+        .with_config(ClientConfig::new().middleware(Middle {}));
+    let retry = Client::new(&t.server.base_url(), reqwest::Client::new())
+        .with_config(ClientConfig::new().retry(RetryPolicy::new(1)));
+
+    // Response middleware sees the complete body of buffered responses
+    let r = Test {}.exec_output(&middleware, ExecMode::Stream).await;
+    match r.unwrap() {
+        ExecOutput::Buffered(result) => assert_eq!(result.parse().unwrap(), 1),
+        o => panic!("Expected a buffered result, got {:?}", o),
+    }
+    let r = Test {}
+        .with_middleware(&Middle {})
+        .exec_output(&t.client, ExecMode::Stream)
+        .await;
+    match r.unwrap() {
+        ExecOutput::Buffered(result) => assert_eq!(result.parse().unwrap(), 1),
+        o => panic!("Expected a buffered result, got {:?}", o),
+    }
+
+    let r = Test {}.exec_output(&retry, ExecMode::Stream).await;
+    assert!(!r.unwrap().is_stream());
+
+    m.assert_hits(3);
+}
+
+#[test(tokio::test)]
+async fn test_collect_ndjson() {
+    use bytes::Bytes;
+    use futures_util::StreamExt;
+    use rustify::stream::{collect_ndjson, single, ByteStream, Collected};
+
+    let chunks = |chunks: &[&'static str]| {
+        futures_util::stream::iter(
+            chunks
+                .iter()
+                .map(|c| Ok(Bytes::from_static(c.as_bytes())))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let limit = Duration::from_millis(50);
+
+    let body = single(b"{\"id\":1}\n\n{\"id\":2}".to_vec());
+    let r = collect_ndjson::<serde_json::Value>(body, limit).await;
+    assert_eq!(r.unwrap().items().len(), 2);
+
+    // Items received before the time limit are kept, the incomplete line is
+    // discarded
+    let body: ByteStream = chunks(&["{\"id\":1}\n{\"id\"", ":2}\n{\"id\""])
+        .chain(futures_util::stream::pending())
+        .boxed();
+    let r = collect_ndjson::<serde_json::Value>(body, limit)
+        .await
+        .unwrap();
+    assert!(r.is_timed_out());
+    assert_eq!(
+        r,
+        Collected::TimedOut(vec![
+            serde_json::json!({"id": 1}),
+            serde_json::json!({"id": 2})
+        ])
+    );
+
+    let body: ByteStream = chunks(&["{\"id\":1}\nnot json\n"]).boxed();
+    let r = collect_ndjson::<serde_json::Value>(body, limit).await;
+    assert!(matches!(
+        r,
+        Err(ClientError::ResponseDeserializeError { .. })
+    ));
+}
@@ -0,0 +1,62 @@
+mod common;
+
+#[allow(dead_code)]
+mod petstore {
+    include!("codegen/petstore.rs");
+}
+
+use common::TestServer;
+use httpmock::prelude::*;
+use rustify::endpoint::Endpoint;
+use serde_json::json;
+use test_log::test;
+
+#[test]
+fn test_generate() {
+    let source = rustify_codegen::generate(include_bytes!("codegen/petstore.json")).unwrap();
+    assert_eq!(source, include_str!("codegen/petstore.rs"));
+
+    let r = rustify_codegen::generate(br#"{"swagger": "2.0", "paths": {}}"#);
+    assert!(matches!(
+        r,
+        Err(rustify_codegen::Error::VersionError { .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_generated_endpoints() {
+    let t = TestServer::default();
+    let list = t.server.mock(|when, then| {
+        when.method(GET).path("/pets").query_param("limit", "1");
+        then.status(200)
+            .json_body(json!([{ "id": 1, "name": "Rex", "birthDate": null }]));
+    });
+    let create = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/pets")
+            .json_body(json!({ "name": "Tom", "type": "cat" }));
+        then.status(201)
+            .json_body(json!({ "id": 2, "name": "Tom" }));
+    });
+
+    let pets = petstore::ListPets { limit: Some(1) }
+        .exec(&t.client)
+        .await
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert_eq!(pets[0].name, "Rex");
+
+    let endpoint = petstore::CreatePet {
+        name: "Tom".to_string(),
+        tags: None,
+        type_: Some("cat".to_string()),
+    };
+    assert_eq!(endpoint.metadata().scopes, &["pets:read", "pets:write"]);
+    let pet = endpoint.exec(&t.client).await.unwrap().parse().unwrap();
+
+    list.assert();
+    create.assert();
+    assert_eq!(pet.id, 2);
+    assert_eq!(pet.birth_date, None);
+}
@@ -0,0 +1,73 @@
+// Generated by rustify_codegen from Petstore 1.0.0, do not edit.
+
+use rustify_derive::Endpoint;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NewPet {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<std::collections::HashMap<String, String>>,
+    /// The species of the pet
+    #[serde(rename = "type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+}
+
+/// A pet in the store
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Pet {
+    #[serde(rename = "birthDate")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub birth_date: Option<String>,
+    pub id: i64,
+    pub name: String,
+}
+
+pub type PetName = String;
+
+/// Lists all pets
+#[derive(Clone, Debug, Endpoint)]
+#[endpoint(path = "pets", method = "GET", response = "Vec<Pet>", operation_id = "listPets", scopes = ["pets:read"])]
+pub struct ListPets {
+    #[endpoint(query)]
+    pub limit: Option<i32>,
+}
+
+#[derive(Clone, Debug, Endpoint, Serialize)]
+#[endpoint(path = "pets", method = "POST", response = "Pet", operation_id = "createPet", scopes = ["pets:read", "pets:write"])]
+pub struct CreatePet {
+    #[endpoint(body)]
+    pub name: String,
+    #[endpoint(body)]
+    pub tags: Option<std::collections::HashMap<String, String>>,
+    /// The species of the pet
+    #[endpoint(body)]
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+}
+
+#[derive(Clone, Debug, Endpoint)]
+#[endpoint(path = "pets/{self.pet_id}", method = "GET", response = "Pet", operation_id = "getPet", scopes = ["pets:read"])]
+pub struct GetPet {
+    /// The ID of the pet
+    #[endpoint(skip)]
+    pub pet_id: i64,
+}
+
+#[derive(Clone, Debug, Endpoint)]
+#[endpoint(path = "pets/{self.pet_id}", method = "DELETE", scopes = ["pets:read"], deprecated = "Deprecated by the API")]
+pub struct DeletePetsPetId {
+    /// The ID of the pet
+    #[endpoint(skip)]
+    pub pet_id: i64,
+}
+
+#[derive(Clone, Debug, Endpoint)]
+#[endpoint(path = "pets/{self.pet_id}/photo", method = "PUT", operation_id = "uploadPhoto", scopes = ["pets:read"])]
+pub struct UploadPhoto {
+    #[endpoint(skip)]
+    pub pet_id: i64,
+    #[endpoint(raw)]
+    pub body: Vec<u8>,
+}
@@ -1,9 +1,11 @@
+#![allow(dead_code)]
+
 use httpmock::prelude::*;
 #[cfg(feature = "blocking")]
 use rustify::blocking::clients::reqwest::Client as ReqwestBlocking;
 use rustify::{
     clients::reqwest::Client as Reqwest,
-    endpoint::{Endpoint, MiddleWare, Wrapper},
+    endpoint::{Endpoint, MiddleWare, Unwrap, Wrapper},
     errors::ClientError,
 };
 use serde::{de::DeserializeOwned, Deserialize};
@@ -72,6 +74,12 @@ impl<T: DeserializeOwned + Send + Sync> Wrapper for TestGenericWrapper<T> {
     type Value = T;
 }
 
+impl<T: DeserializeOwned + Send + Sync> Unwrap for TestGenericWrapper<T> {
+    fn into_value(self) -> Self::Value {
+        self.result
+    }
+}
+
 pub struct Middle {}
 
 impl MiddleWare for Middle {
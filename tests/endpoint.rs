@@ -5,8 +5,11 @@ use std::fmt::Debug;
 use common::{Middle, TestGenericWrapper, TestResponse, TestServer};
 use derive_builder::Builder;
 use httpmock::prelude::*;
-use rustify::endpoint::Endpoint;
-use rustify_derive::Endpoint;
+use rustify::{
+    endpoint::{Endpoint, Unwrap},
+    errors::ClientError,
+};
+use rustify_derive::{Endpoint, Wrapper};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
 use std::marker::PhantomData;
@@ -77,6 +80,242 @@ async fn test_query() {
     assert!(r.is_ok());
 }
 
+#[test(tokio::test)]
+async fn test_query_dual() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {
+        #[endpoint(dual)]
+        pub token: String,
+        pub name: String,
+        #[endpoint(query)]
+        pub age: u64,
+    }
+
+    let t = TestServer::default();
+    let e = Test {
+        token: "abc".to_string(),
+        name: "test".to_string(),
+        age: 30,
+    };
+    let m = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path")
+            .query_param("token", "abc")
+            .query_param("age", "30")
+            .json_body(json!({"token": "abc", "name": "test"}));
+        then.status(200);
+    });
+    let r = e.exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_body_on_get() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/search", allow_body_on_get)]
+    struct Search {
+        pub query: String,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/get")]
+    struct Get {
+        pub query: String,
+    }
+
+    let t = TestServer::default();
+    let m1 = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/search")
+            .json_body(json!({"query": "test"}));
+        then.status(200);
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/get")
+            .json_body(json!({"query": "test"}));
+        then.status(200);
+    });
+    let r1 = Search {
+        query: "test".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+    let r2 = Get {
+        query: "test".to_string(),
+    }
+    .exec(&t.client)
+    .await;
+
+    // Bodies are sent either way, the flag only declares the intent
+    m1.assert();
+    m2.assert();
+    assert!(r1.is_ok());
+    assert!(r2.is_ok());
+    assert!(Search {
+        query: String::new()
+    }
+    .allow_body_on_get());
+    assert!(!Get {
+        query: String::new()
+    }
+    .allow_body_on_get());
+}
+
+#[test(tokio::test)]
+async fn test_body_none_as_null() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "PATCH", serialize_none_as_null)]
+    struct Test {
+        pub name: Option<String>,
+        #[endpoint(skip_none)]
+        pub age: Option<u64>,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct TestField {
+        #[endpoint(serialize_none_as_null)]
+        pub name: Option<String>,
+        pub age: Option<u64>,
+    }
+
+    let t = TestServer::default();
+    let m1 = t.server.mock(|when, then| {
+        when.method("PATCH")
+            .path("/test/path")
+            .json_body(json!({ "name": null }));
+        then.status(200);
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path")
+            .json_body(json!({ "name": null }));
+        then.status(200);
+    });
+    let r1 = Test {
+        name: None,
+        age: None,
+    }
+    .exec(&t.client)
+    .await;
+    let r2 = TestField {
+        name: None,
+        age: None,
+    }
+    .exec(&t.client)
+    .await;
+
+    m1.assert();
+    m2.assert();
+    assert!(r1.is_ok());
+    assert!(r2.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_body_patch() {
+    use rustify::patch::{pointer, JsonPatch, MergePatch};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/json", method = "PATCH")]
+    struct Json {
+        patch: JsonPatch,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/merge", method = "PATCH")]
+    struct Merge {
+        #[endpoint(body)]
+        patch: MergePatch<serde_json::Value>,
+    }
+
+    let t = TestServer::default();
+    let m1 = t.server.mock(|when, then| {
+        when.method("PATCH")
+            .path("/test/json")
+            .header("Content-Type", "application/json-patch+json")
+            .json_body(json!([
+                {"op": "test", "path": "/version", "value": 1},
+                {"op": "replace", "path": "/a~1b", "value": "c"},
+                {"op": "move", "from": "/d", "path": "/e"},
+            ]));
+        then.status(200);
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method("PATCH")
+            .path("/test/merge")
+            .header("Content-Type", "application/merge-patch+json")
+            .json_body(json!({"name": "new", "tags": null, "nested": {"b": 2}}));
+        then.status(200);
+    });
+    let r1 = Json {
+        patch: JsonPatch::new()
+            .test("/version", 1)
+            .replace(&pointer(&["a/b"]), "c")
+            .move_to("/d", "/e"),
+    }
+    .exec(&t.client)
+    .await;
+    let r2 = Merge {
+        patch: MergePatch::diff(
+            &json!({"name": "old", "tags": [1], "nested": {"a": 1, "b": 1}, "same": true}),
+            &json!({"name": "new", "nested": {"a": 1, "b": 2}, "same": true}),
+        ),
+    }
+    .exec(&t.client)
+    .await;
+
+    m1.assert();
+    m2.assert();
+    assert!(r1.is_ok());
+    assert!(r2.is_ok());
+}
+
+#[cfg(feature = "chrono")]
+#[test(tokio::test)]
+async fn test_query_datetime() {
+    use chrono::{TimeZone, Utc};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {
+        #[endpoint(query, datetime = "epoch")]
+        pub since: chrono::DateTime<Utc>,
+        #[endpoint(query, datetime = "%Y-%m-%d")]
+        pub day: Option<chrono::DateTime<Utc>>,
+        #[endpoint(query, datetime = "rfc3339")]
+        pub until: Option<chrono::DateTime<Utc>>,
+    }
+
+    let t = TestServer::default();
+    let time = Utc.with_ymd_and_hms(2021, 9, 27, 12, 0, 0).unwrap();
+    let e = Test {
+        since: time,
+        day: Some(time),
+        until: None,
+    };
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .query_param("since", "1632744000")
+            .query_param("day", "2021-09-27")
+            .matches(|req| {
+                !req.query_params
+                    .as_ref()
+                    .map(|q| q.iter().any(|(k, _)| k == "until"))
+                    .unwrap_or(false)
+            });
+        then.status(200);
+    });
+    let r = e.exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+}
+
 #[test(tokio::test)]
 async fn test_path_with_format() {
     #[derive(Endpoint)]
@@ -178,174 +417,1646 @@ async fn test_result() {
 }
 
 #[test(tokio::test)]
-async fn test_builder() {
-    #[derive(Builder, Endpoint)]
-    #[endpoint(path = "test/path", method = "POST", builder = "true")]
-    #[builder(setter(into))]
-    struct Test {
-        name: String,
+async fn test_result_diagnostics() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
+
+    #[derive(Debug, Deserialize)]
+    struct TestResponse {
+        #[allow(dead_code)]
+        age: u8,
     }
 
     let t = TestServer::default();
+    let e = Test {};
     let m = t.server.mock(|when, then| {
-        when.method(POST).path("/test/path");
-        then.status(200);
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .body("{\n  \"name\": \"test\",\n  \"age\": \"thirty\"\n}");
     });
-    let r = Test::builder()
-        .name("test")
-        .build()
-        .unwrap()
-        .exec(&t.client)
-        .await;
+    let r = e.exec(&t.client).await.unwrap().parse();
 
     m.assert();
-    assert!(r.is_ok());
+    match r {
+        Err(ClientError::ResponseDeserializeError {
+            line,
+            column,
+            excerpt,
+            ..
+        }) => {
+            assert_eq!(line, 3);
+            assert_eq!(column, 17);
+            assert_eq!(excerpt, r#"  "age": "thirty""#);
+        }
+        _ => panic!("expected a deserialization error"),
+    }
 }
 
 #[test(tokio::test)]
-async fn test_mutate() {
+async fn test_result_blank_body() {
+    use rustify::{
+        enums::{BodyStrictness, ResponseType},
+        http::parse_with,
+    };
+
     #[derive(Endpoint)]
-    #[endpoint(path = "test/path", response = "TestResponse")]
+    #[endpoint(path = "test/path", response = "Option<TestResponse>")]
     struct Test {}
 
     let t = TestServer::default();
-    let e = Test {};
     let m = t.server.mock(|when, then| {
-        when.method(GET)
-            .path("/test/path")
-            .header("X-API-Token", "mytoken");
-        then.status(200).json_body(json!({"result": {"age": 30}}));
+        when.method(GET).path("/test/path");
+        then.status(200).body(" \r\n ");
     });
-    let r = e.with_middleware(&Middle {}).exec(&t.client).await;
+    let r = Test {}.exec(&t.client).await.unwrap().parse();
 
     m.assert();
-    assert!(r.is_ok());
-    assert_eq!(r.unwrap().parse().unwrap().age, 30);
+    assert!(matches!(r, Ok(None)));
+    for body in ["", "  \n", "null"] {
+        let r: Result<(), _> =
+            parse_with(body.as_bytes(), ResponseType::JSON, BodyStrictness::LENIENT);
+        assert!(r.is_ok());
+    }
+    let r: Result<Option<TestResponse>, _> =
+        parse_with(b"  ", ResponseType::JSON, BodyStrictness::STRICT);
+    assert!(matches!(
+        r,
+        Err(ClientError::ResponseDeserializeError { .. })
+    ));
 }
 
 #[test(tokio::test)]
-async fn test_wrapper() {
+async fn test_result_rate_limit() {
+    use rustify::ratelimit::RateLimitInfo;
+    use std::time::Duration;
+
     #[derive(Endpoint)]
-    #[endpoint(path = "test/path", response = "TestResponse")]
+    #[endpoint(path = "test/path")]
     struct Test {}
 
-    fn strip<T>(wrapper: TestGenericWrapper<T>) -> T {
-        wrapper.result
-    }
-
     let t = TestServer::default();
-    let e = Test {};
     let m = t.server.mock(|when, then| {
         when.method(GET).path("/test/path");
-        then.status(200).json_body(json!({"result": {"age": 30}}));
+        then.status(200)
+            .header("X-RateLimit-Limit", "100")
+            .header("X-RateLimit-Remaining", "0")
+            .header("X-RateLimit-Reset", "30");
     });
-    let r = e.exec(&t.client).await.unwrap();
-    let r = r.wrap::<_>().map(strip).unwrap();
+    let r = Test {}.exec(&t.client).await.unwrap();
 
     m.assert();
-    assert_eq!(r.age, 30);
+    let info = r.rate_limit().unwrap();
+    assert_eq!(info.limit, Some(100));
+    assert!(info.is_exhausted());
+    assert_eq!(info.wait_time(), Some(Duration::from_secs(30)));
+
+    let mut headers = http::HeaderMap::new();
+    headers.insert(
+        "RateLimit",
+        "limit=10, remaining=5, reset=2".parse().unwrap(),
+    );
+    headers.insert("Retry-After", "7".parse().unwrap());
+    let info = RateLimitInfo::from_headers(&headers).unwrap();
+    assert_eq!(info.limit, Some(10));
+    assert_eq!(info.remaining, Some(5));
+    assert_eq!(info.reset, Some(Duration::from_secs(2)));
+    assert_eq!(info.wait_time(), Some(Duration::from_secs(7)));
+    assert!(RateLimitInfo::from_headers(&http::HeaderMap::new()).is_none());
 }
 
 #[test(tokio::test)]
-async fn test_raw_response() {
+async fn test_result_tls_info() {
+    use rustify::{endpoint::EndpointResult, enums::ResponseType, tls::TlsInfo};
+
     #[derive(Endpoint)]
-    #[endpoint(path = "test/path", response = "TestResponse")]
+    #[endpoint(path = "test/path")]
     struct Test {}
-    let resp_data = json!({"result": {"age": 30}});
 
     let t = TestServer::default();
-    let e = Test {};
     let m = t.server.mock(|when, then| {
         when.method(GET).path("/test/path");
-        then.status(200).json_body(json!({"result": {"age": 30}}));
+        then.status(200);
     });
-    let r = e.exec(&t.client).await;
+    let r = Test {}.exec(&t.client).await.unwrap();
 
     m.assert();
-    assert!(r.is_ok());
-    assert_eq!(r.unwrap().raw(), resp_data.to_string().as_bytes());
+    assert!(r.tls_info().is_none());
+
+    let info = TlsInfo {
+        peer_certificate: Some(b"cert".to_vec()),
+        ..TlsInfo::default()
+    };
+    let resp = http::Response::builder()
+        .extension(info)
+        .body(Vec::new())
+        .unwrap();
+    let r: EndpointResult<()> = EndpointResult::new(resp, ResponseType::JSON);
+    assert_eq!(
+        r.tls_info().and_then(|i| i.peer_fingerprint()).as_deref(),
+        Some("06298432e8066b29e2223bcc23aa9504b56ae508fabf3435508869b9c3190e22")
+    );
 }
 
-#[allow(dead_code)]
 #[test(tokio::test)]
-async fn test_generic() {
-    #[derive(Builder, Debug, Endpoint)]
-    #[endpoint(path = "test/path/{self.name}", response = "TestResponse<T>")]
-    #[builder(setter(into))]
-    struct Test<T: DeserializeOwned + Serialize + Debug + Send + Sync> {
-        #[endpoint(skip)]
-        name: String,
-        #[endpoint(skip)]
-        #[builder(default = "None", setter(skip))]
-        data: Option<PhantomData<T>>,
-    }
+async fn test_result_unknown_fields() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
 
-    #[derive(Clone, Debug, Serialize, Deserialize)]
-    struct TestData {
+    #[derive(Deserialize)]
+    struct TestResponse {
         age: u8,
+        info: TestInfo,
     }
 
-    #[derive(Debug, Serialize, Deserialize)]
-    struct TestResponse<T> {
-        data: T,
-        version: u8,
+    #[derive(Deserialize)]
+    struct TestInfo {
+        #[allow(dead_code)]
+        id: u64,
     }
 
     let t = TestServer::default();
+    let e = Test {};
     let m = t.server.mock(|when, then| {
-        when.method(GET).path("/test/path/test");
+        when.method(GET).path("/test/path");
         then.status(200)
-            .json_body(json!({"result": {"data": {"age": 30}, "version": 1}}));
+            .json_body(json!({"age": 30, "name": "test", "info": {"id": 1, "tag": "a"}}));
     });
-    let r: TestResponse<TestData> = TestBuilder::default()
-        .name("test")
-        .build()
-        .unwrap()
-        .with_middleware(&Middle {})
-        .exec(&t.client)
-        .await
-        .unwrap()
-        .parse()
-        .unwrap();
+    let r = e.exec(&t.client).await.unwrap();
+    let mut unknown = Vec::new();
+    let parsed = r.parse_reporting(|f| unknown.push(f.to_string())).unwrap();
+
     m.assert();
-    assert_eq!(r.data.age, 30);
+    assert_eq!(parsed.age, 30);
+    assert_eq!(parsed.info.id, 1);
+    unknown.sort();
+    assert_eq!(unknown, vec!["info.tag", "name"]);
+    assert!(r.parse().is_ok());
 }
 
 #[test(tokio::test)]
-async fn test_complex() {
-    #[derive(Builder, Default, Endpoint)]
-    #[endpoint(
-        path = "test/path/{self.name}",
-        method = "POST",
-        response = "TestResponse",
-        builder = "true"
-    )]
-    #[builder(setter(into, strip_option), default)]
+async fn test_builder() {
+    #[derive(Builder, Endpoint)]
+    #[endpoint(path = "test/path", method = "POST", builder = "true")]
+    #[builder(setter(into))]
     struct Test {
-        #[endpoint(skip)]
         name: String,
-        kind: String,
-        special: Option<bool>,
-        optional: Option<String>,
     }
 
     let t = TestServer::default();
     let m = t.server.mock(|when, then| {
-        when.method(POST)
-            .path("/test/path/test")
-            .json_body(json!({ "kind": "test" }));
-        then.status(200).json_body(json!({"result": {"age": 30}}));
+        when.method(POST).path("/test/path");
+        then.status(200);
     });
     let r = Test::builder()
         .name("test")
-        .kind("test")
         .build()
         .unwrap()
-        .with_middleware(&Middle {})
         .exec(&t.client)
         .await;
 
     m.assert();
     assert!(r.is_ok());
-    assert_eq!(r.unwrap().parse().unwrap().age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_mutate() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let e = Test {};
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("X-API-Token", "mytoken");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let r = e.with_middleware(&Middle {}).exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert_eq!(r.unwrap().parse().unwrap().age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_journal() {
+    use rustify::journal::{Journal, JournalEntry, JsonLines};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let journal = Journal::new(JsonLines::new(Vec::new()));
+    let r = Test {}.with_middleware(&journal).exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    let out = journal.sink.into_inner();
+    let lines = std::str::from_utf8(&out)
+        .unwrap()
+        .lines()
+        .collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1);
+    let entry: JournalEntry = serde_json::from_str(lines[0]).unwrap();
+    assert!(entry.operation.ends_with("Test"));
+    assert_eq!(entry.method, "GET");
+    assert!(entry.url.ends_with("/test/path"));
+    assert_eq!(entry.status, 200);
+    assert_eq!(entry.retries, 0);
+}
+
+#[test(tokio::test)]
+async fn test_gzip_body() {
+    use rustify::encoding::GzipBody;
+    use std::io::Read;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.path}", method = "POST")]
+    struct Test {
+        #[endpoint(skip)]
+        path: String,
+        data: String,
+    }
+
+    fn decompressed(req: &HttpMockRequest) -> bool {
+        let mut body = String::new();
+        flate2::read::GzDecoder::new(req.body.as_deref().unwrap_or_default())
+            .read_to_string(&mut body)
+            .is_ok()
+            && body.contains(&"a".repeat(2048))
+    }
+
+    let t = TestServer::default();
+    let large = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/large")
+            .header("Content-Encoding", "gzip")
+            .matches(decompressed);
+        then.status(200);
+    });
+    let small = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/small")
+            .json_body(json!({ "data": "a" }));
+        then.status(200);
+    });
+    let gzip = GzipBody::new();
+    let test = |path: &str, data: String| Test {
+        path: path.to_string(),
+        data,
+    };
+
+    let r1 = test("large", "a".repeat(2048))
+        .with_middleware(&gzip)
+        .exec(&t.client)
+        .await;
+    let r2 = test("small", "a".to_string())
+        .with_middleware(&gzip)
+        .exec(&t.client)
+        .await;
+
+    large.assert();
+    small.assert();
+    assert!(r1.is_ok());
+    assert!(r2.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_logger() {
+    use rustify::{
+        capture::BodyCapture,
+        logging::{Logger, RequestLog},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Endpoint, Serialize)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {
+        password: String,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path");
+        then.status(200)
+            .header("Set-Cookie", "session=secret")
+            .header("X-Api-Key", "secret")
+            .json_body(json!({ "name": "test" }));
+    });
+    let logs: Arc<Mutex<Vec<RequestLog>>> = Arc::default();
+    let sink = logs.clone();
+    let logger = Logger::new()
+        .headers(true)
+        .redact("X-Api-Key")
+        .bodies(BodyCapture::new())
+        .sink(move |log| sink.lock().unwrap().push(log.clone()));
+    let endpoint = Test {
+        password: "secret".to_string(),
+    };
+    let r = endpoint.with_middleware(&logger).exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    let logs = logs.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    let log = &logs[0];
+    assert!(log.operation.ends_with("Test"));
+    assert_eq!(log.method, "POST");
+    assert!(log.url.ends_with("/test/path"));
+    assert_eq!(log.status, 200);
+    assert!(log.to_string().starts_with("POST http"));
+    for name in ["set-cookie", "x-api-key"] {
+        let (_, value) = log
+            .response_headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .unwrap();
+        assert_eq!(value, "<redacted>");
+    }
+    assert_eq!(
+        log.request_body.as_deref(),
+        Some(r#"{"password":"<redacted>"}"#)
+    );
+    assert_eq!(log.response_body.as_deref(), Some(r#"{"name":"test"}"#));
+}
+
+#[tokio::test]
+async fn test_exec_span() {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+    use tracing_subscriber::fmt::{format::FmtSpan, MakeWriter};
+
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Output {
+        type Writer = Output;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path");
+        then.status(202);
+    });
+    let output = Output::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(output.clone())
+        .with_span_events(FmtSpan::CLOSE)
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let r = Test {}.exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    // Spans closed within the exec span are prefixed with it, so it's last
+    let line = output
+        .lines()
+        .rfind(|l| l.contains("exec{"))
+        .expect("exec span was not closed");
+    assert!(line.contains("path=test/path"));
+    assert!(line.contains("method=POST"));
+    assert!(line.contains("status=202"));
+    assert!(line.contains("latency_ms="));
+}
+
+#[test(tokio::test)]
+async fn test_clock_skew() {
+    use rustify::skew::ClockSkew;
+    use std::time::{Duration, SystemTime};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let date = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(3600));
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).header("Date", date.as_str());
+    });
+    let skew = ClockSkew::new();
+    let r = Test {}.with_middleware(&skew).exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert!(skew.is_observed());
+    assert!((skew.offset_millis() + 3_600_000).abs() < 2000);
+    let behind = SystemTime::now().duration_since(skew.now());
+    assert!(behind.unwrap() > Duration::from_secs(3590));
+}
+
+#[test(tokio::test)]
+async fn test_middleware_only_for() {
+    use rustify::{endpoint::MiddleWare, matcher::Matcher};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.name}", method = "POST")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m_match = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/users")
+            .header("X-API-Token", "mytoken");
+        then.status(200).json_body(json!({"result": {}}));
+    });
+    let m_skip = t.server.mock(|when, then| {
+        when.method(POST).path("/test/other").matches(|req| {
+            !req.headers
+                .as_ref()
+                .map(|h| h.iter().any(|(k, _)| k.eq_ignore_ascii_case("X-API-Token")))
+                .unwrap_or(false)
+        });
+        then.status(200);
+    });
+    let middle = Middle {}.only_for(
+        Matcher::new()
+            .method(http::Method::POST)
+            .path("test/u*")
+            .when(|t| t.name.ends_with("Test")),
+    );
+
+    let r1 = Test {
+        name: "users".to_string(),
+    }
+    .with_middleware(&middle)
+    .exec(&t.client)
+    .await;
+    let r2 = Test {
+        name: "other".to_string(),
+    }
+    .with_middleware(&middle)
+    .exec(&t.client)
+    .await;
+
+    m_match.assert();
+    m_skip.assert();
+    assert!(r1.is_ok());
+    assert!(r2.is_ok());
+}
+
+#[test(tokio::test)]
+async fn test_proxy_selector() {
+    use rustify::{clients::reqwest::Client, errors::ClientError, proxy::ProxySelector};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let mut m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+    let client = Client::default("http://api.example.invalid");
+    let proxy = ProxySelector::always(&t.server.base_url());
+    let r = Test {}.with_middleware(&proxy).exec(&client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert_eq!(format!("{:?}", client.proxies), "ProxyPool { proxies: 1 }");
+
+    let proxy = ProxySelector::always("not a proxy");
+    let r = Test {}.with_middleware(&proxy).exec(&client).await;
+    assert!(matches!(r, Err(ClientError::ProxyError { .. })));
+
+    // Proxy clients are derived from the builder of the client
+    m.delete();
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("X-Client", "custom");
+        then.status(200);
+    });
+    let client = Client::from_builder("http://api.example.invalid", || {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("X-Client", http::HeaderValue::from_static("custom"));
+        reqwest::Client::builder().default_headers(headers)
+    })
+    .unwrap();
+    let proxy = ProxySelector::always(&t.server.base_url());
+    let r = Test {}.with_middleware(&proxy).exec(&client).await;
+    m.assert();
+    assert!(r.is_ok());
+
+    // Clients created from a built client can't derive proxy clients
+    let client = Client::new("http://api.example.invalid", reqwest::Client::new());
+    let r = Test {}.with_middleware(&proxy).exec(&client).await;
+    assert!(matches!(r, Err(ClientError::ProxyError { .. })));
+}
+
+#[test(tokio::test)]
+async fn test_exec_void() {
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/{self.name}",
+        method = "DELETE",
+        response = "TestResponse"
+    )]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m1 = t.server.mock(|when, then| {
+        when.method(DELETE).path("/test/ok");
+        then.status(204).body("not json");
+    });
+    let m2 = t.server.mock(|when, then| {
+        when.method(DELETE).path("/test/missing");
+        then.status(404);
+    });
+    let r1 = Test {
+        name: "ok".to_string(),
+    }
+    .exec_void(&t.client)
+    .await;
+    let r2 = Test {
+        name: "missing".to_string(),
+    }
+    .exec_void(&t.client)
+    .await;
+
+    m1.assert();
+    m2.assert();
+    assert!(r1.is_ok());
+    assert!(matches!(
+        r2,
+        Err(ClientError::ServerResponseError { code: 404, .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_wrapper() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
+
+    fn strip<T>(wrapper: TestGenericWrapper<T>) -> T {
+        wrapper.result
+    }
+
+    let t = TestServer::default();
+    let e = Test {};
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let r = e.exec(&t.client).await.unwrap();
+    let r = r.wrap::<_>().map(strip).unwrap();
+
+    m.assert();
+    assert_eq!(r.age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_raw_response() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
+    let resp_data = json!({"result": {"age": 30}});
+
+    let t = TestServer::default();
+    let e = Test {};
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let r = e.exec(&t.client).await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert_eq!(r.unwrap().raw(), resp_data.to_string().as_bytes());
+}
+
+#[allow(dead_code)]
+#[test(tokio::test)]
+async fn test_generic() {
+    #[derive(Builder, Debug, Endpoint)]
+    #[endpoint(path = "test/path/{self.name}", response = "TestResponse<T>")]
+    #[builder(setter(into))]
+    struct Test<T: DeserializeOwned + Serialize + Debug + Send + Sync> {
+        #[endpoint(skip)]
+        name: String,
+        #[endpoint(skip)]
+        #[builder(default = "None", setter(skip))]
+        data: Option<PhantomData<T>>,
+    }
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct TestData {
+        age: u8,
+    }
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct TestResponse<T> {
+        data: T,
+        version: u8,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/test");
+        then.status(200)
+            .json_body(json!({"result": {"data": {"age": 30}, "version": 1}}));
+    });
+    let r: TestResponse<TestData> = TestBuilder::default()
+        .name("test")
+        .build()
+        .unwrap()
+        .with_middleware(&Middle {})
+        .exec(&t.client)
+        .await
+        .unwrap()
+        .parse()
+        .unwrap();
+    m.assert();
+    assert_eq!(r.data.age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_complex() {
+    #[derive(Builder, Default, Endpoint)]
+    #[endpoint(
+        path = "test/path/{self.name}",
+        method = "POST",
+        response = "TestResponse",
+        builder = "true"
+    )]
+    #[builder(setter(into, strip_option), default)]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+        kind: String,
+        special: Option<bool>,
+        optional: Option<String>,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path/test")
+            .json_body(json!({ "kind": "test" }));
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let r = Test::builder()
+        .name("test")
+        .kind("test")
+        .build()
+        .unwrap()
+        .with_middleware(&Middle {})
+        .exec(&t.client)
+        .await;
+
+    m.assert();
+    assert!(r.is_ok());
+    assert_eq!(r.unwrap().parse().unwrap().age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_wrapper_derive() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Test {}
+
+    #[derive(Deserialize, Wrapper)]
+    struct TestWrapper<T> {
+        #[allow(dead_code)]
+        status: String,
+        #[wrapper(value)]
+        data: T,
+    }
+
+    let t = TestServer::default();
+    let e = Test {};
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .json_body(json!({"status": "ok", "data": {"age": 30}}));
+    });
+    let r = e.exec(&t.client).await.unwrap();
+    let r = r.wrap::<TestWrapper<_>>().unwrap().into_value();
+
+    m.assert();
+    assert_eq!(r.age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_endpoint_wrapper() {
+    #[derive(Deserialize, Wrapper)]
+    struct TestWrapper<T> {
+        #[wrapper(error)]
+        error: Option<String>,
+        #[wrapper(value)]
+        data: T,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path/{self.name}",
+        response = "Option<TestResponse>",
+        wrapper = "TestWrapper"
+    )]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m_ok = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/ok");
+        then.status(200).json_body(json!({"data": {"age": 30}}));
+    });
+    let m_err = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/err");
+        then.status(200).json_body(json!({"error": "not allowed"}));
+    });
+    let ok = Test {
+        name: "ok".to_string(),
+    }
+    .exec(&t.client)
+    .await
+    .unwrap()
+    .parse();
+    let err = Test {
+        name: "err".to_string(),
+    }
+    .exec(&t.client)
+    .await
+    .unwrap()
+    .parse();
+
+    m_ok.assert();
+    m_err.assert();
+    assert_eq!(ok.unwrap().unwrap().age, 30);
+    assert!(matches!(err, Err(ClientError::ApiError { message }) if message == "not allowed"));
+}
+
+#[test(tokio::test)]
+async fn test_nested_wrapper() {
+    #[derive(Deserialize, Wrapper)]
+    struct Outer<T> {
+        #[wrapper(value)]
+        response: T,
+    }
+
+    #[derive(Deserialize, Wrapper)]
+    struct Inner<T> {
+        #[wrapper(error)]
+        error: Option<String>,
+        #[wrapper(value)]
+        data: T,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path",
+        response = "TestResponse",
+        wrapper = "Outer<Inner>"
+    )]
+    struct Test {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "TestResponse")]
+    struct Unwrapped {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .json_body(json!({"response": {"data": {"age": 30}}}));
+    });
+    let wrapped = Test {}.exec(&t.client).await.unwrap().parse();
+    let unwrapped = Unwrapped {}
+        .exec(&t.client)
+        .await
+        .unwrap()
+        .unwrap_nested::<Outer<Inner<_>>>();
+
+    m.assert_hits(2);
+    assert_eq!(wrapped.unwrap().age, 30);
+    assert_eq!(unwrapped.unwrap().age, 30);
+}
+
+#[test(tokio::test)]
+async fn test_extract() {
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path",
+        response = "Vec<TestResponse>",
+        extract = "data.items"
+    )]
+    struct Test {}
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path",
+        response = "TestResponse",
+        extract = "data.items.1"
+    )]
+    struct Second {}
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path",
+        response = "TestResponse",
+        extract = "data.missing"
+    )]
+    struct Missing {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .json_body(json!({"data": {"items": [{"age": 30}, {"age": 40}]}}));
+    });
+    let all = Test {}.exec(&t.client).await.unwrap().parse().unwrap();
+    let second = Second {}.exec(&t.client).await.unwrap().parse().unwrap();
+    let missing = Missing {}.exec(&t.client).await.unwrap().parse();
+
+    m.assert_hits(3);
+    assert_eq!(all.iter().map(|r| r.age).collect::<Vec<_>>(), vec![30, 40]);
+    assert_eq!(second.age, 40);
+    assert!(matches!(
+        missing,
+        Err(ClientError::ResponseParseError { .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_encryption() {
+    use rustify::encryption::{Cipher, Encryption};
+
+    struct Reverse;
+
+    impl Cipher for Reverse {
+        fn key_id(&self) -> String {
+            "k1".to_string()
+        }
+
+        fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            Ok(plaintext.iter().rev().copied().collect())
+        }
+
+        fn decrypt(&self, key_id: Option<&str>, ciphertext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            assert_eq!(key_id, Some("k2"));
+            Ok(ciphertext.iter().rev().copied().collect())
+        }
+    }
+
+    #[derive(Builder, Endpoint)]
+    #[endpoint(path = "test/path", method = "POST", response = "TestResponse")]
+    struct Test {
+        pub name: String,
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path")
+            .header("content-type", "application/octet-stream")
+            .header("x-encryption-key-id", "k1")
+            .body("}\"elgnaj\":\"eman\"{");
+        then.status(200)
+            .header("content-type", "application/octet-stream")
+            .header("x-encryption-key-id", "k2")
+            .header("x-plaintext-content-type", "application/json")
+            .body("}24:\"ega\"{");
+    });
+    let e = Test {
+        name: "jangle".to_string(),
+    };
+    let r = e
+        .with_middleware(&Encryption::new(Reverse))
+        .exec(&t.client)
+        .await;
+
+    m.assert();
+    assert_eq!(r.unwrap().parse().unwrap().age, 42);
+}
+
+#[test(tokio::test)]
+async fn test_encrypted_fields() {
+    use rustify::{
+        clients::reqwest::Client,
+        config::ClientConfig,
+        encryption::{self, Cipher},
+    };
+
+    struct Reverse;
+
+    impl Cipher for Reverse {
+        fn key_id(&self) -> String {
+            "k1".to_string()
+        }
+
+        fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            Ok(plaintext.iter().rev().copied().collect())
+        }
+
+        fn decrypt(&self, _: Option<&str>, ciphertext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            Ok(ciphertext.iter().rev().copied().collect())
+        }
+    }
+
+    struct Identity;
+
+    impl Cipher for Identity {
+        fn key_id(&self) -> String {
+            "k2".to_string()
+        }
+
+        fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            Ok(plaintext.to_vec())
+        }
+
+        fn decrypt(&self, _: Option<&str>, ciphertext: &[u8]) -> Result<Vec<u8>, ClientError> {
+            Ok(ciphertext.to_vec())
+        }
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", method = "POST")]
+    struct Test {
+        pub name: String,
+        #[endpoint(encrypt)]
+        pub ssn: String,
+        #[endpoint(encrypt)]
+        pub phone: Option<String>,
+    }
+
+    let reversed = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().field_cipher(Reverse)),
+    );
+    let m_reversed = reversed.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path")
+            .json_body(json!({"name": "test", "ssn": "IjMyMSI="}));
+        then.status(200);
+    });
+    let identity = TestServer::with_client(
+        Client::default("").with_config(ClientConfig::new().field_cipher(Identity)),
+    );
+    let m_identity = identity.server.mock(|when, then| {
+        when.method(POST)
+            .path("/test/path")
+            .json_body(json!({"name": "test", "ssn": "IjEyMyI="}));
+        then.status(200);
+    });
+    let e = Test {
+        name: "test".to_string(),
+        ssn: "123".to_string(),
+        phone: None,
+    };
+    let r_reversed = e.exec(&reversed.client).await;
+    let r_identity = e.exec(&identity.client).await;
+    let r_missing = e.exec(&TestServer::default().client).await;
+
+    m_reversed.assert();
+    m_identity.assert();
+    assert!(r_reversed.is_ok());
+    assert!(r_identity.is_ok());
+    assert!(r_missing.is_err());
+    assert_eq!(
+        encryption::decrypt_field::<String>(&Reverse, "IjMyMSI=").unwrap(),
+        "123"
+    );
+}
+
+#[test]
+fn test_eq() {
+    use std::collections::HashSet;
+
+    #[allow(dead_code)]
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.id}", method = "POST", eq)]
+    struct Test {
+        #[endpoint(skip)]
+        id: u64,
+        #[endpoint(skip)]
+        note: String,
+        #[endpoint(query)]
+        page: Option<u32>,
+        name: String,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", eq)]
+    struct Generic<T: Send + Sync> {
+        #[endpoint(query)]
+        page: u32,
+        #[endpoint(skip)]
+        data: PhantomData<T>,
+        #[endpoint(skip)]
+        skipped: f64,
+    }
+
+    let test = |id, note: &str, page, name: &str| Test {
+        id,
+        note: note.to_string(),
+        page,
+        name: name.to_string(),
+    };
+    let e = test(1, "first", Some(2), "test");
+    assert!(e == test(1, "second", Some(2), "test"));
+    assert!(e != test(2, "first", Some(2), "test"));
+    assert!(e != test(1, "first", None, "test"));
+    assert!(e != test(1, "first", Some(2), "other"));
+
+    let mut set = HashSet::new();
+    assert!(set.insert(e));
+    assert!(!set.insert(test(1, "second", Some(2), "test")));
+    assert!(set.insert(test(2, "first", Some(2), "test")));
+
+    let generic = |page, skipped| Generic::<f64> {
+        page,
+        data: PhantomData,
+        skipped,
+    };
+    assert!(generic(1, 1.0) == generic(1, 2.0));
+    assert!(generic(1, 1.0) != generic(2, 1.0));
+}
+
+#[test]
+fn test_cache_policy() {
+    use rustify::cache::CachePolicy;
+    use std::time::Duration;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", cache = "no-store")]
+    struct NoStore {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", cache = "ttl=90s")]
+    struct Ttl {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", cache = "ttl=250ms")]
+    struct Millis {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Unset {}
+
+    let req = NoStore {}.request("http://localhost").unwrap();
+    assert_eq!(CachePolicy::of(req.extensions()), CachePolicy::NoStore);
+    assert_eq!(
+        Ttl {}.cache_policy(),
+        CachePolicy::Ttl(Duration::from_secs(90))
+    );
+    assert_eq!(
+        Millis {}.cache_policy(),
+        CachePolicy::Ttl(Duration::from_millis(250))
+    );
+    let req = Unset {}.request("http://localhost").unwrap();
+    assert_eq!(CachePolicy::of(req.extensions()), CachePolicy::Default);
+}
+
+#[test(tokio::test)]
+async fn test_deprecated() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", deprecated = "use ListUsersV2")]
+    struct ListUsers {}
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct ListUsersV2 {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200);
+    });
+
+    assert_eq!(ListUsers {}.deprecated(), Some("use ListUsersV2"));
+    assert_eq!(ListUsersV2 {}.deprecated(), None);
+    for _ in 0..2 {
+        assert!(ListUsers {}.exec(&t.client).await.is_ok());
+    }
+    m.assert_hits(2);
+}
+
+#[test(tokio::test)]
+async fn test_status_responses() {
+    #[derive(Debug, Deserialize)]
+    struct PendingJob {
+        id: String,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path/{self.name}",
+        method = "POST",
+        response(200 = "TestResponse", 202 = "PendingJob")
+    )]
+    struct Job {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m_done = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path/done");
+        then.status(200).json_body(json!({"age": 30}));
+    });
+    let m_pending = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path/pending");
+        then.status(202).json_body(json!({"id": "job-1"}));
+    });
+    let m_other = t.server.mock(|when, then| {
+        when.method(POST).path("/test/path/other");
+        then.status(201).json_body(json!({"age": 30}));
+    });
+    let exec = |name: &str| {
+        let e = Job {
+            name: name.to_string(),
+        };
+        let client = &t.client;
+        async move { e.exec(client).await.unwrap().parse() }
+    };
+
+    let done = exec("done").await;
+    let pending = exec("pending").await;
+    let other = exec("other").await;
+
+    m_done.assert();
+    m_pending.assert();
+    m_other.assert();
+    assert!(matches!(done, Ok(JobResponse::TestResponse(r)) if r.age == 30));
+    assert!(matches!(pending, Ok(JobResponse::PendingJob(j)) if j.id == "job-1"));
+    assert!(matches!(
+        other,
+        Err(ClientError::ResponseStatusError { code: 201, .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_discriminated_responses() {
+    #[derive(Debug, Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Org {
+        members: u32,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path/{self.name}",
+        discriminator = "type",
+        response("user" = "User", "org" = "Org")
+    )]
+    struct Account {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m_user = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/user");
+        then.status(200)
+            .json_body(json!({"type": "user", "name": "test"}));
+    });
+    let m_org = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/org");
+        then.status(200)
+            .json_body(json!({"type": "org", "members": 3}));
+    });
+    let m_bot = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/bot");
+        then.status(200).json_body(json!({"type": "bot"}));
+    });
+    let exec = |name: &str| {
+        let e = Account {
+            name: name.to_string(),
+        };
+        let client = &t.client;
+        async move { e.exec(client).await.unwrap().parse() }
+    };
+
+    let user = exec("user").await;
+    let org = exec("org").await;
+    let bot = exec("bot").await;
+
+    m_user.assert();
+    m_org.assert();
+    m_bot.assert();
+    assert!(matches!(user, Ok(AccountResponse::User(u)) if u.name == "test"));
+    assert!(matches!(org, Ok(AccountResponse::Org(o)) if o.members == 3));
+    assert!(matches!(
+        bot,
+        Err(ClientError::ResponseDiscriminatorError { value: Some(v), .. }) if v == "bot"
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_lazy_result() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path/{self.name}", response = "TestResponse")]
+    struct Test {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    let t = TestServer::default();
+    let m_ok = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/ok");
+        then.status(200).json_body(json!({"age": 30}));
+    });
+    let m_bad = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path/bad");
+        then.status(200).body("not json");
+    });
+    let ok = Test {
+        name: "ok".to_string(),
+    }
+    .exec_lazy(&t.client)
+    .await
+    .unwrap();
+    let bad = Test {
+        name: "bad".to_string(),
+    }
+    .exec_lazy(&t.client)
+    .await;
+
+    m_ok.assert();
+    m_bad.assert();
+    assert!(!ok.is_parsed());
+    assert_eq!(ok.get().unwrap().age, 30);
+    assert!(ok.is_parsed());
+    assert_eq!(ok.into_inner().unwrap().age, 30);
+    let bad = bad.unwrap();
+    assert_eq!(bad.raw(), b"not json");
+    assert!(bad.get().is_err());
+}
+
+#[cfg(feature = "arbitrary_precision")]
+#[test(tokio::test)]
+async fn test_arbitrary_precision() {
+    #[derive(Deserialize)]
+    struct Balance {
+        amount: serde_json::Number,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "Balance")]
+    struct Test {}
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET).path("/test/path");
+        then.status(200)
+            .body(r#"{"amount": 12345678901234567890.123456789}"#);
+    });
+    let r = Test {}.exec(&t.client).await.unwrap().parse().unwrap();
+
+    m.assert();
+    assert_eq!(r.amount.to_string(), "12345678901234567890.123456789");
+}
+
+#[test]
+fn test_validate_endpoints() {
+    #[derive(Default, Endpoint)]
+    #[endpoint(path = "users/{self.id}")]
+    struct GetUser {
+        #[endpoint(skip)]
+        id: u64,
+    }
+
+    #[derive(Default, Endpoint)]
+    #[endpoint(path = "users/{self.name}/posts")]
+    struct GetPosts {
+        #[endpoint(skip)]
+        name: String,
+    }
+
+    #[derive(Default, Endpoint)]
+    #[endpoint(path = "users", method = "POST")]
+    struct CreateUser {
+        name: String,
+    }
+
+    rustify::validate_endpoints!(GetUser, CreateUser);
+    assert!(rustify::validate::validate(&GetPosts::default()).is_err());
+    assert!(rustify::validate::validate(&GetPosts {
+        name: "test".to_string()
+    })
+    .is_ok());
+
+    let panic = std::panic::catch_unwind(|| rustify::validate_endpoints!(GetUser, GetPosts));
+    let message = panic.unwrap_err().downcast::<String>().unwrap();
+    assert!(message.contains("GetPosts: Error building endpoint request: path `users//posts`"));
+    assert!(!message.contains("GetUser"));
+}
+
+#[cfg(feature = "diagnostics")]
+#[test]
+fn test_error_diagnostics() {
+    use miette::Diagnostic;
+    use rustify::enums::ResponseType;
+
+    let body = b"{\n  \"age\": \"thirty\"\n}";
+    let err = rustify::http::parse::<TestResponse>(body, ResponseType::JSON).unwrap_err();
+    let labels = err.labels().unwrap().collect::<Vec<_>>();
+    let source = err
+        .source_code()
+        .unwrap()
+        .read_span(labels[0].inner(), 0, 0);
+
+    assert_eq!(err.code().unwrap().to_string(), "rustify::deserialization");
+    assert!(err.help().is_some());
+    assert_eq!(labels.len(), 1);
+    assert_eq!(labels[0].offset(), 18);
+    assert_eq!(source.unwrap().data(), b"\"");
+}
+
+#[test]
+fn test_hostile_inputs() {
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/{self.id}")]
+    struct Test {
+        #[endpoint(skip)]
+        id: String,
+        #[endpoint(query)]
+        filter: String,
+    }
+
+    // A small xorshift generator keeps the inputs reproducible
+    let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let alphabet = [
+        "a", "/", "?", "#", "%", "%zz", "{", "}", "..", " ", "\r\n", "\0", "\u{7f}", "é", "🦀",
+        "\u{202e}", "&", "=", ":", "@", "\\",
+    ];
+    let mut inputs: Vec<String> = vec![
+        String::new(),
+        "mailto:someone".to_string(),
+        "data:text/plain,hello".to_string(),
+        "http://".to_string(),
+        "http://[::1".to_string(),
+        "a".repeat(10_000),
+    ];
+    for _ in 0..500 {
+        let len = next() % 12;
+        inputs.push(
+            (0..len)
+                .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+                .collect(),
+        );
+    }
+
+    for input in inputs.iter() {
+        let _ = rustify::http::build_url(input, input, Some(input.clone()));
+        let _ = rustify::http::build_url("http://localhost", input, Some(input.clone()));
+        let _ = rustify::http::build_header(input, input);
+        let _ = rustify::http::build_header("x-test", input);
+        let _ = Test {
+            id: input.clone(),
+            filter: input.clone(),
+        }
+        .request("http://localhost");
+    }
+
+    assert!(matches!(
+        rustify::http::build_url("mailto:someone", "test", None),
+        Err(ClientError::UrlParseError { .. })
+    ));
+    assert!(matches!(
+        rustify::http::build_header("x-test", "a\r\nx-injected: b"),
+        Err(ClientError::HeaderError { name, .. }) if name == "x-test"
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_middleware_stack() {
+    use rustify::endpoint::{MiddleWare, MiddlewareStack};
+    use std::sync::Mutex;
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    struct Trace<'a> {
+        name: &'static str,
+        calls: &'a Mutex<Vec<String>>,
+    }
+
+    impl MiddleWare for Trace<'_> {
+        fn request<E: Endpoint>(
+            &self,
+            _: &E,
+            req: &mut http::Request<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("request {}", self.name));
+            req.headers_mut()
+                .append("X-Trace", http::HeaderValue::from_static(self.name));
+            Ok(())
+        }
+
+        fn response<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut http::Response<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("response {}", self.name));
+            Ok(())
+        }
+    }
+
+    let t = TestServer::default();
+    let m = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .header("X-Trace", "a")
+            .header("X-API-Token", "mytoken");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let calls = Mutex::new(Vec::new());
+    let trace = |name| Trace {
+        name,
+        calls: &calls,
+    };
+    let stack = MiddlewareStack::new(trace("a"), trace("b")).push(Middle {});
+    let r = Test {}.with_middleware(&stack).exec(&t.client).await;
+
+    m.assert();
+    assert_eq!(r.unwrap().raw(), b"{\"age\":30}");
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec!["request a", "request b", "response b", "response a"]
+    );
+
+    calls.lock().unwrap().clear();
+    let stack = trace("a").and(&stack);
+    let r = Test {}.with_middleware(&stack).exec(&t.client).await;
+    assert!(r.is_ok());
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "request a",
+            "request a",
+            "request b",
+            "response b",
+            "response a",
+            "response a"
+        ]
+    );
+}
+
+#[test(tokio::test)]
+async fn test_named_middleware() {
+    use rustify::{endpoint::MiddleWare, errors::ErrorKind};
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path")]
+    struct Test {}
+
+    struct Fail;
+
+    impl MiddleWare for Fail {
+        fn request<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut http::Request<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            Err(ClientError::ApiError {
+                message: "denied".to_string(),
+            })
+        }
+
+        fn response<E: Endpoint>(
+            &self,
+            _: &E,
+            _: &mut http::Response<Vec<u8>>,
+        ) -> Result<(), ClientError> {
+            Ok(())
+        }
+    }
+
+    let t = TestServer::default();
+    let stack = Middle {}
+        .named("token")
+        .and(Fail.named("auth"))
+        .named("stack");
+    let res = Test {}.with_middleware(&stack).exec(&t.client).await;
+    let err = match res {
+        Err(e) => e,
+        Ok(_) => panic!("expected the auth layer to fail"),
+    };
+
+    assert_eq!(err.layers(), vec!["stack", "auth"]);
+    assert_eq!(err.to_string(), "Middleware layer stack failed");
+    assert_eq!(err.kind(), ErrorKind::Api);
+    assert!(matches!(err.root(), ClientError::ApiError { message } if message == "denied"));
+    assert!(matches!(
+        err,
+        ClientError::Middleware { layer, source }
+            if layer == "stack" && matches!(*source, ClientError::Middleware { .. })
+    ));
+}
+
+#[test(tokio::test)]
+async fn test_exec_as() {
+    use rustify::enums::ResponseType;
+
+    #[derive(Endpoint)]
+    #[endpoint(
+        path = "test/path",
+        response = "TestResponse",
+        wrapper = "TestGenericWrapper"
+    )]
+    struct Test {
+        #[endpoint(query)]
+        export: Option<String>,
+    }
+
+    #[derive(Endpoint)]
+    #[endpoint(path = "test/path", response = "String", response_type = "TEXT")]
+    struct TestText {
+        #[endpoint(query)]
+        export: Option<String>,
+    }
+
+    let t = TestServer::default();
+    let m_json = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .query_param("export", "json");
+        then.status(200).json_body(json!({"result": {"age": 30}}));
+    });
+    let m_csv = t.server.mock(|when, then| {
+        when.method(GET)
+            .path("/test/path")
+            .query_param("export", "csv");
+        then.status(200).body("age\n30\n");
+    });
+
+    let r = Test {
+        export: Some("json".to_string()),
+    }
+    .exec(&t.client)
+    .await;
+    assert_eq!(r.unwrap().parse().unwrap().age, 30);
+
+    let export = Some("csv".to_string());
+    let r = Test {
+        export: export.clone(),
+    }
+    .exec_as::<String>(&t.client, ResponseType::TEXT)
+    .await;
+    assert_eq!(r.unwrap().parse().unwrap(), "age\n30\n");
+
+    let r = TestText { export }.exec(&t.client).await;
+    assert_eq!(r.unwrap().parse().unwrap(), "age\n30\n");
+
+    m_json.assert();
+    m_csv.assert_hits(2);
 }
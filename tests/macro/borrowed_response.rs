@@ -0,0 +1,9 @@
+use rustify::endpoint::Endpoint;
+use rustify_derive::Endpoint;
+use serde::Serialize;
+
+#[derive(Debug, Endpoint, Serialize)]
+#[endpoint(path = "test/path", response = "Vec<&'static str>")]
+struct Test {}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use rustify::endpoint::Endpoint;
+use rustify_derive::Endpoint;
+use serde::Serialize;
+
+struct TestResponse {}
+
+#[derive(Debug, Endpoint, Serialize)]
+#[endpoint(path = "test/path", response = "TestResponse")]
+struct Test {}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use rustify::endpoint::Endpoint;
+use rustify_derive::Endpoint;
+use serde::Serialize;
+
+#[derive(Debug, Endpoint, Serialize)]
+#[endpoint(path = "test/path", scopes = "repo:read org:read")]
+struct Test {}
+
+#[derive(Debug, Endpoint, Serialize)]
+#[endpoint(path = "test/path", scopes = ["repo:read", 1])]
+struct TestTwo {}
+
+fn main() {}
@@ -0,0 +1,128 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Uri};
+use rustify::{
+    hmac::{Component, HmacSigner},
+    signing,
+};
+use test_log::test;
+
+#[test]
+fn test_canonical_query() {
+    let uri: Uri = "https://example.com/path?b=2&a=z&a=1&c&d=a+b%2Fc"
+        .parse()
+        .unwrap();
+
+    assert_eq!(signing::canonical_query(&uri), "a=1&a=z&b=2&c=&d=a%20b%2Fc");
+    assert_eq!(
+        signing::canonical_query(&"https://example.com".parse().unwrap()),
+        ""
+    );
+}
+
+#[test]
+fn test_canonical_path() {
+    let uri: Uri = "https://example.com/a%20b/c~d".parse().unwrap();
+
+    assert_eq!(signing::canonical_path(&uri), "/a%20b/c~d");
+
+    // Encoded slashes stay part of their segment
+    let uri: Uri = "https://example.com/keys/a%2Fb/c%2fd/".parse().unwrap();
+    assert_eq!(signing::canonical_path(&uri), "/keys/a%2Fb/c%2Fd/");
+    assert_eq!(signing::encode("a/b c", false), "a/b%20c");
+    assert_eq!(signing::encode("a/b c", true), "a%2Fb%20c");
+}
+
+#[test]
+fn test_canonical_headers() {
+    let mut headers = HeaderMap::new();
+    headers.insert("Host", HeaderValue::from_static("example.com"));
+    headers.append("X-Multi", HeaderValue::from_static("  a   b "));
+    headers.append("X-Multi", HeaderValue::from_static("c"));
+
+    assert_eq!(
+        signing::canonical_headers(&headers, &["host", "X-Multi"]),
+        "host:example.com\nx-multi:a b,c\n"
+    );
+    assert_eq!(
+        signing::signed_headers(&["X-Multi", "host"]),
+        "host;x-multi"
+    );
+}
+
+#[test]
+fn test_hash_body() {
+    assert_eq!(
+        signing::hash_body(b""),
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    );
+}
+
+#[test]
+fn test_hmac_sha256() {
+    // Test cases 2 and 6 of RFC 4231
+    assert_eq!(
+        signing::hex(&signing::hmac_sha256(
+            b"Jefe",
+            b"what do ya want for nothing?"
+        )),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+    assert_eq!(
+        signing::hex(&signing::hmac_sha256(
+            &[0xaa; 131],
+            b"Test Using Larger Than Block-Size Key - Hash Key First"
+        )),
+        "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+    );
+}
+
+#[test]
+fn test_hmac_signer() {
+    let time = UNIX_EPOCH + Duration::from_secs(1440938160);
+    let request = || {
+        Request::builder()
+            .method("POST")
+            .uri("https://api.example.com/v1/charges?limit=10")
+            .header("X-Account", "acct_1")
+            .body(br#"{"amount":100}"#.to_vec())
+            .unwrap()
+    };
+
+    // Signs the method, path, body, and timestamp by default
+    let signer = HmacSigner::new(b"secret", HeaderName::from_static("x-signature"));
+    let mut req = request();
+    signer.sign_at(&mut req, time).unwrap();
+    assert_eq!(
+        req.headers()["x-signature"],
+        "f63a97984800b6feb8f2551286e69692f3e25e5712d44cc73118f8dc47cd91b9"
+    );
+    assert!(req.headers()["x-signature"].is_sensitive());
+
+    let signer = signer
+        .components(vec![
+            Component::Timestamp,
+            Component::Method,
+            Component::PathAndQuery,
+            Component::BodyHash,
+            Component::Header(HeaderName::from_static("x-account")),
+        ])
+        .separator(".")
+        .timestamp_header(HeaderName::from_static("x-timestamp"))
+        .base64()
+        .prefix("v1=");
+    let mut req = request();
+    signer.sign_at(&mut req, time).unwrap();
+    assert_eq!(req.headers()["x-timestamp"], "1440938160");
+    assert_eq!(
+        req.headers()["x-signature"],
+        "v1=TVMxzrSd+3s918Gx5l9OQKVvRwcC9+fPiXEdCspkGVY="
+    );
+    assert_eq!(
+        String::from_utf8(signer.canonical_string(&req, 1)).unwrap(),
+        format!(
+            "1.POST./v1/charges?limit=10.{}.acct_1",
+            signing::hash_body(br#"{"amount":100}"#)
+        )
+    );
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "sigv4")]
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use http::Request;
+use rustify::{signing, sigv4::SigV4};
+use test_log::test;
+
+/// 2015-08-30T12:36:00Z, the time used by the examples in the AWS docs
+fn example_time() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1440938160)
+}
+
+#[test]
+fn test_sigv4() {
+    let signer = SigV4::new(
+        "AKIDEXAMPLE",
+        "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+        "us-east-1",
+        "iam",
+    );
+    assert_eq!(
+        signing::hex(&signer.signing_key("20150830")),
+        "c4afb1cc5771d871763a393e44b703571b55cc28424d1a5e86da6ed3c154a4b9"
+    );
+
+    let mut req = Request::builder()
+        .uri("https://iam.amazonaws.com/?Version=2010-05-08&Action=ListUsers")
+        .header(
+            "Content-Type",
+            "application/x-www-form-urlencoded; charset=utf-8",
+        )
+        .body(Vec::new())
+        .unwrap();
+    signer.sign_at(&mut req, example_time()).unwrap();
+
+    assert_eq!(req.headers()["host"], "iam.amazonaws.com");
+    assert_eq!(req.headers()["x-amz-date"], "20150830T123600Z");
+    assert!(!req.headers().contains_key("x-amz-content-sha256"));
+    assert_eq!(
+        req.headers()["authorization"],
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+         SignedHeaders=content-type;host;x-amz-date, \
+         Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+    );
+}
+
+#[test]
+fn test_sigv4_s3() {
+    let signer = SigV4::new("AKIDEXAMPLE", "secret", "eu-west-1", "s3").session_token("session");
+    let mut req = Request::builder()
+        .method("PUT")
+        .uri("https://bucket.s3.amazonaws.com/a%20b.txt")
+        .body(b"hello".to_vec())
+        .unwrap();
+    signer.sign_at(&mut req, example_time()).unwrap();
+
+    assert_eq!(
+        req.headers()["x-amz-content-sha256"],
+        signing::hash_body(b"hello").as_str()
+    );
+    assert_eq!(req.headers()["x-amz-security-token"], "session");
+    assert!(req.headers()["x-amz-security-token"].is_sensitive());
+    let authorization = req.headers()["authorization"].to_str().unwrap();
+    assert!(authorization.starts_with(
+        "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/eu-west-1/s3/aws4_request, \
+         SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token, "
+    ));
+}